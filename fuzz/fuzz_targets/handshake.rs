@@ -0,0 +1,11 @@
+// The bytes a server reads from a client, from the name of the wire format on.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate simple_chat_fuzz;
+
+use simple_chat_fuzz::read_frames;
+
+fuzz_target!(|data: &[u8]| {
+    read_frames(data.to_vec());
+});
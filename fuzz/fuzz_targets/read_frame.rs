@@ -0,0 +1,16 @@
+// The frames of a connection, in the wire format chosen by the first byte.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate simple_chat_fuzz;
+
+use simple_chat_fuzz::read_frames;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((&format, frames)) = data.split_first() {
+        let format = ["bincode", "json", "msgpack"][format as usize % 3];
+        let mut input = format!("{}\n", format).into_bytes();
+        input.extend_from_slice(frames);
+        read_frames(input);
+    }
+});
@@ -0,0 +1,45 @@
+// The chat's codec, built on its own for the fuzz targets, the chat being a binary.
+extern crate bincode;
+extern crate bytes;
+extern crate chrono;
+extern crate rmp_serde;
+extern crate serde;
+extern crate serde_json;
+extern crate socket2;
+
+#[allow(dead_code)]
+#[path = "../../src/codec.rs"]
+mod codec;
+#[allow(dead_code)]
+#[path = "../../src/record.rs"]
+mod record;
+#[allow(dead_code)]
+#[path = "../../src/session.rs"]
+mod session;
+#[allow(dead_code)]
+#[path = "../../src/socket.rs"]
+mod socket;
+#[allow(dead_code)]
+#[path = "../../src/transport.rs"]
+mod transport;
+
+use codec::{FrameStream, ProtocolViolation};
+use std::io::{Cursor, ErrorKind};
+
+// Read the frames of a connection until it fails, which it must with one of the documented errors,
+// going on past the frames which can be skipped, as the chat does.
+pub fn read_frames(input: Vec<u8>) {
+    let expected = |kind| matches!(kind, ErrorKind::InvalidData | ErrorKind::UnexpectedEof);
+    let mut stream = match FrameStream::accept(Cursor::new(input)) {
+        Ok(stream) => stream,
+        Err(error) => return assert!(expected(error.kind()), "{:?}", error),
+    };
+    loop {
+        if let Err(error) = stream.read_frame() {
+            if ProtocolViolation::of(&error).is_some_and(ProtocolViolation::is_recoverable) {
+                continue;
+            }
+            return assert!(expected(error.kind()), "{:?}", error);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use bcrypt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+
+// Decides which clients may chat, from a user name and password,
+// shared by the threads taking the clients in.
+pub trait Authenticator: Send + Sync {
+    // A password the client proves it knows, without sending it,
+    // instead of sending its user name and password.
+    fn shared_password(&self) -> Option<&str> {
+        None
+    }
+
+    fn authenticate(&self, user: &str, password: &str) -> Result<bool, String>;
+}
+
+// A single password, for any user name.
+pub struct StaticPassword {
+    password: String,
+}
+
+impl StaticPassword {
+    pub fn new(password: String) -> StaticPassword {
+        StaticPassword {
+            password,
+        }
+    }
+}
+
+impl Authenticator for StaticPassword {
+    fn shared_password(&self) -> Option<&str> {
+        Some(&self.password)
+    }
+
+    fn authenticate(&self, _user: &str, password: &str) -> Result<bool, String> {
+        // Compared as MACs, in a time which doesn't tell how much of the password was right.
+        let mac = |password: &str| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(self.password.as_bytes())
+                .expect("HMAC can take a key of any size");
+            mac.update(password.as_bytes());
+            mac
+        };
+        Ok(mac(password).verify_slice(&mac(&self.password).finalize().into_bytes()).is_ok())
+    }
+}
+
+// Users from an Apache htpasswd file with bcrypt hashes, as made by `htpasswd -B`,
+// read on each connection so that changes apply to the next one.
+pub struct HtpasswdFile {
+    path: String,
+}
+
+impl HtpasswdFile {
+    pub fn new(path: String) -> HtpasswdFile {
+        HtpasswdFile {
+            path,
+        }
+    }
+}
+
+impl Authenticator for HtpasswdFile {
+    fn authenticate(&self, user: &str, password: &str) -> Result<bool, String> {
+        let source = fs::read_to_string(&self.path)
+            .map_err(|error| format!("failed to read {}: {}", self.path, error))?;
+        let hash = source.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|&(name, _)| name == user)
+            .map(|(_, hash)| hash.trim());
+        match hash {
+            None => Ok(false),
+            Some(hash) if hash.starts_with("$2") => {
+                bcrypt::verify(password, hash).map_err(|error| error.to_string())
+            },
+            Some(_) => Err(format!("the hash of {} in {} isn't bcrypt, use `htpasswd -B`", user, self.path)),
+        }
+    }
+}
+
+// An external program, given the user name as argument and the password on its input,
+// exiting successfully if they are valid, for example a wrapper around PAM or LDAP.
+pub struct ExternalCommand {
+    program: String,
+}
+
+impl ExternalCommand {
+    pub fn new(program: String) -> ExternalCommand {
+        ExternalCommand {
+            program,
+        }
+    }
+}
+
+impl Authenticator for ExternalCommand {
+    fn authenticate(&self, user: &str, password: &str) -> Result<bool, String> {
+        let mut child = Command::new(&self.program)
+            .arg(user)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("failed to run {}: {}", self.program, error))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "{}", password);
+        }
+        let status = child.wait().map_err(|error| error.to_string())?;
+        Ok(status.success())
+    }
+}
+
+// An authenticator from a `password:<password>`, `htpasswd:<path>` or `command:<program>` spec.
+pub fn authenticator_from_spec(spec: &str) -> Result<Box<dyn Authenticator>, String> {
+    match spec.split_once(':') {
+        Some(("password", password)) => Ok(Box::new(StaticPassword::new(password.to_string()))),
+        Some(("htpasswd", path)) => Ok(Box::new(HtpasswdFile::new(path.to_string()))),
+        Some(("command", program)) => Ok(Box::new(ExternalCommand::new(program.to_string()))),
+        _ => Err(format!("unknown authenticator '{}', use password:, htpasswd: or command:", spec)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_authenticators() {
+        let path = env::temp_dir().join("simple_chat_test_htpasswd");
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        fs::write(&path, format!("alice:{}\nbob:{{SHA}}W6ph5Mm5Pz8GgiULbPgzG37mj9g=\n", hash)).unwrap();
+        let htpasswd = authenticator_from_spec(&format!("htpasswd:{}", path.display())).unwrap();
+        assert_eq!(htpasswd.shared_password(), None);
+        assert_eq!(htpasswd.authenticate("alice", "secret"), Ok(true));
+        assert_eq!(htpasswd.authenticate("alice", "wrong"), Ok(false));
+        assert_eq!(htpasswd.authenticate("carol", "secret"), Ok(false));
+        assert!(htpasswd.authenticate("bob", "password").is_err());
+        let _ = fs::remove_file(&path);
+
+        let password = authenticator_from_spec("password:secret").unwrap();
+        assert_eq!(password.shared_password(), Some("secret"));
+        assert_eq!(password.authenticate("anyone", "secret"), Ok(true));
+        assert_eq!(password.authenticate("anyone", "secre"), Ok(false));
+        assert_eq!(password.authenticate("anyone", "secrets"), Ok(false));
+        assert!(authenticator_from_spec("ldap:server").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_command() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = env::temp_dir().join("simple_chat_test_auth.sh");
+        fs::write(&path, "#!/bin/sh\nread password\n[ \"$1\" = alice ] && [ \"$password\" = secret ]\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let command = authenticator_from_spec(&format!("command:{}", path.display())).unwrap();
+        assert_eq!(command.shared_password(), None);
+        assert_eq!(command.authenticate("alice", "secret"), Ok(true));
+        assert_eq!(command.authenticate("alice", "wrong"), Ok(false));
+        assert_eq!(command.authenticate("bob", "secret"), Ok(false));
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(ExternalCommand::new("true".to_string()).authenticate("alice", ""), Ok(true));
+        assert_eq!(ExternalCommand::new("false".to_string()).authenticate("alice", "secret"), Ok(false));
+        assert!(ExternalCommand::new("/nonexistent/simple_chat_auth".to_string()).authenticate("alice", "secret").is_err());
+    }
+}
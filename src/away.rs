@@ -0,0 +1,64 @@
+use chrono::{DateTime, Local};
+use std::time::SystemTime;
+
+
+// Away mode, see `/away`: each peer is told the reason once, and the messages missed are kept for `/back`.
+pub struct Away {
+    pub reason: String,
+    since: SystemTime,
+    // The peers already told.
+    replied: Vec<String>,
+    // Who sent each message missed, when, and its text.
+    missed: Vec<(String, SystemTime, String)>,
+}
+
+fn time(time: SystemTime) -> String {
+    let time: DateTime<Local> = time.into();
+    time.format("%H:%M:%S").to_string()
+}
+
+impl Away {
+    pub fn new(reason: String) -> Away {
+        Away {
+            reason,
+            since: SystemTime::now(),
+            replied: vec![],
+            missed: vec![],
+        }
+    }
+
+    // Keep the message missed, returning the reply to it, if the peer wasn't told yet.
+    pub fn message(&mut self, peer: &str, sent_at: SystemTime, text: &str) -> Option<String> {
+        self.missed.push((peer.to_string(), sent_at, text.to_string()));
+        if self.replied.iter().any(|replied| replied == peer) {
+            return None;
+        }
+        self.replied.push(peer.to_string());
+        Some(tr!("away-reply", reason = self.reason.as_str()))
+    }
+
+    // How many messages were missed since when, followed by those.
+    pub fn summary(&self) -> String {
+        let mut lines = vec![tr!("away-summary", count = self.missed.len(), since = time(self.since))];
+        lines.extend(self.missed.iter().map(|(peer, sent_at, text)| format!("[{}] {}: {}", time(*sent_at), peer, text)));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_away() {
+        let mut away = Away::new("at lunch".to_string());
+        assert_eq!(away.message("alice", SystemTime::now(), "hi"), Some("I'm away: at lunch".to_string()));
+        assert_eq!(away.message("alice", SystemTime::now(), "are you there?"), None);
+        assert!(away.message("bob", SystemTime::now(), "hello").is_some());
+        let summary = away.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("3 messages while away since "));
+        assert!(lines[2].ends_with(" alice: are you there?"));
+    }
+}
@@ -0,0 +1,90 @@
+use config;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+
+// The addresses the server refuses clients from, one per line,
+// kept in the config directory.
+// Nicknames are chosen by clients, so banning one bans the address it connected from.
+#[derive(Debug, Default)]
+pub struct BanList {
+    path: Option<PathBuf>,
+    addresses: Vec<IpAddr>,
+}
+
+impl BanList {
+    pub fn parse(source: &str) -> BanList {
+        BanList {
+            path: None,
+            addresses: source.lines().filter_map(|line| line.trim().parse().ok()).collect(),
+        }
+    }
+
+    // The list kept in the config directory, empty if there is none yet.
+    pub fn load() -> BanList {
+        let path = config::config_dir().map(|dir| dir.join("bans"));
+        let source = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        BanList {
+            path,
+            ..BanList::parse(&source)
+        }
+    }
+
+    // Read the list again, as edited by hand.
+    pub fn reload(&mut self) {
+        *self = BanList::load();
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn is_banned(&self, address: IpAddr) -> bool {
+        self.addresses.contains(&address)
+    }
+
+    // Returns whether it wasn't already banned.
+    pub fn ban(&mut self, address: IpAddr) -> Result<bool, String> {
+        if self.is_banned(address) {
+            return Ok(false);
+        }
+        self.addresses.push(address);
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        let source: String = self.addresses.iter().map(|address| format!("{}\n", address)).collect();
+        fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_ban_list() {
+        let mut bans = BanList::parse("10.0.0.1\nnot an address\n::1\n");
+        assert!(bans.is_banned("10.0.0.1".parse().unwrap()));
+        assert!(bans.is_banned("::1".parse().unwrap()));
+        assert!(!bans.is_banned("10.0.0.2".parse().unwrap()));
+        assert_eq!(bans.ban("10.0.0.1".parse().unwrap()), Ok(false));
+
+        let path = env::temp_dir().join("simple_chat_test_bans");
+        bans.path = Some(path.clone());
+        assert_eq!(bans.ban("10.0.0.2".parse().unwrap()), Ok(true));
+        let saved = BanList::parse(&fs::read_to_string(&path).unwrap());
+        assert_eq!(saved.addresses, bans.addresses);
+        let _ = fs::remove_file(&path);
+    }
+}
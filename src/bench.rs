@@ -0,0 +1,154 @@
+use codec::{Frame, FrameStream, MessageId, WireFormat};
+use stats::SessionStats;
+use std::fmt;
+use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+
+// What `simple_chat bench` sends, unless told otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchOptions {
+    pub messages: usize,
+    // Of the text of each message, in bytes.
+    pub size: usize,
+    pub wire_format: WireFormat,
+    // How many messages may be waiting for their ack at once.
+    pub window: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> BenchOptions {
+        BenchOptions {
+            messages: 100_000,
+            size: 512,
+            wire_format: WireFormat::default(),
+            window: 64,
+        }
+    }
+}
+
+pub struct BenchReport {
+    options: BenchOptions,
+    // From the first message sent to the last ack received.
+    elapsed: Duration,
+    // From each message being sent to its ack being received.
+    latencies: SessionStats,
+}
+
+impl BenchReport {
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn messages_per_second(&self) -> f64 {
+        self.options.messages as f64 / self.elapsed.as_secs_f64()
+    }
+
+    // Of the text of the messages, in millions of bytes.
+    pub fn megabytes_per_second(&self) -> f64 {
+        (self.options.messages * self.options.size) as f64 / 1_000_000.0 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", tr!("bench-summary", messages = self.options.messages, size = self.options.size,
+                              format = self.options.wire_format.name(), elapsed = format!("{:.2?}", self.elapsed)))?;
+        write!(f, "  {}", tr!("bench-throughput", messages = format!("{:.0}", self.messages_per_second()),
+                              megabytes = format!("{:.1}", self.megabytes_per_second())))?;
+        if let (Some(min), Some(median), Some(p95), Some(p99), Some(max)) = (self.latencies.roundtrip_percentile(0),
+                                                                             self.latencies.roundtrip_percentile(50),
+                                                                             self.latencies.roundtrip_percentile(95),
+                                                                             self.latencies.roundtrip_percentile(99),
+                                                                             self.latencies.roundtrip_percentile(100)) {
+            write!(f, "\n  {}", tr!("bench-latency", min = format!("{:?}", min), median = format!("{:?}", median),
+                                     p95 = format!("{:?}", p95), p99 = format!("{:?}", p99), max = format!("{:?}", max)))?;
+        }
+        Ok(())
+    }
+}
+
+// Acknowledge each message, as the chat does, until the client is done.
+fn serve(listener: TcpListener) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    let mut stream = FrameStream::accept(stream)?;
+    loop {
+        match stream.read_frame() {
+            Ok((_, Frame::Message { id, .. })) => stream.write_ack(id)?,
+            Ok(_) => continue,
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// Send the messages to a server on the loopback interface, over TCP,
+// the acks being read on another thread as they come.
+pub fn run(options: BenchOptions) -> io::Result<BenchReport> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let server = thread::spawn(move || serve(listener));
+    let stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    let mut writer = FrameStream::connect(stream, options.wire_format)?;
+    let mut reader = writer.try_clone()?;
+    // When each message waiting for its ack was sent, in order, at most `window` of them.
+    let (sent_chan, sent_port) = mpsc::sync_channel::<Instant>(options.window.max(1) - 1);
+    let messages = options.messages;
+    let acks = thread::spawn(move || -> io::Result<SessionStats> {
+        let mut latencies = SessionStats::new();
+        let mut acked = 0;
+        while acked < messages {
+            match reader.read_frame()? {
+                (_, Frame::Ack(_)) => {
+                    let sent = sent_port.recv().map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+                    latencies.record_roundtrip(sent.elapsed());
+                    acked += 1;
+                },
+                _ => continue,
+            }
+        }
+        Ok(latencies)
+    });
+    let text = "x".repeat(options.size);
+    let started = Instant::now();
+    for id in 1..=options.messages {
+        let sent = Instant::now();
+        let frame = Frame::Message { id: id as MessageId, text: text.clone(), signature: None, ttl: None };
+        writer.write_frame(SystemTime::now(), &frame)?;
+        if sent_chan.send(sent).is_err() {
+            break;
+        }
+    }
+    let latencies = acks.join().map_err(|_| io::Error::other("the thread reading the acks panicked"))??;
+    let elapsed = started.elapsed();
+    let _ = writer.get_ref().shutdown(Shutdown::Both);
+    server.join().map_err(|_| io::Error::other("the server panicked"))??;
+    Ok(BenchReport {
+        options,
+        elapsed,
+        latencies,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench() {
+        for format in [WireFormat::Bincode, WireFormat::Json, WireFormat::MessagePack].iter() {
+            let options = BenchOptions { messages: 500, size: 64, wire_format: *format, window: 8 };
+            let report = run(options).unwrap();
+            assert!(report.latencies.roundtrip_percentile(100).is_some());
+            assert!(report.messages_per_second() > 0.0);
+            let summary = report.to_string();
+            assert!(summary.starts_with(&format!("500 messages of 64 bytes, {}, in ", format.name())), "{}", summary);
+            assert!(summary.contains("latency: min "), "{}", summary);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use chrono::{DateTime, Local};
+use codec::{Frame, FrameStream};
+use std::env;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+
+const DEFAULT_WIDTH: usize = 80;
+
+pub type ListenerId = usize;
+
+struct Connected<S> {
+    id: ListenerId,
+    // Once the client gave it, for the operator to whisper to it.
+    nick: Option<String>,
+    stream: Arc<Mutex<FrameStream<S>>>,
+}
+
+struct Clients<S> {
+    next_id: ListenerId,
+    connected: Vec<Connected<S>>,
+}
+
+// The clients of a server in broadcast mode,
+// each shared with the thread reading from it, which refuses their messages.
+pub struct Listeners<S> {
+    clients: Mutex<Clients<S>>,
+}
+
+impl<S> Default for Listeners<S> {
+    fn default() -> Listeners<S> {
+        Listeners {
+            clients: Mutex::new(Clients {
+                next_id: 1,
+                connected: vec![],
+            }),
+        }
+    }
+}
+
+impl<S: Read + Write> Listeners<S> {
+    pub fn add(&self, stream: FrameStream<S>) -> (ListenerId, Arc<Mutex<FrameStream<S>>>) {
+        let mut clients = self.clients.lock().unwrap();
+        let id = clients.next_id;
+        clients.next_id += 1;
+        let stream = Arc::new(Mutex::new(stream));
+        clients.connected.push(Connected {
+            id,
+            nick: None,
+            stream: stream.clone(),
+        });
+        (id, stream)
+    }
+
+    // Returns its previous nickname, if it had given one.
+    pub fn rename(&self, id: ListenerId, nick: String) -> Option<String> {
+        let mut clients = self.clients.lock().unwrap();
+        clients.connected.iter_mut().find(|client| client.id == id).and_then(|client| client.nick.replace(nick))
+    }
+
+    // Returns whether it was still connected, and its nickname, if it had given one.
+    pub fn remove(&self, id: ListenerId) -> (bool, Option<String>) {
+        let mut clients = self.clients.lock().unwrap();
+        match clients.connected.iter().position(|client| client.id == id) {
+            Some(index) => (true, clients.connected.remove(index).nick),
+            None => (false, None),
+        }
+    }
+
+    // Remove those for which `kicked` returns true, returning how many.
+    pub fn remove_where<F: FnMut(&FrameStream<S>) -> bool>(&self, mut kicked: F) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        let count = clients.connected.len();
+        clients.connected.retain(|client| !kicked(&client.stream.lock().unwrap()));
+        count - clients.connected.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap().connected.len()
+    }
+
+    // Returns how many it reached, forgetting those which went away.
+    pub fn send(&self, sent_at: SystemTime, frame: &Frame) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        clients.connected.retain(|client| client.stream.lock().unwrap().write_frame(sent_at, frame).is_ok());
+        clients.connected.len()
+    }
+
+    // Send to all the clients but the one, returning how many it reached, forgetting those which went away.
+    pub fn send_others(&self, id: ListenerId, sent_at: SystemTime, frame: &Frame) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        clients.connected.retain(|client| client.id == id || client.stream.lock().unwrap().write_frame(sent_at, frame).is_ok());
+        clients.connected.iter().filter(|client| client.id != id).count()
+    }
+
+    // Send to the clients with the nickname only, returning how many it reached.
+    pub fn send_to(&self, nick: &str, sent_at: SystemTime, frame: &Frame) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        let mut reached = 0;
+        clients.connected.retain(|client| {
+            if !matches!(client.nick, Some(ref name) if name.eq_ignore_ascii_case(nick)) {
+                return true;
+            }
+            let sent = client.stream.lock().unwrap().write_frame(sent_at, frame).is_ok();
+            if sent {
+                reached += 1;
+            }
+            sent
+        });
+        reached
+    }
+}
+
+// The width of the terminal, as exported by the shell, if it is.
+pub fn terminal_width() -> usize {
+    env::var("COLUMNS").ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(line);
+                line = String::new();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// Across the whole width, between rules, with the date and time it was sent.
+pub fn render_announcement(sent_at: SystemTime, text: &str, width: usize) -> String {
+    let sent_at: DateTime<Local> = sent_at.into();
+    let rule = "=".repeat(width);
+    let mut lines = vec![rule.clone(), format!("{:>width$}", sent_at.format("%Y-%m-%d %H:%M:%S").to_string(), width = width)];
+    lines.extend(wrap(text, width));
+    lines.push(rule);
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::{Presence, WireFormat};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_listeners() {
+        let listeners = Listeners::default();
+        let (first, _) = listeners.add(FrameStream::connect(Cursor::new(vec![]), WireFormat::Json).unwrap());
+        listeners.add(FrameStream::connect(Cursor::new(vec![]), WireFormat::Bincode).unwrap());
+        assert_eq!(listeners.send(SystemTime::now(), &Frame::Announcement("class starts".to_string())), 2);
+        assert_eq!(listeners.remove(first), (true, None));
+        assert_eq!(listeners.remove(first), (false, None));
+        assert_eq!(listeners.len(), 1);
+        assert_eq!(listeners.remove_where(|_| false), 0);
+        assert_eq!(listeners.remove_where(|_| true), 1);
+        assert_eq!(listeners.len(), 0);
+    }
+
+    #[test]
+    fn test_send_to() {
+        let listeners = Listeners::default();
+        let (alice, alice_stream) = listeners.add(FrameStream::connect(Cursor::new(vec![]), WireFormat::Json).unwrap());
+        let (_, bob_stream) = listeners.add(FrameStream::connect(Cursor::new(vec![]), WireFormat::Json).unwrap());
+        let whisper = Frame::Whisper("see me after class".to_string());
+        assert_eq!(listeners.send_to("alice", SystemTime::now(), &whisper), 0);
+        assert_eq!(listeners.rename(alice, "alice".to_string()), None);
+        assert_eq!(listeners.send_to("Alice", SystemTime::now(), &whisper), 1);
+        let written = |stream: &Arc<Mutex<FrameStream<Cursor<Vec<u8>>>>>| stream.lock().unwrap().get_ref().get_ref().len();
+        assert!(written(&alice_stream) > written(&bob_stream));
+
+        // Told of the others only.
+        let (alice_written, bob_written) = (written(&alice_stream), written(&bob_stream));
+        assert_eq!(listeners.rename(alice, "ally".to_string()), Some("alice".to_string()));
+        let renamed = Frame::Presence(Presence::Renamed { from: "alice".to_string(), to: "ally".to_string() });
+        assert_eq!(listeners.send_others(alice, SystemTime::now(), &renamed), 1);
+        assert_eq!(written(&alice_stream), alice_written);
+        assert!(written(&bob_stream) > bob_written);
+        assert_eq!(listeners.remove(alice), (true, Some("ally".to_string())));
+    }
+
+    #[test]
+    fn test_gone_listener_is_forgotten() {
+        let mut buffer = [0; 10];
+        let listeners = Listeners::default();
+        // Room for the wire format, but not for a frame.
+        listeners.add(FrameStream::connect(Cursor::new(&mut buffer[..]), WireFormat::Bincode).unwrap());
+        assert_eq!(listeners.send(SystemTime::now(), &Frame::Announcement("class starts".to_string())), 0);
+        assert_eq!(listeners.len(), 0);
+    }
+
+    #[test]
+    fn test_render_announcement() {
+        let rendered = render_announcement(SystemTime::now(), "the lab is closed\ntoday, see you tomorrow", 12);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "=".repeat(12));
+        assert_eq!(&lines[2..6], &["the lab is", "closed", "today, see", "you tomorrow"]);
+        assert_eq!(lines[6], "=".repeat(12));
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::iter;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::RecvError;
+
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+// What sending on a full channel does.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OverflowPolicy {
+    // Wait for the receiver to catch up.
+    #[default]
+    Block,
+    // Make room by dropping the oldest message.
+    DropOldest,
+    // Drop the new message, failing the send.
+    Error,
+}
+
+impl OverflowPolicy {
+    pub fn parse(name: &str) -> Result<OverflowPolicy, String> {
+        match name {
+            "block" => Ok(OverflowPolicy::Block),
+            "drop-oldest" => Ok(OverflowPolicy::DropOldest),
+            "error" => Ok(OverflowPolicy::Error),
+            _ => Err(format!("unknown overflow policy '{}', use block, drop-oldest or error", name)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelConfig {
+    pub capacity: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> ChannelConfig {
+        ChannelConfig {
+            capacity: DEFAULT_CAPACITY,
+            policy: OverflowPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SendError<T> {
+    // The receiver is gone.
+    Disconnected(T),
+    // The channel is full, and its policy is to fail.
+    Full(T),
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SendError::Disconnected(_) => write!(f, "the receiver is gone"),
+            SendError::Full(_) => write!(f, "the channel is full"),
+        }
+    }
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    // Since the receiver last asked.
+    dropped: usize,
+    senders: usize,
+    receiving: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    config: ChannelConfig,
+}
+
+// Like `std::sync::mpsc::channel`, holding at most `config.capacity` messages.
+pub fn bounded<T>(config: ChannelConfig) -> (Sender<T>, Receiver<T>) {
+    assert!(config.capacity > 0, "a channel needs room for one message");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            dropped: 0,
+            senders: 1,
+            receiving: true,
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        config,
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if !state.receiving {
+                return Err(SendError::Disconnected(msg));
+            }
+            if state.queue.len() < self.shared.config.capacity {
+                break;
+            }
+            match self.shared.config.policy {
+                OverflowPolicy::Block => state = self.shared.not_full.wait(state).unwrap(),
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                },
+                OverflowPolicy::Error => {
+                    state.dropped += 1;
+                    return Err(SendError::Full(msg));
+                },
+            }
+        }
+        state.queue.push_back(msg);
+        self.shared.not_empty.notify_one();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.state.lock().unwrap().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    // Fails once the channel is empty, and all senders are gone.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(msg) = state.queue.pop_front() {
+                self.shared.not_full.notify_one();
+                return Ok(msg);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self.shared.not_empty.wait(state).unwrap();
+        }
+    }
+
+    // The next message, if one is waiting right now.
+    pub fn try_recv(&self) -> Option<T> {
+        let msg = self.shared.state.lock().unwrap().queue.pop_front();
+        if msg.is_some() {
+            self.shared.not_full.notify_one();
+        }
+        msg
+    }
+
+    // Whether no message is waiting, right now.
+    pub fn is_empty(&self) -> bool {
+        self.shared.state.lock().unwrap().queue.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        iter::from_fn(move || self.recv().ok())
+    }
+
+    // How many messages were dropped since the last call, the channel being full.
+    pub fn take_dropped(&self) -> usize {
+        let mut state = self.shared.state.lock().unwrap();
+        let dropped = state.dropped;
+        state.dropped = 0;
+        dropped
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receiving = false;
+        self.shared.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn config(capacity: usize, policy: OverflowPolicy) -> ChannelConfig {
+        ChannelConfig {
+            capacity,
+            policy,
+        }
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        let (chan, port) = bounded(config(2, OverflowPolicy::DropOldest));
+        for msg in 1..=4 {
+            chan.send(msg).unwrap();
+        }
+        assert_eq!(port.take_dropped(), 2);
+        assert_eq!(port.take_dropped(), 0);
+        drop(chan);
+        assert!(!port.is_empty());
+        assert_eq!(port.iter().collect::<Vec<_>>(), vec![3, 4]);
+
+        let (chan, port) = bounded(config(1, OverflowPolicy::Error));
+        chan.send(1).unwrap();
+        assert_eq!(chan.send(2), Err(SendError::Full(2)));
+        assert_eq!(port.take_dropped(), 1);
+        assert_eq!(port.try_recv(), Some(1));
+        assert_eq!(port.try_recv(), None);
+
+        let (chan, port) = bounded(config(1, OverflowPolicy::Block));
+        chan.send(1).unwrap();
+        let sender = thread::spawn(move || chan.send(2));
+        assert_eq!(port.recv(), Ok(1));
+        sender.join().unwrap().unwrap();
+        assert_eq!(port.recv(), Ok(2));
+        assert_eq!(port.take_dropped(), 0);
+        // The sender is gone.
+        assert_eq!(port.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_receiver_gone() {
+        let (chan, port) = bounded(config(1, OverflowPolicy::Block));
+        chan.send(1).unwrap();
+        let sender = thread::spawn(move || chan.send(2));
+        drop(port);
+        assert_eq!(sender.join().unwrap(), Err(SendError::Disconnected(2)));
+        assert_eq!(OverflowPolicy::parse("drop-oldest"), Ok(OverflowPolicy::DropOldest));
+        assert!(OverflowPolicy::parse("drop").is_err());
+    }
+}
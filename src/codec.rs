@@ -0,0 +1,905 @@
+use bincode;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use record::{FrameDirection, Recorder};
+use rmp_serde;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::iter;
+use std::mem;
+use std::str;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use transport::Transport;
+
+
+// When connecting, the client sends the name of the wire format as a line,
+// such as `json`, and both sides then use it for every frame.
+// In binary formats, each frame is preceded by its length, in 4 big-endian bytes,
+// in JSON each frame is a line, so a session can be followed, or typed, with netcat.
+// Beyond this, a peer sending a frame is considered misbehaving.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024;
+const MAX_FORMAT_NAME_SIZE: usize = 16;
+// How much is read from the connection at once, possibly several frames,
+// or the start of the next one, kept for the next read.
+const READ_SIZE: usize = 8 * 1024;
+// Frames which can't be decoded are skipped, the stream being still in step with the peer,
+// until this many come in a row, the peer then being taken for hostile, or broken.
+const MAX_MALFORMED_FRAMES: u32 = 3;
+// The most bytes a binary message can carry, to fit in a frame in any format,
+// JSON taking up to four characters per byte.
+pub const MAX_BINARY_SIZE: usize = (MAX_FRAME_SIZE - 1024) / 4;
+// The most bytes of text a message can carry, to fit in a frame in any format,
+// JSON taking up to six characters per byte for control characters,
+// longer text being split into parts.
+pub const MAX_TEXT_SIZE: usize = (MAX_FRAME_SIZE - 1024) / 6;
+
+// Messages are numbered by their sender, from 1 for each connection,
+// the receiver putting them back in that order.
+pub type MessageId = u32;
+
+// Files are numbered by their sender, from 1 for each connection, apart from the messages.
+pub type TransferId = u64;
+
+// What a message carries: text for the people chatting,
+// or raw bytes for the applications embedding the chat.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Payload {
+    Text(String),
+    // The start of text too long for a frame, going on in the next message.
+    Fragment(String),
+    // Text ending the turn, which neither side keeps, removed after `ttl` seconds.
+    Ephemeral {
+        text: String,
+        ttl: u64,
+    },
+    Binary {
+        mime: Option<String>,
+        data: Vec<u8>,
+    },
+}
+
+impl Payload {
+    // The messages to send it in, text longer than `max_size` bytes being split into fragments,
+    // followed by the end of it, sent as the whole would have been.
+    pub fn split(self, max_size: usize) -> Vec<Payload> {
+        let (text, ttl) = match self {
+            Payload::Text(text) if text.len() > max_size => (text, None),
+            Payload::Ephemeral { text, ttl } if text.len() > max_size => (text, Some(ttl)),
+            payload => return vec![payload],
+        };
+        let mut fragments = split_text(&text, max_size);
+        let end = fragments.pop().unwrap_or_default();
+        let end = match ttl {
+            Some(ttl) => Payload::Ephemeral { text: end, ttl },
+            None => Payload::Text(end),
+        };
+        fragments.into_iter().map(Payload::Fragment).chain(iter::once(end)).collect()
+    }
+}
+
+// Split text into parts of at most `max_size` bytes, on char boundaries.
+fn split_text(text: &str, max_size: usize) -> Vec<String> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    for c in text.chars() {
+        if part.len() + c.len_utf8() > max_size {
+            parts.push(mem::take(&mut part));
+        }
+        part.push(c);
+    }
+    parts.push(part);
+    parts
+}
+
+// How long to wait for the `Ack` of a message, and how many times to send it again
+// before giving up on the peer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AckPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl Default for AckPolicy {
+    fn default() -> AckPolicy {
+        AckPolicy {
+            timeout: Duration::from_secs(10),
+            retries: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AuthMethod {
+    // Answer with a HMAC-SHA256 of the nonce, keyed with the shared password.
+    Proof,
+    // Answer with a user name and password.
+    Credentials,
+}
+
+// Someone coming into the chat, leaving it, or changing their nickname.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Presence {
+    Joined(String),
+    Left(String),
+    Renamed {
+        from: String,
+        to: String,
+    },
+}
+
+// What is sent over the wire, each frame along with the sender's timestamp.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Frame {
+    // A chat message, acknowledged on receipt, ending the sender's turn,
+    // signed with the sender's identity key if it has one.
+    Message {
+        id: MessageId,
+        text: String,
+        #[serde(default)]
+        signature: Option<Vec<u8>>,
+        // For an ephemeral message, the seconds after which the receiver removes it, never keeping it.
+        #[serde(default)]
+        ttl: Option<u64>,
+    },
+    // A chat message followed by more in the same turn, acknowledged on receipt.
+    MessagePart {
+        id: MessageId,
+        text: String,
+        #[serde(default)]
+        signature: Option<Vec<u8>>,
+        // The start of a message too long for a frame, its text going on in the next one.
+        #[serde(default)]
+        continued: bool,
+    },
+    // Raw bytes, with their MIME type if known, acknowledged on receipt,
+    // never ending the sender's turn, numbered along with the chat messages.
+    Binary {
+        id: MessageId,
+        mime: Option<String>,
+        data: Vec<u8>,
+    },
+    // The receipt of the message with the id, sent again if the message is,
+    // the sender having not received the first one in time.
+    Ack(MessageId),
+    // Sent once the UI has processed the message with the id, not acknowledged.
+    Processed(MessageId),
+    // Sent once the user has seen the message with the id, unless they opted out with
+    // the `read_receipts_sent` setting, not acknowledged.
+    Read(MessageId),
+    // Authentication, before the handshake message is sent:
+    // the server sends a random nonce, and the client answers as asked by the method,
+    // or with a resume token, the server closing the connection with a rejection
+    // if the answer isn't valid.
+    Challenge {
+        nonce: Vec<u8>,
+        method: AuthMethod,
+    },
+    Proof(Vec<u8>),
+    Credentials {
+        user: String,
+        password: String,
+    },
+    Rejected,
+    // Sent while composing a reply, not acknowledged.
+    Typing,
+    // The sender's new nickname, not acknowledged.
+    Nick(String),
+    // Sent by the server after authentication, a token the client can answer
+    // a later challenge with, not acknowledged.
+    // If the token isn't valid, the server sends a new challenge.
+    ResumeToken(String),
+    // Retracts a message previously sent by the sender, not acknowledged.
+    Delete(MessageId),
+    // An emoji reaction to a message previously sent by the receiver, not acknowledged.
+    Reaction {
+        id: MessageId,
+        emoji: String,
+    },
+    // The sender's long-term ed25519 public key, sent once per connection, not acknowledged.
+    Identity(Vec<u8>),
+    // The longest the sender accepts the session to last, in seconds,
+    // the shortest asked for by either side being agreed on, not acknowledged.
+    SessionLimit(u64),
+    // Sent by a server in support-queue mode to a client waiting for the operator,
+    // its position in the queue, from 1, not acknowledged.
+    QueuePosition(u32),
+    // Sent by a server in broadcast mode to all its clients, which never get a turn,
+    // not acknowledged.
+    Announcement(String),
+    // The refusal of a message, in place of its `Ack`, with why.
+    Nack(String),
+    // Sent by a server in broadcast mode to one of its clients only, not acknowledged.
+    Whisper(String),
+    // The server's message of the day, its first message to the client once authenticated,
+    // acknowledged and ending its turn as a message does, but shown apart from the chat.
+    System {
+        id: MessageId,
+        text: String,
+    },
+    // Sent by each peer in p2p mode once connected, a random number,
+    // the peer with the highest speaking first, and both sending another on a tie.
+    Toss(u64),
+    // A file follows in chunks, during the sender's turn, which ends only once they were all sent,
+    // answered with `FileAccept`, or a `Nack` if the receiver can't take it.
+    // The SHA-256 of the file identifies it, for an interrupted transfer to be resumed.
+    FileStart {
+        transfer: TransferId,
+        name: String,
+        size: u64,
+        hash: Vec<u8>,
+    },
+    // Where the chunks are to start from, past those received before the transfer was interrupted,
+    // a whole number of chunks.
+    FileAccept {
+        transfer: TransferId,
+        offset: u64,
+    },
+    // The sender gave up on the file, the receiver removing what it got of it, not acknowledged.
+    FileCancel(TransferId),
+    // Part of a file, numbered from 0, chat frames going in between, not acknowledged.
+    Chunk {
+        transfer: TransferId,
+        seq: u64,
+        data: Vec<u8>,
+    },
+    // Sent by a server in support-queue or broadcast mode to a client beyond its `--max-clients`,
+    // with that number, before closing the connection.
+    ServerFull(u32),
+    // Sent during the sender's turn to measure the link, numbered, answered at once with a `Pong`
+    // of the same number, neither acknowledged.
+    Ping(u64),
+    Pong(u64),
+    // Sent by a server to a client sending faster than its rate limit allows, before the ack
+    // of the message, with how long until it may send again, and whether the message was
+    // dropped rather than delayed, not acknowledged.
+    Throttled {
+        id: MessageId,
+        retry_after_ms: u64,
+        dropped: bool,
+    },
+    // Sent by a server in broadcast mode to its other clients as one comes, goes or is renamed,
+    // shown apart from the chat, not acknowledged.
+    Presence(Presence),
+}
+
+impl Frame {
+    // The name of its kind, such as in errors.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Frame::Message { .. } => "Message",
+            Frame::MessagePart { .. } => "MessagePart",
+            Frame::Binary { .. } => "Binary",
+            Frame::Ack(_) => "Ack",
+            Frame::Processed(_) => "Processed",
+            Frame::Read(_) => "Read",
+            Frame::Challenge { .. } => "Challenge",
+            Frame::Proof(_) => "Proof",
+            Frame::Credentials { .. } => "Credentials",
+            Frame::Rejected => "Rejected",
+            Frame::Typing => "Typing",
+            Frame::Nick(_) => "Nick",
+            Frame::ResumeToken(_) => "ResumeToken",
+            Frame::Delete(_) => "Delete",
+            Frame::Reaction { .. } => "Reaction",
+            Frame::Identity(_) => "Identity",
+            Frame::SessionLimit(_) => "SessionLimit",
+            Frame::QueuePosition(_) => "QueuePosition",
+            Frame::Announcement(_) => "Announcement",
+            Frame::Nack(_) => "Nack",
+            Frame::Whisper(_) => "Whisper",
+            Frame::System { .. } => "System",
+            Frame::Toss(_) => "Toss",
+            Frame::FileStart { .. } => "FileStart",
+            Frame::FileAccept { .. } => "FileAccept",
+            Frame::FileCancel(_) => "FileCancel",
+            Frame::Chunk { .. } => "Chunk",
+            Frame::ServerFull(_) => "ServerFull",
+            Frame::Ping(_) => "Ping",
+            Frame::Pong(_) => "Pong",
+            Frame::Throttled { .. } => "Throttled",
+            Frame::Presence(_) => "Presence",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WireFormat {
+    #[default]
+    Bincode,
+    Json,
+    MessagePack,
+}
+
+impl WireFormat {
+    pub fn parse(name: &str) -> Result<WireFormat, String> {
+        match name {
+            "bincode" => Ok(WireFormat::Bincode),
+            "json" => Ok(WireFormat::Json),
+            "msgpack" => Ok(WireFormat::MessagePack),
+            _ => Err(format!("unknown wire format '{}', use bincode, json or msgpack", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WireFormat::Bincode => "bincode",
+            WireFormat::Json => "json",
+            WireFormat::MessagePack => "msgpack",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OutgoingFrame<'a> {
+    // Milliseconds since the unix epoch.
+    sent_at: u64,
+    frame: &'a Frame,
+}
+
+#[derive(Deserialize)]
+struct IncomingFrame {
+    sent_at: u64,
+    frame: Frame,
+}
+
+fn invalid_data<E: ToString>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+// How the peer broke the protocol, held by the `InvalidData` errors of reading from it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtocolViolation {
+    // The name of the wire format, when connecting.
+    UnknownFormat(String),
+    FormatMismatch {
+        theirs: String,
+        ours: &'static str,
+    },
+    NotUtf8,
+    LineTooLong(usize),
+    // The size announced, with no telling whether it is a size at all.
+    FrameTooLong(usize),
+    // A frame read whole, but which couldn't be decoded.
+    Undecodable(String),
+    TimestampOutOfRange(u64),
+    TooManyMalformed(u32),
+}
+
+impl ProtocolViolation {
+    // The violation behind the error, if it was one.
+    pub fn of(error: &io::Error) -> Option<&ProtocolViolation> {
+        error.get_ref().and_then(|error| error.downcast_ref())
+    }
+
+    // Whether the frame was skipped, the next one being read as if it hadn't been sent,
+    // rather than the stream being out of step with the peer.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(*self, ProtocolViolation::Undecodable(_) | ProtocolViolation::TimestampOutOfRange(_))
+    }
+}
+
+impl fmt::Display for ProtocolViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProtocolViolation::UnknownFormat(ref name) => {
+                write!(f, "unknown wire format '{}', use bincode, json or msgpack", name)
+            },
+            ProtocolViolation::FormatMismatch { ref theirs, ours } => {
+                write!(f, "the peer uses the {} wire format, not {}", theirs, ours)
+            },
+            ProtocolViolation::NotUtf8 => write!(f, "the name of the wire format isn't UTF-8"),
+            ProtocolViolation::LineTooLong(max) => write!(f, "line longer than {} bytes", max),
+            ProtocolViolation::FrameTooLong(size) => {
+                write!(f, "frame of {} bytes, longer than {} bytes", size, MAX_FRAME_SIZE)
+            },
+            ProtocolViolation::Undecodable(ref error) => write!(f, "undecodable frame: {}", error),
+            ProtocolViolation::TimestampOutOfRange(sent_at) => write!(f, "timestamp out of range: {}", sent_at),
+            ProtocolViolation::TooManyMalformed(count) => write!(f, "{} malformed frames in a row", count),
+        }
+    }
+}
+
+impl error::Error for ProtocolViolation {}
+
+impl From<ProtocolViolation> for io::Error {
+    fn from(violation: ProtocolViolation) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, violation)
+    }
+}
+
+// A connection, sending and receiving frames in the format agreed on.
+pub struct FrameStream<S> {
+    stream: S,
+    format: WireFormat,
+    // With the number of the connection in the log.
+    recorder: Option<(Recorder, u32)>,
+    // Of the frames read last, how many in a row couldn't be decoded.
+    malformed: u32,
+    // Read from the peer, and not decoded yet, the frames being cut out of it without copying,
+    // its memory reused once they were decoded.
+    read_buffer: BytesMut,
+    // Each frame sent is encoded in it, along with its length, and written at once.
+    write_buffer: BytesMut,
+}
+
+impl<S: Read + Write> FrameStream<S> {
+    fn new(stream: S, format: WireFormat) -> FrameStream<S> {
+        FrameStream {
+            stream,
+            format,
+            recorder: None,
+            malformed: 0,
+            read_buffer: BytesMut::with_capacity(READ_SIZE),
+            write_buffer: BytesMut::new(),
+        }
+    }
+
+    pub fn connect(mut stream: S, format: WireFormat) -> io::Result<FrameStream<S>> {
+        stream.write_all(format!("{}\n", format.name()).as_bytes())?;
+        stream.flush()?;
+        Ok(FrameStream::new(stream, format))
+    }
+
+    // Fails with `InvalidData` if the client asked for an unknown format.
+    pub fn accept(stream: S) -> io::Result<FrameStream<S>> {
+        let mut stream = FrameStream::new(stream, WireFormat::default());
+        let name = stream.read_format_name()?;
+        stream.format = WireFormat::parse(&name).map_err(|_| ProtocolViolation::UnknownFormat(name))?;
+        Ok(stream)
+    }
+
+    // In p2p mode, both peers send the name of their wire format, and read that of the other,
+    // failing with `InvalidData` if they differ.
+    pub fn pair(stream: S, format: WireFormat) -> io::Result<FrameStream<S>> {
+        let mut stream = FrameStream::connect(stream, format)?;
+        let theirs = stream.read_format_name()?;
+        if theirs != format.name() {
+            return Err(ProtocolViolation::FormatMismatch { theirs, ours: format.name() }.into());
+        }
+        Ok(stream)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    // Record the frames sent and received from now on, as a new connection of the log.
+    pub fn record(&mut self, recorder: &Recorder) {
+        self.recorder = Some((recorder.clone(), recorder.connection()));
+    }
+
+    fn log(&self, direction: FrameDirection, frame: &Frame) {
+        if let Some((ref recorder, connection)) = self.recorder {
+            recorder.record(connection, direction, frame);
+        }
+    }
+
+    // Read what the peer sent since, after what is already in the buffer,
+    // failing with `UnexpectedEof` if it disconnected.
+    fn fill(&mut self) -> io::Result<()> {
+        let start = self.read_buffer.len();
+        self.read_buffer.resize(start + READ_SIZE, 0);
+        let read = loop {
+            match self.stream.read(&mut self.read_buffer[start..]) {
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                read => break read,
+            }
+        };
+        self.read_buffer.truncate(start + *read.as_ref().unwrap_or(&0));
+        match read? {
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // A line, without its line ending, left in the buffer until whole,
+    // for a read timing out in the middle of it to be picked up by the next.
+    fn read_line(&mut self, max_size: usize) -> io::Result<Bytes> {
+        let mut scanned = 0;
+        loop {
+            if let Some(end) = self.read_buffer[scanned..].iter().position(|&byte| byte == b'\n') {
+                let end = scanned + end;
+                if end > max_size {
+                    break;
+                }
+                let line = self.read_buffer.split_to(end + 1).freeze();
+                return Ok(line.slice(..end));
+            }
+            scanned = self.read_buffer.len();
+            if scanned > max_size {
+                break;
+            }
+            self.fill()?;
+        }
+        Err(ProtocolViolation::LineTooLong(max_size).into())
+    }
+
+    // The bytes of a frame, after its length, once they were all received.
+    fn read_sized(&mut self) -> io::Result<Bytes> {
+        while self.read_buffer.len() < 4 {
+            self.fill()?;
+        }
+        let size = u32::from_be_bytes([self.read_buffer[0], self.read_buffer[1], self.read_buffer[2], self.read_buffer[3]]) as usize;
+        if size > MAX_FRAME_SIZE {
+            return Err(ProtocolViolation::FrameTooLong(size).into());
+        }
+        while self.read_buffer.len() < 4 + size {
+            self.fill()?;
+        }
+        self.read_buffer.advance(4);
+        Ok(self.read_buffer.split_to(size).freeze())
+    }
+
+    // The name of the wire format, from its line, strictly UTF-8 rather than made up of replacement characters.
+    fn read_format_name(&mut self) -> io::Result<String> {
+        let name = self.read_line(MAX_FORMAT_NAME_SIZE)?;
+        let name = str::from_utf8(&name).map_err(|_| ProtocolViolation::NotUtf8)?;
+        Ok(name.trim().to_string())
+    }
+
+    pub fn write_frame(&mut self, sent_at: SystemTime, frame: &Frame) -> io::Result<()> {
+        let outgoing = OutgoingFrame {
+            sent_at: sent_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            frame,
+        };
+        self.write_buffer.clear();
+        if self.format != WireFormat::Json {
+            // The length, once known.
+            self.write_buffer.put_u32(0);
+        }
+        let mut writer = (&mut self.write_buffer).writer();
+        match self.format {
+            WireFormat::Bincode => bincode::serialize_into(&mut writer, &outgoing).map_err(invalid_data)?,
+            WireFormat::MessagePack => rmp_serde::encode::write_named(&mut writer, &outgoing).map_err(invalid_data)?,
+            WireFormat::Json => {
+                serde_json::to_writer(&mut writer, &outgoing)?;
+                self.write_buffer.put_u8(b'\n');
+            },
+        }
+        if self.format != WireFormat::Json {
+            let size = (self.write_buffer.len() - 4) as u32;
+            self.write_buffer[..4].copy_from_slice(&size.to_be_bytes());
+        }
+        self.stream.write_all(&self.write_buffer)?;
+        self.stream.flush()?;
+        self.log(FrameDirection::Sent, frame);
+        Ok(())
+    }
+
+    // Fails with `UnexpectedEof` if the peer disconnected,
+    // and `InvalidData` if it sent too much, or something which isn't a frame,
+    // whatever the bytes received, as fuzzed by `fuzz/`.
+    // The error then holds a `ProtocolViolation`, after which reading can go on if it is recoverable.
+    pub fn read_frame(&mut self) -> io::Result<(SystemTime, Frame)> {
+        let bytes = match self.format {
+            WireFormat::Json => self.read_line(MAX_FRAME_SIZE)?,
+            WireFormat::Bincode | WireFormat::MessagePack => self.read_sized()?,
+        };
+        let decoded = match self.format {
+            WireFormat::Bincode => bincode::deserialize::<IncomingFrame>(&bytes).map_err(|error| error.to_string()),
+            WireFormat::Json => serde_json::from_slice(&bytes).map_err(|error| error.to_string()),
+            WireFormat::MessagePack => rmp_serde::from_slice(&bytes).map_err(|error| error.to_string()),
+        };
+        let decoded = decoded.map_err(ProtocolViolation::Undecodable).and_then(|IncomingFrame { sent_at, frame }| {
+            UNIX_EPOCH.checked_add(Duration::from_millis(sent_at))
+                .map(|sent_at| (sent_at, frame))
+                .ok_or(ProtocolViolation::TimestampOutOfRange(sent_at))
+        });
+        let (sent_at, frame) = match decoded {
+            Ok(decoded) => decoded,
+            Err(violation) => {
+                self.malformed += 1;
+                if self.malformed >= MAX_MALFORMED_FRAMES {
+                    return Err(ProtocolViolation::TooManyMalformed(self.malformed).into());
+                }
+                return Err(violation.into());
+            },
+        };
+        self.malformed = 0;
+        self.log(FrameDirection::Received, &frame);
+        Ok((sent_at, frame))
+    }
+
+    pub fn write_ack(&mut self, id: MessageId) -> io::Result<()> {
+        self.write_frame(SystemTime::now(), &Frame::Ack(id))
+    }
+
+    // Returns whether the message was acknowledged, rather than refused,
+    // skipping the late acks of earlier messages.
+    pub fn read_ack(&mut self, id: MessageId) -> io::Result<bool> {
+        loop {
+            match self.read_frame()?.1 {
+                Frame::Ack(acked) if acked < id => continue,
+                Frame::Ack(acked) => return Ok(acked == id),
+                _ => return Ok(false),
+            }
+        }
+    }
+}
+
+impl<S: Transport> FrameStream<S> {
+    // Another handle to the same connection, to read from one thread while writing from another,
+    // what was read ahead going to it too, for the handle reading on.
+    pub fn try_clone(&self) -> io::Result<FrameStream<S>> {
+        Ok(FrameStream {
+            stream: self.stream.try_clone()?,
+            format: self.format,
+            recorder: self.recorder.clone(),
+            malformed: 0,
+            read_buffer: BytesMut::from(&self.read_buffer[..]),
+            write_buffer: BytesMut::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let text = "a message which would not have fit in a single frame before, é included,\nover two lines";
+        for format in [WireFormat::Bincode, WireFormat::Json, WireFormat::MessagePack].iter() {
+            let mut client = FrameStream::connect(Cursor::new(vec![]), *format).unwrap();
+            client.write_frame(SystemTime::now(), &Frame::Message { id: 7, text: text.to_string(), signature: Some(vec![1; 64]), ttl: Some(60) }).unwrap();
+            client.write_frame(SystemTime::now(), &Frame::Processed(7)).unwrap();
+            client.write_frame(SystemTime::now(), &Frame::Read(7)).unwrap();
+            let data = vec![0, 0x89, b'P', b'N', b'G', 0xff];
+            client.write_frame(SystemTime::now(), &Frame::Binary { id: 8, mime: Some("image/png".to_string()), data: data.clone() }).unwrap();
+            client.stream.set_position(0);
+
+            let mut server = FrameStream::accept(client.stream).unwrap();
+            assert_eq!(server.format, *format);
+            let (sent_at, frame) = server.read_frame().unwrap();
+            assert_eq!(frame, Frame::Message { id: 7, text: text.to_string(), signature: Some(vec![1; 64]), ttl: Some(60) });
+            assert!(sent_at.elapsed().unwrap() < Duration::from_secs(5));
+            assert_eq!(server.read_frame().unwrap().1, Frame::Processed(7));
+            assert_eq!(server.read_frame().unwrap().1, Frame::Read(7));
+            assert_eq!(server.read_frame().unwrap().1, Frame::Binary { id: 8, mime: Some("image/png".to_string()), data });
+            assert_eq!(server.read_frame().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        // Browsers don't sign their messages.
+        let mut stream = FrameStream::accept(Cursor::new(b"json\n{\"sent_at\": 0, \"frame\": {\"Message\": {\"id\": 1, \"text\": \"hi\"}}}\n".to_vec())).unwrap();
+        assert_eq!(stream.read_frame().unwrap().1, Frame::Message { id: 1, text: "hi".to_string(), signature: None, ttl: None });
+    }
+
+    #[test]
+    fn test_read_ack() {
+        let mut client = FrameStream::connect(Cursor::new(vec![]), WireFormat::Bincode).unwrap();
+        for frame in [Frame::Ack(1), Frame::Ack(2), Frame::Nack("refused".to_string())].iter() {
+            client.write_frame(SystemTime::now(), frame).unwrap();
+        }
+        client.stream.set_position(0);
+
+        let mut server = FrameStream::accept(client.stream).unwrap();
+        assert!(server.read_ack(2).unwrap());
+        assert!(!server.read_ack(3).unwrap());
+        assert_eq!(server.read_ack(3).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let stream = Cursor::new(b"xml\n".to_vec());
+        assert_eq!(FrameStream::accept(stream).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        let mut stream = FrameStream::accept(Cursor::new(b"json\n{\"sent_at\": 0}\n".to_vec())).unwrap();
+        assert_eq!(stream.read_frame().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut stream = FrameStream::accept(Cursor::new(b"bincode\n\xff\xff\xff\xff".to_vec())).unwrap();
+        assert_eq!(stream.read_frame().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    // Gives what was written in the pieces given, timing out in between.
+    struct Trickle {
+        pieces: Vec<Vec<u8>>,
+        timed_out: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.timed_out = !self.timed_out;
+            if self.timed_out {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            if self.pieces.is_empty() {
+                return Ok(0);
+            }
+            let piece = self.pieces.remove(0);
+            buffer[..piece.len()].copy_from_slice(&piece);
+            Ok(piece.len())
+        }
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            Ok(buffer.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_partial_reads() {
+        for format in [WireFormat::Bincode, WireFormat::Json, WireFormat::MessagePack].iter() {
+            let mut client = FrameStream::connect(Cursor::new(vec![]), *format).unwrap();
+            for id in 1..=3 {
+                client.write_frame(SystemTime::now(), &Frame::Message { id, text: "é".repeat(10), signature: None, ttl: None }).unwrap();
+            }
+            // The format line and the first two frames at once, the last one a byte at a time.
+            let written = client.stream.into_inner();
+            let last = written.len() - written.len() / 3 + 1;
+            let mut pieces = vec![written[..last].to_vec()];
+            pieces.extend(written[last..].iter().map(|&byte| vec![byte]));
+            let mut stream = FrameStream::accept(Trickle { pieces, timed_out: true }).unwrap();
+            let mut read = vec![];
+            while read.len() < 3 {
+                match stream.read_frame() {
+                    Ok((_, Frame::Message { id, .. })) => read.push(id),
+                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => continue,
+                    result => panic!("{:?}", result.map(|(_, frame)| frame)),
+                }
+            }
+            // Picked up after each timeout, where it was.
+            assert_eq!(read, vec![1, 2, 3]);
+            assert_eq!(stream.read_buffer.len(), 0);
+        }
+    }
+
+    fn violation<T>(result: io::Result<T>) -> ProtocolViolation {
+        let error = result.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        ProtocolViolation::of(&error).unwrap().clone()
+    }
+
+    #[test]
+    fn test_corrupted_stream() {
+        assert_eq!(violation(FrameStream::accept(Cursor::new(b"js\xffon\n".to_vec()))), ProtocolViolation::NotUtf8);
+        assert_eq!(violation(FrameStream::accept(Cursor::new(b"xml\n".to_vec()))), ProtocolViolation::UnknownFormat("xml".to_string()));
+        assert_eq!(violation(FrameStream::accept(Cursor::new(vec![b'j'; 100]))), ProtocolViolation::LineTooLong(MAX_FORMAT_NAME_SIZE));
+
+        // Garbage between two frames is skipped, the next frame being read as sent.
+        let mut input = b"json\n\x00\xff garbage\n{\"sent_at\": 0, \"frame\": \"Typing\"}\n".to_vec();
+        input.extend(b"{\"sent_at\": 0, \"frame\": {\"Message\": {\"id\": 1, \"text\": \"caf\xe9\"}}}\n");
+        let mut stream = FrameStream::accept(Cursor::new(input)).unwrap();
+        assert!(matches!(violation(stream.read_frame()), ProtocolViolation::Undecodable(_)));
+        assert_eq!(stream.read_frame().unwrap().1, Frame::Typing);
+        // Text which isn't UTF-8 is refused, rather than turned into replacement characters.
+        assert!(violation(stream.read_frame()).is_recoverable());
+        assert_eq!(stream.read_frame().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut client = FrameStream::connect(Cursor::new(vec![]), WireFormat::Bincode).unwrap();
+        client.stream.write_all(&[0, 0, 0, 3, 0xff, 0xff, 0xff]).unwrap();
+        client.write_frame(SystemTime::now(), &Frame::Processed(7)).unwrap();
+        for _ in 0..MAX_MALFORMED_FRAMES {
+            client.stream.write_all(&[0, 0, 0, 1, 0xff]).unwrap();
+        }
+        client.stream.write_all(&[0, 1, 0, 1]).unwrap();
+        client.stream.set_position(0);
+        let mut server = FrameStream::accept(client.stream).unwrap();
+        assert!(violation(server.read_frame()).is_recoverable());
+        assert_eq!(server.read_frame().unwrap().1, Frame::Processed(7));
+        for _ in 1..MAX_MALFORMED_FRAMES {
+            assert!(violation(server.read_frame()).is_recoverable());
+        }
+        // Until the peer sends nothing else.
+        let too_many = violation(server.read_frame());
+        assert_eq!(too_many, ProtocolViolation::TooManyMalformed(MAX_MALFORMED_FRAMES));
+        assert!(!too_many.is_recoverable());
+        assert_eq!(violation(server.read_frame()), ProtocolViolation::FrameTooLong(65537));
+    }
+
+    #[test]
+    fn test_split_payload() {
+        let text = "aébc".to_string();
+        assert_eq!(Payload::Text(text.clone()).split(5), vec![Payload::Text(text.clone())]);
+        // Never in the middle of a char.
+        assert_eq!(Payload::Text(text.clone()).split(2), vec![
+            Payload::Fragment("a".to_string()),
+            Payload::Fragment("é".to_string()),
+            Payload::Text("bc".to_string()),
+        ]);
+        assert_eq!(Payload::Ephemeral { text, ttl: 60 }.split(3), vec![
+            Payload::Fragment("aé".to_string()),
+            Payload::Ephemeral { text: "bc".to_string(), ttl: 60 },
+        ]);
+        let data = vec![0; 10];
+        assert_eq!(Payload::Binary { mime: None, data: data.clone() }.split(2), vec![Payload::Binary { mime: None, data }]);
+    }
+
+    fn any_frame() -> impl Strategy<Value = Frame> {
+        let method = prop_oneof![Just(AuthMethod::Proof), Just(AuthMethod::Credentials)];
+        prop_oneof![
+            (any::<MessageId>(), ".*", proptest::option::of(any::<Vec<u8>>()), proptest::option::of(any::<u64>()))
+                .prop_map(|(id, text, signature, ttl)| Frame::Message { id, text, signature, ttl }),
+            (any::<MessageId>(), ".*", proptest::option::of(any::<Vec<u8>>()), any::<bool>())
+                .prop_map(|(id, text, signature, continued)| Frame::MessagePart { id, text, signature, continued }),
+            (any::<MessageId>(), proptest::option::of(".*"), any::<Vec<u8>>())
+                .prop_map(|(id, mime, data)| Frame::Binary { id, mime, data }),
+            any::<MessageId>().prop_map(Frame::Ack),
+            any::<MessageId>().prop_map(Frame::Processed),
+            any::<MessageId>().prop_map(Frame::Read),
+            (any::<Vec<u8>>(), method).prop_map(|(nonce, method)| Frame::Challenge { nonce, method }),
+            any::<Vec<u8>>().prop_map(Frame::Proof),
+            (".*", ".*").prop_map(|(user, password)| Frame::Credentials { user, password }),
+            Just(Frame::Rejected),
+            Just(Frame::Typing),
+            ".*".prop_map(Frame::Nick),
+            ".*".prop_map(Frame::ResumeToken),
+            any::<MessageId>().prop_map(Frame::Delete),
+            (any::<MessageId>(), ".*").prop_map(|(id, emoji)| Frame::Reaction { id, emoji }),
+            any::<Vec<u8>>().prop_map(Frame::Identity),
+            any::<u64>().prop_map(Frame::SessionLimit),
+            any::<u32>().prop_map(Frame::QueuePosition),
+            ".*".prop_map(Frame::Announcement),
+            ".*".prop_map(Frame::Nack),
+            ".*".prop_map(Frame::Whisper),
+            (any::<MessageId>(), ".*").prop_map(|(id, text)| Frame::System { id, text }),
+            any::<u64>().prop_map(Frame::Toss),
+            (any::<TransferId>(), ".*", any::<u64>(), any::<Vec<u8>>())
+                .prop_map(|(transfer, name, size, hash)| Frame::FileStart { transfer, name, size, hash }),
+            (any::<TransferId>(), any::<u64>()).prop_map(|(transfer, offset)| Frame::FileAccept { transfer, offset }),
+            any::<TransferId>().prop_map(Frame::FileCancel),
+            (any::<TransferId>(), any::<u64>(), any::<Vec<u8>>()).prop_map(|(transfer, seq, data)| Frame::Chunk { transfer, seq, data }),
+            any::<u32>().prop_map(Frame::ServerFull),
+            any::<u64>().prop_map(Frame::Ping),
+            any::<u64>().prop_map(Frame::Pong),
+            (any::<MessageId>(), any::<u64>(), any::<bool>())
+                .prop_map(|(id, retry_after_ms, dropped)| Frame::Throttled { id, retry_after_ms, dropped }),
+            prop_oneof![
+                ".*".prop_map(Presence::Joined),
+                ".*".prop_map(Presence::Left),
+                (".*", ".*").prop_map(|(from, to)| Presence::Renamed { from, to }),
+            ].prop_map(Frame::Presence),
+        ]
+    }
+
+    fn any_format() -> impl Strategy<Value = WireFormat> {
+        prop_oneof![Just(WireFormat::Bincode), Just(WireFormat::Json), Just(WireFormat::MessagePack)]
+    }
+
+    proptest! {
+        #[test]
+        fn test_frames_roundtrip(format in any_format(), frames in proptest::collection::vec(any_frame(), 1..5),
+                                 sent_at in 0..u64::from(u32::MAX)) {
+            let sent_at = UNIX_EPOCH + Duration::from_millis(sent_at);
+            let mut client = FrameStream::connect(Cursor::new(vec![]), format).unwrap();
+            for frame in frames.iter() {
+                client.write_frame(sent_at, frame).unwrap();
+            }
+            client.stream.set_position(0);
+
+            let mut server = FrameStream::accept(client.stream).unwrap();
+            for frame in frames {
+                prop_assert_eq!(server.read_frame().unwrap(), (sent_at, frame));
+            }
+            prop_assert_eq!(server.read_frame().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        // Whatever the peer sends, reading fails with one of the documented errors, rather than panicking.
+        #[test]
+        fn test_arbitrary_bytes(format in any_format(), bytes in any::<Vec<u8>>(), handshake in any::<Vec<u8>>()) {
+            let expected = |error: io::Error| matches!(error.kind(), io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof);
+            if let Err(error) = FrameStream::accept(Cursor::new(handshake)) {
+                prop_assert!(expected(error));
+            }
+            let mut input = format!("{}\n", format.name()).into_bytes();
+            input.extend(bytes);
+            let mut stream = FrameStream::accept(Cursor::new(input)).unwrap();
+            let error = loop {
+                match stream.read_frame() {
+                    Err(ref error) if ProtocolViolation::of(error).is_some_and(ProtocolViolation::is_recoverable) => {},
+                    Err(error) => break error,
+                    Ok(_) => {},
+                }
+            };
+            prop_assert!(expected(error));
+        }
+    }
+}
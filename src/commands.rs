@@ -0,0 +1,119 @@
+// Slash commands, typed in place of a message, such as `/nick alice`.
+// Input starting with `//` is sent as a message, without the first slash.
+
+pub enum CommandOutcome {
+    // Show this to the user, and prompt again.
+    Reply(String),
+    // Show this to the user, and stop prompting, without sending a message.
+    EndTurn(String),
+    Quit,
+}
+
+pub type CommandHandler<S> = fn(&mut S, &[&str]) -> Result<CommandOutcome, String>;
+
+struct Command<S> {
+    name: &'static str,
+    usage: &'static str,
+    help: String,
+    handler: CommandHandler<S>,
+}
+
+// Commands acting on some UI state `S`, with a built-in `/help`.
+pub struct CommandRegistry<S> {
+    commands: Vec<Command<S>>,
+}
+
+pub enum Input<'a> {
+    Message(&'a str),
+    Command(&'a str, Vec<&'a str>),
+}
+
+pub fn parse(input: &str) -> Input<'_> {
+    if input.starts_with("//") {
+        return Input::Message(&input[1..]);
+    }
+    if !input.starts_with('/') {
+        return Input::Message(input);
+    }
+    let mut words = input[1..].split_whitespace();
+    let name = words.next().unwrap_or("");
+    Input::Command(name, words.collect())
+}
+
+impl<S> CommandRegistry<S> {
+    pub fn new() -> CommandRegistry<S> {
+        CommandRegistry {
+            commands: vec![],
+        }
+    }
+
+    pub fn register(&mut self,
+                    name: &'static str,
+                    usage: &'static str,
+                    help: String,
+                    handler: CommandHandler<S>) {
+        self.commands.retain(|command| command.name != name);
+        self.commands.push(Command {
+            name,
+            usage,
+            help,
+            handler,
+        });
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = tr!("commands");
+        for command in self.commands.iter() {
+            help.push_str(&format!("\n  /{} {} - {}", command.name, command.usage, command.help));
+        }
+        help.push_str(&format!("\n  /help - {}", tr!("help-help")));
+        help
+    }
+
+    pub fn run(&self, state: &mut S, name: &str, arguments: &[&str]) -> Result<CommandOutcome, String> {
+        if name == "help" {
+            return Ok(CommandOutcome::Reply(self.help()));
+        }
+        match self.commands.iter().find(|command| command.name == name) {
+            Some(command) => (command.handler)(state, arguments),
+            None => Err(tr!("unknown-command", name = name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(counter: &mut usize, arguments: &[&str]) -> Result<CommandOutcome, String> {
+        match arguments {
+            [by] => {
+                *counter += by.parse::<usize>().map_err(|error| error.to_string())?;
+                Ok(CommandOutcome::Reply(counter.to_string()))
+            },
+            _ => Err("usage: /count <by>".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = CommandRegistry::new();
+        registry.register("count", "<by>", "increment the counter".to_string(), count);
+        let mut counter = 0;
+        match parse("/count 2") {
+            Input::Command(name, arguments) => {
+                assert_eq!(name, "count");
+                assert!(registry.run(&mut counter, name, &arguments).is_ok());
+            },
+            Input::Message(_) => panic!("expected a command"),
+        }
+        assert_eq!(counter, 2);
+        assert!(registry.run(&mut counter, "count", &[]).is_err());
+        assert!(registry.run(&mut counter, "unknown", &[]).is_err());
+        assert!(registry.help().contains("/count <by> - increment the counter"));
+        match parse("//not a command") {
+            Input::Message(text) => assert_eq!(text, "/not a command"),
+            Input::Command(..) => panic!("expected a message"),
+        }
+    }
+}
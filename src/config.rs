@@ -0,0 +1,215 @@
+use input::InputMode;
+use notify::NotificationSettings;
+use serde::Deserialize;
+use socket::SocketOptions;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use theme::Theme;
+use toml;
+
+
+// Settings read from the config file, each overridden by its command line flag.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // The address the server listens on, and the client connects to.
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub nickname: Option<String>,
+    // Either "dialog", "stdin" or "headless".
+    pub ui: Option<InputMode>,
+    // The nicknames whose messages are not shown, see `/ignore`.
+    pub ignored: Vec<String>,
+    // On the server, the greeting sent to each client as it connects.
+    pub motd: Option<String>,
+    // On the server, the most messages per second accepted from a client.
+    pub rate_limit: Option<f64>,
+    // On the server, who may chat, as with `--auth`, such as "htpasswd:<path>".
+    pub auth: Option<String>,
+    // The log of `--log-file`, and when it is rotated, as with `--log-rotate`, such as "daily".
+    pub log_file: Option<String>,
+    pub log_rotate: Option<String>,
+    // In the window, how many lines are kept to scroll back through.
+    pub scrollback: Option<usize>,
+    // In the window, the size of the text in points, and how much it is zoomed in with Ctrl+plus.
+    pub font_size: Option<f32>,
+    pub zoom: Option<f32>,
+    // The colors of the chat in a terminal.
+    pub theme: Theme,
+    // How the connections to the peer are tuned.
+    pub socket: SocketOptions,
+    // What alerts, see `/dnd`.
+    pub notifications: NotificationSettings,
+}
+
+// `$XDG_CONFIG_HOME/simple_chat`, or `~/.config/simple_chat`.
+pub fn config_dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("simple_chat"))
+}
+
+// `$XDG_DATA_HOME/simple_chat`, or `~/.local/share/simple_chat`.
+pub fn data_dir() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))?;
+    Some(data_home.join("simple_chat"))
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+impl Config {
+    pub fn parse(source: &str) -> Result<Config, String> {
+        toml::from_str(source).map_err(|error| error.to_string())
+    }
+
+    pub fn load(path: &PathBuf) -> Result<Config, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("failed to read config {}: {}", path.display(), error))?;
+        Config::parse(&source).map_err(|error| format!("invalid config {}: {}", path.display(), error))
+    }
+
+    // The config at the default path, if there is one.
+    pub fn load_default() -> Result<Config, String> {
+        match default_path() {
+            Some(path) if path.exists() => Config::load(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+}
+
+// Set one of the settings in the config file, leaving the others as they are.
+fn save(path: &Path, key: &str, value: toml::Value) -> Result<(), String> {
+    let mut table: toml::Table = match fs::read_to_string(path) {
+        Ok(source) => source.parse().map_err(|error| format!("invalid config {}: {}", path.display(), error))?,
+        Err(_) => toml::Table::new(),
+    };
+    table.insert(key.to_string(), value);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+    }
+    let source = toml::to_string(&table).map_err(|error| error.to_string())?;
+    fs::write(path, source).map_err(|error| format!("failed to write config {}: {}", path.display(), error))
+}
+
+// Keep the ignore list in the config file.
+pub fn save_ignored(path: &Path, ignored: &[String]) -> Result<(), String> {
+    let ignored = ignored.iter().cloned().map(toml::Value::String).collect();
+    save(path, "ignored", toml::Value::Array(ignored))
+}
+
+// Keep the zoom factor of the window, to be shown as large next time.
+#[cfg(any(feature = "gui", test))]
+pub fn save_zoom(path: &Path, zoom: f32) -> Result<(), String> {
+    // As set, rather than the closest double to the float.
+    save(path, "zoom", toml::Value::Float((f64::from(zoom) * 100.0).round() / 100.0))
+}
+
+pub fn save_notifications(path: &Path, settings: &NotificationSettings) -> Result<(), String> {
+    save(path, "notifications", toml::Value::try_from(settings).map_err(|error| error.to_string())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::QuietHours;
+    use theme::Color;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("
+            address = \"192.168.1.10\"
+            port = 9000
+            nickname = \"alice\"
+            ui = \"stdin\"
+            ignored = [\"mallory\"]
+            motd = \"Welcome\"
+            rate_limit = 2.5
+            auth = \"htpasswd:/etc/simple_chat/users\"
+            log_file = \"/var/log/simple_chat.log\"
+            log_rotate = \"daily\"
+            scrollback = 500
+            font_size = 16.0
+            zoom = 1.5
+
+            [theme]
+            own = \"#00ff7f\"
+            error = \"bright-red\"
+            nicknames = [\"yellow\", \"blue\"]
+
+            [socket]
+            nodelay = false
+            recv_buffer = 262144
+
+            [notifications]
+            muted = [\"bob\"]
+            quiet_hours = \"22:00-07:00\"
+            keywords = [\"deploy\"]
+        ").unwrap();
+        assert_eq!(config, Config {
+            address: Some("192.168.1.10".to_string()),
+            port: Some(9000),
+            nickname: Some("alice".to_string()),
+            ui: Some(InputMode::Stdin),
+            ignored: vec!["mallory".to_string()],
+            motd: Some("Welcome".to_string()),
+            rate_limit: Some(2.5),
+            auth: Some("htpasswd:/etc/simple_chat/users".to_string()),
+            log_file: Some("/var/log/simple_chat.log".to_string()),
+            log_rotate: Some("daily".to_string()),
+            scrollback: Some(500),
+            font_size: Some(16.0),
+            zoom: Some(1.5),
+            theme: Theme {
+                own: Color::Rgb(0, 255, 127),
+                error: Color::Ansi(91),
+                nicknames: vec![Color::Ansi(33), Color::Ansi(34)],
+                ..Theme::default()
+            },
+            socket: SocketOptions {
+                nodelay: false,
+                recv_buffer: Some(262144),
+                ..SocketOptions::default()
+            },
+            notifications: NotificationSettings {
+                muted: vec!["bob".to_string()],
+                quiet_hours: Some(QuietHours::parse("22:00-07:00").unwrap()),
+                keywords: vec!["deploy".to_string()],
+                ..NotificationSettings::default()
+            },
+        });
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("ui = \"gui\"").is_err());
+        assert!(Config::parse("colour = \"blue\"").is_err());
+        assert!(Config::parse("[theme]\nown = \"orange\"").is_err());
+        assert!(Config::parse("[notifications]\nquiet_hours = \"late\"").is_err());
+    }
+
+    #[test]
+    fn test_save_ignored() {
+        let path = env::temp_dir().join("simple_chat_test_config.toml");
+        fs::write(&path, "nickname = \"alice\"\n").unwrap();
+        save_ignored(&path, &["mallory".to_string(), "eve".to_string()]).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.nickname.as_deref(), Some("alice"));
+        assert_eq!(config.ignored, vec!["mallory", "eve"]);
+        let settings = NotificationSettings {
+            dnd: true,
+            quiet_hours: Some(QuietHours::parse("23:00-06:30").unwrap()),
+            ..NotificationSettings::default()
+        };
+        save_notifications(&path, &settings).unwrap();
+        save_ignored(&path, &[]).unwrap();
+        save_zoom(&path, 1.25).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert!(config.ignored.is_empty());
+        assert_eq!(config.zoom, Some(1.25));
+        assert_eq!(config.notifications, settings);
+        let _ = fs::remove_file(&path);
+    }
+}
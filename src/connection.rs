@@ -0,0 +1,104 @@
+use channel::{SendError, Sender};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionState {
+    // The client is connecting, or the server waiting for a client.
+    Connecting,
+    // Connected, going through the password challenge and the handshake.
+    Handshaking,
+    // The password challenge passed, the handshake follows.
+    Authenticated,
+    // Chatting.
+    Active,
+    // The connection is ending, undelivered messages being set aside.
+    Draining,
+    // For good, the component is done.
+    Closed,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = match *self {
+            ConnectionState::Connecting => "state-connecting",
+            ConnectionState::Handshaking => "state-handshaking",
+            ConnectionState::Authenticated => "state-authenticated",
+            ConnectionState::Active => "state-active",
+            ConnectionState::Draining => "state-draining",
+            ConnectionState::Closed => "state-closed",
+        };
+        write!(f, "{}", tr!(id))
+    }
+}
+
+// The state of a connection, updated by its component, and read through its handle.
+#[derive(Clone)]
+pub struct SharedState {
+    state: Arc<Mutex<ConnectionState>>,
+}
+
+impl SharedState {
+    pub fn new() -> SharedState {
+        SharedState {
+            state: Arc::new(Mutex::new(ConnectionState::Connecting)),
+        }
+    }
+
+    pub fn get(&self) -> ConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    // Returns whether the state changed.
+    pub fn set(&self, state: ConnectionState) -> bool {
+        let mut current = self.state.lock().unwrap();
+        if *current == state {
+            return false;
+        }
+        *current = state;
+        true
+    }
+}
+
+// A handle on a client or server component, to control it and query its state.
+pub struct ComponentHandle<M> {
+    chan: Sender<M>,
+    state: SharedState,
+}
+
+impl<M> ComponentHandle<M> {
+    pub fn new(chan: Sender<M>, state: SharedState) -> ComponentHandle<M> {
+        ComponentHandle {
+            chan,
+            state,
+        }
+    }
+
+    pub fn send(&self, msg: M) -> Result<(), SendError<M>> {
+        self.chan.send(msg)
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use channel::{self, ChannelConfig};
+
+    #[test]
+    fn test_handle_state() {
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let state = SharedState::new();
+        let handle = ComponentHandle::new(chan, state.clone());
+        assert_eq!(handle.state(), ConnectionState::Connecting);
+        assert!(state.set(ConnectionState::Active));
+        assert!(!state.set(ConnectionState::Active));
+        assert_eq!(handle.state(), ConnectionState::Active);
+        handle.send(1).unwrap();
+        assert_eq!(port.recv(), Ok(1));
+    }
+}
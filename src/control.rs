@@ -0,0 +1,223 @@
+use serde_json::{self, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+
+// The codes of JSON-RPC 2.0 errors.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// The request was understood, but failed, such as a message sent out of turn.
+const REQUEST_FAILED: i64 = -32000;
+
+// The control clients, numbered from 0 as they connect.
+pub type ClientId = usize;
+
+#[derive(Debug, PartialEq)]
+pub enum Method {
+    // Send a message, in the conversation numbered from 1 if given,
+    // or else in the one waiting for a reply.
+    Send {
+        text: String,
+        conversation: Option<usize>,
+    },
+    // Be notified of the messages sent and received from now on.
+    Subscribe,
+    ListPeers,
+    Shutdown,
+}
+
+// A request of a control client, for the UI to answer.
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub client: ClientId,
+    pub id: Value,
+    pub method: Method,
+}
+
+fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": code,
+            "message": message,
+        },
+    })
+}
+
+fn parse_method(name: &str, params: &Value) -> Result<Method, (i64, String)> {
+    match name {
+        "send" => {
+            let text = params.get("text").and_then(Value::as_str)
+                .ok_or((INVALID_PARAMS, "send takes a 'text'".to_string()))?;
+            let conversation = match params.get("conversation") {
+                None => None,
+                Some(conversation) => Some(conversation.as_u64()
+                    .ok_or((INVALID_PARAMS, "'conversation' is a number".to_string()))? as usize),
+            };
+            Ok(Method::Send {
+                text: text.to_string(),
+                conversation,
+            })
+        },
+        "subscribe" => Ok(Method::Subscribe),
+        "list_peers" => Ok(Method::ListPeers),
+        "shutdown" => Ok(Method::Shutdown),
+        _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", name))),
+    }
+}
+
+// A request on a line, or the error to answer with.
+fn parse_request(line: &str) -> Result<(Value, Method), Value> {
+    let request: Value = serde_json::from_str(line)
+        .map_err(|parse_error| error(&Value::Null, PARSE_ERROR, &parse_error.to_string()))?;
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let name = match request.get("method").and_then(Value::as_str) {
+        Some(name) if request.get("jsonrpc") == Some(&json!("2.0")) => name,
+        _ => return Err(error(&id, INVALID_REQUEST, "expected a JSON-RPC 2.0 request")),
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    match parse_method(name, &params) {
+        Ok(method) => Ok((id, method)),
+        Err((code, message)) => Err(error(&id, code, &message)),
+    }
+}
+
+#[derive(Default)]
+struct Clients {
+    streams: HashMap<ClientId, TcpStream>,
+    subscribed: HashSet<ClientId>,
+    next: ClientId,
+}
+
+impl Clients {
+    // Forgets the client if it went away.
+    fn write(&mut self, client: ClientId, message: &Value) {
+        let written = match self.streams.get_mut(&client) {
+            Some(stream) => writeln!(stream, "{}", message).is_ok(),
+            None => return,
+        };
+        if !written {
+            self.streams.remove(&client);
+            self.subscribed.remove(&client);
+        }
+    }
+}
+
+// Shared with the UI, to answer the requests and notify the subscribers.
+#[derive(Clone)]
+pub struct ControlSocket {
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl ControlSocket {
+    pub fn reply(&self, request: &Request, result: Result<Value, String>) {
+        let response = match result {
+            Ok(result) => json!({
+                "jsonrpc": "2.0",
+                "id": request.id,
+                "result": result,
+            }),
+            Err(message) => error(&request.id, REQUEST_FAILED, &message),
+        };
+        self.clients.lock().unwrap().write(request.client, &response);
+    }
+
+    pub fn subscribe(&self, client: ClientId) {
+        self.clients.lock().unwrap().subscribed.insert(client);
+    }
+
+    pub fn notify(&self, method: &str, params: Value) {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        });
+        let mut clients = self.clients.lock().unwrap();
+        let subscribed: Vec<ClientId> = clients.subscribed.iter().cloned().collect();
+        for client in subscribed {
+            clients.write(client, &notification);
+        }
+    }
+}
+
+fn serve_client<F: Fn(Request)>(stream: TcpStream, client: ClientId, clients: &Mutex<Clients>, on_request: F) {
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_request(&line) {
+            Ok((id, method)) => on_request(Request {
+                client,
+                id,
+                method,
+            }),
+            Err(response) => clients.lock().unwrap().write(client, &response),
+        }
+    }
+    let mut clients = clients.lock().unwrap();
+    clients.streams.remove(&client);
+    clients.subscribed.remove(&client);
+}
+
+// Accept control clients on a local address, each sending a JSON-RPC request per line,
+// and getting the responses, and the notifications it subscribed to, a line each.
+pub fn start_control_socket<F>(address: &str, on_request: F) -> io::Result<ControlSocket>
+    where F: Fn(Request) + Clone + Send + 'static {
+    let listener = TcpListener::bind(address)?;
+    let socket = ControlSocket {
+        clients: Arc::new(Mutex::new(Clients::default())),
+    };
+    let clients = socket.clients.clone();
+    let _ = thread::Builder::new().spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(_) => continue,
+            };
+            let client = {
+                let mut clients = clients.lock().unwrap();
+                let client = clients.next;
+                clients.next += 1;
+                clients.streams.insert(client, writer);
+                client
+            };
+            let clients = clients.clone();
+            let on_request = on_request.clone();
+            let _ = thread::Builder::new().spawn(move || serve_client(stream, client, &clients, on_request));
+        }
+    });
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let (id, method) = parse_request(r#"{"jsonrpc": "2.0", "id": 1, "method": "send", "params": {"text": "hi", "conversation": 2}}"#).unwrap();
+        assert_eq!(id, json!(1));
+        assert_eq!(method, Method::Send { text: "hi".to_string(), conversation: Some(2) });
+        assert_eq!(parse_request(r#"{"jsonrpc": "2.0", "id": "a", "method": "list_peers"}"#).unwrap().1, Method::ListPeers);
+
+        let code = |line: &str| parse_request(line).unwrap_err()["error"]["code"].as_i64().unwrap();
+        assert_eq!(code("not json"), PARSE_ERROR);
+        assert_eq!(code(r#"{"id": 1, "method": "shutdown"}"#), INVALID_REQUEST);
+        assert_eq!(code(r#"{"jsonrpc": "2.0", "id": 1, "method": "dance"}"#), METHOD_NOT_FOUND);
+        assert_eq!(code(r#"{"jsonrpc": "2.0", "id": 1, "method": "send"}"#), INVALID_PARAMS);
+    }
+}
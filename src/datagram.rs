@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use transport::Transport;
+
+
+// Small enough not to be fragmented on the way.
+const MAX_PAYLOAD: usize = 1200;
+const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(200);
+// Without an ack for this long, the peer is considered gone.
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+// Often enough for the NATs on the way to keep the hole open, even when nothing is said.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// The first byte of each datagram. A hello is followed by one of the hello states,
+// data by its number and bytes, and an ack by the number of the data received.
+pub const HELLO: u8 = b'H';
+const DATA: u8 = b'D';
+const ACK: u8 = b'A';
+const FIN: u8 = b'F';
+
+// While punching, whether the peer's hellos came through yet,
+// and once the stream is up, to keep the hole open.
+pub const NOT_HEARD: u8 = 0;
+pub const HEARD: u8 = 1;
+const KEEPALIVE: u8 = 2;
+
+struct State {
+    incoming: VecDeque<u8>,
+    // The number of the next data expected from the peer.
+    expected: u32,
+    // The number of the last of ours the peer acknowledged.
+    acked: u32,
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+struct Link {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    shared: Arc<Shared>,
+    read_timeout: Mutex<Option<Duration>>,
+    // The number of our next data, locked while sending it.
+    next: Mutex<u32>,
+}
+
+impl Link {
+    fn shutdown(&self) {
+        let _ = self.socket.send_to(&[FIN], self.peer);
+        self.shared.close();
+    }
+}
+
+// Once the last handle is dropped, as a socket being closed.
+impl Drop for Link {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+// A reliable stream over UDP, with a peer reached by punching through the NATs on the way:
+// the bytes written are sent in numbered datagrams, one at a time, each sent again until
+// the peer acknowledges it, and those received are put back in order.
+pub struct DatagramStream {
+    link: Arc<Link>,
+}
+
+impl DatagramStream {
+    // Over a socket which exchanged hellos with the peer.
+    pub fn new(socket: UdpSocket, peer: SocketAddr) -> io::Result<DatagramStream> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                incoming: VecDeque::new(),
+                expected: 1,
+                acked: 0,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let receiver = socket.try_clone()?;
+        receiver.set_read_timeout(Some(RETRANSMIT_INTERVAL))?;
+        let received = shared.clone();
+        thread::Builder::new().spawn(move || receive(receiver, peer, received))?;
+        Ok(DatagramStream {
+            link: Arc::new(Link {
+                socket,
+                peer,
+                shared,
+                read_timeout: Mutex::new(None),
+                next: Mutex::new(1),
+            }),
+        })
+    }
+}
+
+fn number(bytes: &[u8]) -> Option<u32> {
+    bytes.get(..4).and_then(|bytes| bytes.try_into().ok()).map(u32::from_be_bytes)
+}
+
+// Until the stream is closed, put the peer's data in order, acknowledging it,
+// note the acks of ours, and keep the hole open.
+fn receive(socket: UdpSocket, peer: SocketAddr, shared: Arc<Shared>) {
+    let mut datagram = [0; MAX_PAYLOAD + 5];
+    let (mut last_sent, mut last_heard) = (Instant::now(), Instant::now());
+    while !shared.state.lock().unwrap().closed {
+        if last_sent.elapsed() >= KEEPALIVE_INTERVAL {
+            let _ = socket.send_to(&[HELLO, KEEPALIVE], peer);
+            last_sent = Instant::now();
+        }
+        // Having missed a few keepalives, the peer went away without a word.
+        if last_heard.elapsed() >= KEEPALIVE_INTERVAL * 3 {
+            return shared.close();
+        }
+        let size = match socket.recv_from(&mut datagram) {
+            Ok((size, from)) if from == peer && size > 0 => {
+                last_heard = Instant::now();
+                size
+            },
+            Ok(_) => continue,
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut => continue,
+            Err(_) => return shared.close(),
+        };
+        let mut state = shared.state.lock().unwrap();
+        match (datagram[0], number(&datagram[1..size])) {
+            (DATA, Some(id)) => {
+                if id == state.expected {
+                    state.incoming.extend(&datagram[5..size]);
+                    state.expected += 1;
+                }
+                // Also the data received before, whose ack was lost.
+                if id < state.expected {
+                    let mut ack = vec![ACK];
+                    ack.extend_from_slice(&id.to_be_bytes());
+                    let _ = socket.send_to(&ack, peer);
+                    last_sent = Instant::now();
+                }
+            },
+            (ACK, Some(id)) => state.acked = state.acked.max(id),
+            (FIN, _) => state.closed = true,
+            // The peer is still punching, not knowing we heard it.
+            (HELLO, _) if datagram.get(1) != Some(&KEEPALIVE) => {
+                let _ = socket.send_to(&[HELLO, KEEPALIVE], peer);
+            },
+            _ => {},
+        }
+        shared.changed.notify_all();
+    }
+}
+
+impl Read for DatagramStream {
+    // Ends with 0 bytes read once either end shut the stream down, or went away.
+    fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+        let deadline = self.link.read_timeout.lock().unwrap().map(|timeout| Instant::now() + timeout);
+        let shared = &self.link.shared;
+        let mut state = shared.state.lock().unwrap();
+        while state.incoming.is_empty() && !state.closed && !bytes.is_empty() {
+            state = match deadline {
+                None => shared.changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::ErrorKind::WouldBlock.into());
+                    }
+                    shared.changed.wait_timeout(state, deadline - now).unwrap().0
+                },
+            };
+        }
+        let count = bytes.len().min(state.incoming.len());
+        for (byte, incoming) in bytes.iter_mut().zip(state.incoming.drain(..count)) {
+            *byte = incoming;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for DatagramStream {
+    // Returns once the peer acknowledged the bytes, at most a datagram of them.
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if bytes.is_empty() {
+            return Ok(0);
+        }
+        let link = &self.link;
+        let mut next = link.next.lock().unwrap();
+        let count = bytes.len().min(MAX_PAYLOAD);
+        let mut datagram = vec![DATA];
+        datagram.extend_from_slice(&next.to_be_bytes());
+        datagram.extend_from_slice(&bytes[..count]);
+        let deadline = Instant::now() + PEER_TIMEOUT;
+        let mut sent_at: Option<Instant> = None;
+        let mut state = link.shared.state.lock().unwrap();
+        while state.acked < *next {
+            if state.closed {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                drop(state);
+                link.shutdown();
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            if sent_at.is_none_or(|sent_at| now >= sent_at + RETRANSMIT_INTERVAL) {
+                link.socket.send_to(&datagram, link.peer)?;
+                sent_at = Some(now);
+            }
+            state = link.shared.changed.wait_timeout(state, RETRANSMIT_INTERVAL).unwrap().0;
+        }
+        *next += 1;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for DatagramStream {
+    fn try_clone(&self) -> io::Result<DatagramStream> {
+        Ok(DatagramStream {
+            link: self.link.clone(),
+        })
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.link.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        Some(self.link.peer.ip())
+    }
+
+    fn shutdown(&self) {
+        self.link.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream() {
+        let (ours, theirs) = (UdpSocket::bind("127.0.0.1:0").unwrap(), UdpSocket::bind("127.0.0.1:0").unwrap());
+        let (our_address, their_address) = (ours.local_addr().unwrap(), theirs.local_addr().unwrap());
+        let mut stream = DatagramStream::new(ours, their_address).unwrap();
+        let mut peer = DatagramStream::new(theirs, our_address).unwrap();
+        // More than a datagram, each acknowledged in turn.
+        let message = vec![7; MAX_PAYLOAD * 2 + 10];
+        stream.write_all(&message).unwrap();
+        let mut received = vec![0; message.len()];
+        peer.read_exact(&mut received).unwrap();
+        assert_eq!(received, message);
+
+        peer.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+        assert_eq!(peer.read(&mut [0; 1]).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        stream.shutdown();
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(peer.read(&mut [0; 1]).unwrap(), 0);
+        assert!(stream.write(b"gone").is_err());
+    }
+}
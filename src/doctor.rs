@@ -0,0 +1,116 @@
+use bench::{self, BenchOptions};
+use codec::WireFormat;
+use input;
+use std::env;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The outcome of a check: what was found, or what went wrong and what to do about it.
+type CheckResult = Result<String, String>;
+
+fn check_port(address: &str) -> CheckResult {
+    match TcpListener::bind(address) {
+        Ok(_) => Ok(format!("{} is free for the server", address)),
+        Err(error) => {
+            let addr: SocketAddr = address.parse().map_err(|_| format!("invalid address {}", address))?;
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(_) => Ok(format!("{} is in use, a server seems to be running, clients can connect", address)),
+                Err(_) => Err(format!("{} can't be used ({}), stop the program using it, \
+                                       for example found with `lsof -i :{}`", address, error, addr.port())),
+            }
+        },
+    }
+}
+
+fn check_loopback() -> CheckResult {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|error| format!("can't listen on the loopback interface ({}), check that it is up", error))?;
+    let addr = listener.local_addr().map_err(|error| error.to_string())?;
+    TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .map_err(|error| format!("can't connect over the loopback interface ({}), check that no firewall \
+                                  rule blocks local connections", error))?;
+    Ok("local connections are accepted".to_string())
+}
+
+// A message going through the whole protocol, from the handshake to its ack, in each wire format.
+fn check_roundtrip() -> CheckResult {
+    let mut roundtrips = vec![];
+    for &format in [WireFormat::Bincode, WireFormat::Json, WireFormat::MessagePack].iter() {
+        let options = BenchOptions { messages: 1, size: 16, wire_format: format, window: 1 };
+        let report = bench::run(options)
+            .map_err(|error| format!("a {} message wasn't acknowledged over the loopback interface ({}), \
+                                      please report it with the output of `doctor`", format.name(), error))?;
+        roundtrips.push(format!("{} in {:.1?}", format.name(), report.elapsed()));
+    }
+    Ok(format!("messages are acknowledged over the loopback interface, {}", roundtrips.join(", ")))
+}
+
+fn check_name_resolution() -> CheckResult {
+    let addrs: Vec<SocketAddr> = ("localhost", 0).to_socket_addrs()
+        .map_err(|error| format!("localhost doesn't resolve ({}), check /etc/hosts", error))?
+        .collect();
+    if addrs.iter().any(|addr| addr.ip().is_loopback()) {
+        Ok("localhost resolves to the loopback interface".to_string())
+    } else {
+        Err(format!("localhost resolves to {:?}, not the loopback interface, check /etc/hosts", addrs))
+    }
+}
+
+fn check_dialogs() -> CheckResult {
+    match input::dialog_unavailable_reason() {
+        None => Ok("dialogs can be shown".to_string()),
+        Some(reason) => Err(format!("dialogs can't be shown ({}), messages will be read from the terminal, \
+                                     install zenity or run from a graphical session to use dialogs", reason)),
+    }
+}
+
+// The checks, by name, and whether chatting is possible without them passing.
+fn run_checks(address: &str) -> Vec<(&'static str, bool, CheckResult)> {
+    vec![
+        ("port", false, check_port(address)),
+        ("loopback", false, check_loopback()),
+        ("round trip", false, check_roundtrip()),
+        ("name resolution", false, check_name_resolution()),
+        ("dialogs", true, check_dialogs()),
+    ]
+}
+
+// Print the result of each check, returning whether the required ones passed.
+// Along with what to include in a bug report.
+pub fn run(address: &str) -> bool {
+    println!("simple_chat {} on {} {}", env!("CARGO_PKG_VERSION"), env::consts::OS, env::consts::ARCH);
+    let mut passed = true;
+    for (name, optional, result) in run_checks(address) {
+        match result {
+            Ok(found) => println!("[ok]   {}: {}", name, found),
+            Err(problem) if optional => println!("[warn] {}: {}", name, problem),
+            Err(problem) => {
+                passed = false;
+                println!("[fail] {}: {}", name, problem);
+            },
+        }
+    }
+    passed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        assert!(check_loopback().is_ok());
+        assert!(check_roundtrip().unwrap().contains("json in "));
+        assert!(check_name_resolution().is_ok());
+        // A port in use by a listening server is fine for clients.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        assert!(check_port(&address).is_ok());
+        drop(listener);
+        assert!(check_port(&address).is_ok());
+        assert!(check_port("not an address").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use config;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+
+// The message being written to each peer, kept in the data directory as it changes,
+// for it not to be lost with the window, or the chat itself.
+#[derive(Debug, Default)]
+pub struct Drafts {
+    path: Option<PathBuf>,
+    drafts: BTreeMap<String, String>,
+}
+
+impl Drafts {
+    pub fn parse(source: &str) -> Drafts {
+        Drafts {
+            path: None,
+            drafts: serde_json::from_str(source).unwrap_or_default(),
+        }
+    }
+
+    // The drafts kept in the data directory, none if there are none yet.
+    pub fn load() -> Drafts {
+        let path = config::data_dir().map(|dir| dir.join("drafts.json"));
+        let source = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        Drafts {
+            path,
+            ..Drafts::parse(&source)
+        }
+    }
+
+    pub fn get(&self, peer: &str) -> Option<&str> {
+        self.drafts.get(peer).map(String::as_str)
+    }
+
+    // Each peer with a draft, by nickname.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.drafts.iter().map(|(peer, draft)| (peer.as_str(), draft.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.drafts.is_empty()
+    }
+
+    // An empty draft is no draft.
+    pub fn set(&mut self, peer: &str, draft: &str) -> Result<(), String> {
+        let changed = if draft.is_empty() {
+            self.drafts.remove(peer).is_some()
+        } else {
+            self.drafts.insert(peer.to_string(), draft.to_string()).as_deref() != Some(draft)
+        };
+        if !changed {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        let source = serde_json::to_string_pretty(&self.drafts).map_err(|error| error.to_string())?;
+        fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_drafts() {
+        let path = env::temp_dir().join("simple_chat_test_drafts.json");
+        let mut drafts = Drafts {
+            path: Some(path.clone()),
+            ..Drafts::default()
+        };
+        drafts.set("bob", "see you\nat noon").unwrap();
+        drafts.set("alice", "hi").unwrap();
+        drafts.set("alice", "").unwrap();
+        assert_eq!(drafts.get("alice"), None);
+
+        let source = fs::read_to_string(&path).unwrap();
+        let drafts = Drafts::parse(&source);
+        assert_eq!(drafts.iter().collect::<Vec<_>>(), vec![("bob", "see you\nat noon")]);
+        assert!(Drafts::parse("not json").is_empty());
+        let _ = fs::remove_file(&path);
+    }
+}
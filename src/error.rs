@@ -0,0 +1,54 @@
+use std::io;
+use thiserror::Error;
+
+
+// What went wrong in a component, short of the connection ending, which is a `DisconnectReason`.
+// Sent to the UI as `MainControlMsg::Error`, for the user to know, rather than being ignored.
+#[derive(Debug, Error)]
+pub enum ChatError {
+    // Writing to the peer failed, the connection most likely being gone,
+    // which the component finds out on its next read.
+    #[error("failed to send a frame ({frame}) to the peer: {source}")]
+    Send {
+        frame: &'static str,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to listen on {address}: {source}")]
+    Listen {
+        address: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to accept clients: {0}")]
+    Accept(#[source] io::Error),
+    // Setting the options of the `[socket]` section of the config, the connection going on without them.
+    #[error("failed to set the socket options: {0}")]
+    Socket(#[source] io::Error),
+}
+
+impl ChatError {
+    pub fn send(frame: &'static str) -> impl FnOnce(io::Error) -> ChatError {
+        move |source| ChatError::Send { frame, source }
+    }
+}
+
+// For the events holding errors to be compared in tests, I/O errors not being comparable.
+impl PartialEq for ChatError {
+    fn eq(&self, other: &ChatError) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_error() {
+        let error = ChatError::send("Ack")(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert_eq!(error.to_string(), "failed to send a frame (Ack) to the peer: broken pipe");
+        assert!(std::error::Error::source(&error).is_some());
+        assert_ne!(error, ChatError::Accept(io::ErrorKind::BrokenPipe.into()));
+    }
+}
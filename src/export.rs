@@ -0,0 +1,124 @@
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use serde_json;
+use store::StoredMessage;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Html,
+    Text,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Result<ExportFormat, String> {
+        match name {
+            "json" => Ok(ExportFormat::Json),
+            "html" => Ok(ExportFormat::Html),
+            "txt" => Ok(ExportFormat::Text),
+            _ => Err(format!("unknown export format '{}', use json, html or txt", name)),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+            ExportFormat::Text => "txt",
+        }
+    }
+}
+
+// The start of a day, as `YYYY-MM-DD` in local time.
+pub fn parse_since(date: &str) -> Result<SystemTime, String> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date '{}', use YYYY-MM-DD", date))?;
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local.from_local_datetime(&midnight)
+        .earliest()
+        .map(SystemTime::from)
+        .ok_or_else(|| format!("invalid date '{}'", date))
+}
+
+fn timestamp(message: &StoredMessage) -> String {
+    let sent_at: DateTime<Local> = message.sent_at.into();
+    sent_at.to_rfc3339()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn to_json(messages: &[StoredMessage]) -> String {
+    let messages: Vec<_> = messages.iter().map(|message| {
+        json!({
+            "timestamp": timestamp(message),
+            "unix_millis": message.sent_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            "sender": message.sender,
+            "peer": message.peer,
+            "status": message.status.name(),
+            "text": message.text,
+        })
+    }).collect();
+    serde_json::to_string_pretty(&messages).unwrap_or_default()
+}
+
+fn to_html(messages: &[StoredMessage]) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Simple chat transcript</title></head>\n\
+                                 <body>\n<table>\n<tr><th>Time</th><th>From</th><th>To</th><th>Status</th><th>Message</th></tr>\n");
+    for message in messages {
+        html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                               timestamp(message),
+                               escape_html(&message.sender),
+                               escape_html(&message.peer),
+                               message.status.name(),
+                               escape_html(&message.text)));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn to_text(messages: &[StoredMessage]) -> String {
+    messages.iter()
+        .map(|message| format!("{} ({})\n", message, message.status.name()))
+        .collect()
+}
+
+pub fn export(messages: &[StoredMessage], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => to_json(messages),
+        ExportFormat::Html => to_html(messages),
+        ExportFormat::Text => to_text(messages),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use store::MessageStatus;
+
+    #[test]
+    fn test_export() {
+        let messages = vec![StoredMessage {
+            sent_at: parse_since("2026-10-16").unwrap(),
+            sender: "alice".to_string(),
+            peer: "bob".to_string(),
+            status: MessageStatus::Delivered,
+            text: "<b>hi</b> & bye".to_string(),
+        }];
+        let json: Value = serde_json::from_str(&export(&messages, ExportFormat::Json)).unwrap();
+        assert_eq!(json[0]["sender"], "alice");
+        assert_eq!(json[0]["status"], "delivered");
+        assert!(json[0]["timestamp"].as_str().unwrap().starts_with("2026-10-16T00:00:00"));
+        assert!(export(&messages, ExportFormat::Html).contains("<td>&lt;b&gt;hi&lt;/b&gt; &amp; bye</td>"));
+        assert!(export(&messages, ExportFormat::Text)
+            .ends_with("] alice (with bob): <b>hi</b> & bye (delivered)\n"));
+        assert!(ExportFormat::parse("pdf").is_err());
+        assert!(parse_since("yesterday").is_err());
+    }
+}
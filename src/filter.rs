@@ -0,0 +1,168 @@
+use std::fs;
+
+
+// What the server does with a message from a client with a word the filter has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FilterAction {
+    // Passed on, the operator being told.
+    Flag,
+    // Passed on with the word replaced by asterisks.
+    Redact,
+    // Not passed on.
+    Drop,
+}
+
+impl FilterAction {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FilterAction::Flag => "flag",
+            FilterAction::Redact => "redact",
+            FilterAction::Drop => "drop",
+        }
+    }
+}
+
+// A rule, one per line in the filter file: `<drop|redact|flag> <word>`, the word matching
+// whole words whatever their case, and `*` in it any letters, for example `redact darn*`.
+#[derive(Debug, PartialEq)]
+struct FilterRule {
+    action: FilterAction,
+    pattern: Vec<char>,
+}
+
+// Whether the word matches the pattern, `*` matching any run of characters.
+fn matches(pattern: &[char], word: &[char]) -> bool {
+    match pattern.split_first() {
+        None => word.is_empty(),
+        Some((&'*', rest)) => (0..=word.len()).any(|skipped| matches(rest, &word[skipped..])),
+        Some((&expected, rest)) => word.first() == Some(&expected) && matches(rest, &word[1..]),
+    }
+}
+
+// The words of the text, where they are.
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = vec![];
+    let mut start = None;
+    for (at, character) in text.char_indices().chain(Some((text.len(), ' '))) {
+        match (start, character.is_alphanumeric()) {
+            (None, true) => start = Some(at),
+            (Some(from), false) => {
+                words.push((from, at));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    words
+}
+
+// What the filter did to a message.
+#[derive(Debug, PartialEq)]
+pub struct Filtered {
+    // The strongest action of the rules matching.
+    pub action: FilterAction,
+    // Those matching, as they are in the message.
+    pub words: Vec<String>,
+    // The text passed on, redacted if need be.
+    pub text: String,
+}
+
+// The rules the server checks the messages of its clients against, before passing them on.
+#[derive(Debug, Default)]
+pub struct WordFilter {
+    rules: Vec<FilterRule>,
+}
+
+impl WordFilter {
+    pub fn parse(source: &str) -> Result<WordFilter, String> {
+        let mut rules = vec![];
+        for line in source.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let action = match line.split_once(' ') {
+                Some(("drop", _)) => FilterAction::Drop,
+                Some(("redact", _)) => FilterAction::Redact,
+                Some(("flag", _)) => FilterAction::Flag,
+                _ => return Err(format!("a filter rule is drop, redact or flag, and a word: '{}'", line)),
+            };
+            let pattern = line.split_once(' ').map_or("", |(_, pattern)| pattern.trim());
+            if pattern.contains(char::is_whitespace) {
+                return Err(format!("a filter rule has a single word: '{}'", line));
+            }
+            rules.push(FilterRule {
+                action,
+                pattern: pattern.to_lowercase().chars().collect(),
+            });
+        }
+        Ok(WordFilter {
+            rules,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<WordFilter, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("failed to read filter {}: {}", path, error))?;
+        WordFilter::parse(&source)
+    }
+
+    // `None` if no rule matches.
+    pub fn apply(&self, text: &str) -> Option<Filtered> {
+        let mut action = None;
+        let mut found = vec![];
+        let mut redacted = String::new();
+        let mut shown = 0;
+        for (start, end) in words(text) {
+            let word: Vec<char> = text[start..end].to_lowercase().chars().collect();
+            let strongest = self.rules.iter()
+                .filter(|rule| matches(&rule.pattern, &word))
+                .map(|rule| rule.action)
+                .max();
+            let strongest = match strongest {
+                Some(strongest) => strongest,
+                None => continue,
+            };
+            found.push(text[start..end].to_string());
+            action = action.max(Some(strongest));
+            if strongest == FilterAction::Redact {
+                redacted.push_str(&text[shown..start]);
+                redacted.push_str(&"*".repeat(text[start..end].chars().count()));
+                shown = end;
+            }
+        }
+        redacted.push_str(&text[shown..]);
+        Some(Filtered {
+            action: action?,
+            words: found,
+            text: redacted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let filter = WordFilter::parse("
+            # Kept civil.
+            redact darn*
+            flag refund
+            drop http*
+            drop spam
+        ").unwrap();
+        assert_eq!(filter.apply("all fine"), None);
+        assert_eq!(filter.apply("Darned printer, darn it"), Some(Filtered {
+            action: FilterAction::Redact,
+            words: vec!["Darned".to_string(), "darn".to_string()],
+            text: "****** printer, **** it".to_string(),
+        }));
+        let flagged = filter.apply("a refund, darn").unwrap();
+        assert_eq!(flagged.action, FilterAction::Redact);
+        assert_eq!(flagged.text, "a refund, ****");
+        assert_eq!(filter.apply("I want a refund").unwrap().action, FilterAction::Flag);
+        assert_eq!(filter.apply("buy at https://example.com").unwrap().action, FilterAction::Drop);
+        // Whole words only.
+        assert_eq!(filter.apply("spammer"), None);
+        assert!(WordFilter::parse("ban spam").is_err());
+        assert!(WordFilter::parse("drop two words").is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use eframe::{self, egui};
+use links;
+use markdown::{self, Style};
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::mpsc::Receiver;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+
+// How often the window checks for updates from the chat.
+const REFRESH: Duration = Duration::from_millis(100);
+// The lines kept in the window, the oldest being dropped, unless configured otherwise.
+pub const DEFAULT_SCROLLBACK: usize = 2000;
+
+// How the window is shown.
+pub struct Options {
+    // The lines kept, the oldest being dropped.
+    pub scrollback: usize,
+    // Closing the window minimizes it instead, until raised again from the tray.
+    pub to_tray: bool,
+    // The size of the text in points, the sizes of headings and small text following it.
+    pub font_size: Option<f32>,
+    // How much everything is scaled, as last set with Ctrl+plus and Ctrl+minus.
+    pub zoom: f32,
+}
+
+// What the chat shows in the window.
+#[derive(Debug, PartialEq)]
+pub enum Update {
+    Line(String),
+    // What the input is for, or None while waiting for the peer.
+    Prompt(Option<String>),
+    Status {
+        text: String,
+        connected: bool,
+    },
+    // The draft of the message the prompt is for, put back in the input unless something is typed there.
+    Draft(String),
+    // Brought back from the tray, on a click on its icon.
+    Raise,
+}
+
+// What the user does in the window.
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    // Started typing in the input.
+    Typing,
+    // What is in the input, each time it changes.
+    Draft(String),
+    Line(String),
+    // Zoomed in or out with Ctrl+plus, Ctrl+minus or Ctrl+0, to the new factor.
+    Zoomed(f32),
+    Closed,
+}
+
+struct ChatWindow<'a, F> {
+    updates: Receiver<Update>,
+    // The chat, the window closing once it ends.
+    chat: &'a JoinHandle<()>,
+    on_event: F,
+    lines: VecDeque<String>,
+    scrollback: usize,
+    // Where the lines are scrolled to, and the height of a page of them, as last shown.
+    offset: f32,
+    page_height: f32,
+    // Where to scroll on the next frame: to a page up or down, or to the bottom for a new line.
+    scroll_to: Option<f32>,
+    prompt: Option<String>,
+    status: String,
+    connected: bool,
+    input: String,
+    // The link clicked, for the user to confirm opening it.
+    link: Option<String>,
+    // With `--tray`, closing the window minimizes it instead, until raised again from the tray.
+    to_tray: bool,
+    raise: bool,
+    zoom: f32,
+}
+
+impl<'a, F: FnMut(Event)> ChatWindow<'a, F> {
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Line(line) => {
+                if self.lines.len() == self.scrollback {
+                    self.lines.pop_front();
+                }
+                self.lines.push_back(line);
+                self.scroll_to = Some(f32::INFINITY);
+            },
+            Update::Prompt(prompt) => self.prompt = prompt,
+            Update::Status { text, connected } => {
+                self.status = text;
+                self.connected = connected;
+            },
+            Update::Draft(draft) => {
+                if self.input.is_empty() {
+                    self.input = draft;
+                }
+            },
+            Update::Raise => self.raise = true,
+        }
+    }
+
+    // Scroll by a number of pages, up if negative.
+    fn page(&mut self, pages: f32) {
+        self.scroll_to = Some((self.offset + pages * self.page_height).max(0.0));
+    }
+
+    // A line, its links shown as such.
+    fn show_line(ui: &mut egui::Ui, line: &str, clicked: &mut Option<String>) {
+        let spans = markdown::parse(line);
+        if links::spans(line).is_empty() && spans.iter().all(|span| span.style == Style::default()) {
+            ui.label(line);
+            return;
+        }
+        let styled = |text: &str, style: Style| {
+            let mut text = egui::RichText::new(text);
+            if style.bold {
+                text = text.strong();
+            }
+            if style.italic {
+                text = text.italics();
+            }
+            if style.code {
+                text = text.code();
+            }
+            text
+        };
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            for span in spans {
+                let text = &span.text;
+                // On lines of their own, links in them being code like the rest.
+                if span.style.block {
+                    ui.end_row();
+                    ui.label(styled(text, span.style));
+                    ui.end_row();
+                    continue;
+                }
+                let mut shown = 0;
+                for (start, end) in links::spans(text) {
+                    ui.label(styled(&text[shown..start], span.style));
+                    if ui.link(styled(&text[start..end], span.style)).on_hover_text(links::url(&text[start..end])).clicked() {
+                        *clicked = Some(text[start..end].to_string());
+                    }
+                    shown = end;
+                }
+                ui.label(styled(&text[shown..], span.style));
+            }
+        });
+    }
+
+    // Asking whether to open the link clicked, as it comes from the peer.
+    fn confirm_link(&mut self, ctx: &egui::Context) {
+        let link = match self.link {
+            Some(ref link) => link.clone(),
+            None => return,
+        };
+        let mut answer = None;
+        egui::Window::new(tr!("title-open-link"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(tr!("confirm-open-link", url = links::url(&link)));
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("button-open")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("button-cancel")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+        match answer {
+            Some(true) => {
+                self.link = None;
+                if let Err(error) = links::open(&link) {
+                    self.apply(Update::Line(tr!("link-open-failed", error = error.to_string())));
+                }
+            },
+            Some(false) => self.link = None,
+            None => {},
+        }
+    }
+
+    // Told once egui changed the zoom factor, on the keyboard shortcuts it handles.
+    fn zoomed(&mut self, zoom: f32) {
+        if zoom != self.zoom {
+            self.zoom = zoom;
+            (self.on_event)(Event::Zoomed(zoom));
+        }
+    }
+
+    fn send(&mut self) {
+        let line = mem::take(&mut self.input);
+        if !line.trim().is_empty() {
+            (self.on_event)(Event::Line(line));
+        }
+    }
+}
+
+impl<'a, F: FnMut(Event)> eframe::App for ChatWindow<'a, F> {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        while let Ok(update) = self.updates.try_recv() {
+            self.apply(update);
+        }
+        self.zoomed(ctx.zoom_factor());
+        if self.chat.is_finished() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        } else if self.to_tray && ctx.input(|input| input.viewport().close_requested()) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+        }
+        if mem::take(&mut self.raise) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        }
+        egui::TopBottomPanel::top("status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let color = if self.connected { egui::Color32::GREEN } else { egui::Color32::RED };
+                ui.colored_label(color, "\u{25cf}");
+                ui.label(&self.status);
+            });
+        });
+        egui::TopBottomPanel::bottom("input").show(ctx, |ui| {
+            // Commands can be run while waiting for the peer.
+            let waiting = tr!("prompt-waiting");
+            ui.label(self.prompt.as_deref().unwrap_or(&waiting));
+            ui.horizontal(|ui| {
+                let was_empty = self.input.is_empty();
+                // Enter sends, and Shift+Enter goes to a new line of the message.
+                let field = egui::TextEdit::multiline(&mut self.input)
+                    .desired_rows(1)
+                    .return_key(egui::KeyboardShortcut::new(egui::Modifiers::SHIFT, egui::Key::Enter))
+                    .desired_width(ui.available_width() - 60.0);
+                let field = ui.add(field);
+                if was_empty && !self.input.is_empty() {
+                    (self.on_event)(Event::Typing);
+                }
+                if field.changed() {
+                    (self.on_event)(Event::Draft(self.input.clone()));
+                }
+                let entered = field.has_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter) && !input.modifiers.shift);
+                if ui.button("Send").clicked() || entered {
+                    self.send();
+                    field.request_focus();
+                }
+            });
+        });
+        if ctx.input(|input| input.key_pressed(egui::Key::PageUp)) {
+            self.page(-1.0);
+        }
+        if ctx.input(|input| input.key_pressed(egui::Key::PageDown)) {
+            self.page(1.0);
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let mut area = egui::ScrollArea::vertical().auto_shrink([false, false]);
+            if let Some(offset) = self.scroll_to.take() {
+                // Past the end is the bottom.
+                area = area.vertical_scroll_offset(offset);
+            }
+            let mut clicked = None;
+            let shown = area.show(ui, |ui| {
+                for line in self.lines.iter() {
+                    Self::show_line(ui, line, &mut clicked);
+                }
+            });
+            if clicked.is_some() {
+                self.link = clicked;
+            }
+            self.offset = shown.state.offset.y.min((shown.content_size.y - shown.inner_rect.height()).max(0.0));
+            self.page_height = shown.inner_rect.height();
+        });
+        self.confirm_link(ctx);
+        ctx.request_repaint_after(REFRESH);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        (self.on_event)(Event::Closed);
+    }
+}
+
+// Scale the sizes of all the text styles, for the body text to be `size` points.
+fn set_font_size(ctx: &egui::Context, size: f32) {
+    ctx.style_mut(|style| {
+        let scale = size / style.text_styles[&egui::TextStyle::Body].size;
+        for font in style.text_styles.values_mut() {
+            font.size *= scale;
+        }
+    });
+}
+
+// Show the chat, running on its own thread, in a window until it ends or the window is closed,
+// or only minimized if going `to_tray`, keeping the last `scrollback` lines, PageUp and PageDown scrolling through them.
+pub fn run<F: FnMut(Event)>(title: &str,
+                            updates: Receiver<Update>,
+                            chat: &JoinHandle<()>,
+                            options: Options,
+                            on_event: F)
+                            -> Result<(), String> {
+    let Options { scrollback, to_tray, font_size, zoom } = options;
+    let window = ChatWindow {
+        updates,
+        chat,
+        on_event,
+        lines: VecDeque::new(),
+        scrollback,
+        offset: 0.0,
+        page_height: 0.0,
+        scroll_to: None,
+        prompt: None,
+        status: tr!("status-connecting"),
+        connected: false,
+        input: String::new(),
+        link: None,
+        to_tray,
+        raise: false,
+        zoom,
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([640.0, 480.0]),
+        ..Default::default()
+    };
+    eframe::run_native(title, options, Box::new(move |creation| {
+        if let Some(size) = font_size {
+            set_font_size(&creation.egui_ctx, size);
+        }
+        creation.egui_ctx.set_zoom_factor(zoom);
+        Ok(Box::new(window))
+    }))
+        .map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn test_chat_window() {
+        let (_updates, port) = mpsc::channel();
+        let mut events = vec![];
+        let chat = thread::spawn(|| {});
+        let mut window = ChatWindow {
+            updates: port,
+            chat: &chat,
+            on_event: |event| events.push(event),
+            lines: VecDeque::new(),
+            scrollback: 100,
+            offset: 0.0,
+            page_height: 0.0,
+            scroll_to: None,
+            prompt: None,
+            status: String::new(),
+            connected: false,
+            input: String::new(),
+            link: None,
+            to_tray: false,
+            raise: false,
+            zoom: 1.0,
+        };
+        for line in 0..101 {
+            window.apply(Update::Line(line.to_string()));
+        }
+        assert_eq!(window.lines.len(), 100);
+        assert_eq!(window.lines.front().unwrap(), "1");
+        // Paging from where the lines were last shown, a new line jumping back to the bottom.
+        window.offset = 500.0;
+        window.page_height = 300.0;
+        window.page(-1.0);
+        assert_eq!(window.scroll_to, Some(200.0));
+        window.offset = 200.0;
+        window.page(-1.0);
+        assert_eq!(window.scroll_to, Some(0.0));
+        window.page(1.0);
+        assert_eq!(window.scroll_to, Some(500.0));
+        window.apply(Update::Line("new".to_string()));
+        assert_eq!(window.scroll_to, Some(f32::INFINITY));
+        window.apply(Update::Status { text: "server: active".to_string(), connected: true });
+        assert!(window.connected);
+        window.apply(Update::Draft("see you".to_string()));
+        assert_eq!(window.input, "see you");
+        window.apply(Update::Draft("not over what is typed".to_string()));
+        assert_eq!(window.input, "see you");
+        window.apply(Update::Raise);
+        assert!(window.raise);
+
+        window.zoomed(1.0);
+        window.zoomed(1.1);
+
+        window.input = " ".to_string();
+        window.send();
+        window.input = "hello".to_string();
+        window.send();
+        assert!(window.input.is_empty());
+        drop(window);
+        assert_eq!(events, vec![Event::Zoomed(1.1), Event::Line("hello".to_string())]);
+    }
+
+    #[test]
+    fn test_font_size() {
+        let ctx = egui::Context::default();
+        set_font_size(&ctx, 20.0);
+        let style = ctx.style();
+        assert_eq!(style.text_styles[&egui::TextStyle::Body].size, 20.0);
+        assert!(style.text_styles[&egui::TextStyle::Heading].size > 20.0);
+        assert!(style.text_styles[&egui::TextStyle::Small].size < 20.0);
+    }
+}
@@ -0,0 +1,313 @@
+use chrono::{DateTime, Local};
+use codec::MessageId;
+use links;
+use markdown;
+use mentions;
+use std::fmt;
+use std::time::SystemTime;
+use theme::Theme;
+
+
+// Beyond this, the oldest messages are forgotten.
+const HISTORY_SIZE: usize = 1000;
+
+// Our messages are shown as soon as they are sent, before the peer acknowledges them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delivery {
+    Sending,
+    Delivered,
+    // Seen by the peer, if it sends read receipts.
+    Read,
+    Failed,
+}
+
+pub struct HistoryEntry {
+    pub id: MessageId,
+    // Whether we sent the message, ids being assigned by the sender.
+    pub own: bool,
+    pub sender: String,
+    pub sent_at: SystemTime,
+    // `None` once deleted.
+    pub text: Option<String>,
+    // Who reacted, with which emoji, in order.
+    pub reactions: Vec<(String, String)>,
+    // The row of the message in the message store, if it is kept.
+    pub stored_as: Option<i64>,
+    pub delivery: Delivery,
+    // From a peer with an identity key, but not signed with it.
+    pub unverified: bool,
+    // An ephemeral message, never kept in the message store or transcript.
+    pub never_persist: bool,
+    // Our nickname, if the message mentions it, for the mentions to stand out.
+    pub mentions: Option<String>,
+    // The keywords watched which the message has, to stand out as well.
+    pub keywords: Vec<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(id: MessageId, own: bool, sender: &str, sent_at: SystemTime, text: String) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            own,
+            sender: sender.to_string(),
+            sent_at,
+            text: Some(text),
+            reactions: vec![],
+            stored_as: None,
+            delivery: Delivery::Delivered,
+            unverified: false,
+            never_persist: false,
+            mentions: None,
+            keywords: vec![],
+        }
+    }
+
+    // Each emoji, with how many reacted with it, in the order first used.
+    pub fn reaction_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = vec![];
+        for (_, emoji) in self.reactions.iter() {
+            match counts.iter_mut().find(|&&mut (counted, _)| counted == emoji) {
+                Some(&mut (_, ref mut count)) => *count += 1,
+                None => counts.push((emoji, 1)),
+            }
+        }
+        counts
+    }
+
+    // As shown in a terminal, the timestamp and the sender in the theme's colors,
+    // and the Markdown of the text styled if the theme is for it.
+    pub fn paint(&self, theme: &Theme) -> String {
+        let sent_at: DateTime<Local> = self.sent_at.into();
+        let sender_color = if self.own { theme.own } else { theme.nickname(&self.sender) };
+        let mut line = format!("{} #{} {}: ",
+                               theme.paint(theme.timestamp, &format!("[{}]", sent_at.format("%H:%M:%S"))),
+                               self.id,
+                               theme.paint(sender_color, &self.sender));
+        let text = self.text.as_ref().map(|text| if theme.markdown { markdown::to_ansi(text) } else { text.clone() });
+        let mut words: Vec<String> = self.mentions.iter().map(|nick| format!("@{}", nick)).collect();
+        words.extend(self.keywords.iter().cloned());
+        match text {
+            Some(text) => line.push_str(&mentions::highlight(&text, &words, |word| theme.paint(theme.mention, word))),
+            None => line.push_str(&tr!("message-deleted")),
+        }
+        if self.mentions.is_some() && self.text.is_some() {
+            line.push_str(&format!(" {}", tr!("message-mentions-you")));
+        }
+        match self.delivery {
+            Delivery::Sending => line.push_str(&format!(" {}", tr!("message-sending"))),
+            Delivery::Failed => line.push_str(&format!(" {}", tr!("message-not-delivered"))),
+            Delivery::Read => line.push_str(&format!(" {}", tr!("message-read"))),
+            Delivery::Delivered => {},
+        }
+        if self.unverified {
+            line.push_str(&format!(" {}", tr!("message-unverified")));
+        }
+        let counts = self.reaction_counts();
+        if !counts.is_empty() {
+            let counts: Vec<String> = counts.iter().map(|&(emoji, count)| format!("{} {}", emoji, count)).collect();
+            line.push_str(&format!("\n    {}", counts.join("  ")));
+        }
+        line
+    }
+}
+
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.paint(&Theme::plain()))
+    }
+}
+
+// The messages of the session, in the order they were delivered.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    // Our messages shown before being delivered.
+    sending: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn echo(&mut self, mut entry: HistoryEntry) -> &HistoryEntry {
+        entry.delivery = Delivery::Sending;
+        self.sending.push(entry);
+        self.sending.last().unwrap()
+    }
+
+    // Take back our message once its delivery succeeded or failed, to push it in its place.
+    pub fn take_echo(&mut self, id: MessageId) -> Option<HistoryEntry> {
+        let index = self.sending.iter().position(|entry| entry.id == id)?;
+        Some(self.sending.remove(index))
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.entries.len() == HISTORY_SIZE {
+            self.entries.remove(0);
+        }
+        self.entries.push(entry);
+    }
+
+    // Mark a message as deleted, returning it if it was found, and not already deleted.
+    pub fn delete(&mut self, id: MessageId, own: bool) -> Option<&HistoryEntry> {
+        let entry = self.entries.iter_mut().rev().find(|entry| entry.id == id && entry.own == own)?;
+        entry.text.take()?;
+        Some(entry)
+    }
+
+    // Remove a message altogether, such as an ephemeral one once expired,
+    // whether delivered or still being sent.
+    pub fn remove(&mut self, id: MessageId, own: bool) -> Option<HistoryEntry> {
+        let matches = |entry: &HistoryEntry| entry.id == id && entry.own == own;
+        if let Some(index) = self.entries.iter().rposition(matches) {
+            return Some(self.entries.remove(index));
+        }
+        let index = self.sending.iter().position(matches)?;
+        Some(self.sending.remove(index))
+    }
+
+    // Mark our message as read by the peer, returning it if it was found, and not already read.
+    pub fn read(&mut self, id: MessageId) -> Option<&HistoryEntry> {
+        let entry = self.entries.iter_mut().rev().find(|entry| entry.id == id && entry.own)?;
+        if entry.delivery != Delivery::Delivered {
+            return None;
+        }
+        entry.delivery = Delivery::Read;
+        Some(entry)
+    }
+
+    // The text of a message, if it was found, and not deleted.
+    pub fn text(&self, id: MessageId, own: bool) -> Option<&str> {
+        self.entries.iter().rev().find(|entry| entry.id == id && entry.own == own)?.text.as_deref()
+    }
+
+    // Add a reaction to a message, returning the message if it was found, and not deleted.
+    pub fn react(&mut self, id: MessageId, own: bool, reactor: &str, emoji: &str) -> Option<&HistoryEntry> {
+        let entry = self.entries.iter_mut().rev().find(|entry| entry.id == id && entry.own == own)?;
+        entry.text.as_ref()?;
+        let reaction = (reactor.to_string(), emoji.to_string());
+        if !entry.reactions.contains(&reaction) {
+            entry.reactions.push(reaction);
+        }
+        Some(entry)
+    }
+
+    // The links in the peer's messages, the latest first.
+    pub fn links(&self) -> Vec<&str> {
+        self.entries.iter().rev()
+            .filter(|entry| !entry.own)
+            .filter_map(|entry| entry.text.as_deref())
+            .flat_map(|text| links::find(text).into_iter().rev())
+            .collect()
+    }
+
+    // The last `count` messages.
+    pub fn last(&self, count: usize) -> &[HistoryEntry] {
+        &self.entries[self.entries.len().saturating_sub(count)..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: MessageId, own: bool, text: &str) -> HistoryEntry {
+        let sender = if own { "me" } else { "peer" };
+        HistoryEntry::new(id, own, sender, SystemTime::now(), text.to_string())
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut history = History::default();
+        history.push(entry(1, false, "hi"));
+        history.push(entry(1, true, "hello"));
+        assert!(history.delete(1, true).is_some());
+        assert!(history.delete(1, true).is_none());
+        assert!(history.delete(2, false).is_none());
+        assert_eq!(history.text(1, false), Some("hi"));
+        assert_eq!(history.text(1, true), None);
+        let last = history.last(5);
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[0].text.as_deref(), Some("hi"));
+        assert!(last[1].to_string().ends_with("#1 me: (message deleted)"));
+    }
+
+    #[test]
+    fn test_links() {
+        let mut history = History::default();
+        history.push(entry(1, false, "see https://example.com and www.example.org"));
+        history.push(entry(1, true, "my own https://example.net"));
+        history.push(entry(2, false, "gone https://example.info"));
+        history.push(entry(3, false, "and http://example.com/latest"));
+        history.delete(2, false);
+        assert_eq!(history.links(), vec!["http://example.com/latest", "www.example.org", "https://example.com"]);
+    }
+
+    #[test]
+    fn test_local_echo() {
+        let mut history = History::default();
+        assert!(history.echo(entry(1, true, "hello")).to_string().ends_with("#1 me: hello (sending)"));
+        history.echo(entry(2, true, "still there?"));
+        // Echoes aren't part of the history until delivered.
+        history.push(entry(1, false, "hi"));
+        let mut delivered = history.take_echo(1).unwrap();
+        delivered.delivery = Delivery::Delivered;
+        history.push(delivered);
+        let mut failed = history.take_echo(2).unwrap();
+        failed.delivery = Delivery::Failed;
+        history.push(failed);
+        assert!(history.take_echo(2).is_none());
+        let last: Vec<String> = history.last(5).iter().map(HistoryEntry::to_string).collect();
+        assert!(last[0].ends_with("#1 peer: hi"));
+        assert!(last[1].ends_with("#1 me: hello"));
+        assert!(last[2].ends_with("#2 me: still there? (not delivered)"));
+        assert!(history.read(1).is_some());
+        assert!(history.read(1).is_none());
+        // Never delivered.
+        assert!(history.read(2).is_none());
+        assert!(history.last(5)[1].to_string().ends_with("#1 me: hello (read)"));
+        let mut spoofed = entry(2, false, "send me your password");
+        spoofed.unverified = true;
+        assert!(spoofed.to_string().ends_with("#2 peer: send me your password (not signed by the peer)"));
+        let theme = Theme::default();
+        let painted = spoofed.paint(&theme);
+        assert!(painted.starts_with("\u{1b}[90m["));
+        assert!(painted.contains(&format!("#2 {}: send me", theme.paint(theme.nickname("peer"), "peer"))));
+
+        let mut mention = entry(3, false, "@me, lunch?");
+        mention.mentions = Some("me".to_string());
+        assert!(mention.to_string().ends_with("#3 peer: @me, lunch? (mentions you)"));
+        assert!(mention.paint(&theme).contains(&format!("{}, lunch?", theme.paint(theme.mention, "@me"))));
+        mention.keywords = vec!["lunch".to_string()];
+        assert!(mention.paint(&theme).contains(&format!(", {}?", theme.paint(theme.mention, "lunch"))));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut history = History::default();
+        history.push(entry(1, false, "gone in a minute"));
+        history.push(entry(1, true, "noted"));
+        history.echo(entry(2, true, "me too"));
+        assert_eq!(history.remove(1, false).unwrap().text.as_deref(), Some("gone in a minute"));
+        assert!(history.remove(1, false).is_none());
+        // Expiring before delivery, it isn't shown once delivered.
+        assert!(history.remove(2, true).is_some());
+        assert!(history.take_echo(2).is_none());
+        let last = history.last(5);
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].text.as_deref(), Some("noted"));
+    }
+
+    #[test]
+    fn test_reactions() {
+        let mut history = History::default();
+        history.push(entry(1, true, "hello"));
+        assert!(history.react(1, true, "alice", "👍").is_some());
+        assert!(history.react(1, true, "bob", "👍").is_some());
+        assert!(history.react(1, true, "bob", "🎉").is_some());
+        // Reacting twice the same way counts once.
+        let entry = history.react(1, true, "bob", "👍").unwrap();
+        assert_eq!(entry.reaction_counts(), vec![("👍", 2), ("🎉", 1)]);
+        assert!(entry.to_string().ends_with("hello\n    👍 2  🎉 1"));
+        assert!(history.react(1, false, "bob", "👍").is_none());
+        history.delete(1, true);
+        assert!(history.react(1, true, "carol", "👍").is_none());
+    }
+}
@@ -0,0 +1,178 @@
+use codec::MessageId;
+use config;
+use ed25519_dalek::{SECRET_KEY_LENGTH, Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+
+// The long-term identity of this installation, created on first use,
+// and kept in the config directory.
+pub fn load_or_create_key() -> Result<SigningKey, String> {
+    let path = config::config_dir().ok_or("no config directory to keep the identity key in")?.join("identity_key");
+    if let Ok(bytes) = fs::read(&path) {
+        let secret: [u8; SECRET_KEY_LENGTH] = bytes.as_slice().try_into()
+            .map_err(|_| format!("invalid identity key {}", path.display()))?;
+        return Ok(SigningKey::from_bytes(&secret));
+    }
+    let key = SigningKey::generate(&mut OsRng);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // Only readable by the user, as anyone with it can pass for them.
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    file.write_all(&key.to_bytes()).map_err(|error| error.to_string())?;
+    Ok(key)
+}
+
+// A short digest of a public key, for users to compare out of band.
+pub fn fingerprint(key: &[u8]) -> String {
+    let digest = Sha256::digest(key);
+    let pairs: Vec<String> = digest[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("SHA256:{}", pairs.join(":"))
+}
+
+// What is signed of a message: its text, and its id, so that it can't be passed for another one.
+fn signed_bytes(id: MessageId, text: &str) -> Vec<u8> {
+    let mut bytes = id.to_be_bytes().to_vec();
+    bytes.extend_from_slice(text.as_bytes());
+    bytes
+}
+
+pub fn sign_message(key: &SigningKey, id: MessageId, text: &str) -> Vec<u8> {
+    key.sign(&signed_bytes(id, text)).to_bytes().to_vec()
+}
+
+// Whether the message was signed with the key, by the peer rather than whoever relayed it.
+pub fn verify_message(key: &VerifyingKey, id: MessageId, text: &str, signature: &[u8]) -> bool {
+    match Signature::from_slice(signature) {
+        Ok(signature) => key.verify(&signed_bytes(id, text), &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn to_hex(key: &[u8]) -> String {
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len() / 2).map(|index| u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()).collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyCheck {
+    // First seen, and now remembered.
+    New,
+    Known,
+    // Not the key seen before, with the fingerprint of that one.
+    Changed(String),
+}
+
+// The identity keys of the peers seen so far, one `<peer> <hex key>` per line,
+// trusted on first use.
+#[derive(Debug, Default)]
+pub struct KnownPeers {
+    path: Option<PathBuf>,
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl KnownPeers {
+    pub fn parse(source: &str) -> KnownPeers {
+        let keys = source.lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter_map(|(peer, key)| Some((peer.to_string(), from_hex(key.trim())?)))
+            .collect();
+        KnownPeers {
+            path: None,
+            keys,
+        }
+    }
+
+    // The keys kept in the config directory, none if there are none yet.
+    pub fn load() -> KnownPeers {
+        let path = config::config_dir().map(|dir| dir.join("known_peers"));
+        let source = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        KnownPeers {
+            path,
+            ..KnownPeers::parse(&source)
+        }
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    // A changed key is not remembered, the line of the peer has to be removed
+    // from the file to trust the new one.
+    pub fn check(&mut self, peer: &str, key: &[u8]) -> Result<KeyCheck, String> {
+        match self.keys.get(peer) {
+            Some(known) if known.as_slice() == key => return Ok(KeyCheck::Known),
+            Some(known) => return Ok(KeyCheck::Changed(fingerprint(known))),
+            None => {},
+        }
+        self.keys.insert(peer.to_string(), key.to_vec());
+        self.save()?;
+        Ok(KeyCheck::New)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        let source: String = self.keys.iter()
+            .map(|(peer, key)| format!("{} {}\n", peer, to_hex(key)))
+            .collect();
+        fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_on_first_use() {
+        let alice = SigningKey::from_bytes(&[1; SECRET_KEY_LENGTH]).verifying_key();
+        let mallory = SigningKey::from_bytes(&[2; SECRET_KEY_LENGTH]).verifying_key();
+        let mut known = KnownPeers::parse("bob 00ff\ncarol 0\n");
+        assert_eq!(known.check("alice", alice.as_bytes()), Ok(KeyCheck::New));
+        assert_eq!(known.check("alice", alice.as_bytes()), Ok(KeyCheck::Known));
+        assert_eq!(known.check("alice", mallory.as_bytes()),
+                   Ok(KeyCheck::Changed(fingerprint(alice.as_bytes()))));
+        // The first key is kept.
+        assert_eq!(known.check("alice", alice.as_bytes()), Ok(KeyCheck::Known));
+        assert_eq!(known.check("bob", &[0, 255]), Ok(KeyCheck::Known));
+        // Invalid lines are ignored.
+        assert_eq!(known.check("carol", &[0]), Ok(KeyCheck::New));
+        assert!(fingerprint(alice.as_bytes()).starts_with("SHA256:"));
+    }
+
+    #[test]
+    fn test_message_signatures() {
+        let alice = SigningKey::from_bytes(&[1; SECRET_KEY_LENGTH]);
+        let mallory = SigningKey::from_bytes(&[2; SECRET_KEY_LENGTH]);
+        let signature = sign_message(&alice, 1, "hi");
+        assert!(verify_message(&alice.verifying_key(), 1, "hi", &signature));
+        assert!(!verify_message(&alice.verifying_key(), 1, "hi!", &signature));
+        assert!(!verify_message(&alice.verifying_key(), 2, "hi", &signature));
+        assert!(!verify_message(&alice.verifying_key(), 1, "hi", &sign_message(&mallory, 1, "hi")));
+        assert!(!verify_message(&alice.verifying_key(), 1, "hi", &[0; 3]));
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::Local;
+use config;
+use image::{self, imageops::FilterType, DynamicImage, ImageFormat, RgbImage};
+use std::fs;
+use std::path::PathBuf;
+
+
+// The largest sides an image is shrunk to, in turn, until it fits in a message.
+const SHRUNK_SIDES: &[u32] = &[1024, 512, 256, 128, 64];
+// Of an image shrunk to fit.
+const JPEG_QUALITY: u8 = 75;
+
+// The MIME type of the image, if it is one that can be shown.
+pub fn mime_type(data: &[u8]) -> Option<&'static str> {
+    match image::guess_format(data).ok()? {
+        format @ ImageFormat::Png | format @ ImageFormat::Jpeg | format @ ImageFormat::Gif => Some(format.to_mime_type()),
+        _ => None,
+    }
+}
+
+fn extension(mime: &str) -> &'static str {
+    ImageFormat::from_mime_type(mime)
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("bin")
+}
+
+// The image to send, with its MIME type, re-encoded as a smaller JPEG
+// if it doesn't fit in `max_size` bytes.
+pub fn prepare(data: Vec<u8>, max_size: usize) -> Result<(Vec<u8>, &'static str), String> {
+    let mime = mime_type(&data).ok_or("not a PNG, JPEG or GIF image")?;
+    if data.len() <= max_size {
+        return Ok((data, mime));
+    }
+    let image = image::load_from_memory(&data).map_err(|error| error.to_string())?;
+    for &side in SHRUNK_SIDES {
+        let shrunk = DynamicImage::ImageRgb8(image.thumbnail(side, side).to_rgb8());
+        let mut jpeg = vec![];
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY);
+        shrunk.write_with_encoder(encoder).map_err(|error| error.to_string())?;
+        if jpeg.len() <= max_size {
+            return Ok((jpeg, ImageFormat::Jpeg.to_mime_type()));
+        }
+    }
+    Err(format!("too large, even shrunk, images are sent in at most {} bytes", max_size))
+}
+
+// The nickname of the peer, which it chose, made safe to be part of a file name:
+// without path separators, nor anything else than letters, digits, `-`, `_` and `.`.
+fn safe_name(sender: &str) -> String {
+    sender.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+// In the `images` data directory, named after the time and the sender.
+pub fn save_received(data: &[u8], mime: &str, sender: &str) -> Result<PathBuf, String> {
+    let dir = config::data_dir().ok_or("no data directory to keep images in")?.join("images");
+    fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+    let name = format!("{}-{}.{}", Local::now().format("%Y-%m-%d-%H%M%S"), safe_name(sender), extension(mime));
+    let path = dir.join(name);
+    fs::write(&path, data).map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(path)
+}
+
+// The image drawn with half blocks in 24-bit color, two pixels per character,
+// at most `width` characters wide.
+pub fn thumbnail(data: &[u8], width: u32) -> Result<String, String> {
+    let image = image::load_from_memory(data).map_err(|error| error.to_string())?;
+    let image = image.resize(width, width, FilterType::Triangle).to_rgb8();
+    Ok(half_blocks(&image))
+}
+
+fn half_blocks(image: &RgbImage) -> String {
+    let mut lines = vec![];
+    for y in (0..image.height()).step_by(2) {
+        let mut line = String::new();
+        for x in 0..image.width() {
+            let top = image.get_pixel(x, y);
+            // The last row of an odd height is drawn over black.
+            let bottom = if y + 1 < image.height() { image.get_pixel(x, y + 1).0 } else { [0, 0, 0] };
+            line.push_str(&format!("\u{1b}[38;2;{};{};{}m\u{1b}[48;2;{};{};{}m\u{2580}",
+                                   top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]));
+        }
+        line.push_str("\u{1b}[0m");
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use std::io::Cursor;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb([(x * 7) as u8, (y * 13) as u8, ((x * y) % 256) as u8]));
+        let mut data = Cursor::new(vec![]);
+        image.write_to(&mut data, ImageFormat::Png).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_prepare() {
+        let small = png(4, 4);
+        assert_eq!(mime_type(&small), Some("image/png"));
+        assert_eq!(prepare(small.clone(), 1024), Ok((small, "image/png")));
+
+        let (shrunk, mime) = prepare(png(600, 400), 8 * 1024).unwrap();
+        assert_eq!(mime, "image/jpeg");
+        assert!(shrunk.len() <= 8 * 1024);
+
+        assert!(prepare(b"not an image".to_vec(), 1024).is_err());
+        assert_eq!(extension("image/jpeg"), "jpg");
+    }
+
+    #[test]
+    fn test_safe_name() {
+        assert_eq!(safe_name("alice"), "alice");
+        assert_eq!(safe_name("../../.bashrc"), ".._.._.bashrc");
+        assert_eq!(safe_name("C:\\Users\\bob"), "C__Users_bob");
+        assert_eq!(safe_name("zoë *"), "zoë__");
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let thumbnail = thumbnail(&png(8, 3), 8).unwrap();
+        let lines: Vec<&str> = thumbnail.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].matches('\u{2580}').count(), 8);
+        assert!(lines[1].contains("\u{1b}[48;2;0;0;0m"));
+    }
+}
@@ -0,0 +1,94 @@
+use codec::MessageId;
+use std::collections::BTreeMap;
+use std::mem;
+use std::time::{Duration, Instant};
+
+
+struct Sent<T> {
+    message: T,
+    last_sent: Instant,
+    // How many times it was sent, from 1.
+    attempts: u32,
+}
+
+// Our messages sent without waiting for their ack, by id,
+// until they are acknowledged or given up on.
+pub struct InFlight<T> {
+    messages: BTreeMap<MessageId, Sent<T>>,
+}
+
+impl<T> Default for InFlight<T> {
+    fn default() -> InFlight<T> {
+        InFlight {
+            messages: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> InFlight<T> {
+    pub fn insert(&mut self, id: MessageId, message: T) {
+        self.messages.insert(id, Sent {
+            message,
+            last_sent: Instant::now(),
+            attempts: 1,
+        });
+    }
+
+    // Returns the message, with the roundtrip since it was last sent,
+    // if it was still waiting for its ack.
+    pub fn acknowledge(&mut self, id: MessageId) -> Option<(T, Duration)> {
+        self.messages.remove(&id).map(|sent| (sent.message, sent.last_sent.elapsed()))
+    }
+
+    // How long until the first of the messages was sent `timeout` ago.
+    pub fn time_left(&self, timeout: Duration) -> Option<Duration> {
+        self.messages.values()
+            .map(|sent| timeout.saturating_sub(sent.last_sent.elapsed()))
+            .min()
+    }
+
+    // The messages sent `timeout` ago or more, with how many times they were sent,
+    // to send them again.
+    pub fn expired(&mut self, timeout: Duration) -> Vec<(MessageId, u32, &T)> {
+        self.messages.iter_mut()
+            .filter(|(_, sent)| sent.last_sent.elapsed() >= timeout)
+            .map(|(&id, sent)| {
+                sent.last_sent = Instant::now();
+                sent.attempts += 1;
+                (id, sent.attempts - 1, &sent.message)
+            })
+            .collect()
+    }
+
+    // All the messages waiting, in the order they were sent, to give up on them.
+    pub fn take_all(&mut self) -> Vec<(MessageId, T)> {
+        let messages = mem::take(&mut self.messages);
+        messages.into_iter().map(|(id, sent)| (id, sent.message)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn test_in_flight() {
+        let mut in_flight = InFlight::default();
+        in_flight.insert(1, "one");
+        in_flight.insert(2, "two");
+        assert!(in_flight.expired(Duration::from_secs(5)).is_empty());
+        assert!(in_flight.acknowledge(1).is_some());
+        assert!(in_flight.acknowledge(1).is_none());
+
+        sleep(Duration::from_millis(20));
+        assert_eq!(in_flight.time_left(Duration::from_millis(10)), Some(Duration::default()));
+        assert_eq!(in_flight.expired(Duration::from_millis(10)), vec![(2, 1, &"two")]);
+        // Sent again just now.
+        assert!(in_flight.expired(Duration::from_millis(10)).is_empty());
+
+        in_flight.insert(3, "three");
+        assert_eq!(in_flight.take_all(), vec![(2, "two"), (3, "three")]);
+        assert_eq!(in_flight.time_left(Duration::from_millis(10)), None);
+    }
+}
@@ -0,0 +1,206 @@
+use serde::Deserialize;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tinyfiledialogs;
+
+
+// Programs tinyfiledialogs can use to show graphical dialogs on Linux and BSDs.
+const DIALOG_BACKENDS: &[&str] = &["zenity", "kdialog", "qarma", "matedialog", "yad", "Xdialog"];
+
+// How the user is prompted for input.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputMode {
+    Dialog,
+    // Read lines from the terminal, end of input (Ctrl-D) standing for 'Cancel'.
+    Stdin,
+    // No input, the chat being driven through the control socket.
+    Headless,
+}
+
+pub fn in_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
+
+// Why dialogs can't be shown, if that's the case.
+pub fn dialog_unavailable_reason() -> Option<String> {
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        return None;
+    }
+    if env::var_os("DISPLAY").is_none() && env::var_os("WAYLAND_DISPLAY").is_none() {
+        return Some("no graphical display is available".to_string());
+    }
+    if !DIALOG_BACKENDS.iter().any(|backend| in_path(backend)) {
+        return Some(format!("none of {} is installed", DIALOG_BACKENDS.join(", ")));
+    }
+    None
+}
+
+impl InputMode {
+    // Use dialogs when possible, telling the user when falling back to the terminal.
+    pub fn detect() -> InputMode {
+        match dialog_unavailable_reason() {
+            None => InputMode::Dialog,
+            Some(reason) => {
+                println!("{}", tr!("dialogs-unavailable", reason = reason));
+                InputMode::Stdin
+            },
+        }
+    }
+
+    pub fn input_box(&self, title: &str, prompt: &str, default: &str) -> Option<String> {
+        match *self {
+            InputMode::Dialog => tinyfiledialogs::input_box(title, prompt, default),
+            InputMode::Stdin => {
+                print!("{}: ", prompt);
+                let _ = io::stdout().flush();
+                let stdin = io::stdin();
+                let mut lock = stdin.lock();
+                read_line(&mut lock)
+            },
+            InputMode::Headless => None,
+        }
+    }
+
+    // A message, of several lines: in a text area if dialogs are used and zenity or kdialog
+    // can show one, and in the terminal, a line ending with a backslash going on with the next.
+    // The draft is what the dialog starts with, and is recalled in the terminal.
+    pub fn message_box(&self, title: &str, prompt: &str, draft: &str) -> Option<String> {
+        match *self {
+            InputMode::Dialog => text_area(title, prompt, draft).unwrap_or_else(|| tinyfiledialogs::input_box(title, prompt, draft)),
+            InputMode::Stdin => {
+                if !draft.is_empty() {
+                    println!("{}", tr!("draft-recalled", draft = draft));
+                }
+                print!("{}: ", prompt);
+                let _ = io::stdout().flush();
+                let stdin = io::stdin();
+                let mut lock = stdin.lock();
+                read_message(&mut lock)
+            },
+            InputMode::Headless => None,
+        }
+    }
+
+    // Note: in the terminal, the password is echoed.
+    pub fn password_box(&self, title: &str, prompt: &str) -> Option<String> {
+        match *self {
+            InputMode::Dialog => tinyfiledialogs::password_box(title, prompt),
+            InputMode::Stdin | InputMode::Headless => self.input_box(title, prompt, ""),
+        }
+    }
+
+    // Whether the user answers yes, no being the default, and the answer without input.
+    pub fn confirm(&self, title: &str, question: &str) -> bool {
+        match *self {
+            InputMode::Dialog => {
+                tinyfiledialogs::message_box_yes_no(title, question, tinyfiledialogs::MessageBoxIcon::Question, tinyfiledialogs::YesNo::No)
+                    == tinyfiledialogs::YesNo::Yes
+            },
+            InputMode::Stdin => {
+                print!("{} [y/N] ", question);
+                let _ = io::stdout().flush();
+                let stdin = io::stdin();
+                let mut lock = stdin.lock();
+                read_line(&mut lock).is_some_and(|answer| is_yes(&answer))
+            },
+            InputMode::Headless => false,
+        }
+    }
+
+    // Printed, and also shown in a dialog when using those.
+    pub fn warn(&self, title: &str, message: &str) {
+        println!("{}", message);
+        if *self == InputMode::Dialog {
+            tinyfiledialogs::message_box_ok(title, message, tinyfiledialogs::MessageBoxIcon::Warning);
+        }
+    }
+}
+
+// `None` if no program to show it is installed, and `Some(None)` on 'Cancel'.
+fn text_area(title: &str, prompt: &str, text: &str) -> Option<Option<String>> {
+    // zenity reads the text to start with from its input.
+    let mut command = if in_path("zenity") {
+        let mut command = Command::new("zenity");
+        command.args(["--text-info", "--editable", "--title", title]);
+        command
+    } else if in_path("kdialog") {
+        let mut command = Command::new("kdialog");
+        command.args(["--title", title, "--textinputbox", prompt, text]);
+        command
+    } else {
+        return None;
+    };
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().ok()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return Some(None);
+    }
+    Some(Some(String::from_utf8_lossy(&output.stdout).trim_end_matches(['\n', '\r']).to_string()))
+}
+
+fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    answer == "y" || answer == "yes" || answer == tr!("answer-yes").to_lowercase()
+}
+
+// Read a line, without its line ending, `None` at the end of input.
+pub fn read_line<R: BufRead>(reader: &mut R) -> Option<String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+    }
+}
+
+// Read a message, each line ending with a backslash going on with the next, `None` at the end of input.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Option<String> {
+    let mut message = read_line(reader)?;
+    while message.ends_with('\\') {
+        message.pop();
+        message.push('\n');
+        match read_line(reader) {
+            Some(line) => message.push_str(&line),
+            None => break,
+        }
+    }
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_line() {
+        let mut input = Cursor::new("hello\r\n\nlast");
+        assert_eq!(read_line(&mut input), Some("hello".to_string()));
+        assert_eq!(read_line(&mut input), Some("".to_string()));
+        assert_eq!(read_line(&mut input), Some("last".to_string()));
+        assert_eq!(read_line(&mut input), None);
+    }
+
+    #[test]
+    fn test_is_yes() {
+        assert!(is_yes(" Y\n"));
+        assert!(is_yes("yes"));
+        assert!(!is_yes(""));
+        assert!(!is_yes("n"));
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut input = Cursor::new("first line\\\nsecond\\\n\nnext message\\");
+        assert_eq!(read_message(&mut input), Some("first line\nsecond\n".to_string()));
+        assert_eq!(read_message(&mut input), Some("next message\n".to_string()));
+        assert_eq!(read_message(&mut input), None);
+    }
+}
@@ -0,0 +1,132 @@
+use config;
+use rand::RngCore;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+pub const TOKEN_SIZE: usize = 16;
+// Unless `--expires` says otherwise.
+pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, PartialEq)]
+struct Invitation {
+    token: String,
+    expires: SystemTime,
+    room: String,
+}
+
+// The invitations to the rooms of the rendezvous server, issued with `simple_chat invite`,
+// one per line as `<token> <expiry, in seconds since the epoch> <room>`, kept in the data directory.
+// A room is protected once invited to, the peers then having to give a token of it which
+// hasn't expired, the expired ones being kept for the room to stay protected.
+#[derive(Debug, Default)]
+pub struct Invitations {
+    path: Option<PathBuf>,
+    invitations: Vec<Invitation>,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    Some(config::data_dir()?.join("invitations"))
+}
+
+impl Invitations {
+    pub fn parse(source: &str) -> Invitations {
+        let invitations = source.lines().filter_map(|line| {
+            let mut fields = line.trim().splitn(3, ' ');
+            let token = fields.next()?.to_string();
+            let expires = UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?);
+            let room = fields.next()?.to_string();
+            Some(Invitation {
+                token,
+                expires,
+                room,
+            })
+        }).collect();
+        Invitations {
+            path: None,
+            invitations,
+        }
+    }
+
+    // Those kept at the path, none if there are none yet.
+    pub fn load(path: Option<&Path>) -> Invitations {
+        let source = path.and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        Invitations {
+            path: path.map(Path::to_path_buf),
+            ..Invitations::parse(&source)
+        }
+    }
+
+    // A new token to the room, valid for as long as given, returning it.
+    pub fn issue(&mut self, room: &str, lifetime: Duration) -> Result<String, String> {
+        let mut bytes = [0; TOKEN_SIZE];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.invitations.push(Invitation {
+            token: token.clone(),
+            expires: SystemTime::now() + lifetime,
+            room: room.to_string(),
+        });
+        self.save()?;
+        Ok(token)
+    }
+
+    pub fn is_protected(&self, room: &str) -> bool {
+        self.invitations.iter().any(|invitation| invitation.room == room)
+    }
+
+    // Whether a peer giving the token may join the room, any peer joining those not protected.
+    // The token can be given again until it expires, both peers registering more than once.
+    pub fn admits(&self, room: &str, token: Option<&str>, now: SystemTime) -> bool {
+        if !self.is_protected(room) {
+            return true;
+        }
+        self.invitations.iter().any(|invitation| {
+            invitation.room == room && Some(invitation.token.as_str()) == token && invitation.expires > now
+        })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        let source: String = self.invitations.iter().map(|invitation| {
+            let expires = invitation.expires.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            format!("{} {} {}\n", invitation.token, expires, invitation.room)
+        }).collect();
+        fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_invitations() {
+        let path = env::temp_dir().join("simple_chat_test_invitations");
+        let _ = fs::remove_file(&path);
+        let mut invitations = Invitations::load(Some(&path));
+        let now = SystemTime::now();
+        assert!(invitations.admits("general", None, now));
+        let token = invitations.issue("general chat", Duration::from_secs(60 * 60)).unwrap();
+        assert_eq!(token.len(), 2 * TOKEN_SIZE);
+
+        let invitations = Invitations::load(Some(&path));
+        assert!(invitations.admits("general chat", Some(&token), now));
+        assert!(!invitations.admits("general chat", None, now));
+        assert!(!invitations.admits("general chat", Some("0123"), now));
+        // Expired.
+        assert!(!invitations.admits("general chat", Some(&token), now + Duration::from_secs(2 * 60 * 60)));
+        // Only to its room.
+        assert!(invitations.admits("general", None, now));
+        assert!(Invitations::parse("not an invitation\n").invitations.is_empty());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,97 @@
+use std::io;
+use std::process::{Command, Stdio};
+use std::thread;
+
+
+// How a link starts, the rest of it going up to the next space.
+const STARTS: &[&str] = &["https://", "http://", "www."];
+// Closing a sentence or a quote around a link, rather than being part of it.
+const TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', '\'', '"', ']', '>'];
+
+// Where the links are in the text, as byte ranges.
+pub fn spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut from = 0;
+    while let Some(start) = STARTS.iter().filter_map(|start| text[from..].find(start)).min().map(|found| from + found) {
+        let end = text[start..].find(char::is_whitespace).map_or(text.len(), |end| start + end);
+        from = end;
+        // Not the middle of a word, such as `awww.`.
+        if text[..start].chars().next_back().is_some_and(char::is_alphanumeric) {
+            continue;
+        }
+        let mut link = text[start..end].trim_end_matches(TRAILING);
+        // A closing parenthesis is part of the link only if it closes one opened in it, as on Wikipedia.
+        while link.ends_with(')') && link.matches(')').count() > link.matches('(').count() {
+            link = link[..link.len() - 1].trim_end_matches(TRAILING);
+        }
+        if !STARTS.contains(&link) && STARTS.iter().any(|start| link.starts_with(start)) {
+            spans.push((start, start + link.len()));
+        }
+    }
+    spans
+}
+
+// The links in the text, in order.
+pub fn find(text: &str) -> Vec<&str> {
+    spans(text).into_iter().map(|(start, end)| &text[start..end]).collect()
+}
+
+// The text with its links underlined, for a terminal.
+pub fn underline(text: &str) -> String {
+    let mut underlined = String::with_capacity(text.len());
+    let mut shown = 0;
+    for (start, end) in spans(text) {
+        underlined.push_str(&text[shown..start]);
+        underlined.push_str(&format!("\u{1b}[4m{}\u{1b}[24m", &text[start..end]));
+        shown = end;
+    }
+    underlined.push_str(&text[shown..]);
+    underlined
+}
+
+// Where the link leads, `www.` ones being taken to be on the web.
+pub fn url(link: &str) -> String {
+    if link.starts_with("www.") {
+        format!("https://{}", link)
+    } else {
+        link.to_string()
+    }
+}
+
+// Open the link with the system's browser.
+pub fn open(link: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command.arg(url(link))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Not to leave it a zombie, the browser possibly staying open long after.
+    thread::Builder::new().spawn(move || child.wait()).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("see https://example.com/a?b=c, or www.rust-lang.org."),
+                   vec!["https://example.com/a?b=c", "www.rust-lang.org"]);
+        assert_eq!(find("(https://en.wikipedia.org/wiki/Rust_(programming_language))"),
+                   vec!["https://en.wikipedia.org/wiki/Rust_(programming_language)"]);
+        assert_eq!(find("\"http://example.com\"\nnext line"), vec!["http://example.com"]);
+        assert!(find("awww.not a link, nor https:// alone").is_empty());
+        assert_eq!(underline("go to www.example.com!"), "go to \u{1b}[4mwww.example.com\u{1b}[24m!");
+        assert_eq!(url("www.example.com"), "https://www.example.com");
+        assert_eq!(url("http://example.com"), "http://example.com");
+    }
+}
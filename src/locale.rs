@@ -0,0 +1,123 @@
+pub use fluent_bundle::FluentArgs;
+use fluent_bundle::FluentResource;
+use fluent_bundle::concurrent::FluentBundle;
+use std::env;
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+
+// The languages the UI is translated to, with their messages,
+// the first being the default, and used for the messages missing from the others.
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.ftl")),
+    ("fr", include_str!("locales/fr.ftl")),
+];
+
+// That of `--lang`, set once at start.
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+pub struct Locale {
+    // That of the language chosen, then that of the default one.
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+fn bundle(lang: &str, source: &str) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = lang.parse().expect("the shipped locales have valid names");
+    let resource = FluentResource::try_new(source.to_string()).expect("the shipped messages are valid");
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // The isolation marks around arguments show up as is in a terminal.
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).expect("the shipped messages are defined once");
+    bundle
+}
+
+impl Locale {
+    pub fn new(lang: &str) -> Result<Locale, String> {
+        let source = LOCALES.iter().find(|&&(name, _)| name == lang).map(|&(_, source)| source)
+            .ok_or_else(|| format!("no translation to '{}', the languages are: {}", lang, languages().join(", ")))?;
+        let mut bundles = vec![bundle(lang, source)];
+        if lang != LOCALES[0].0 {
+            bundles.push(bundle(LOCALES[0].0, LOCALES[0].1));
+        }
+        Ok(Locale {
+            bundles,
+        })
+    }
+
+    // The message, or its id if no language has it.
+    pub fn text(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in self.bundles.iter() {
+            if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+                let mut errors = vec![];
+                return bundle.format_pattern(pattern, args, &mut errors).into_owned();
+            }
+        }
+        id.to_string()
+    }
+}
+
+pub fn languages() -> Vec<&'static str> {
+    LOCALES.iter().map(|&(name, _)| name).collect()
+}
+
+// The language of the environment, such as "fr" of `LANG=fr_FR.UTF-8`, if it is translated to.
+pub fn detect() -> Option<String> {
+    let lang = env::var("LC_ALL").or_else(|_| env::var("LANG")).ok()?;
+    let lang = lang.split(['_', '.', '-']).next()?.to_string();
+    if languages().contains(&lang.as_str()) {
+        Some(lang)
+    } else {
+        None
+    }
+}
+
+pub fn set(lang: &str) -> Result<(), String> {
+    let _ = LOCALE.set(Locale::new(lang)?);
+    Ok(())
+}
+
+// The message in the language set, by default the first one.
+pub fn text(id: &str, args: Option<&FluentArgs>) -> String {
+    LOCALE.get_or_init(|| Locale::new(LOCALES[0].0).unwrap()).text(id, args)
+}
+
+// The message of the given id in the UI's language, with its arguments as `name = value`.
+macro_rules! tr {
+    ($id:expr) => {
+        ::locale::text($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)*) => {{
+        let mut args = ::locale::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        ::locale::text($id, Some(&args))
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locales() {
+        let french = Locale::new("fr").unwrap();
+        let mut args = FluentArgs::new();
+        args.set("count", 2);
+        assert_eq!(french.text("visitors-waiting", Some(&args)), "2 visiteurs en attente");
+        args.set("count", 1);
+        assert_eq!(french.text("visitors-waiting", Some(&args)), "1 visiteur en attente");
+        assert_eq!(french.text("no-such-message", None), "no-such-message");
+        assert!(Locale::new("xx").is_err());
+
+        // Each translation has all the messages of the default one.
+        let ids: Vec<&str> = LOCALES[0].1.lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .filter_map(|line| line.split(" =").next())
+            .collect();
+        assert!(ids.len() > 100);
+        for &(lang, _) in LOCALES[1..].iter() {
+            let translated = &Locale::new(lang).unwrap().bundles[0];
+            let missing: Vec<&&str> = ids.iter().filter(|id| !translated.has_message(id)).collect();
+            assert!(missing.is_empty(), "{} lacks {:?}", lang, missing);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+# The messages of the UI, in English.
+# Arguments such as `{ $peer }` are filled in by the chat, and `<...>` in usages are typed as is.
+
+## Titles and prompts
+
+title = Simple chat { $role }
+title-security = Simple chat { $role } ({ $security })
+title-cancel = Simple chat { $role } - Choose 'Cancel' to quit
+title-security-cancel = Simple chat { $role } ({ $security }) - Choose 'Cancel' to quit
+prompt-send = Send message to { $peer }
+prompt-waiting = Waiting for a reply, commands can still be run
+prompt-visitors = { $count ->
+    [one] { $count } visitor waiting, /next to serve the next one
+   *[other] { $count } visitors waiting, /next to serve the next one
+}
+prompt-password = Password required by the server
+prompt-user = User name required by the server
+prompt-password-only = Password
+dialogs-unavailable = Dialogs can't be shown ({ $reason }), reading input from the terminal instead, press Ctrl-D to quit.
+window-failed = Failed to show the chat window: { $error }
+status-connecting = Connecting
+
+## Connections
+
+state-connecting = connecting
+state-handshaking = handshaking
+state-authenticated = authenticated
+state-active = active
+state-draining = draining
+state-closed = closed
+security-plaintext = plaintext, not encrypted
+security-tls = TLS encrypted
+security-e2e = end-to-end encrypted
+session-security = Session security: { $security }
+cleartext-passwords-taken = Clients send their user name and password in cleartext, the connection not being encrypted
+cleartext-password-asked = The server asks for a user name and password, which are sent in cleartext, the connection not being encrypted
+server-loopback-only = Listening on { $address }, which only this machine reaches: add --address 0.0.0.0 for others to connect
+server-uri = Clients can connect with: simple_chat client { $uri }
+qr-code-failed = Couldn't draw the QR code: { $error }
+refusing-connection = Refusing to connect to { $server }: { $error }
+p2p-started = Connecting to the peer at { $address }, or waiting for it to connect there
+p2p-first = Connected to the peer, you speak first
+p2p-second = Connected to the peer, which speaks first
+p2p-rendezvous = Waiting for the peer in the room { $room } of { $rendezvous }
+p2p-punched = Reached the peer directly, through the NATs on the way
+p2p-relayed = Couldn't reach the peer directly, the rendezvous server relays the chat
+onion-published = Published as the onion service { $address }, for clients to connect to through Tor
+reason-quit = quit
+reason-peer-quit = the peer has gone away
+reason-network-error = network error: { $error }
+reason-auth-failed = authentication failed, the server rejected the password
+reason-protocol-error = protocol error: { $error }
+frame-skipped = Skipped a frame from { $peer } which couldn't be read: { $error }
+reason-session-expired = the session time ran out
+reason-server-full = the server is full, taking at most { $max } clients
+disconnected = Disconnected, { $reason }
+disconnected-from = Disconnected from { $address }, { $reason }
+server-shut-down = Server shut down, { $reason }
+connect-failed = Could not reach { $address } ({ $error }), attempt { $attempt } of { $attempts }
+connect-retry-in = Trying again in { $seconds }s
+quitting = "{ $role }" quitting
+events-dropped = { $count ->
+    [one] { $count } event was dropped, the UI didn't keep up
+   *[other] { $count } events were dropped, the UI didn't keep up
+}
+
+## Messages
+
+message-deleted = (message deleted)
+message-sending = (sending)
+message-not-delivered = (not delivered)
+message-read = (read)
+message-unverified = (not signed by the peer)
+message-mentions-you = (mentions you)
+message-delivered = Message #{ $id } delivered
+binary-delivered = Binary message #{ $id } delivered
+binary-received = #{ $id } { $size } bytes of binary data ({ $preview })
+image-received = #{ $id } sent an image, saved to { $path }
+image-save-failed = Failed to save the image from { $peer }: { $error }
+image-show-failed = Failed to show the image: { $error }
+reaction-received = { $peer } reacted { $emoji } to:
+    { $entry }
+message-missing = Message #{ $id } from { $peer } never arrived
+messages-missing = Messages #{ $first } to #{ $last } from { $peer } never arrived
+system-sender = { $peer } (message of the day)
+message-expired = Message #{ $id } expired, and was removed
+peer-deleted = { $peer } deleted message #{ $id }
+peer-read = { $peer } has read message #{ $id }
+presence-joined = { $nick } joined
+presence-left = { $nick } left
+presence-renamed = { $from } is now known as { $to }
+peer-typing = { $peer } is typing...
+urgent-message = Urgent message from { $peer }
+mentioned-by = { $peer } mentioned you
+plugin-sender = { $plugin } (bot)
+message-queued = The client went away, the message will be sent to the next one, as queued message #{ $id }
+history-open-failed = Failed to open the message history, it won't be kept: { $error }
+store-failed = Failed to store the message: { $error }
+contact-save-failed = Failed to save the contact: { $error }
+store-delete-failed = Failed to delete the stored message: { $error }
+exported = Exported { $count } messages to { $path }
+export-write-failed = Failed to write { $path }: { $error }
+option-needs-value = { $option } takes a value
+unknown-option = Unknown option: { $option }
+listen-failed = Failed to listen on { $address }: { $error }
+positive-number-needed = { $option } takes a positive number, not { $value }
+chance-needed = { $option } takes a chance from 0 to 1
+invalid-seed = Invalid seed: { $seed }
+proxying = Forwarding the clients of { $address } to { $server }, under { $conditions }
+invalid-connection = Invalid connection number: { $connection }
+speed-needed = --speed takes a positive factor, not { $speed }
+replay-no-peer = No peer at { $address }: { $error }
+rendezvous-started = Introducing the peers, and relaying those which can't reach each other, at { $address }
+invitation-issued = Invitation to the room { $room }, valid until { $expires }, for the peers to join with --room { $room } --invite { $token }
+
+## Identity
+
+identity-key-failed = Failed to load the identity key, the peer won't be able to verify you: { $error }
+identity-first-session = First session with { $peer }, trusting its identity key { $fingerprint }
+identity-remember-failed = Failed to remember the identity key of { $peer }: { $error }
+identity-changed = WARNING: THE IDENTITY KEY OF { $peer } HAS CHANGED!
+    It was { $previous }, it is now { $fingerprint }.
+    Someone could be impersonating them, or they reinstalled.
+    Check with them out of band, and if the change is expected, remove the line of { $peer } from { $location }.
+token-save-failed = Failed to save the resume token: { $error }
+
+## Sessions
+
+session-ends-in = The session ends in { $duration }
+session-time-up = The session time is up, closing the connection
+stats-summary = Session summary:
+stats-duration = duration: { $duration }
+stats-messages = messages: { $sent } sent, { $received } received
+stats-bytes = bytes: { $sent } sent, { $received } received
+stats-average-roundtrip = average roundtrip: { $average }
+stats-roundtrip = roundtrip: min { $min }, median { $median }, p95 { $p95 }, p99 { $p99 }
+stats-delivery-failures = delivery failures: { $count }
+stats-saturations = all workers busy: { $count } times, up to { $waiting } clients waiting
+bench-running = Sending { $messages } messages of { $size } bytes over the loopback interface...
+bench-summary = { $messages } messages of { $size } bytes, { $format }, in { $elapsed }
+bench-throughput = { $messages } messages/s, { $megabytes } MB/s
+bench-latency = latency: min { $min }, median { $median }, p95 { $p95 }, p99 { $p99 }, max { $max }
+summary-log-failed = Failed to log the session summary to { $path }: { $error }
+
+## Server
+
+authenticator-failed = Failed to authenticate a client: { $error }
+client-auth-failed = A client failed to authenticate
+client-throttled = The client is sending too fast, throttling
+throttled-dropped = Slow down, the server dropped message #{ $id }, retry in { $seconds }s
+throttled-delayed = Slow down, the server is holding message #{ $id } back, retry in { $seconds }s
+client-banned = Refused a client from { $address }, which is banned
+client-refused-full = Refused a client from { $address }, the server being full
+unknown-address = an unknown address
+workers-saturated = All { $workers } workers are busy, { $waiting } clients waiting to be read from, see --workers
+visitors-waiting = { $count ->
+    [one] { $count } visitor waiting
+   *[other] { $count } visitors waiting
+}
+transcript-start-failed = Failed to start the transcript: { $error }
+transcript-write-failed = Failed to write the transcript: { $error }
+visitor-served = Now serving { $peer }, /close to end the conversation
+visitor-left = The conversation with { $peer } ended
+visitor-left-transcript = The conversation with { $peer } ended, its transcript is in { $path }
+no-visitor = No visitor is being served
+no-visitor-next = No visitor is being served, /next to serve the next one
+announcement-sent = { $count ->
+    [one] Sent to { $count } client
+   *[other] Sent to { $count } clients
+}
+listeners-changed = { $count ->
+    [one] { $count } client listening
+   *[other] { $count } clients listening
+}
+tray-unread = { $count ->
+    [0] No unread messages
+    [one] { $count } unread message
+   *[other] { $count } unread messages
+}
+queue-position = You are #{ $position } in the queue, please wait for the operator
+whisper-to = (whisper to { $nick }) { $text }
+whisper-from = { $peer } (whisper)
+whisper-undelivered = No client named { $nick } is listening, the whisper wasn't delivered
+watch-failed = Changes to the config won't apply until restarting: { $error }
+config-reloaded = Config reloaded, applied: { $settings }
+config-reload-failed = Failed to reload the config, keeping the current settings: { $error }
+bans-reloaded = Ban list reloaded
+
+## Commands
+
+commands = Commands:
+unknown-command = Unknown command /{ $name }, try /help
+usage = Usage: { $usage }
+help-help = show this help
+help-quit = leave the chat
+help-nick = change your nickname
+help-who = list who is in the chat
+help-msg = whisper to a single client, in broadcast mode
+help-identity = show the fingerprints of the identity keys
+help-conversations = list the connections, * marking the current one
+help-switch = reply in another conversation
+help-status = show the state of the connection
+help-stats = show the message counts and roundtrip latencies so far
+help-ping = measure the roundtrip time of the link to the peer, on your turn
+help-ignore = hide the messages of a peer
+help-unignore = show the messages of a peer again
+help-ignores = list the ignored peers
+help-dnd = do not disturb: mute everything, some peers, quiet hours, or all but mentions
+help-draft = list the unsent drafts, send that of the peer, or clear it
+help-schedule = send a message at a given local time
+help-scheduled = list the scheduled messages, or cancel one
+help-away = reply to each peer once with the reason, keeping the messages missed
+help-back = end away mode, showing the messages missed
+help-watch = alert on messages with a keyword, or list those watched
+help-unwatch = stop watching a keyword
+help-settings = show or change a feedback setting
+help-sendfile = send a file of any size, in chunks, the chat going on meanwhile
+help-image = send a PNG, JPEG or GIF image, shrunk if too large
+help-delete = retract a message you sent
+help-ephemeral = send a message which disappears after the given seconds, and is never kept
+help-react = react to a message from the peer
+help-copy = copy a message from the peer to the clipboard
+help-search = search the messages of all sessions
+help-contacts = list the peers chatted with, or keep a note about one
+help-history = show the last messages
+help-links = list the links in the peer's messages, the latest first
+help-open = open a link listed by /links in the browser, the latest by default
+help-queued = list the messages queued for the next client
+help-cancel = stop sending a file, or cancel a message queued for the next client
+help-queue = show how many visitors are waiting
+help-close = end the conversation with the visitor being served
+help-next = serve the next visitor, once the conversation is closed
+help-kick = disconnect the client being served
+help-ban = disconnect a client, and refuse its address from now on
+help-revoke = revoke the resume tokens of a user, or of all
+nick-changed = You are now known as { $nick }
+who = In the chat: { $nick } (you), { $peer }
+history-not-kept = The history isn't kept, see --no-history
+no-search-results = No messages match '{ $query }'
+no-contacts = No contacts yet
+no-such-contact = No contact named { $nick }
+contact-noted = Note kept about { $nick }
+contact-without-address = { $nick } is a client, with no address to connect to
+not-support-queue = The server isn't in support-queue mode
+closing-visitor = Closing the conversation with { $peer }
+close-first = Close the conversation with { $peer } first, with /close
+no-client-named = No client is named { $nick }
+address-unknown = The address of { $nick } isn't known
+only-server-kicks = Only the server can kick clients
+only-broadcast-whispers = Only the server in broadcast mode can whisper to a client
+only-server-bans = Only the server can ban clients
+kicked = { $nick } was kicked
+banned = { $target } was banned
+already-banned = { $address } is already banned
+not-a-file = { $path } is not a file
+preparing-file = Preparing { $path }, { $size } bytes, to send it
+file-sent = Sent { $name }
+file-sending = Sending { $name } as file { $transfer }{ $offset ->
+    [0] {""}
+   *[other] , resuming at byte { $offset } where the last transfer stopped
+}, /cancel { $transfer } stops it
+file-cancelled = Stopped sending { $name }
+file-cancelled-by-peer = { $peer } stopped sending { $name }
+cancelling-file = Stopping { $name }
+no-transfer = No file { $id } is being sent
+file-not-accepted = the peer didn't accept the file in time
+file-incoming = { $peer } is sending { $name }, { $size } bytes{ $offset ->
+    [0] {""}
+   *[other] , resuming at byte { $offset }
+}
+file-received = sent { $name }, saved as { $path }
+transfer-failed = Failed to transfer { $name }: { $error }
+sending-image = Sending { $path } as a { $mime } of { $size } bytes
+connection-status = Connection: { $state }
+ping-on-our-turn = Pinging { $peer } once it's your turn.
+pinging = Pinging { $peer }.
+ping-answered = { $peer } answered the ping in { $roundtrip } ({ $link }).
+ping-lost = { $peer } didn't answer the ping ({ $link }).
+link-quality = rtt { $roundtrip }, loss { $loss }%
+cant-ignore-yourself = You can't ignore yourself
+already-ignored = { $nick } is already ignored
+ignoring = Ignoring { $nick }
+not-ignored = { $nick } is not ignored
+unignored = No longer ignoring { $nick }
+nobody-ignored = Nobody is ignored
+ignored-list = Ignored: { $nicks }
+no-config-file = no config file to keep the ignore list in
+notification-settings = Notifications: { $settings }
+already-muted = { $nick } is already muted
+not-muted = { $nick } is not muted
+no-config-file-notifications = no config file to keep the notification settings in
+no-config-file-zoom = no config file to keep the zoom of the window in
+keyword-watched = { $peer } wrote { $keywords }
+no-keywords = No keywords watched
+keywords-list = Watching: { $keywords }
+already-watched = { $keyword } is already watched
+watching = Watching { $keyword }
+not-watched = { $keyword } is not watched
+unwatched = No longer watching { $keyword }
+message-dropped-by-filter = (message dropped by the filter)
+filter-dropped = Message #{ $id } dropped by the filter: { $words }
+filter-redacted = Message #{ $id } redacted by the filter: { $words }
+filter-flagged = Message #{ $id } flagged by the filter: { $words }
+identity-none = none
+identity-not-received = not received yet
+identity-keys = Identity keys, to compare out of band:
+    {"  "}you: { $yours }
+    {"  "}{ $peer }: { $theirs }
+settings = Settings: { $settings }
+only-server-tokens = Only the server issues resume tokens
+tokens-revoked = { $count ->
+    [one] Revoked { $count } resume token
+   *[other] Revoked { $count } resume tokens
+}
+only-server-queues = Only the server queues messages
+no-messages-queued = No messages are queued
+invalid-ttl = Invalid number of seconds: { $ttl }
+ephemeral-sent = (disappears in { $seconds }s)
+invalid-id = Invalid message id: { $id }
+no-queued-message = No queued message #{ $id }
+queued-cancelled = Cancelled queued message #{ $id }
+no-own-message = No message #{ $id } of yours to delete
+message-deleted-by-you = Deleted message #{ $id }
+no-message-to-react = No message #{ $id } from { $peer } to react to
+no-message-to-copy = No message #{ $id } from { $peer } to copy
+message-copied = Message #{ $id } copied to the clipboard
+clipboard-failed = Couldn't use the clipboard: { $error }
+invalid-count = Invalid number of messages: { $count }
+invalid-number = Invalid number: { $number }
+no-links = No links yet.
+no-such-link = There is no link { $number }, see /links.
+title-open-link = Open the link?
+confirm-open-link = Open { $url } in the browser?
+link-not-opened = The link wasn't opened.
+link-opened = Opened { $url } in the browser.
+link-open-failed = Failed to open the link: { $error }
+answer-yes = yes
+button-open = Open
+button-cancel = Cancel
+conversation-closed = (closed)
+conversation-your-turn = (your turn)
+peer-on-address = { $peer } on { $address }
+no-conversation = No conversation { $number }
+no-conversation-see = No conversation { $number }, see /conversations
+connection-closed = The connection to { $address } is closed
+waiting-for-reply = Waiting for { $peer } to reply
+draft-recalled = Your draft: { $draft } (/draft send to send it)
+draft-not-saved = The draft could not be kept: { $error }
+no-drafts = No drafts
+no-draft = No draft for { $peer }
+draft-sent = Sent the draft to { $peer }
+draft-cleared = Cleared the draft for { $peer }
+drafts-kept = { $count ->
+    [one] A draft was kept from before, see /draft
+   *[other] { $count } drafts were kept from before, see /draft
+}
+waiting-for-reply-draft = Waiting for { $peer } to reply, the message is kept as a draft
+scheduled = Message #{ $id } to { $peer } scheduled for { $at }
+scheduled-in-past = { $at } is already past
+scheduled-sent = Sent scheduled message #{ $id } to { $peer }
+nothing-scheduled = No messages scheduled
+not-scheduled = No scheduled message #{ $id }
+schedule-cancelled = Cancelled scheduled message #{ $id }
+away-reply = I'm away: { $reason }
+now-away = Away: { $reason }, /back to return
+not-away = You are not away
+away-summary = { $count ->
+    [one] { $count } message while away since { $since }
+   *[other] { $count } messages while away since { $since }
+}
+now-chatting = Now chatting with { $peer }
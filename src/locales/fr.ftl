@@ -0,0 +1,371 @@
+# Les messages de l'interface, en français.
+# Les arguments tels que `{ $peer }` sont remplis par le chat, et les `<...>` des usages sont tapés tels quels.
+
+## Titres et invites
+
+title = Simple chat { $role }
+title-security = Simple chat { $role } ({ $security })
+title-cancel = Simple chat { $role } - Choisir « Annuler » pour quitter
+title-security-cancel = Simple chat { $role } ({ $security }) - Choisir « Annuler » pour quitter
+prompt-send = Envoyer un message à { $peer }
+prompt-waiting = En attente d'une réponse, les commandes restent possibles
+prompt-visitors = { $count ->
+    [one] { $count } visiteur en attente, /next pour servir le suivant
+   *[other] { $count } visiteurs en attente, /next pour servir le suivant
+}
+prompt-password = Mot de passe demandé par le serveur
+prompt-user = Nom d'utilisateur demandé par le serveur
+prompt-password-only = Mot de passe
+dialogs-unavailable = Les boîtes de dialogue ne peuvent pas s'afficher ({ $reason }), lecture depuis le terminal, Ctrl-D pour quitter.
+window-failed = Impossible d'afficher la fenêtre du chat : { $error }
+status-connecting = Connexion
+
+## Connexions
+
+state-connecting = connexion
+state-handshaking = négociation
+state-authenticated = authentifié
+state-active = active
+state-draining = en fermeture
+state-closed = fermée
+security-plaintext = en clair, non chiffrée
+security-tls = chiffrée par TLS
+security-e2e = chiffrée de bout en bout
+session-security = Sécurité de la session : { $security }
+cleartext-passwords-taken = Les clients envoient leur nom d'utilisateur et leur mot de passe en clair, la connexion n'étant pas chiffrée
+cleartext-password-asked = Le serveur demande un nom d'utilisateur et un mot de passe, qui sont envoyés en clair, la connexion n'étant pas chiffrée
+server-loopback-only = En écoute sur { $address }, que seule cette machine atteint : ajoutez --address 0.0.0.0 pour que d'autres se connectent
+server-uri = Les clients peuvent se connecter avec : simple_chat client { $uri }
+qr-code-failed = Impossible de dessiner le code QR : { $error }
+refusing-connection = Connexion à { $server } refusée : { $error }
+p2p-started = Connexion au pair sur { $address }, ou attente de sa connexion à cette adresse
+p2p-first = Connecté au pair, vous parlez en premier
+p2p-second = Connecté au pair, qui parle en premier
+p2p-rendezvous = En attente du pair dans le salon { $room } de { $rendezvous }
+p2p-punched = Pair joint directement, à travers les NAT sur le chemin
+p2p-relayed = Impossible de joindre le pair directement, le serveur de rendez-vous relaie la discussion
+onion-published = Publié comme service onion { $address }, pour que les clients s'y connectent via Tor
+reason-quit = fin demandée
+reason-peer-quit = l'interlocuteur est parti
+reason-network-error = erreur réseau : { $error }
+reason-auth-failed = échec de l'authentification, le serveur a refusé le mot de passe
+reason-protocol-error = erreur de protocole : { $error }
+frame-skipped = Trame de { $peer } ignorée, illisible : { $error }
+reason-session-expired = la durée de la session est écoulée
+reason-server-full = le serveur est plein, { $max } clients au plus
+disconnected = Déconnecté, { $reason }
+disconnected-from = Déconnecté de { $address }, { $reason }
+server-shut-down = Serveur arrêté, { $reason }
+connect-failed = Impossible de joindre { $address } ({ $error }), tentative { $attempt } sur { $attempts }
+connect-retry-in = Nouvelle tentative dans { $seconds } s
+quitting = « { $role } » quitte
+events-dropped = { $count ->
+    [one] { $count } événement perdu, l'interface n'a pas suivi
+   *[other] { $count } événements perdus, l'interface n'a pas suivi
+}
+
+## Messages
+
+message-deleted = (message supprimé)
+message-sending = (envoi)
+message-not-delivered = (non remis)
+message-read = (lu)
+message-unverified = (non signé par l'interlocuteur)
+message-mentions-you = (vous mentionne)
+message-delivered = Message n°{ $id } remis
+binary-delivered = Message binaire n°{ $id } remis
+binary-received = n°{ $id } { $size } octets de données binaires ({ $preview })
+image-received = n°{ $id } a envoyé une image, enregistrée dans { $path }
+image-save-failed = Impossible d'enregistrer l'image de { $peer } : { $error }
+image-show-failed = Impossible d'afficher l'image : { $error }
+reaction-received = { $peer } a réagi { $emoji } à :
+    { $entry }
+message-missing = Le message n°{ $id } de { $peer } n'est jamais arrivé
+messages-missing = Les messages n°{ $first } à n°{ $last } de { $peer } ne sont jamais arrivés
+system-sender = { $peer } (message du jour)
+message-expired = Le message n°{ $id } a expiré, et a été retiré
+peer-deleted = { $peer } a supprimé le message n°{ $id }
+peer-read = { $peer } a lu le message n°{ $id }
+presence-joined = { $nick } est arrivé
+presence-left = { $nick } est parti
+presence-renamed = { $from } s'appelle désormais { $to }
+peer-typing = { $peer } écrit...
+urgent-message = Message urgent de { $peer }
+mentioned-by = { $peer } vous a mentionné
+plugin-sender = { $plugin } (robot)
+message-queued = Le client est parti, le message sera envoyé au suivant, en tant que message n°{ $id } en file
+history-open-failed = Impossible d'ouvrir l'historique des messages, il ne sera pas conservé : { $error }
+store-failed = Impossible d'enregistrer le message : { $error }
+contact-save-failed = Impossible d'enregistrer le contact : { $error }
+store-delete-failed = Impossible de supprimer le message enregistré : { $error }
+exported = { $count } messages exportés dans { $path }
+export-write-failed = Impossible d'écrire { $path } : { $error }
+option-needs-value = { $option } attend une valeur
+unknown-option = Option inconnue : { $option }
+listen-failed = Impossible d'écouter sur { $address } : { $error }
+positive-number-needed = { $option } attend un nombre positif, pas { $value }
+chance-needed = { $option } attend une probabilité de 0 à 1
+invalid-seed = Graine invalide : { $seed }
+proxying = Les clients de { $address } sont relayés vers { $server }, avec { $conditions }
+invalid-connection = Numéro de connexion invalide : { $connection }
+speed-needed = --speed attend un facteur positif, pas { $speed }
+replay-no-peer = Aucun pair à { $address } : { $error }
+rendezvous-started = Mise en relation des pairs, et relais de ceux qui ne peuvent se joindre, à { $address }
+invitation-issued = Invitation au salon { $room }, valable jusqu'au { $expires }, pour que les pairs le rejoignent avec --room { $room } --invite { $token }
+
+## Identité
+
+identity-key-failed = Impossible de charger la clé d'identité, l'interlocuteur ne pourra pas vous vérifier : { $error }
+identity-first-session = Première session avec { $peer }, sa clé d'identité { $fingerprint } est retenue
+identity-remember-failed = Impossible de retenir la clé d'identité de { $peer } : { $error }
+identity-changed = ATTENTION : LA CLÉ D'IDENTITÉ DE { $peer } A CHANGÉ !
+    C'était { $previous }, c'est maintenant { $fingerprint }.
+    Quelqu'un pourrait usurper son identité, ou bien il a réinstallé.
+    Vérifiez par un autre moyen, et si le changement est attendu, retirez la ligne de { $peer } de { $location }.
+token-save-failed = Impossible d'enregistrer le jeton de reprise : { $error }
+
+## Sessions
+
+session-ends-in = La session se termine dans { $duration }
+session-time-up = La durée de la session est écoulée, fermeture de la connexion
+stats-summary = Résumé de la session :
+stats-duration = durée : { $duration }
+stats-messages = messages : { $sent } envoyés, { $received } reçus
+stats-bytes = octets : { $sent } envoyés, { $received } reçus
+stats-average-roundtrip = aller-retour moyen : { $average }
+stats-roundtrip = aller-retour : min { $min }, médian { $median }, p95 { $p95 }, p99 { $p99 }
+stats-delivery-failures = échecs de remise : { $count }
+stats-saturations = tous les threads occupés : { $count } fois, jusqu'à { $waiting } clients en attente
+bench-running = Envoi de { $messages } messages de { $size } octets sur l'interface de bouclage...
+bench-summary = { $messages } messages de { $size } octets, { $format }, en { $elapsed }
+bench-throughput = { $messages } messages/s, { $megabytes } Mo/s
+bench-latency = latence : min { $min }, médiane { $median }, p95 { $p95 }, p99 { $p99 }, max { $max }
+summary-log-failed = Impossible d'écrire le résumé de la session dans { $path } : { $error }
+
+## Serveur
+
+authenticator-failed = Impossible d'authentifier un client : { $error }
+client-auth-failed = Un client a échoué à s'authentifier
+client-throttled = Le client envoie trop vite, ralentissement
+throttled-dropped = Ralentissez, le serveur a écarté le message n°{ $id }, réessayez dans { $seconds } s
+throttled-delayed = Ralentissez, le serveur retient le message n°{ $id }, réessayez dans { $seconds } s
+client-banned = Client de { $address } refusé, cette adresse est bannie
+client-refused-full = Client refusé ({ $address }), le serveur étant plein
+unknown-address = une adresse inconnue
+workers-saturated = Les { $workers } threads sont occupés, { $waiting } clients attendent d'être lus, voir --workers
+visitors-waiting = { $count ->
+    [one] { $count } visiteur en attente
+   *[other] { $count } visiteurs en attente
+}
+transcript-start-failed = Impossible de commencer la transcription : { $error }
+transcript-write-failed = Impossible d'écrire la transcription : { $error }
+visitor-served = { $peer } est servi, /close pour terminer la conversation
+visitor-left = La conversation avec { $peer } est terminée
+visitor-left-transcript = La conversation avec { $peer } est terminée, sa transcription est dans { $path }
+no-visitor = Aucun visiteur n'est servi
+no-visitor-next = Aucun visiteur n'est servi, /next pour servir le suivant
+announcement-sent = { $count ->
+    [one] Envoyé à { $count } client
+   *[other] Envoyé à { $count } clients
+}
+listeners-changed = { $count ->
+    [one] { $count } client à l'écoute
+   *[other] { $count } clients à l'écoute
+}
+tray-unread = { $count ->
+    [0] Aucun message non lu
+    [one] { $count } message non lu
+   *[other] { $count } messages non lus
+}
+queue-position = Vous êtes n°{ $position } dans la file, veuillez attendre l'opérateur
+whisper-to = (chuchoté à { $nick }) { $text }
+whisper-from = { $peer } (chuchoté)
+whisper-undelivered = Aucun client nommé { $nick } n'écoute, le message chuchoté n'a pas été remis
+watch-failed = Les changements de la configuration ne s'appliqueront qu'au redémarrage : { $error }
+config-reloaded = Configuration rechargée, appliqué : { $settings }
+config-reload-failed = Impossible de recharger la configuration, les réglages actuels sont conservés : { $error }
+bans-reloaded = Liste des bannis rechargée
+
+## Commandes
+
+commands = Commandes :
+unknown-command = Commande /{ $name } inconnue, essayez /help
+usage = Usage : { $usage }
+help-help = afficher cette aide
+help-quit = quitter le chat
+help-nick = changer de pseudonyme
+help-who = lister qui est dans le chat
+help-msg = chuchoter à un seul client, en mode diffusion
+help-identity = afficher les empreintes des clés d'identité
+help-conversations = lister les connexions, * marquant l'actuelle
+help-switch = répondre dans une autre conversation
+help-status = afficher l'état de la connexion
+help-stats = afficher les nombres de messages et les latences aller-retour jusqu'ici
+help-ping = mesurer le temps aller-retour du lien avec le pair, à votre tour
+help-ignore = masquer les messages d'un interlocuteur
+help-unignore = afficher de nouveau les messages d'un interlocuteur
+help-ignores = lister les interlocuteurs ignorés
+help-dnd = ne pas déranger : tout couper, certains interlocuteurs, des heures calmes, ou tout sauf les mentions
+help-draft = lister les brouillons non envoyés, envoyer celui de l'interlocuteur, ou l'effacer
+help-schedule = envoyer un message à une heure donnée
+help-scheduled = lister les messages programmés, ou en annuler un
+help-away = répondre une fois à chaque interlocuteur avec la raison, en gardant les messages manqués
+help-back = quitter le mode absent, en montrant les messages manqués
+help-watch = alerter pour les messages contenant un mot-clé, ou lister ceux surveillés
+help-unwatch = ne plus surveiller un mot-clé
+help-settings = afficher ou changer un réglage de retour
+help-sendfile = envoyer un fichier de toute taille, par morceaux, la discussion continuant entre-temps
+help-image = envoyer une image PNG, JPEG ou GIF, réduite si trop grande
+help-delete = retirer un message envoyé
+help-ephemeral = envoyer un message qui disparaît après le nombre de secondes donné, et n'est jamais conservé
+help-react = réagir à un message de l'interlocuteur
+help-copy = copier un message de l'interlocuteur dans le presse-papiers
+help-search = chercher dans les messages de toutes les sessions
+help-contacts = lister les interlocuteurs, ou garder une note sur l'un d'eux
+help-history = afficher les derniers messages
+help-links = lister les liens des messages du pair, le plus récent en premier
+help-open = ouvrir dans le navigateur un lien listé par /links, le plus récent par défaut
+help-queued = lister les messages en file pour le prochain client
+help-cancel = arrêter l'envoi d'un fichier, ou annuler un message en file pour le prochain client
+help-queue = afficher combien de visiteurs attendent
+help-close = terminer la conversation avec le visiteur servi
+help-next = servir le visiteur suivant, une fois la conversation fermée
+help-kick = déconnecter le client servi
+help-ban = déconnecter un client, et refuser son adresse désormais
+help-revoke = révoquer les jetons de reprise d'un utilisateur, ou de tous
+nick-changed = Vous vous appelez désormais { $nick }
+who = Dans le chat : { $nick } (vous), { $peer }
+history-not-kept = L'historique n'est pas conservé, voir --no-history
+no-search-results = Aucun message ne correspond à « { $query } »
+no-contacts = Aucun contact pour l'instant
+no-such-contact = Aucun contact nommé { $nick }
+contact-noted = Note enregistrée sur { $nick }
+contact-without-address = { $nick } est un client, sans adresse où se connecter
+not-support-queue = Le serveur n'est pas en mode file d'assistance
+closing-visitor = Fermeture de la conversation avec { $peer }
+close-first = Fermez d'abord la conversation avec { $peer }, avec /close
+no-client-named = Aucun client ne s'appelle { $nick }
+address-unknown = L'adresse de { $nick } n'est pas connue
+only-server-kicks = Seul le serveur peut expulser des clients
+only-broadcast-whispers = Seul le serveur en mode diffusion peut chuchoter à un client
+only-server-bans = Seul le serveur peut bannir des clients
+kicked = { $nick } a été expulsé
+banned = { $target } a été banni
+already-banned = { $address } est déjà banni
+not-a-file = { $path } n'est pas un fichier
+preparing-file = Préparation de { $path }, { $size } octets, pour l'envoyer
+file-sent = { $name } envoyé
+file-sending = Envoi de { $name } comme fichier { $transfer }{ $offset ->
+    [0] {""}
+   *[other] , reprise à l'octet { $offset } où le dernier transfert s'est arrêté
+}, /cancel { $transfer } l'arrête
+file-cancelled = Envoi de { $name } arrêté
+file-cancelled-by-peer = { $peer } a arrêté d'envoyer { $name }
+cancelling-file = Arrêt de { $name }
+no-transfer = Aucun fichier { $id } n'est en cours d'envoi
+file-not-accepted = le correspondant n'a pas accepté le fichier à temps
+file-incoming = { $peer } envoie { $name }, { $size } octets{ $offset ->
+    [0] {""}
+   *[other] , reprise à l'octet { $offset }
+}
+file-received = a envoyé { $name }, enregistré sous { $path }
+transfer-failed = Échec du transfert de { $name } : { $error }
+sending-image = Envoi de { $path } en { $mime } de { $size } octets
+connection-status = Connexion : { $state }
+ping-on-our-turn = Ping de { $peer } dès que ce sera votre tour.
+pinging = Ping de { $peer }.
+ping-answered = { $peer } a répondu au ping en { $roundtrip } ({ $link }).
+ping-lost = { $peer } n'a pas répondu au ping ({ $link }).
+link-quality = rtt { $roundtrip }, perte { $loss } %
+cant-ignore-yourself = Vous ne pouvez pas vous ignorer
+already-ignored = { $nick } est déjà ignoré
+ignoring = { $nick } est ignoré
+not-ignored = { $nick } n'est pas ignoré
+unignored = { $nick } n'est plus ignoré
+nobody-ignored = Personne n'est ignoré
+ignored-list = Ignorés : { $nicks }
+no-config-file = aucun fichier de configuration où garder la liste des ignorés
+notification-settings = Notifications : { $settings }
+already-muted = { $nick } est déjà en sourdine
+not-muted = { $nick } n'est pas en sourdine
+no-config-file-notifications = aucun fichier de configuration où garder les réglages des notifications
+no-config-file-zoom = aucun fichier de configuration où garder le zoom de la fenêtre
+keyword-watched = { $peer } a écrit { $keywords }
+no-keywords = Aucun mot-clé surveillé
+keywords-list = Mots-clés surveillés : { $keywords }
+already-watched = { $keyword } est déjà surveillé
+watching = { $keyword } est surveillé
+not-watched = { $keyword } n'est pas surveillé
+unwatched = { $keyword } n'est plus surveillé
+message-dropped-by-filter = (message supprimé par le filtre)
+filter-dropped = Message #{ $id } supprimé par le filtre : { $words }
+filter-redacted = Message #{ $id } caviardé par le filtre : { $words }
+filter-flagged = Message #{ $id } signalé par le filtre : { $words }
+identity-none = aucune
+identity-not-received = pas encore reçue
+identity-keys = Clés d'identité, à comparer par un autre moyen :
+    {"  "}vous : { $yours }
+    {"  "}{ $peer } : { $theirs }
+settings = Réglages : { $settings }
+only-server-tokens = Seul le serveur émet des jetons de reprise
+tokens-revoked = { $count ->
+    [one] { $count } jeton de reprise révoqué
+   *[other] { $count } jetons de reprise révoqués
+}
+only-server-queues = Seul le serveur met des messages en file
+no-messages-queued = Aucun message en file
+invalid-ttl = Nombre de secondes invalide : { $ttl }
+ephemeral-sent = (disparaît dans { $seconds } s)
+invalid-id = Numéro de message invalide : { $id }
+no-queued-message = Aucun message n°{ $id } en file
+queued-cancelled = Message n°{ $id } en file annulé
+no-own-message = Aucun message n°{ $id } de votre part à supprimer
+message-deleted-by-you = Message n°{ $id } supprimé
+no-message-to-react = Aucun message n°{ $id } de { $peer } auquel réagir
+no-message-to-copy = Aucun message n°{ $id } de { $peer } à copier
+message-copied = Message n°{ $id } copié dans le presse-papiers
+clipboard-failed = Impossible d'utiliser le presse-papiers : { $error }
+invalid-count = Nombre de messages invalide : { $count }
+invalid-number = Nombre invalide : { $number }
+no-links = Aucun lien pour l'instant.
+no-such-link = Il n'y a pas de lien { $number }, voir /links.
+title-open-link = Ouvrir le lien ?
+confirm-open-link = Ouvrir { $url } dans le navigateur ?
+link-not-opened = Le lien n'a pas été ouvert.
+link-opened = { $url } ouvert dans le navigateur.
+link-open-failed = Échec de l'ouverture du lien : { $error }
+answer-yes = oui
+button-open = Ouvrir
+button-cancel = Annuler
+conversation-closed = (fermée)
+conversation-your-turn = (à vous)
+peer-on-address = { $peer } sur { $address }
+no-conversation = Aucune conversation { $number }
+no-conversation-see = Aucune conversation { $number }, voir /conversations
+connection-closed = La connexion à { $address } est fermée
+waiting-for-reply = En attente de la réponse de { $peer }
+draft-recalled = Votre brouillon : { $draft } (/draft send pour l'envoyer)
+draft-not-saved = Le brouillon n'a pas pu être gardé : { $error }
+no-drafts = Aucun brouillon
+no-draft = Aucun brouillon pour { $peer }
+draft-sent = Brouillon envoyé à { $peer }
+draft-cleared = Brouillon pour { $peer } effacé
+drafts-kept = { $count ->
+    [one] Un brouillon a été gardé de la dernière fois, voir /draft
+   *[other] { $count } brouillons ont été gardés de la dernière fois, voir /draft
+}
+waiting-for-reply-draft = En attente de la réponse de { $peer }, le message est gardé comme brouillon
+scheduled = Message #{ $id } à { $peer } programmé pour { $at }
+scheduled-in-past = { $at } est déjà passé
+scheduled-sent = Message programmé #{ $id } envoyé à { $peer }
+nothing-scheduled = Aucun message programmé
+not-scheduled = Aucun message programmé #{ $id }
+schedule-cancelled = Message programmé #{ $id } annulé
+away-reply = Je suis absent : { $reason }
+now-away = Absent : { $reason }, /back pour revenir
+not-away = Vous n'êtes pas absent
+away-summary = { $count ->
+    [one] { $count } message pendant l'absence depuis { $since }
+   *[other] { $count } messages pendant l'absence depuis { $since }
+}
+now-chatting = Vous discutez maintenant avec { $peer }
@@ -0,0 +1,163 @@
+use chrono::{Local, NaiveDate};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+
+// How many of the older logs are kept, once rotated by size.
+const KEPT_LOGS: usize = 5;
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+// When the log is started afresh, the current one being kept alongside it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    // Once bigger than this many bytes, as `chat.log.1`, the older ones being shifted to `.2` and so on.
+    Size(u64),
+    // On the first line of a new day, as `chat.log.2024-05-01`, for the day it was written.
+    Daily,
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation::Size(DEFAULT_MAX_SIZE)
+    }
+}
+
+impl Rotation {
+    // Such as "daily", "500K", "10M" or "1G".
+    pub fn parse(value: &str) -> Result<Rotation, String> {
+        if value == "daily" {
+            return Ok(Rotation::Daily);
+        }
+        let invalid = || format!("'{}' is neither 'daily' nor a size such as 10M", value);
+        let (number, unit) = match value.char_indices().find(|&(_, c)| !c.is_ascii_digit()) {
+            Some((at, _)) => value.split_at(at),
+            None => (value, ""),
+        };
+        let unit = match unit {
+            "" => 1,
+            "K" | "k" => 1024,
+            "M" | "m" => 1024 * 1024,
+            "G" | "g" => 1024 * 1024 * 1024,
+            _ => return Err(invalid()),
+        };
+        number.parse::<u64>().ok().filter(|&size| size > 0).map(|size| Rotation::Size(size * unit)).ok_or_else(invalid)
+    }
+}
+
+struct Current {
+    file: File,
+    size: u64,
+    // The day it was started on, for daily rotation.
+    day: NaiveDate,
+}
+
+// The log of a long-running chat, such as a server's: connections, errors,
+// and what was said, without the text itself unless asked for. Shared by the threads writing to it.
+#[derive(Clone)]
+pub struct LogFile {
+    path: PathBuf,
+    rotation: Rotation,
+    current: Arc<Mutex<Current>>,
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    OpenOptions::new().create(true).append(true).open(path)
+        .map_err(|error| format!("failed to open the log {}: {}", path.display(), error))
+}
+
+impl LogFile {
+    // Appending to the log if it already exists.
+    pub fn open(path: &str, rotation: Rotation) -> Result<LogFile, String> {
+        let path = PathBuf::from(path);
+        let file = open(&path)?;
+        let metadata = file.metadata().map_err(|error| error.to_string())?;
+        let day = metadata.modified().map(|modified| chrono::DateTime::<Local>::from(modified).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+        Ok(LogFile {
+            path,
+            rotation,
+            current: Arc::new(Mutex::new(Current {
+                file,
+                size: metadata.len(),
+                day,
+            })),
+        })
+    }
+
+    // Where the log is moved to when rotated, the suffix being its day, or how recent it is.
+    fn rotated(&self, suffix: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", suffix));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&self, current: &mut Current) -> Result<(), String> {
+        match self.rotation {
+            Rotation::Size(_) => {
+                let _ = fs::remove_file(self.rotated(&KEPT_LOGS.to_string()));
+                for number in (1..KEPT_LOGS).rev() {
+                    let _ = fs::rename(self.rotated(&number.to_string()), self.rotated(&(number + 1).to_string()));
+                }
+                fs::rename(&self.path, self.rotated("1")).map_err(|error| error.to_string())?;
+            },
+            Rotation::Daily => {
+                fs::rename(&self.path, self.rotated(&current.day.format("%Y-%m-%d").to_string()))
+                    .map_err(|error| error.to_string())?;
+            },
+        }
+        current.file = open(&self.path)?;
+        current.size = 0;
+        current.day = Local::now().date_naive();
+        Ok(())
+    }
+
+    // The log is for looking into later, the chat going on if it can't be written to.
+    pub fn write(&self, line: &str) {
+        let now = Local::now();
+        let line = format!("[{}] {}\n", now.format("%Y-%m-%d %H:%M:%S"), line);
+        let mut current = self.current.lock().unwrap();
+        let due = match self.rotation {
+            Rotation::Size(max_size) => current.size > 0 && current.size + line.len() as u64 > max_size,
+            Rotation::Daily => current.day != now.date_naive(),
+        };
+        if due {
+            let _ = self.rotate(&mut current);
+        }
+        if current.file.write_all(line.as_bytes()).is_ok() {
+            current.size += line.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_rotation() {
+        assert_eq!(Rotation::parse("daily"), Ok(Rotation::Daily));
+        assert_eq!(Rotation::parse("10M"), Ok(Rotation::Size(10 * 1024 * 1024)));
+        assert_eq!(Rotation::parse("500"), Ok(Rotation::Size(500)));
+        assert!(Rotation::parse("0").is_err());
+        assert!(Rotation::parse("ten").is_err());
+        assert!(Rotation::parse("10T").is_err());
+
+        let dir = env::temp_dir().join("simple_chat_test_logs");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chat.log");
+        let log = LogFile::open(path.to_str().unwrap(), Rotation::Size(64)).unwrap();
+        // Each line is more than half the size, so each starts a new log.
+        for line in 0..8 {
+            log.write(&format!("line {} of the log", line));
+        }
+        assert!(fs::read_to_string(&path).unwrap().ends_with("line 7 of the log\n"));
+        assert!(fs::read_to_string(dir.join("chat.log.1")).unwrap().ends_with("line 6 of the log\n"));
+        assert!(fs::read_to_string(dir.join("chat.log.5")).unwrap().ends_with("line 2 of the log\n"));
+        assert!(!dir.join("chat.log.6").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,220 +1,5249 @@
+extern crate arboard;
+extern crate bcrypt;
+extern crate bincode;
+extern crate bytes;
+extern crate chrono;
+#[cfg(feature = "gui")]
+extern crate eframe;
+extern crate ed25519_dalek;
+extern crate fluent_bundle;
+extern crate hmac;
+extern crate image;
+#[cfg(feature = "tray")]
+extern crate ksni;
+// Not to be confused with our `notify` module, of the desktop notifications.
+extern crate notify as fs_notify;
+#[cfg(test)]
+extern crate proptest;
+extern crate qrcode;
+extern crate rand;
+extern crate rmp_serde;
+#[cfg(feature = "scripting")]
+extern crate rhai;
+extern crate rusqlite;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+extern crate sha1;
+extern crate sha2;
+extern crate socket2;
+extern crate thiserror;
 extern crate tinyfiledialogs;
+extern crate toml;
+extern crate unic_langid;
+
+// First, for the other modules to use `tr!`.
+#[macro_use]
+mod locale;
+mod auth;
+mod away;
+mod bans;
+mod bench;
+mod broadcast;
+mod channel;
+mod codec;
+mod commands;
+mod config;
+mod connection;
+mod control;
+mod datagram;
+mod doctor;
+mod drafts;
+mod error;
+mod export;
+mod filter;
+#[cfg(feature = "gui")]
+mod gui;
+mod history;
+mod identity;
+mod images;
+mod in_flight;
+mod input;
+mod invite;
+mod links;
+mod logfile;
+mod markdown;
+mod mentions;
+mod message;
+mod notify;
+mod offline_queue;
+#[macro_use]
+mod output;
+mod ping;
+#[cfg(test)]
+mod pipe;
+mod plugin;
+mod pool;
+mod proxy;
+mod rate_limit;
+mod record;
+mod rendezvous;
+mod resume;
+mod schedule;
+#[cfg(feature = "scripting")]
+mod script;
+mod security;
+mod sequence;
+mod session;
+mod settings;
+mod socket;
+mod stats;
+mod store;
+mod support_queue;
+mod theme;
+mod tor;
+mod transcript;
+mod transfer;
+mod transport;
+mod tray;
+mod uri;
+mod watch;
+mod web;
+mod webhook;
 
+use arboard::Clipboard;
+use auth::{Authenticator, StaticPassword};
+use away::Away;
+use bans::BanList;
+use bench::BenchOptions;
+use broadcast::Listeners;
+use channel::{ChannelConfig, OverflowPolicy, Receiver, SendError, Sender};
+use chrono::{DateTime, Local};
+use codec::{AckPolicy, AuthMethod, Frame, FrameStream, MessageId, Payload, Presence, ProtocolViolation, TransferId, WireFormat, MAX_BINARY_SIZE, MAX_TEXT_SIZE};
+use commands::{CommandOutcome, CommandRegistry, Input};
+use config::Config;
+use connection::{ComponentHandle, ConnectionState, SharedState};
+use control::ControlSocket;
+use drafts::Drafts;
+use ed25519_dalek::{PUBLIC_KEY_LENGTH, SigningKey, VerifyingKey};
+use error::ChatError;
+use hmac::{Hmac, Mac};
+use history::{Delivery, History, HistoryEntry};
+use in_flight::InFlight;
+use identity::{KeyCheck, KnownPeers};
+use input::InputMode;
+use invite::Invitations;
+use logfile::{LogFile, Rotation};
+use message::{ChatMessage, MessageKind};
+use notify::{Alert, Notification, NotificationRules, NotificationSettings, QuietHours};
+use offline_queue::OfflineQueue;
+use output::Output;
+use plugin::ChatPlugin;
+use ping::LinkQuality;
+use pool::WorkerPool;
+use proxy::NetworkConditions;
+use rand::RngCore;
+use rate_limit::{RateLimit, RateLimiter, ThrottlePolicy};
+use record::{FrameDirection, RecordedFrame, Recorder};
+use resume::TokenStore;
+use schedule::Schedule;
+use security::{SecurityHistory, SecurityLevel};
+use sequence::{Ordered, ReorderBuffer};
+use session::SessionLimit;
+use socket::SocketOptions;
+use settings::FeedbackSettings;
+use stats::SessionStats;
+use export::ExportFormat;
+use filter::{FilterAction, WordFilter};
+use store::{MessageStatus, MessageStore};
+use support_queue::VisitorQueue;
+use theme::Theme;
+use transcript::Transcript;
+use transfer::{IncomingTransfer, OutgoingTransfer};
+use transport::{Listener, Transport};
+use tray::Tray;
+use uri::ChatUri;
+use webhook::{Direction, RecentMessages, WebhookEvent};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryInto;
 use std::env;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::fs::{self, OpenOptions};
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::mem;
+use std::net::{IpAddr, Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 
 #[derive(Debug, PartialEq)]
 enum MainControlMsg {
-    RoundTrip(Duration),
-    IncomingMessage(String),
-    ClientDisconnected,
-    ServerShutDown
+    // We are sending a message, to show it before it is delivered.
+    MessageSending {
+        id: MessageId,
+        text: String,
+        // If ephemeral, how long until it is removed.
+        ttl: Option<Duration>,
+    },
+    // Our message couldn't be delivered, the peer having gone away,
+    // or not acknowledged it however many times it was sent.
+    SendFailed(MessageId),
+    // The peer's network layer acknowledged receipt of a message we sent.
+    MessageDelivered {
+        id: MessageId,
+        text: String,
+        roundtrip: Duration,
+    },
+    BinaryDelivered {
+        id: MessageId,
+        roundtrip: Duration,
+    },
+    // The peer's UI confirmed it has processed our message with the id.
+    MessageProcessed(MessageId),
+    // The peer has seen our message with the id.
+    MessageRead(MessageId),
+    // The peer is composing a reply.
+    PeerTyping,
+    // The peer chose a new nickname.
+    PeerRenamed(String),
+    // In broadcast mode, a client came, went or was renamed.
+    Presence(Presence),
+    // The peer's public identity key.
+    PeerIdentity(Vec<u8>),
+    // The session is nearing its agreed length.
+    SessionTimeLeft(Duration),
+    // The session reached its agreed length, and is being closed.
+    SessionExpired,
+    // Verified if signed with the peer's identity key, and ephemeral if it has a time to live.
+    // Of the `System` kind for the server's message of the day, the handshake of the conversation.
+    IncomingMessage {
+        message: ChatMessage,
+        verified: bool,
+        ttl: Option<Duration>,
+    },
+    // Part of a message split by the peer, the rest follows in the same turn.
+    IncomingMessagePart {
+        message: ChatMessage,
+        verified: bool,
+    },
+    // Messages from the peer which never arrived, the following ones being shown without them.
+    MessagesMissing {
+        first: MessageId,
+        last: MessageId,
+    },
+    // A file is being sent to the peer, in chunks, or it was all sent.
+    FileSending {
+        transfer: TransferId,
+        name: String,
+        size: u64,
+        // Past what the peer received before, if resuming an interrupted transfer.
+        offset: u64,
+    },
+    FileSent {
+        transfer: TransferId,
+        name: String,
+    },
+    // The peer is sending a file, written to disk as it comes, until saved at `path`.
+    FileIncoming {
+        transfer: TransferId,
+        name: String,
+        size: u64,
+        offset: u64,
+    },
+    FileReceived {
+        transfer: TransferId,
+        sent_at: SystemTime,
+        name: String,
+        path: PathBuf,
+    },
+    // How much of a file we are sending was sent, every so often.
+    TransferProgress {
+        id: TransferId,
+        sent: u64,
+        total: u64,
+    },
+    // The sender of a file gave up on it, us with `/cancel`, or the peer.
+    TransferCancelled {
+        transfer: TransferId,
+        name: String,
+        by_peer: bool,
+    },
+    // A file couldn't be read, or written, the chat going on without it.
+    TransferFailed {
+        name: String,
+        error: String,
+    },
+    // Raw bytes from the peer, in the middle of its turn.
+    IncomingBinary {
+        id: MessageId,
+        sent_at: SystemTime,
+        mime: Option<String>,
+        data: Vec<u8>,
+    },
+    // The peer retracted a message it sent.
+    MessageDeleted(MessageId),
+    // The time to live of an ephemeral message, ours or the peer's, is over.
+    MessageExpired {
+        id: MessageId,
+        own: bool,
+    },
+    // The peer reacted to a message we sent.
+    ReactionReceived(ChatMessage),
+    // The server requires a password, answer with `ComponentControlMsg::Password`.
+    PasswordRequired,
+    // The server requires a user name and password, answer with `ComponentControlMsg::Credentials`.
+    CredentialsRequired,
+    // A client failed the password challenge.
+    AuthenticationFailed,
+    // The authenticator couldn't decide, the client is then rejected.
+    AuthenticatorError(String),
+    // The server issued a token, to authenticate with on the next connection.
+    ResumeTokenIssued(String),
+    // The client is sending faster than the rate limit allows.
+    ClientThrottled,
+    // The icon in the tray was clicked, with `--tray`, to bring the chat up.
+    TrayActivated,
+    // The server told us to slow down, our message having been dropped, or delayed.
+    Throttled {
+        id: MessageId,
+        retry_after: Duration,
+        dropped: bool,
+    },
+    // A plugin sent a reply to the client.
+    PluginReply {
+        plugin: String,
+        text: String,
+    },
+    // A message was injected into the chat through the webhook endpoint.
+    MessageInjected(String),
+    // The client went away before our message was delivered,
+    // it will be sent when a client connects again.
+    MessageQueued(MessageId),
+    MessageCancelled(MessageId),
+    // In support-queue mode, the number of clients waiting for the operator changed.
+    QueueChanged(usize),
+    // In support-queue mode, a conversation with the next client starts, or ended.
+    VisitorServed,
+    VisitorLeft,
+    // Our position in the server's support queue.
+    QueuePosition(u32),
+    // In broadcast mode, the server is ready for the operator's first message,
+    // which it then sends to all its clients.
+    BroadcastReady,
+    AnnouncementSent {
+        text: String,
+        listeners: usize,
+    },
+    // In broadcast mode, a client connected or went away.
+    ListenersChanged(usize),
+    // The address of the client being served.
+    ClientAddress(IpAddr),
+    // A client connected from a banned address, and was turned away.
+    BannedClientRefused(IpAddr),
+    // A client connected beyond `--max-clients`, and was told the server is full.
+    ServerFullRefused(Option<IpAddr>),
+    // In broadcast mode, a client connected while all the workers were busy with others,
+    // its messages waiting to be read, with how many such clients there are.
+    WorkersSaturated {
+        workers: usize,
+        waiting: usize,
+    },
+    // In p2p mode, the rendezvous server brought us to the peer, directly or through its relay.
+    PeerReached {
+        relayed: bool,
+    },
+    // In p2p mode, the toss decided whether we speak first.
+    PeerConnected {
+        first: bool,
+    },
+    // A message broadcast by the server.
+    Announcement {
+        sent_at: SystemTime,
+        text: String,
+    },
+    // A message the server in broadcast mode sent to us only.
+    Whisper {
+        sent_at: SystemTime,
+        text: String,
+    },
+    // No client with the nickname is connected to the server in broadcast mode.
+    WhisperUndelivered(String),
+    // Time for the periodic summary of the session statistics.
+    StatsDue,
+    // Time to measure the link again, with `--ping-interval`.
+    PingDue,
+    // How long the peer took to answer our ping, if it did in time.
+    PingAnswered(Option<Duration>),
+    // Time to send a message scheduled with `/schedule`.
+    ScheduledDue,
+    // On the server, the filter dropped, redacted or flagged a message of the client, for the words given.
+    MessageFiltered {
+        id: MessageId,
+        action: FilterAction,
+        words: Vec<String>,
+    },
+    // The config file, or the ban list, was changed on disk.
+    ConfigChanged,
+    BanListChanged,
+    // Something the user did in the chat window.
+    #[cfg(feature = "gui")]
+    Window(gui::Event),
+    // A request from a client of the control socket.
+    Control(control::Request),
+    ConnectionStateChanged(ConnectionState),
+    // The server couldn't be reached, the client trying again after a while.
+    ConnectAttemptFailed {
+        error: String,
+        attempt: u32,
+        retries: u32,
+    },
+    // Counting down to the next attempt to reach the server.
+    ConnectRetryIn(u64),
+    // How the socket of a new connection was tuned, for the log.
+    SocketConfigured(String),
+    // The peer sent a frame which couldn't be decoded, skipped for the conversation to go on.
+    ProtocolViolation(ProtocolViolation),
+    // Something went wrong in the component, the conversation going on.
+    Error(ChatError),
+    ClientDisconnected(DisconnectReason),
+    ServerShutDown(DisconnectReason),
+}
+
+impl MainControlMsg {
+    // Whether it comes from the chat window or the control socket, rather than from a connection.
+    #[cfg(feature = "gui")]
+    fn is_from_user(&self) -> bool {
+        matches!(*self, MainControlMsg::Window(_) | MainControlMsg::Control(_) | MainControlMsg::TrayActivated)
+    }
+
+    #[cfg(not(feature = "gui"))]
+    fn is_from_user(&self) -> bool {
+        matches!(*self, MainControlMsg::Control(_) | MainControlMsg::TrayActivated)
+    }
+}
+
+// The messages received from the peer, put back in order,
+// without those dropped by the rate limit, and the key they are signed with.
+struct IncomingMessages {
+    reorder: ReorderBuffer<Option<(SystemTime, Frame)>>,
+    // The peer's identity key, once received.
+    peer_key: Option<VerifyingKey>,
+    // The peer's nickname, once given, as the sender of its messages.
+    peer_nick: Option<String>,
+    // The files being received, by their number.
+    transfers: HashMap<TransferId, IncomingTransfer>,
+    // On the server, that of `--filter`.
+    filter: Option<Arc<WordFilter>>,
+    // The start of a message too long for a frame, as its parts come,
+    // and whether they were all signed by the peer.
+    fragments: Option<(String, bool)>,
+}
+
+impl IncomingMessages {
+    fn new(window: usize) -> IncomingMessages {
+        IncomingMessages {
+            reorder: ReorderBuffer::new(window),
+            peer_key: None,
+            peer_nick: None,
+            transfers: HashMap::new(),
+            filter: None,
+            fragments: None,
+        }
+    }
+
+    fn add_fragment(&mut self, text: &str, verified: bool) {
+        let fragments = self.fragments.get_or_insert_with(|| (String::new(), true));
+        fragments.0.push_str(text);
+        fragments.1 &= verified;
+    }
+
+    // The whole text of the message ending with this one, and whether all of it was signed.
+    fn reassemble(&mut self, text: String, verified: bool) -> (String, bool) {
+        match self.fragments.take() {
+            Some((start, start_verified)) => (start + text.as_str(), start_verified && verified),
+            None => (text, verified),
+        }
+    }
+
+    fn message(&self, id: MessageId, sent_at: SystemTime, kind: MessageKind, body: String) -> ChatMessage {
+        ChatMessage::new(id, self.peer_nick.as_deref(), sent_at, kind, body)
+    }
+
+    // The text of the message as passed on, once through the filter, if it isn't dropped,
+    // letting the operator know of what the filter did.
+    fn screen(&self, id: MessageId, text: String, main_chan: &EventSender) -> Option<String> {
+        let filtered = match self.filter.as_ref().and_then(|filter| filter.apply(&text)) {
+            Some(filtered) => filtered,
+            None => return Some(text),
+        };
+        let _ = main_chan.send(MainControlMsg::MessageFiltered {
+            id,
+            action: filtered.action,
+            words: filtered.words,
+        });
+        match filtered.action {
+            FilterAction::Drop => None,
+            FilterAction::Redact | FilterAction::Flag => Some(filtered.text),
+        }
+    }
+
+    // Answer with where the file is to be sent from, or why it can't be received.
+    fn start_transfer<T: Transport>(&mut self,
+                                    stream: &mut FrameStream<T>,
+                                    main_chan: &EventSender,
+                                    id: TransferId,
+                                    name: String,
+                                    size: u64,
+                                    hash: &[u8]) {
+        let transfer = transfer::files_dir().and_then(|dir| IncomingTransfer::create(&dir, &name, size, hash));
+        match transfer {
+            Ok(transfer) => {
+                let offset = transfer.offset();
+                main_chan.report(send_frame(stream, Frame::FileAccept { transfer: id, offset }));
+                let _ = main_chan.send(MainControlMsg::FileIncoming { transfer: id, name, size, offset });
+                self.transfers.insert(id, transfer);
+                // Nothing follows an empty file.
+                if size == 0 {
+                    self.write_chunk(id, SystemTime::now(), 0, &[], main_chan);
+                }
+            },
+            Err(error) => {
+                main_chan.report(send_frame(stream, Frame::Nack(error.clone())));
+                let _ = main_chan.send(MainControlMsg::TransferFailed { name, error });
+            },
+        }
+    }
+
+    fn cancel_transfer(&mut self, id: TransferId, main_chan: &EventSender) {
+        if let Some(transfer) = self.transfers.remove(&id) {
+            let name = transfer.name.clone();
+            transfer.abandon();
+            let _ = main_chan.send(MainControlMsg::TransferCancelled { transfer: id, name, by_peer: true });
+        }
+    }
+
+    // The chunks of a file which couldn't be written are dropped.
+    fn write_chunk(&mut self, id: TransferId, sent_at: SystemTime, seq: u64, data: &[u8], main_chan: &EventSender) {
+        let written = match self.transfers.get_mut(&id) {
+            Some(transfer) => transfer.write(seq, data),
+            None => return,
+        };
+        let event = match written {
+            Ok(None) => return,
+            Ok(Some(path)) => {
+                let name = self.transfers.remove(&id).map(|transfer| transfer.name).unwrap_or_default();
+                MainControlMsg::FileReceived { transfer: id, sent_at, name, path }
+            },
+            Err(error) => {
+                let transfer = self.transfers.remove(&id).expect("the transfer was just written to");
+                let name = transfer.name.clone();
+                transfer.abandon();
+                MainControlMsg::TransferFailed { name, error }
+            },
+        };
+        let _ = main_chan.send(event);
+    }
+
+    // Whether the message was signed by the peer, rather than made up by whoever relayed it.
+    fn verified(&self, id: MessageId, text: &str, signature: &Option<Vec<u8>>) -> bool {
+        match (self.peer_key.as_ref(), signature.as_ref()) {
+            (Some(key), Some(signature)) => identity::verify_message(key, id, text, signature),
+            _ => false,
+        }
+    }
+}
+
+// The UI's connections, numbered from 0.
+type ConnectionId = usize;
+
+// The events of one connection, tagged with its id on their way to the UI.
+#[derive(Clone)]
+struct EventSender {
+    connection: ConnectionId,
+    chan: Sender<(ConnectionId, MainControlMsg)>,
+}
+
+impl EventSender {
+    fn new(connection: ConnectionId, chan: Sender<(ConnectionId, MainControlMsg)>) -> EventSender {
+        EventSender {
+            connection,
+            chan,
+        }
+    }
+
+    fn send(&self, msg: MainControlMsg) -> Result<(), SendError<(ConnectionId, MainControlMsg)>> {
+        self.chan.send((self.connection, msg))
+    }
+
+    // Let the UI know of the error, if there was one.
+    fn report(&self, result: Result<(), ChatError>) {
+        if let Err(error) = result {
+            let _ = self.send(MainControlMsg::Error(error));
+        }
+    }
+}
+
+// Why the connection ended, for good on the client, and for the server as a whole.
+#[derive(Debug, PartialEq)]
+enum DisconnectReason {
+    // The user asked to quit.
+    Quit,
+    // The peer closed the connection.
+    PeerQuit,
+    NetworkError(String),
+    // The server rejected our credentials.
+    AuthFailed,
+    // The peer sent something we don't understand.
+    ProtocolError(String),
+    // The peer broke the wire format, such that the stream can't be read on.
+    ProtocolViolation(ProtocolViolation),
+    // The session reached its agreed length.
+    SessionExpired,
+    // The server already had as many clients as it takes.
+    ServerFull(u32),
+}
+
+impl DisconnectReason {
+    fn from_read_error(error: io::Error) -> DisconnectReason {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset => DisconnectReason::PeerQuit,
+            io::ErrorKind::InvalidData => match ProtocolViolation::of(&error) {
+                Some(violation) => DisconnectReason::ProtocolViolation(violation.clone()),
+                None => DisconnectReason::ProtocolError(error.to_string()),
+            },
+            _ => DisconnectReason::NetworkError(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DisconnectReason::Quit => write!(f, "{}", tr!("reason-quit")),
+            DisconnectReason::PeerQuit => write!(f, "{}", tr!("reason-peer-quit")),
+            DisconnectReason::NetworkError(ref error) => write!(f, "{}", tr!("reason-network-error", error = error.as_str())),
+            DisconnectReason::AuthFailed => write!(f, "{}", tr!("reason-auth-failed")),
+            DisconnectReason::ProtocolError(ref error) => write!(f, "{}", tr!("reason-protocol-error", error = error.as_str())),
+            DisconnectReason::ProtocolViolation(ref violation) => {
+                write!(f, "{}", tr!("reason-protocol-error", error = violation.to_string()))
+            },
+            DisconnectReason::SessionExpired => write!(f, "{}", tr!("reason-session-expired")),
+            DisconnectReason::ServerFull(max) => write!(f, "{}", tr!("reason-server-full", max = max)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ServerOptions {
+    authenticator: Option<Arc<dyn Authenticator>>,
+    // Shared with the UI, to revoke them.
+    resume_tokens: Arc<Mutex<TokenStore>>,
+    // Shared with the UI, to apply the changes of the config file to the next messages.
+    rate_limit: Arc<Mutex<Option<RateLimit>>>,
+    // The greeting sent to each client, shared with the UI for the same reason.
+    motd: Arc<Mutex<Option<String>>>,
+    // The longest a session with a client may last.
+    session_limit: Option<Duration>,
+    // The undelivered messages kept for the next client,
+    // shared with the UI, to cancel them.
+    offline_queue: Arc<Mutex<OfflineQueue>>,
+    plugins: Vec<Box<dyn ChatPlugin>>,
+    // Where to post relayed messages as JSON.
+    webhook_url: Option<String>,
+    // Where to accept messages to inject into the chat, and serve the recent ones.
+    inject_address: Option<String>,
+    // Where to serve the chat page, browsers joining as clients.
+    web_address: Option<String>,
+    // Let clients wait in a queue while the operator serves them one at a time.
+    support_queue: bool,
+    // Send the operator's messages to all clients, refusing theirs.
+    broadcast: bool,
+    // In either mode, how many clients may be connected at once, those waiting included.
+    max_clients: Option<usize>,
+    // In broadcast mode, how many threads read from the clients.
+    workers: Option<usize>,
+    // Of the channel from the UI to the component.
+    channel: ChannelConfig,
+    ack: AckPolicy,
+    // The addresses clients are refused from, shared with the UI, to add to it.
+    bans: Arc<Mutex<BanList>>,
+    // Where to log the frames of each client, with `--record`.
+    recorder: Option<Recorder>,
+    // To sign our messages with.
+    signing_key: Option<SigningKey>,
+    // How the connections of the clients are tuned.
+    socket: SocketOptions,
+    // What is done with the messages of clients with some words, with `--filter`.
+    filter: Option<Arc<WordFilter>>,
+}
+
+#[derive(Clone, Default)]
+struct ClientOptions {
+    wire_format: WireFormat,
+    channel: ChannelConfig,
+    ack: AckPolicy,
+    recorder: Option<Recorder>,
+    signing_key: Option<SigningKey>,
+    // Tor's SOCKS port, to reach onion services through, if not the default one.
+    tor_socks: Option<String>,
+    // How long to wait for the server to accept the connection, if not as long as the system does.
+    connect_timeout: Option<Duration>,
+    // How many more times to try reaching the server, waiting `retry_delay` in between.
+    connect_retries: u32,
+    retry_delay: Duration,
+    socket: SocketOptions,
+}
+
+impl ServerOptions {
+    fn register_plugin(&mut self, plugin: Box<dyn ChatPlugin>) {
+        self.plugins.push(plugin);
+    }
+}
+
+enum Turn {
+    // Our messages were delivered, wait for the peer's reply.
+    Sent(Vec<String>),
+    // The peer went away, with the messages that were not delivered.
+    Undelivered(VecDeque<(SystemTime, String)>),
+    // In support-queue mode, the operator ended the conversation,
+    // or the client was kicked.
+    Closed,
+    Quit,
 }
 
 enum ComponentControlMsg {
     OutgoingMessage(String),
+    // Ending our turn as a message does, removed by the peer after the time to live.
+    OutgoingEphemeral {
+        text: String,
+        ttl: Duration,
+    },
+    // Raw bytes, with their MIME type if known, sent without ending our turn.
+    OutgoingBinary {
+        mime: Option<String>,
+        data: Vec<u8>,
+    },
+    // A file of any size, sent in chunks, our turn ending only once it was all sent.
+    OutgoingFile(PathBuf),
+    // Stop sending the file.
+    CancelTransfer(TransferId),
+    // The UI has processed the peer's message with the id.
+    MessageProcessed(MessageId),
+    // The user has seen the peer's message with the id.
+    MessageRead(MessageId),
+    // The user is composing a message.
+    Typing,
+    // Measure the round-trip time of the link, on our turn.
+    Ping,
+    // The user chose a new nickname.
+    Nick(String),
+    // Our public identity key, to send to the peer.
+    Identity(Vec<u8>),
+    // The longest we accept the session to last.
+    SessionLimit(Duration),
+    // Retract a message we sent.
+    Delete(MessageId),
+    // React to a message the peer sent.
+    React {
+        id: MessageId,
+        emoji: String,
+    },
+    // A message from outside the chat, sent without ending our turn.
+    InjectMessage(String),
+    Password(String),
+    Credentials {
+        user: String,
+        password: String,
+    },
+    // Answer a challenge with a token, instead of a password.
+    ResumeToken(String),
+    // In support-queue mode, end the conversation with the client being served,
+    // or serve the next one once it is over.
+    CloseVisitor,
+    NextVisitor,
+    // On the server, close the connections from the address,
+    // telling those in the chat why.
+    Kick {
+        address: IpAddr,
+        notice: String,
+    },
+    // In broadcast mode, send to the clients with the nickname only.
+    Whisper {
+        nick: String,
+        text: String,
+    },
     Quit
 }
 
-// TODO: implement a proper codec.
-// Currently assuming messages are < 24 bytes, and padding them.
-// Also assuming ACK message is 3 bytes.
-const EMPTY_MESSAGE: &'static str = "\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}";
+const DEFAULT_ADDRESS: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 8000;
+
+const NONCE_SIZE: usize = 16;
+
+// Greeting the clients, unless the config file has a `motd`.
+const DEFAULT_MOTD: &str = "Lets chat!!";
+
+const SEARCH_RESULTS: usize = 50;
+
+// Kept on the server, for `GET /messages`.
+const RECENT_MESSAGES: usize = 200;
+
+// In characters, of the images received.
+const THUMBNAIL_WIDTH: u32 = 40;
+
+// How many messages received after a missing one wait for it.
+const REORDER_WINDOW: usize = 32;
+
+// In p2p mode, how many times to try dialing the peer, or listening for it,
+// if it was itself between the two.
+const DIAL_ATTEMPTS: u32 = 3;
+
+// For a client reaching the server, unless set with `--connect-timeout` and `--connect-retries`,
+// the server possibly still starting.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_RETRIES: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(3);
+
+// How long a client connecting has to choose its wire format, and then to answer the challenge.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// Before accepting again once it failed, such as for lack of file descriptors, not to spin.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+// The threads reading from the clients of a server in broadcast mode, unless set with `--workers`,
+// those beyond waiting for a client to leave.
+const DEFAULT_WORKERS: usize = 64;
+
+fn acknowledge_receipt<T: Transport>(stream: &mut FrameStream<T>, id: MessageId) -> Result<(), ChatError> {
+    stream.write_ack(id).map_err(ChatError::send("Ack"))
+}
+
+// Returns whether the message was acknowledged, failing with `WouldBlock` or `TimedOut`
+// if the peer didn't answer in time.
+fn wait_for_ack<T: Transport>(stream: &mut FrameStream<T>, id: MessageId, timeout: Duration) -> io::Result<bool> {
+    let _ = stream.get_ref().set_read_timeout(Some(timeout));
+    let acked = stream.read_ack(id);
+    let _ = stream.get_ref().set_read_timeout(None);
+    acked
+}
 
-fn time_roundtrip<F: FnMut()>(mut f: F) -> Duration {
-    let sys_time = SystemTime::now();
-    f();
-    sys_time.elapsed().unwrap()
+fn timed_out(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut
 }
 
-fn acknowledge_receipt(stream: &mut TcpStream) {
-    let _ = stream.write("ACK".as_bytes());
-    stream.flush().unwrap();
+// Whether the frame which failed to be read can be skipped, the UI being told,
+// rather than the connection being given up on.
+fn skipped_frame(error: &io::Error, main_chan: &EventSender) -> bool {
+    match ProtocolViolation::of(error) {
+        Some(violation) if violation.is_recoverable() => {
+            let _ = main_chan.send(MainControlMsg::ProtocolViolation(violation.clone()));
+            true
+        },
+        _ => false,
+    }
 }
 
-fn wait_for_ack(stream: &mut TcpStream) {
-    let mut buffer = [0; 3];
-    let _ = stream.read(&mut buffer);
+fn send_frame<T: Transport>(stream: &mut FrameStream<T>, frame: Frame) -> Result<(), ChatError> {
+    stream.write_frame(SystemTime::now(), &frame).map_err(ChatError::send(frame.name()))
 }
 
-fn send_chat(stream: &mut TcpStream, chat: &str) {
-    let _ = stream.write(chat.as_bytes());
-    stream.flush().unwrap();
+fn send_motd<T: Transport>(stream: &mut FrameStream<T>, next_id: &mut MessageId, motd: &str) -> Result<(), ChatError> {
+    let sent = send_frame(stream, Frame::System {
+        id: *next_id,
+        text: motd.to_string(),
+    });
+    *next_id += 1;
+    sent
 }
 
-fn wait_for_message(stream: &mut TcpStream,
-                    main_chan: &Sender<MainControlMsg>)
-                    -> bool {
-    let mut buffer = [0; 24];
-    let _ = stream.read(&mut buffer);
-    let message = String::from_utf8_lossy(&buffer[..]);
-    if message == EMPTY_MESSAGE {
-        // Peer disconnected
-        return false;
+// Binary payloads never end a turn, and ephemeral ones always do. Text is signed with our identity key, if we have one.
+fn chat_frame(id: MessageId, payload: Payload, ends_turn: bool, key: Option<&SigningKey>) -> Frame {
+    match payload {
+        Payload::Text(text) => {
+            let signature = key.map(|key| identity::sign_message(key, id, &text));
+            if ends_turn {
+                Frame::Message { id, text, signature, ttl: None }
+            } else {
+                Frame::MessagePart { id, text, signature, continued: false }
+            }
+        },
+        Payload::Fragment(text) => {
+            let signature = key.map(|key| identity::sign_message(key, id, &text));
+            Frame::MessagePart { id, text, signature, continued: true }
+        },
+        Payload::Ephemeral { text, ttl } => {
+            let signature = key.map(|key| identity::sign_message(key, id, &text));
+            Frame::Message { id, text, signature, ttl: Some(ttl) }
+        },
+        Payload::Binary { mime, data } => Frame::Binary { id, mime, data },
     }
-    acknowledge_receipt(stream);
-    let _ = main_chan.send(MainControlMsg::IncomingMessage(message.to_string()));
-    true
 }
 
-fn wait_for_input(stream: &mut TcpStream,
-                main_chan: &Sender<MainControlMsg>,
-                port: &Receiver<ComponentControlMsg>)
-                -> bool {
-    let control_msg = match port.recv() {
-        Err(_) => return false,
-        Ok(control_msg) => control_msg,
+// Our side of a connection: the number of our next message,
+// and those sent but not acknowledged yet.
+struct Outgoing {
+    next_id: MessageId,
+    next_transfer: TransferId,
+    next_ping: u64,
+    in_flight: InFlight<(SystemTime, Payload, bool)>,
+    ack: AckPolicy,
+    signing_key: Option<SigningKey>,
+}
+
+impl Outgoing {
+    fn new(ack: AckPolicy, signing_key: Option<SigningKey>) -> Outgoing {
+        Outgoing {
+            next_id: 1,
+            next_transfer: 1,
+            next_ping: 1,
+            in_flight: InFlight::default(),
+            ack,
+            signing_key,
+        }
+    }
+
+    // Send a message without waiting for its ack, returning its id,
+    // that of its last part if its text is too long for a frame, the parts being numbered as messages.
+    // Failing to, it is still in flight, to be reported as not delivered once no ack comes.
+    fn send<T: Transport>(&mut self,
+                          stream: &mut FrameStream<T>,
+                          main_chan: &EventSender,
+                          sent_at: SystemTime,
+                          payload: Payload,
+                          ends_turn: bool)
+                          -> MessageId {
+        let mut id = self.next_id;
+        for payload in payload.split(MAX_TEXT_SIZE) {
+            id = self.next_id;
+            self.next_id += 1;
+            let frame = chat_frame(id, payload.clone(), ends_turn, self.signing_key.as_ref());
+            main_chan.report(stream.write_frame(sent_at, &frame).map_err(ChatError::send(frame.name())));
+            self.in_flight.insert(id, (sent_at, payload, ends_turn));
+        }
+        id
+    }
+}
+
+// Send messages without waiting for each ack, the last one ending our turn if `end_turn`,
+// then wait for the acks of all those in flight.
+// Fails with the messages that were not delivered, if the peer went away, hangs or refused one.
+fn deliver<T: Transport>(stream: &mut FrameStream<T>,
+                         main_chan: &EventSender,
+                         messages: VecDeque<(SystemTime, String)>,
+                         end_turn: bool,
+                         outgoing: &mut Outgoing)
+                         -> Result<(), VecDeque<(SystemTime, String)>> {
+    let count = messages.len();
+    for (index, (sent_at, text)) in messages.into_iter().enumerate() {
+        outgoing.send(stream, main_chan, sent_at, Payload::Text(text), end_turn && index + 1 == count);
+    }
+    await_acks(stream, main_chan, outgoing)
+}
+
+// Tell the UI our message was delivered, unless it is a late ack of a message sent again,
+// or of the start of one, which is delivered with its last part.
+fn acknowledged(in_flight: &mut InFlight<(SystemTime, Payload, bool)>, id: MessageId, main_chan: &EventSender) {
+    match in_flight.acknowledge(id) {
+        Some(((_, Payload::Text(text), _), roundtrip)) |
+        Some(((_, Payload::Ephemeral { text, .. }, _), roundtrip)) => {
+            let _ = main_chan.send(MainControlMsg::MessageDelivered {
+                id,
+                text,
+                roundtrip,
+            });
+        },
+        Some(((_, Payload::Binary { .. }, _), roundtrip)) => {
+            let _ = main_chan.send(MainControlMsg::BinaryDelivered {
+                id,
+                roundtrip,
+            });
+        },
+        Some(((_, Payload::Fragment(_), _), _)) | None => {},
+    }
+}
+
+fn throttled(main_chan: &EventSender, id: MessageId, retry_after_ms: u64, dropped: bool) {
+    let _ = main_chan.send(MainControlMsg::Throttled {
+        id,
+        retry_after: Duration::from_millis(retry_after_ms),
+        dropped,
+    });
+}
+
+// Wait for the acks of our messages in flight, in whatever order they come,
+// sending those not acknowledged in time again, as allowed by the ack policy.
+fn await_acks<T: Transport>(stream: &mut FrameStream<T>,
+                            main_chan: &EventSender,
+                            outgoing: &mut Outgoing)
+                            -> Result<(), VecDeque<(SystemTime, String)>> {
+    let ack = outgoing.ack;
+    let signing_key = outgoing.signing_key.as_ref();
+    let in_flight = &mut outgoing.in_flight;
+    let delivered = loop {
+        let timeout = match in_flight.time_left(ack.timeout) {
+            Some(timeout) => timeout.max(Duration::from_millis(1)),
+            None => break true,
+        };
+        let _ = stream.get_ref().set_read_timeout(Some(timeout));
+        let frame = stream.read_frame();
+        let _ = stream.get_ref().set_read_timeout(None);
+        match frame {
+            Ok((_, Frame::Ack(id))) => acknowledged(in_flight, id, main_chan),
+            Ok((_, Frame::Throttled { id, retry_after_ms, dropped })) => throttled(main_chan, id, retry_after_ms, dropped),
+            // Of a ping given up on.
+            Ok((_, Frame::Pong(_))) => {},
+            Err(ref error) if skipped_frame(error, main_chan) => {},
+            Err(ref error) if timed_out(error) => {
+                let expired = in_flight.expired(ack.timeout);
+                if expired.iter().any(|&(_, times_sent, _)| times_sent > ack.retries) {
+                    break false;
+                }
+                // The peer recognizes a message sent again by its id.
+                for (id, _, &(sent_at, ref payload, ends_turn)) in expired {
+                    let frame = chat_frame(id, payload.clone(), ends_turn, signing_key);
+                    main_chan.report(stream.write_frame(sent_at, &frame).map_err(ChatError::send(frame.name())));
+                }
+            },
+            _ => break false,
+        }
+    };
+    if delivered {
+        return Ok(());
+    }
+    // Only text can be queued for the next client, unless ephemeral, never to be kept,
+    // the start of a message going back with the rest of it.
+    let mut start = String::new();
+    Err(in_flight.take_all().into_iter().filter_map(|(id, (sent_at, payload, _))| {
+        if let Payload::Fragment(text) = payload {
+            start.push_str(&text);
+            return None;
+        }
+        let _ = main_chan.send(MainControlMsg::SendFailed(id));
+        let start = mem::take(&mut start);
+        match payload {
+            Payload::Text(text) => Some((sent_at, start + text.as_str())),
+            Payload::Fragment(_) | Payload::Ephemeral { .. } | Payload::Binary { .. } => None,
+        }
+    }).collect())
+}
+
+// Announce the file to the peer, its chunks following with those of the files before it.
+fn start_transfer<T: Transport>(stream: &mut FrameStream<T>,
+                                main_chan: &EventSender,
+                                path: &Path,
+                                outgoing: &mut Outgoing,
+                                transfers: &mut VecDeque<OutgoingTransfer>) {
+    let transfer = match OutgoingTransfer::open(outgoing.next_transfer, path) {
+        Ok(transfer) => transfer,
+        Err(error) => {
+            let name = path.display().to_string();
+            let _ = main_chan.send(MainControlMsg::TransferFailed { name, error: error.to_string() });
+            return;
+        },
+    };
+    let mut transfer = transfer;
+    outgoing.next_transfer += 1;
+    main_chan.report(send_frame(stream, Frame::FileStart {
+        transfer: transfer.id,
+        name: transfer.name.clone(),
+        size: transfer.size,
+        hash: transfer.hash.clone(),
+    }));
+    let accepted = await_accept(stream, main_chan, outgoing, transfer.id)
+        .and_then(|offset| transfer.resume_at(offset).map(|()| offset).map_err(|error| error.to_string()));
+    let offset = match accepted {
+        Ok(offset) => offset,
+        Err(error) => {
+            let _ = main_chan.send(MainControlMsg::TransferFailed { name: transfer.name, error });
+            return;
+        },
+    };
+    let _ = main_chan.send(MainControlMsg::FileSending {
+        transfer: transfer.id,
+        name: transfer.name.clone(),
+        size: transfer.size,
+        offset,
+    });
+    if transfer.is_done() {
+        let _ = main_chan.send(MainControlMsg::FileSent { transfer: transfer.id, name: transfer.name });
+    } else {
+        transfers.push_back(transfer);
+    }
+}
+
+// Where the peer wants the file from, the acks of our messages coming in the meantime.
+fn await_accept<T: Transport>(stream: &mut FrameStream<T>,
+                              main_chan: &EventSender,
+                              outgoing: &mut Outgoing,
+                              transfer: TransferId)
+                              -> Result<u64, String> {
+    loop {
+        let _ = stream.get_ref().set_read_timeout(Some(outgoing.ack.timeout));
+        let frame = stream.read_frame();
+        let _ = stream.get_ref().set_read_timeout(None);
+        match frame {
+            Ok((_, Frame::FileAccept { transfer: accepted, offset })) if accepted == transfer => return Ok(offset),
+            Ok((_, Frame::Ack(id))) => acknowledged(&mut outgoing.in_flight, id, main_chan),
+            Ok((_, Frame::Throttled { id, retry_after_ms, dropped })) => throttled(main_chan, id, retry_after_ms, dropped),
+            Ok((_, Frame::Pong(_))) => {},
+            Ok((_, Frame::Nack(reason))) => return Err(reason),
+            Ok((_, frame)) => return Err(format!("unexpected frame {}", frame.name())),
+            Err(ref error) if skipped_frame(error, main_chan) => {},
+            Err(ref error) if timed_out(error) => return Err(tr!("file-not-accepted")),
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+}
+
+// Tell the UI how long the peer took to answer a ping, the acks of our messages coming in the meantime,
+// the ping being lost if no answer comes before an ack would be sent again.
+fn ping<T: Transport>(stream: &mut FrameStream<T>, main_chan: &EventSender, outgoing: &mut Outgoing) {
+    let seq = outgoing.next_ping;
+    outgoing.next_ping += 1;
+    let sent = Instant::now();
+    if let Err(error) = send_frame(stream, Frame::Ping(seq)) {
+        let _ = main_chan.send(MainControlMsg::Error(error));
+        let _ = main_chan.send(MainControlMsg::PingAnswered(None));
+        return;
+    }
+    let roundtrip = loop {
+        let timeout = match outgoing.ack.timeout.checked_sub(sent.elapsed()) {
+            Some(timeout) if !timeout.is_zero() => timeout,
+            _ => break None,
+        };
+        let _ = stream.get_ref().set_read_timeout(Some(timeout));
+        let frame = stream.read_frame();
+        let _ = stream.get_ref().set_read_timeout(None);
+        match frame {
+            Ok((_, Frame::Pong(answered))) if answered == seq => break Some(sent.elapsed()),
+            Ok((_, Frame::Ack(id))) => acknowledged(&mut outgoing.in_flight, id, main_chan),
+            Ok((_, Frame::Throttled { id, retry_after_ms, dropped })) => throttled(main_chan, id, retry_after_ms, dropped),
+            Ok(_) => {},
+            Err(ref error) if skipped_frame(error, main_chan) => {},
+            Err(_) => break None,
+        }
+    };
+    let _ = main_chan.send(MainControlMsg::PingAnswered(roundtrip));
+}
+
+// Send the next chunk of the first file, a chunk at a time for the chat to go on in between.
+fn send_chunk<T: Transport>(stream: &mut FrameStream<T>,
+                            main_chan: &EventSender,
+                            transfers: &mut VecDeque<OutgoingTransfer>) {
+    let transfer = match transfers.front_mut() {
+        Some(transfer) => transfer,
+        None => return,
+    };
+    let event = match transfer.next_chunk() {
+        Ok((seq, data)) => {
+            main_chan.report(send_frame(stream, Frame::Chunk { transfer: transfer.id, seq, data }));
+            if transfer.progress_due() {
+                let _ = main_chan.send(MainControlMsg::TransferProgress {
+                    id: transfer.id,
+                    sent: transfer.sent,
+                    total: transfer.size,
+                });
+            }
+            if !transfer.is_done() {
+                return;
+            }
+            MainControlMsg::FileSent { transfer: transfer.id, name: transfer.name.clone() }
+        },
+        Err(error) => MainControlMsg::TransferFailed { name: transfer.name.clone(), error: error.to_string() },
+    };
+    transfers.pop_front();
+    let _ = main_chan.send(event);
+}
+
+// The peer removes what it received of the file.
+fn cancel_transfer<T: Transport>(stream: &mut FrameStream<T>,
+                                 main_chan: &EventSender,
+                                 id: TransferId,
+                                 transfers: &mut VecDeque<OutgoingTransfer>) {
+    let index = match transfers.iter().position(|transfer| transfer.id == id) {
+        Some(index) => index,
+        // Sent already.
+        None => return,
+    };
+    let transfer = transfers.remove(index).expect("the transfer was just found");
+    main_chan.report(send_frame(stream, Frame::FileCancel(id)));
+    let _ = main_chan.send(MainControlMsg::TransferCancelled { transfer: id, name: transfer.name, by_peer: false });
+}
+
+fn confirm_processed<T: Transport>(stream: &mut FrameStream<T>, id: MessageId) -> Result<(), ChatError> {
+    send_frame(stream, Frame::Processed(id))
+}
+
+fn auth_response(password: &str, nonce: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(password.as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(nonce);
+    mac
+}
+
+// Returns the user, if the client answered with valid credentials.
+fn check_response(response: Frame,
+                  nonce: &[u8],
+                  authenticator: &dyn Authenticator,
+                  main_chan: &EventSender)
+                  -> Option<String> {
+    let authenticated = match (authenticator.shared_password(), response) {
+        (Some(password), Frame::Proof(proof)) => {
+            // All clients share the password, and an empty user name.
+            let valid = auth_response(password, nonce).verify_slice(&proof).is_ok();
+            return if valid { Some(String::new()) } else { None };
+        },
+        (None, Frame::Credentials { user, password }) => {
+            authenticator.authenticate(&user, &password)
+                .map(|valid| if valid { Some(user) } else { None })
+        },
+        _ => return None,
     };
-    let chat: String = match control_msg {
-        ComponentControlMsg::OutgoingMessage(chat) => chat,
-        ComponentControlMsg::Quit => return false,
+    authenticated.unwrap_or_else(|error| {
+        let _ = main_chan.send(MainControlMsg::AuthenticatorError(error));
+        None
+    })
+}
+
+// Returns whether the client is authenticated, in which case it is issued a resume token.
+fn challenge_client<T: Transport>(stream: &mut FrameStream<T>,
+                                  authenticator: &dyn Authenticator,
+                                  tokens: &Mutex<TokenStore>,
+                                  main_chan: &EventSender)
+                                  -> bool {
+    // A client can try a single token, before having to answer the challenge.
+    let mut may_resume = true;
+    let user = loop {
+        let mut nonce = vec![0; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let method = match authenticator.shared_password() {
+            Some(_) => AuthMethod::Proof,
+            None => AuthMethod::Credentials,
+        };
+        main_chan.report(send_frame(stream, Frame::Challenge {
+            nonce: nonce.clone(),
+            method,
+        }));
+        match stream.read_frame().ok() {
+            Some((_, Frame::ResumeToken(token))) if may_resume => {
+                may_resume = false;
+                if let Some(user) = tokens.lock().unwrap().redeem(&token) {
+                    break Some(user);
+                }
+            },
+            Some((_, response @ Frame::Proof(_))) |
+            Some((_, response @ Frame::Credentials { .. })) => {
+                break check_response(response, &nonce, authenticator, main_chan);
+            },
+            _ => break None,
+        }
     };
-    let duration = time_roundtrip(|| {
-        send_chat(stream, chat.as_str());
-        wait_for_ack(stream);
+    match user {
+        Some(user) => {
+            let token = tokens.lock().unwrap().issue(&user);
+            main_chan.report(send_frame(stream, Frame::ResumeToken(token)));
+            true
+        },
+        None => {
+            main_chan.report(send_frame(stream, Frame::Rejected));
+            false
+        },
+    }
+}
+
+fn answer_challenge<T: Transport>(stream: &mut FrameStream<T>,
+                                  nonce: &[u8],
+                                  method: AuthMethod,
+                                  main_chan: &EventSender,
+                                  port: &Receiver<ComponentControlMsg>)
+                                  -> bool {
+    let _ = main_chan.send(match method {
+        AuthMethod::Credentials => MainControlMsg::CredentialsRequired,
+        AuthMethod::Proof => MainControlMsg::PasswordRequired,
     });
-    let _ = main_chan.send(MainControlMsg::RoundTrip(duration));
+    let response = loop {
+        match port.recv() {
+            Ok(ComponentControlMsg::Password(password)) if method == AuthMethod::Proof => {
+                break Frame::Proof(auth_response(&password, nonce).finalize().into_bytes().to_vec());
+            },
+            Ok(ComponentControlMsg::Credentials { user, password }) if method == AuthMethod::Credentials => {
+                break Frame::Credentials {
+                    user,
+                    password,
+                };
+            },
+            Ok(ComponentControlMsg::ResumeToken(token)) => {
+                main_chan.report(send_frame(stream, Frame::ResumeToken(token)));
+                return true;
+            },
+            Ok(ComponentControlMsg::Quit) | Err(_) => return false,
+            Ok(_) => continue,
+        }
+    };
+    main_chan.report(send_frame(stream, response));
     true
 }
 
-fn start_server(main_chan: Sender<MainControlMsg>) -> Sender<ComponentControlMsg> {
-    let (chan, port) = channel();
-    let _ = thread::Builder::new().spawn(move || {
-        let listener = TcpListener::bind("127.0.0.1:8000").unwrap();
-        let mut keep_accepting = true;
-        while keep_accepting {
-            let client = listener.accept();
-            if let Ok((mut stream, _)) = client {
-                let handshake = "Lets chat!!";
-                send_chat(&mut stream, &handshake);
-                // Handle the first ACK from client...
-                wait_for_ack(&mut stream);
-                loop {
-                    if !wait_for_message(&mut stream, &main_chan) {
-                        // Client disconnect, break out of the loop,
-                        // and start accepting the next one.
-                        break;
-                    }
-                    keep_accepting = wait_for_input(&mut stream, &main_chan, &port);
-                    if !keep_accepting {
-                        // Server shutdown.
-                        break;
+// Returns whether the message should be passed on, the client being told to slow down if not at once.
+fn throttle<T: Transport>(stream: &mut FrameStream<T>,
+                          limiter: &mut Option<RateLimiter>,
+                          main_chan: &EventSender,
+                          id: MessageId,
+                          can_drop: bool)
+                          -> bool {
+    let limiter = match limiter.as_mut() {
+        Some(limiter) => limiter,
+        None => return true,
+    };
+    if limiter.try_acquire() {
+        return true;
+    }
+    let _ = main_chan.send(MainControlMsg::ClientThrottled);
+    let dropped = can_drop && limiter.policy() == ThrottlePolicy::Drop;
+    let retry_after_ms = limiter.retry_after().as_millis().max(1) as u64;
+    main_chan.report(send_frame(stream, Frame::Throttled { id, retry_after_ms, dropped }));
+    if dropped {
+        return false;
+    }
+    limiter.acquire();
+    true
+}
+
+// The limiter for the next messages, replaced if the limit changed since.
+fn current_limiter<'a>(limiter: &'a mut Option<RateLimiter>,
+                       limit: &Mutex<Option<RateLimit>>)
+                       -> &'a mut Option<RateLimiter> {
+    let limit = *limit.lock().unwrap();
+    if limiter.as_ref().map(RateLimiter::limit) != limit {
+        *limiter = limit.map(RateLimiter::new);
+    }
+    limiter
+}
+
+// Returns the messages received during the peer's turn,
+// or why the connection ended.
+fn wait_for_message<T: Transport>(stream: &mut FrameStream<T>,
+                                  main_chan: &EventSender,
+                                  port: &Receiver<ComponentControlMsg>,
+                                  state: &SharedState,
+                                  session: &SessionLimit,
+                                  limiter: &mut Option<RateLimiter>,
+                                  incoming: &mut IncomingMessages)
+                                  -> Result<Vec<String>, DisconnectReason> {
+    let mut received = vec![];
+    let (sent_at, id, text, verified, ttl) = loop {
+        let (sent_at, frame) = match stream.read_frame() {
+            Ok(frame) => frame,
+            Err(ref error) if skipped_frame(error, main_chan) => continue,
+            Err(error) => return Err(DisconnectReason::from_read_error(error)),
+        };
+        match frame {
+            // Sent again, the peer having not received our ack in time.
+            Frame::Message { id, .. } |
+            Frame::MessagePart { id, .. } |
+            Frame::Binary { id, .. } |
+            Frame::System { id, .. } if !incoming.reorder.is_new(id) => {
+                main_chan.report(acknowledge_receipt(stream, id));
+            },
+            Frame::Message { id, .. } | Frame::MessagePart { id, .. } | Frame::Binary { id, .. } | Frame::System { id, .. } => {
+                // The last message of a turn is never dropped,
+                // since the peer then waits for our reply, nor a part of a longer one.
+                let ends_turn = matches!(frame, Frame::Message { .. } | Frame::System { .. });
+                let can_drop = matches!(frame, Frame::MessagePart { continued: false, .. } | Frame::Binary { .. })
+                    && incoming.fragments.is_none();
+                let shown = throttle(stream, limiter, main_chan, id, can_drop);
+                main_chan.report(acknowledge_receipt(stream, id));
+                incoming.reorder.push(id, if shown { Some((sent_at, frame)) } else { None });
+                // Nothing more is sent once the turn ends, whatever is missing then never will be.
+                let ordered = if ends_turn { incoming.reorder.flush() } else { incoming.reorder.ready() };
+                let mut turn_end = None;
+                for ordered in ordered {
+                    match ordered {
+                        Ordered::Message(id, Some((sent_at, Frame::Message { text, signature, ttl, .. }))) => {
+                            let verified = incoming.verified(id, &text, &signature);
+                            let (text, verified) = incoming.reassemble(text, verified);
+                            turn_end = Some((sent_at, id, incoming.screen(id, text, main_chan), verified, ttl.map(Duration::from_secs)));
+                        },
+                        Ordered::Message(id, Some((sent_at, Frame::System { text, .. }))) => {
+                            set_state(state, main_chan, ConnectionState::Active);
+                            let _ = main_chan.send(MainControlMsg::IncomingMessage {
+                                message: incoming.message(id, sent_at, MessageKind::System, text),
+                                verified: false,
+                                ttl: None,
+                            });
+                            return Ok(received);
+                        },
+                        // Shown once the rest of it comes, with the id of its last part.
+                        Ordered::Message(id, Some((_, Frame::MessagePart { text, signature, continued: true, .. }))) => {
+                            let verified = incoming.verified(id, &text, &signature);
+                            incoming.add_fragment(&text, verified);
+                        },
+                        Ordered::Message(id, Some((sent_at, Frame::MessagePart { text, signature, .. }))) => {
+                            let verified = incoming.verified(id, &text, &signature);
+                            let (text, verified) = incoming.reassemble(text, verified);
+                            let text = match incoming.screen(id, text, main_chan) {
+                                Some(text) => text,
+                                None => continue,
+                            };
+                            received.push(text.clone());
+                            let _ = main_chan.send(MainControlMsg::IncomingMessagePart {
+                                message: incoming.message(id, sent_at, MessageKind::Text, text),
+                                verified,
+                            });
+                        },
+                        Ordered::Message(id, Some((sent_at, Frame::Binary { mime, data, .. }))) => {
+                            let _ = main_chan.send(MainControlMsg::IncomingBinary {
+                                id,
+                                sent_at,
+                                mime,
+                                data,
+                            });
+                        },
+                        Ordered::Message(..) => {},
+                        Ordered::Missing { first, last } => {
+                            let _ = main_chan.send(MainControlMsg::MessagesMissing { first, last });
+                        },
                     }
                 }
+                if let Some(turn_end) = turn_end {
+                    break turn_end;
+                }
+            },
+            // Of a message we sent again, the first one having been acknowledged late.
+            Frame::Ack(_) => {},
+            // Of our last message, coming after we stopped waiting for its ack.
+            Frame::Throttled { id, retry_after_ms, dropped } => throttled(main_chan, id, retry_after_ms, dropped),
+            Frame::Ping(seq) => main_chan.report(send_frame(stream, Frame::Pong(seq))),
+            // Of a ping given up on.
+            Frame::Pong(_) => {},
+            Frame::Processed(id) => {
+                // The peer processed one of our messages, keep waiting for theirs.
+                let _ = main_chan.send(MainControlMsg::MessageProcessed(id));
+            },
+            Frame::Read(id) => {
+                // The peer saw one of our messages, keep waiting for theirs.
+                let _ = main_chan.send(MainControlMsg::MessageRead(id));
+            },
+            Frame::Typing => {
+                let _ = main_chan.send(MainControlMsg::PeerTyping);
+            },
+            Frame::Nick(nick) => {
+                incoming.peer_nick = Some(nick.clone());
+                let _ = main_chan.send(MainControlMsg::PeerRenamed(nick));
+            },
+            Frame::Delete(id) => {
+                let _ = main_chan.send(MainControlMsg::MessageDeleted(id));
+            },
+            Frame::Identity(key) => {
+                let bytes: [u8; PUBLIC_KEY_LENGTH] = key.as_slice().try_into()
+                    .map_err(|_| DisconnectReason::ProtocolError("invalid identity key".to_string()))?;
+                let peer_key = VerifyingKey::from_bytes(&bytes)
+                    .map_err(|_| DisconnectReason::ProtocolError("invalid identity key".to_string()))?;
+                incoming.peer_key = Some(peer_key);
+                let _ = main_chan.send(MainControlMsg::PeerIdentity(key));
+            },
+            Frame::SessionLimit(seconds) => {
+                if seconds > 0 {
+                    session.agree(Duration::from_secs(seconds));
+                }
+            },
+            Frame::Announcement(text) => {
+                // Never followed by a turn of ours.
+                set_state(state, main_chan, ConnectionState::Active);
+                let _ = main_chan.send(MainControlMsg::Announcement {
+                    sent_at,
+                    text,
+                });
+                send_introductions(stream, port)?;
+            },
+            Frame::Whisper(text) => {
+                let _ = main_chan.send(MainControlMsg::Whisper {
+                    sent_at,
+                    text,
+                });
+            },
+            Frame::Presence(presence) => {
+                let _ = main_chan.send(MainControlMsg::Presence(presence));
+            },
+            Frame::QueuePosition(position) => {
+                let _ = main_chan.send(MainControlMsg::QueuePosition(position));
+            },
+            Frame::ServerFull(max) => return Err(DisconnectReason::ServerFull(max)),
+            Frame::Reaction { id, emoji } => {
+                let _ = main_chan.send(MainControlMsg::ReactionReceived(incoming.message(id, sent_at, MessageKind::Reaction, emoji)));
+            },
+            Frame::Challenge { nonce, method } => {
+                if !answer_challenge(stream, &nonce, method, main_chan, port) {
+                    return Err(DisconnectReason::Quit);
+                }
+            },
+            Frame::ResumeToken(token) => {
+                // Only issued once the challenge passed.
+                set_state(state, main_chan, ConnectionState::Authenticated);
+                let _ = main_chan.send(MainControlMsg::ResumeTokenIssued(token));
+            },
+            Frame::FileStart { transfer, name, size, hash } => {
+                incoming.start_transfer(stream, main_chan, transfer, name, size, &hash);
+            },
+            Frame::Chunk { transfer, seq, data } => incoming.write_chunk(transfer, sent_at, seq, &data, main_chan),
+            Frame::FileCancel(transfer) => incoming.cancel_transfer(transfer, main_chan),
+            Frame::Rejected => return Err(DisconnectReason::AuthFailed),
+            frame => return Err(DisconnectReason::ProtocolError(format!("unexpected frame {:?}", frame))),
+        }
+    };
+    // On the client, the handshake is over once the server ends its turn.
+    set_state(state, main_chan, ConnectionState::Active);
+    // Dropped by the filter, the message still ends the turn of the client.
+    let text = match text {
+        Some(text) => {
+            received.push(text.clone());
+            text
+        },
+        None => tr!("message-dropped-by-filter"),
+    };
+    let _ = main_chan.send(MainControlMsg::IncomingMessage {
+        message: incoming.message(id, sent_at, MessageKind::Text, text),
+        verified,
+        ttl,
+    });
+    Ok(received)
+}
+
+// A client of a server in broadcast mode never gets a turn, its nickname and key being sent
+// as the announcements come instead, for the operator to whisper to it.
+fn send_introductions<T: Transport>(stream: &mut FrameStream<T>,
+                                    port: &Receiver<ComponentControlMsg>)
+                                    -> Result<(), DisconnectReason> {
+    while let Some(control_msg) = port.try_recv() {
+        match control_msg {
+            ComponentControlMsg::Nick(nick) => send_frame(stream, Frame::Nick(nick)).map_err(|error| DisconnectReason::NetworkError(error.to_string()))?,
+            ComponentControlMsg::Identity(key) => send_frame(stream, Frame::Identity(key)).map_err(|error| DisconnectReason::NetworkError(error.to_string()))?,
+            ComponentControlMsg::Quit => return Err(DisconnectReason::Quit),
+            // Nothing else is sent to a server which only broadcasts.
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+fn set_state(state: &SharedState, main_chan: &EventSender, new_state: ConnectionState) {
+    if state.set(new_state) {
+        let _ = main_chan.send(MainControlMsg::ConnectionStateChanged(new_state));
+    }
+}
+
+// Run a hook on all plugins, returning their replies.
+fn run_plugins<F>(plugins: &mut [Box<dyn ChatPlugin>],
+                  main_chan: &EventSender,
+                  mut hook: F)
+                  -> VecDeque<(SystemTime, String)>
+    where F: FnMut(&mut dyn ChatPlugin) -> Vec<String> {
+    let mut replies = VecDeque::new();
+    for plugin in plugins.iter_mut() {
+        for text in hook(plugin.as_mut()) {
+            let _ = main_chan.send(MainControlMsg::PluginReply {
+                plugin: plugin.name().to_string(),
+                text: text.clone(),
+            });
+            replies.push_back((SystemTime::now(), text));
+        }
+    }
+    replies
+}
+
+fn wait_for_input<T: Transport>(stream: &mut FrameStream<T>,
+                              main_chan: &EventSender,
+                              port: &Receiver<ComponentControlMsg>,
+                              session: &SessionLimit,
+                              outgoing: &mut Outgoing)
+                              -> Turn {
+    let mut sent = vec![];
+    let mut transfers = VecDeque::new();
+    // Our reply, sent once the files are, the chat going on in the meantime.
+    let mut reply = None;
+    let (chat, ttl) = loop {
+        if transfers.is_empty() {
+            if let Some(reply) = reply.take() {
+                break reply;
+            }
+        }
+        let control_msg = if transfers.is_empty() {
+            match port.recv() {
+                Err(_) => return Turn::Quit,
+                Ok(control_msg) => control_msg,
+            }
+        } else {
+            match port.try_recv() {
+                Some(control_msg) => control_msg,
+                None => {
+                    send_chunk(stream, main_chan, &mut transfers);
+                    continue;
+                },
+            }
+        };
+        match control_msg {
+            ComponentControlMsg::OutgoingMessage(chat) => reply = Some((chat, None)),
+            ComponentControlMsg::OutgoingEphemeral { text, ttl } => reply = Some((text, Some(ttl))),
+            ComponentControlMsg::OutgoingBinary { mime, data } => {
+                // Acknowledged along with our reply.
+                outgoing.send(stream, main_chan, SystemTime::now(), Payload::Binary { mime, data }, false);
+            },
+            ComponentControlMsg::OutgoingFile(path) => start_transfer(stream, main_chan, &path, outgoing, &mut transfers),
+            ComponentControlMsg::CancelTransfer(id) => cancel_transfer(stream, main_chan, id, &mut transfers),
+            ComponentControlMsg::InjectMessage(text) => {
+                // Acknowledged along with our reply.
+                outgoing.send(stream, main_chan, SystemTime::now(), Payload::Text(text.clone()), false);
+                let _ = main_chan.send(MainControlMsg::MessageInjected(text.clone()));
+                sent.push(text);
+            },
+            ComponentControlMsg::MessageProcessed(id) => main_chan.report(confirm_processed(stream, id)),
+            ComponentControlMsg::MessageRead(id) => main_chan.report(send_frame(stream, Frame::Read(id))),
+            ComponentControlMsg::Typing => main_chan.report(send_frame(stream, Frame::Typing)),
+            ComponentControlMsg::Ping => ping(stream, main_chan, outgoing),
+            ComponentControlMsg::Nick(nick) => main_chan.report(send_frame(stream, Frame::Nick(nick))),
+            ComponentControlMsg::Identity(key) => main_chan.report(send_frame(stream, Frame::Identity(key))),
+            ComponentControlMsg::SessionLimit(length) => {
+                session.agree(length);
+                main_chan.report(send_frame(stream, Frame::SessionLimit(length.as_secs())));
+            },
+            ComponentControlMsg::Delete(id) => main_chan.report(send_frame(stream, Frame::Delete(id))),
+            ComponentControlMsg::React { id, emoji } => main_chan.report(send_frame(stream, Frame::Reaction { id, emoji })),
+            ComponentControlMsg::Password(_) |
+            ComponentControlMsg::Credentials { .. } |
+            ComponentControlMsg::ResumeToken(_) |
+            ComponentControlMsg::NextVisitor |
+            ComponentControlMsg::Whisper { .. } => continue,
+            ComponentControlMsg::CloseVisitor => return Turn::Closed,
+            ComponentControlMsg::Kick { address, notice } => {
+                if stream.get_ref().peer_ip() != Some(address) {
+                    continue;
+                }
+                main_chan.report(send_frame(stream, Frame::Announcement(notice)));
+                stream.get_ref().shutdown();
+                return Turn::Closed;
+            },
+            ComponentControlMsg::Quit => return Turn::Quit,
+        }
+    };
+    let payload = match ttl {
+        Some(ttl) => Payload::Ephemeral { text: chat.clone(), ttl: ttl.as_secs() },
+        None => Payload::Text(chat.clone()),
+    };
+    // Known by the id of its last part, if split.
+    let id = outgoing.send(stream, main_chan, SystemTime::now(), payload, true);
+    let _ = main_chan.send(MainControlMsg::MessageSending {
+        id,
+        text: chat.clone(),
+        ttl,
+    });
+    match await_acks(stream, main_chan, outgoing) {
+        // Not passed on to the webhook if ephemeral, for it not to be kept.
+        Ok(()) if ttl.is_some() => Turn::Sent(sent),
+        Ok(()) => {
+            sent.push(chat);
+            Turn::Sent(sent)
+        },
+        Err(undelivered) => Turn::Undelivered(undelivered),
+    }
+}
+
+// Warn as the end of the session nears, and close the connection once its time is up.
+fn watch_session<T: Transport>(session: SessionLimit,
+                               stream: &T,
+                               main_chan: EventSender,
+                               state: SharedState) {
+    let stream = match stream.try_clone() {
+        Ok(stream) => stream,
+        Err(_) => return,
+    };
+    let _ = thread::Builder::new().spawn(move || {
+        while !session.ended() {
+            thread::sleep(Duration::from_millis(200));
+            if let Some(left) = session.warning_due() {
+                let _ = main_chan.send(MainControlMsg::SessionTimeLeft(left));
+            }
+            if session.expired() {
+                set_state(&state, &main_chan, ConnectionState::Draining);
+                let _ = main_chan.send(MainControlMsg::SessionExpired);
+                // Ends the component's wait for the peer.
+                stream.shutdown();
+                return;
             }
         }
-        let _ = main_chan.send(MainControlMsg::ServerShutDown);
     });
-    chan
 }
 
-fn start_client(main_chan: Sender<MainControlMsg>) -> Sender<ComponentControlMsg> {
-    let (chan, port) = channel();
-    let _ = thread::Builder::new().spawn(move || {
-        let mut stream = TcpStream::connect("127.0.0.1:8000").expect("please start server first");
-        loop {
-            if !wait_for_message(&mut stream, &main_chan) {
-                 // Client disconnects when server is gone.
-                break;
-            }
-            if !wait_for_input(&mut stream, &main_chan, &port) {
-                // Client also disconnects in responses to a Quit message.
-                break;
-            }
-        }
-        let _ = main_chan.send(MainControlMsg::ClientDisconnected);
-    });
-    chan
+fn start_server(main_chan: EventSender,
+                address: String,
+                options: ServerOptions)
+                -> ComponentHandle<ComponentControlMsg> {
+    if let Some(ref web_address) = options.web_address {
+        // The chat goes on without it.
+        let started = web::start_web_endpoint(web_address, address.clone());
+        main_chan.report(started.map_err(|source| ChatError::Listen { address: web_address.clone(), source }));
+    }
+    if options.broadcast {
+        return start_broadcaster(main_chan, address, options);
+    }
+    match TcpListener::bind(&address) {
+        Ok(listener) => start_server_with(main_chan, listener, options),
+        Err(source) => {
+            let (chan, _) = channel::bounded(options.channel);
+            let state = SharedState::new();
+            stop_serving(&main_chan, &state, ChatError::Listen { address, source });
+            ComponentHandle::new(chan, state)
+        },
+    }
+}
+
+// The server can't go on, for the UI to say why and end.
+fn stop_serving(main_chan: &EventSender, state: &SharedState, error: ChatError) {
+    let _ = main_chan.send(MainControlMsg::Error(error));
+    set_state(state, main_chan, ConnectionState::Closed);
+    let _ = main_chan.send(MainControlMsg::ServerShutDown(DisconnectReason::Quit));
+}
+
+// Serve the clients of the listener, one at a time.
+fn start_server_with<L: Listener>(main_chan: EventSender,
+                                  listener: L,
+                                  options: ServerOptions)
+                                  -> ComponentHandle<ComponentControlMsg> {
+    let (chan, port) = channel::bounded(options.channel);
+    let state = SharedState::new();
+    let handle = ComponentHandle::new(chan.clone(), state.clone());
+    let recent = Arc::new(Mutex::new(RecentMessages::new(RECENT_MESSAGES)));
+    if let Some(ref address) = options.inject_address {
+        let inject_chan = chan.clone();
+        // The chat goes on without it.
+        let started = webhook::start_api_endpoint(address, recent.clone(), move |text| {
+            let _ = inject_chan.send(ComponentControlMsg::InjectMessage(text));
+        });
+        main_chan.report(started.map_err(|source| ChatError::Listen { address: address.clone(), source }));
+    }
+    let webhook = options.webhook_url.as_ref().map(|url| {
+        webhook::start_webhook(url).expect("the URL was checked with --webhook")
+    });
+    let post = move |direction, text: &str| {
+        let event = WebhookEvent {
+            direction,
+            sent_at: SystemTime::now(),
+            text: text.to_string(),
+        };
+        if let Some(ref webhook) = webhook {
+            let _ = webhook.send(event.clone());
+        }
+        recent.lock().unwrap().push(event);
+    };
+    let _ = thread::Builder::new().spawn(move || {
+        let admission = Admission::new(&options);
+        let offline_queue = options.offline_queue;
+        let mut plugins = options.plugins;
+        let mut keep_accepting = true;
+        let visitors = if options.support_queue {
+            match listener.try_clone() {
+                Ok(listener) => {
+                    Some(start_visitor_queue(listener, options.bans.clone(), admission.clone(), main_chan.clone()))
+                },
+                Err(error) => {
+                    stop_serving(&main_chan, &state, ChatError::Accept(error));
+                    return;
+                },
+            }
+        } else {
+            None
+        };
+        // In support-queue mode, whether the operator is ready for the next visitor.
+        let mut ready = true;
+        while keep_accepting {
+            let mut stream = match visitors {
+                Some(ref visitors) => {
+                    if !ready && !wait_for_next_visitor(&port) {
+                        break;
+                    }
+                    ready = true;
+                    let stream = visitors.pop();
+                    update_queue_positions(visitors, &main_chan);
+                    set_state(&state, &main_chan, ConnectionState::Handshaking);
+                    stream
+                },
+                None => {
+                    let stream = match listener.accept() {
+                        Ok(stream) => stream,
+                        Err(_) => {
+                            thread::sleep(ACCEPT_BACKOFF);
+                            continue;
+                        },
+                    };
+                    if refuse_banned(&options.bans, &stream, &main_chan) {
+                        continue;
+                    }
+                    set_state(&state, &main_chan, ConnectionState::Handshaking);
+                    let admitted = admission.accept(stream, &main_chan)
+                        .and_then(|mut stream| if admission.authenticate(&mut stream, &main_chan) { Some(stream) } else { None });
+                    match admitted {
+                        Some(stream) => stream,
+                        None => {
+                            set_state(&state, &main_chan, ConnectionState::Connecting);
+                            continue;
+                        },
+                    }
+                },
+            };
+            // Visitors were authenticated as they joined the queue.
+            if options.authenticator.is_some() {
+                set_state(&state, &main_chan, ConnectionState::Authenticated);
+            }
+            if visitors.is_some() {
+                let _ = main_chan.send(MainControlMsg::VisitorServed);
+            }
+            if let Some(address) = stream.get_ref().peer_ip() {
+                let _ = main_chan.send(MainControlMsg::ClientAddress(address));
+            }
+            let session = SessionLimit::new();
+            if let Some(length) = options.session_limit {
+                session.agree(length);
+                main_chan.report(send_frame(&mut stream, Frame::SessionLimit(length.as_secs())));
+            }
+            watch_session(session.clone(), stream.get_ref(), main_chan.clone(), state.clone());
+            let mut outgoing = Outgoing::new(options.ack, options.signing_key.clone());
+            // Deliver messages queued while no client was connected.
+            let queued = offline_queue.lock().unwrap().take_all();
+            if let Err(undelivered) = deliver(&mut stream, &main_chan, queued, false, &mut outgoing) {
+                set_state(&state, &main_chan, ConnectionState::Draining);
+                offline_queue.lock().unwrap().requeue(undelivered);
+                set_state(&state, &main_chan, ConnectionState::Connecting);
+                continue;
+            }
+            let replies = run_plugins(&mut plugins, &main_chan, |plugin| plugin.on_connect());
+            if deliver(&mut stream, &main_chan, replies, false, &mut outgoing).is_err() {
+                set_state(&state, &main_chan, ConnectionState::Connecting);
+                continue;
+            }
+            let motd = options.motd.lock().unwrap().clone().unwrap_or_else(|| DEFAULT_MOTD.to_string());
+            let handshake_id = outgoing.next_id;
+            main_chan.report(send_motd(&mut stream, &mut outgoing.next_id, &motd));
+            // Handle the first ACK from client...
+            let _ = wait_for_ack(&mut stream, handshake_id, options.ack.timeout);
+            set_state(&state, &main_chan, ConnectionState::Active);
+            let mut limiter = None;
+            let mut incoming = IncomingMessages::new(REORDER_WINDOW);
+            incoming.filter = options.filter.clone();
+            // On client disconnect, break out of the loop,
+            // and start accepting the next one.
+            while let Ok(received) = wait_for_message(&mut stream, &main_chan, &port, &state, &session,
+                                                      current_limiter(&mut limiter, &options.rate_limit),
+                                                      &mut incoming) {
+                for text in received.iter() {
+                    post(Direction::Incoming, text);
+                }
+                let replies = run_plugins(&mut plugins, &main_chan, |plugin| {
+                    received.iter().flat_map(|text| plugin.on_message(text)).collect()
+                });
+                for (_, text) in replies.iter() {
+                    post(Direction::Outgoing, text);
+                }
+                if deliver(&mut stream, &main_chan, replies, false, &mut outgoing).is_err() {
+                    break;
+                }
+                match wait_for_input(&mut stream, &main_chan, &port, &session, &mut outgoing) {
+                    Turn::Sent(sent) => {
+                        for text in sent.iter() {
+                            post(Direction::Outgoing, text);
+                        }
+                    },
+                    Turn::Undelivered(messages) => {
+                        set_state(&state, &main_chan, ConnectionState::Draining);
+                        for (sent_at, text) in messages {
+                            if let Some(id) = offline_queue.lock().unwrap().push(sent_at, text) {
+                                let _ = main_chan.send(MainControlMsg::MessageQueued(id));
+                            }
+                        }
+                        break;
+                    },
+                    Turn::Closed => break,
+                    Turn::Quit => {
+                        // Server shutdown.
+                        keep_accepting = false;
+                        break;
+                    },
+                }
+            }
+            session.end();
+            set_state(&state, &main_chan, ConnectionState::Draining);
+            for plugin in plugins.iter_mut() {
+                plugin.on_disconnect();
+            }
+            if keep_accepting {
+                set_state(&state, &main_chan, ConnectionState::Connecting);
+                if let Some(ref visitors) = visitors {
+                    ready = false;
+                    visitors.served();
+                    let _ = main_chan.send(MainControlMsg::VisitorLeft);
+                }
+            }
+        }
+        set_state(&state, &main_chan, ConnectionState::Closed);
+        let _ = main_chan.send(MainControlMsg::ServerShutDown(DisconnectReason::Quit));
+    });
+    handle
+}
+
+// The operator's messages are sent to all clients at once,
+// which never get a turn, and whose messages are refused.
+fn start_broadcaster(main_chan: EventSender,
+                     address: String,
+                     options: ServerOptions)
+                     -> ComponentHandle<ComponentControlMsg> {
+    let (chan, port) = channel::bounded(options.channel);
+    let state = SharedState::new();
+    let handle = ComponentHandle::new(chan, state.clone());
+    let _ = thread::Builder::new().spawn(move || {
+        let listener = match TcpListener::bind(&address) {
+            Ok(listener) => listener,
+            Err(source) => {
+                stop_serving(&main_chan, &state, ChatError::Listen { address, source });
+                return;
+            },
+        };
+        let listeners = Arc::new(Listeners::default());
+        accept_listeners(listener, listeners.clone(), options, main_chan.clone());
+        set_state(&state, &main_chan, ConnectionState::Active);
+        let _ = main_chan.send(MainControlMsg::BroadcastReady);
+        for control_msg in port.iter() {
+            match control_msg {
+                ComponentControlMsg::OutgoingMessage(text) => {
+                    let reached = listeners.send(SystemTime::now(), &Frame::Announcement(text.clone()));
+                    let _ = main_chan.send(MainControlMsg::AnnouncementSent {
+                        text,
+                        listeners: reached,
+                    });
+                },
+                ComponentControlMsg::Kick { address, notice } => {
+                    let kicked = listeners.remove_where(|stream| {
+                        let from_address = stream.get_ref().peer_addr().ok().map(|peer| peer.ip()) == Some(address);
+                        if from_address {
+                            let _ = stream.get_ref().shutdown(Shutdown::Both);
+                        }
+                        from_address
+                    });
+                    if kicked > 0 {
+                        listeners.send(SystemTime::now(), &Frame::Announcement(notice));
+                        let _ = main_chan.send(MainControlMsg::ListenersChanged(listeners.len()));
+                    }
+                },
+                ComponentControlMsg::Whisper { nick, text } => {
+                    if listeners.send_to(&nick, SystemTime::now(), &Frame::Whisper(text)) == 0 {
+                        let _ = main_chan.send(MainControlMsg::WhisperUndelivered(nick));
+                    }
+                },
+                ComponentControlMsg::Quit => break,
+                _ => continue,
+            }
+        }
+        set_state(&state, &main_chan, ConnectionState::Closed);
+        let _ = main_chan.send(MainControlMsg::ServerShutDown(DisconnectReason::Quit));
+    });
+    handle
+}
+
+// How the server takes a client in, before chatting with it,
+// shared by the threads doing so, one per client.
+#[derive(Clone)]
+struct Admission {
+    socket: SocketOptions,
+    max_clients: Option<usize>,
+    recorder: Option<Recorder>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    resume_tokens: Arc<Mutex<TokenStore>>,
+}
+
+impl Admission {
+    fn new(options: &ServerOptions) -> Admission {
+        Admission {
+            socket: options.socket.clone(),
+            max_clients: options.max_clients,
+            recorder: options.recorder.clone(),
+            authenticator: options.authenticator.clone(),
+            resume_tokens: options.resume_tokens.clone(),
+        }
+    }
+
+    // The client's wire format, `None` if it didn't choose one in time.
+    fn accept<T: Transport>(&self, stream: T, main_chan: &EventSender) -> Option<FrameStream<T>> {
+        configure_socket(&stream, &self.socket, main_chan);
+        let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+        FrameStream::accept(stream).ok()
+    }
+
+    // Returns whether the client answered the challenge in time, if there is one,
+    // the connection then being read from without a timeout.
+    fn authenticate<T: Transport>(&self, stream: &mut FrameStream<T>, main_chan: &EventSender) -> bool {
+        if let Some(ref recorder) = self.recorder {
+            stream.record(recorder);
+        }
+        if let Some(ref authenticator) = self.authenticator {
+            if !challenge_client(stream, authenticator.as_ref(), &self.resume_tokens, main_chan) {
+                let _ = main_chan.send(MainControlMsg::AuthenticationFailed);
+                return false;
+            }
+        }
+        let _ = stream.get_ref().set_read_timeout(None);
+        true
+    }
+}
+
+// Authenticate clients in the background, adding them to those broadcast to,
+// and refuse their messages until they go away.
+fn accept_listeners(listener: TcpListener,
+                    listeners: Arc<Listeners<TcpStream>>,
+                    options: ServerOptions,
+                    main_chan: EventSender) {
+    let _ = thread::Builder::new().spawn(move || {
+        let pool = Arc::new(WorkerPool::new(options.workers.unwrap_or(DEFAULT_WORKERS)));
+        let admission = Admission::new(&options);
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => {
+                    thread::sleep(ACCEPT_BACKOFF);
+                    continue;
+                },
+            };
+            if refuse_banned(&options.bans, &stream, &main_chan) {
+                continue;
+            }
+            // Taken in on a thread of its own, for a client which sends nothing not to keep the next ones out.
+            let (admission, listeners, pool, main_chan) = (admission.clone(), listeners.clone(), pool.clone(), main_chan.clone());
+            let _ = thread::Builder::new().spawn(move || {
+                let mut stream = match admission.accept(stream, &main_chan) {
+                    Some(stream) => stream,
+                    None => return,
+                };
+                if refuse_when_full(&mut stream, listeners.len(), admission.max_clients, &main_chan)
+                    || !admission.authenticate(&mut stream, &main_chan) {
+                    return;
+                }
+                add_listener(stream, listeners, &pool, main_chan);
+            });
+        }
+    });
+}
+
+// Read from the listener on the pool, once it was taken in.
+fn add_listener(stream: FrameStream<TcpStream>,
+                listeners: Arc<Listeners<TcpStream>>,
+                pool: &WorkerPool,
+                main_chan: EventSender) {
+    let mut reader = match stream.try_clone() {
+        Ok(reader) => reader,
+        Err(_) => return,
+    };
+    let (id, writer) = listeners.add(stream);
+    let _ = main_chan.send(MainControlMsg::ListenersChanged(listeners.len()));
+    let reader_chan = main_chan.clone();
+    let waiting = pool.execute(move || {
+        while let Ok((_, frame)) = reader.read_frame() {
+            match frame {
+                Frame::Message { .. } | Frame::MessagePart { .. } | Frame::Binary { .. } => {
+                    let refusal = Frame::Nack("the server only broadcasts".to_string());
+                    let _ = writer.lock().unwrap().write_frame(SystemTime::now(), &refusal);
+                },
+                // Joining once it gives its nickname.
+                Frame::Nick(nick) => {
+                    let presence = match listeners.rename(id, nick.clone()) {
+                        Some(from) => Presence::Renamed { from, to: nick },
+                        None => Presence::Joined(nick),
+                    };
+                    listeners.send_others(id, SystemTime::now(), &Frame::Presence(presence.clone()));
+                    let _ = reader_chan.send(MainControlMsg::Presence(presence));
+                },
+                _ => {},
+            }
+        }
+        let (removed, nick) = listeners.remove(id);
+        if removed {
+            let _ = reader_chan.send(MainControlMsg::ListenersChanged(listeners.len()));
+        }
+        if let Some(nick) = nick {
+            let presence = Presence::Left(nick);
+            listeners.send(SystemTime::now(), &Frame::Presence(presence.clone()));
+            let _ = reader_chan.send(MainControlMsg::Presence(presence));
+        }
+    });
+    if waiting > 0 {
+        let _ = main_chan.send(MainControlMsg::WorkersSaturated { workers: pool.size(), waiting });
+    }
+}
+
+// Accept clients in the background, and keep them waiting for the operator.
+fn start_visitor_queue<L: Listener>(listener: L,
+                                    bans: Arc<Mutex<BanList>>,
+                                    admission: Admission,
+                                    main_chan: EventSender)
+                                    -> Arc<VisitorQueue<FrameStream<L::Stream>>> {
+    let visitors = Arc::new(VisitorQueue::default());
+    let queue = visitors.clone();
+    let _ = thread::Builder::new().spawn(move || {
+        loop {
+            let stream = match listener.accept() {
+                Ok(stream) => stream,
+                Err(_) => {
+                    thread::sleep(ACCEPT_BACKOFF);
+                    continue;
+                },
+            };
+            if refuse_banned(&bans, &stream, &main_chan) {
+                continue;
+            }
+            // Authenticated on a thread of its own, for a visitor which sends nothing not to keep the next ones out,
+            // nor the operator waiting once served.
+            let (admission, queue, main_chan) = (admission.clone(), queue.clone(), main_chan.clone());
+            let _ = thread::Builder::new().spawn(move || {
+                let mut stream = match admission.accept(stream, &main_chan) {
+                    Some(stream) => stream,
+                    None => return,
+                };
+                if refuse_when_full(&mut stream, queue.clients(), admission.max_clients, &main_chan)
+                    || !admission.authenticate(&mut stream, &main_chan) {
+                    return;
+                }
+                queue.push(stream);
+                update_queue_positions(&queue, &main_chan);
+            });
+        }
+    });
+    visitors
+}
+
+// Tell the waiting clients their position, dropping those which went away.
+fn update_queue_positions<T: Transport>(visitors: &VisitorQueue<FrameStream<T>>, main_chan: &EventSender) {
+    visitors.retain_waiting(|position, stream| {
+        stream.write_frame(SystemTime::now(), &Frame::QueuePosition(position as u32)).is_ok()
+    });
+    let _ = main_chan.send(MainControlMsg::QueueChanged(visitors.len()));
+}
+
+// Returns whether the client connected from a banned address, in which case it is dropped.
+fn refuse_banned<T: Transport>(bans: &Mutex<BanList>, stream: &T, main_chan: &EventSender) -> bool {
+    let address = match stream.peer_ip() {
+        Some(address) => address,
+        None => return false,
+    };
+    if !bans.lock().unwrap().is_banned(address) {
+        return false;
+    }
+    let _ = main_chan.send(MainControlMsg::BannedClientRefused(address));
+    true
+}
+
+// Returns whether the server already has as many clients as it takes,
+// in which case the client is told so, and dropped.
+fn refuse_when_full<T: Transport>(stream: &mut FrameStream<T>,
+                                  clients: usize,
+                                  max_clients: Option<usize>,
+                                  main_chan: &EventSender)
+                                  -> bool {
+    let max = match max_clients {
+        Some(max) if clients >= max => max,
+        _ => return false,
+    };
+    let _ = stream.write_frame(SystemTime::now(), &Frame::ServerFull(max as u32));
+    let _ = main_chan.send(MainControlMsg::ServerFullRefused(stream.get_ref().peer_ip()));
+    true
+}
+
+// Send the UI the event, such as to ask for a summary of the statistics, every `interval`, until it quits.
+fn start_timer(interval: Duration, main_chan: EventSender, due: fn() -> MainControlMsg) {
+    let _ = thread::Builder::new().spawn(move || {
+        loop {
+            thread::sleep(interval);
+            if main_chan.send(due()).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+// Returns whether the operator is ready for the next client, or quit.
+fn wait_for_next_visitor(port: &Receiver<ComponentControlMsg>) -> bool {
+    loop {
+        match port.recv() {
+            Ok(ComponentControlMsg::NextVisitor) => return true,
+            Ok(ComponentControlMsg::Quit) | Err(_) => return false,
+            Ok(_) => continue,
+        }
+    }
+}
+
+// Drop a message queued for the next client, returning whether it was still queued.
+fn cancel_queued(queue: &Mutex<OfflineQueue>, main_chan: &EventSender, id: MessageId) -> bool {
+    if !queue.lock().unwrap().cancel(id) {
+        return false;
+    }
+    let _ = main_chan.send(MainControlMsg::MessageCancelled(id));
+    true
+}
+
+fn start_client(main_chan: EventSender,
+                address: String,
+                options: ClientOptions)
+                -> ComponentHandle<ComponentControlMsg> {
+    let server = address.clone();
+    let socks = options.tor_socks.clone().unwrap_or_else(|| tor::DEFAULT_SOCKS.to_string());
+    let timeout = options.connect_timeout;
+    start_client_with(main_chan, address, move || {
+        if tor::is_onion(&server) {
+            tor::connect(&socks, &server)
+        } else {
+            connect_tcp(&server, timeout)
+        }
+    }, options)
+}
+
+// Like `TcpStream::connect`, giving up on each of the addresses of the server after the timeout, if any.
+fn connect_tcp(address: &str, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return TcpStream::connect(address),
+    };
+    let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, format!("{} resolves to no address", address));
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = error,
+        }
+    }
+    Err(last_error)
+}
+
+// Tune the connection as the config says, the values applied being logged.
+fn configure_socket<T: Transport>(stream: &T, options: &SocketOptions, main_chan: &EventSender) {
+    match stream.configure(options) {
+        Ok(Some(applied)) => {
+            let _ = main_chan.send(MainControlMsg::SocketConfigured(applied));
+        },
+        Ok(None) => {},
+        Err(source) => {
+            let _ = main_chan.send(MainControlMsg::Error(ChatError::Socket(source)));
+        },
+    }
+}
+
+// Try `connect` again while it fails, up to `retries` times,
+// with the UI counting down to each new attempt, a second at a time.
+fn connect_with_retries<T, F>(connect: &F, retries: u32, delay: Duration, main_chan: &EventSender) -> io::Result<T>
+    where F: Fn() -> io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        let error = match connect() {
+            Ok(stream) => return Ok(stream),
+            Err(error) if attempt < retries => error,
+            Err(error) => return Err(error),
+        };
+        attempt += 1;
+        let _ = main_chan.send(MainControlMsg::ConnectAttemptFailed { error: error.to_string(), attempt, retries });
+        let mut left = delay;
+        while left > Duration::ZERO {
+            let _ = main_chan.send(MainControlMsg::ConnectRetryIn(left.as_secs_f64().ceil() as u64));
+            let pause = left.min(Duration::from_secs(1));
+            thread::sleep(pause);
+            left -= pause;
+        }
+    }
+}
+
+// Connect to the server at `address` with `connect`, from the component's thread.
+fn start_client_with<T, F>(main_chan: EventSender,
+                           address: String,
+                           connect: F,
+                           options: ClientOptions)
+                           -> ComponentHandle<ComponentControlMsg>
+    where T: Transport, F: Fn() -> io::Result<T> + Send + 'static {
+    let (chan, port) = channel::bounded(options.channel);
+    let state = SharedState::new();
+    let handle = ComponentHandle::new(chan, state.clone());
+    let _ = thread::Builder::new().spawn(move || {
+        let connection = connect_with_retries(&connect, options.connect_retries, options.retry_delay, &main_chan).and_then(|stream| {
+            configure_socket(&stream, &options.socket, &main_chan);
+            FrameStream::connect(stream, options.wire_format)
+        });
+        let mut stream = match connection {
+            Ok(stream) => stream,
+            Err(error) => {
+                let reason = DisconnectReason::NetworkError(format!("no server at {}: {}", address, error));
+                set_state(&state, &main_chan, ConnectionState::Closed);
+                let _ = main_chan.send(MainControlMsg::ClientDisconnected(reason));
+                return;
+            },
+        };
+        if let Some(ref recorder) = options.recorder {
+            stream.record(recorder);
+        }
+        set_state(&state, &main_chan, ConnectionState::Handshaking);
+        let session = SessionLimit::new();
+        watch_session(session.clone(), stream.get_ref(), main_chan.clone(), state.clone());
+        let mut outgoing = Outgoing::new(options.ack, options.signing_key.clone());
+        let mut incoming = IncomingMessages::new(REORDER_WINDOW);
+        let reason = loop {
+            if let Err(reason) = wait_for_message(&mut stream, &main_chan, &port, &state, &session, &mut None, &mut incoming) {
+                break reason;
+            }
+            match wait_for_input(&mut stream, &main_chan, &port, &session, &mut outgoing) {
+                Turn::Sent(_) => {},
+                Turn::Undelivered(_) => {
+                    break DisconnectReason::NetworkError("the server went away while sending".to_string());
+                },
+                Turn::Closed | Turn::Quit => break DisconnectReason::Quit,
+            }
+        };
+        let reason = if session.expired() { DisconnectReason::SessionExpired } else { reason };
+        session.end();
+        set_state(&state, &main_chan, ConnectionState::Closed);
+        let _ = main_chan.send(MainControlMsg::ClientDisconnected(reason));
+    });
+    handle
+}
+
+// Connect to the peer at the address, or if it isn't there yet, wait for it to connect there.
+// Peers started at the same time may each find the other not listening yet, and try again.
+fn dial_or_listen(address: &str) -> io::Result<TcpStream> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match TcpStream::connect(address) {
+            Err(ref error) if error.kind() == io::ErrorKind::ConnectionRefused => {},
+            connected => return connected,
+        }
+        match TcpListener::bind(address) {
+            Ok(listener) => return listener.accept().map(|(stream, _)| stream),
+            Err(ref error) if error.kind() == io::ErrorKind::AddrInUse && attempts < DIAL_ATTEMPTS => {
+                thread::sleep(Duration::from_millis(100));
+            },
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// Returns whether we speak first, tossing again on a tie.
+fn toss<T: Transport>(stream: &mut FrameStream<T>) -> Result<bool, DisconnectReason> {
+    loop {
+        let ours = rand::thread_rng().next_u64();
+        stream.write_frame(SystemTime::now(), &Frame::Toss(ours)).map_err(DisconnectReason::from_read_error)?;
+        let theirs = match stream.read_frame().map_err(DisconnectReason::from_read_error)? {
+            (_, Frame::Toss(theirs)) => theirs,
+            (_, frame) => return Err(DisconnectReason::ProtocolError(format!("unexpected frame {:?}", frame))),
+        };
+        if ours != theirs {
+            return Ok(ours > theirs);
+        }
+    }
+}
+
+fn start_peer(main_chan: EventSender,
+              address: String,
+              options: ClientOptions)
+              -> ComponentHandle<ComponentControlMsg> {
+    let peer = address.clone();
+    start_peer_with(main_chan, address, move || dial_or_listen(&peer), options)
+}
+
+// Chat with a single peer, without a server: whichever side dialed, both go through the same
+// steps, the toss deciding who speaks first, and then take turns as a client and server do.
+fn start_peer_with<T, F>(main_chan: EventSender,
+                         address: String,
+                         connect: F,
+                         options: ClientOptions)
+                         -> ComponentHandle<ComponentControlMsg>
+    where T: Transport, F: FnOnce() -> io::Result<T> + Send + 'static {
+    let (chan, port) = channel::bounded(options.channel);
+    let state = SharedState::new();
+    let handle = ComponentHandle::new(chan, state.clone());
+    let _ = thread::Builder::new().spawn(move || {
+        let connection = connect().and_then(|stream| {
+            configure_socket(&stream, &options.socket, &main_chan);
+            FrameStream::pair(stream, options.wire_format)
+        });
+        let mut stream = match connection {
+            Ok(stream) => stream,
+            Err(error) => {
+                let reason = DisconnectReason::NetworkError(format!("no peer at {}: {}", address, error));
+                set_state(&state, &main_chan, ConnectionState::Closed);
+                let _ = main_chan.send(MainControlMsg::ClientDisconnected(reason));
+                return;
+            },
+        };
+        if let Some(ref recorder) = options.recorder {
+            stream.record(recorder);
+        }
+        set_state(&state, &main_chan, ConnectionState::Handshaking);
+        let session = SessionLimit::new();
+        watch_session(session.clone(), stream.get_ref(), main_chan.clone(), state.clone());
+        let mut outgoing = Outgoing::new(options.ack, options.signing_key.clone());
+        let mut incoming = IncomingMessages::new(REORDER_WINDOW);
+        let reason = match toss(&mut stream) {
+            Ok(first) => {
+                set_state(&state, &main_chan, ConnectionState::Active);
+                let _ = main_chan.send(MainControlMsg::PeerConnected { first });
+                let mut our_turn = first;
+                loop {
+                    if !our_turn {
+                        if let Err(reason) = wait_for_message(&mut stream, &main_chan, &port, &state, &session, &mut None, &mut incoming) {
+                            break reason;
+                        }
+                    }
+                    our_turn = false;
+                    match wait_for_input(&mut stream, &main_chan, &port, &session, &mut outgoing) {
+                        Turn::Sent(_) => {},
+                        Turn::Undelivered(_) => {
+                            break DisconnectReason::NetworkError("the peer went away while sending".to_string());
+                        },
+                        Turn::Closed | Turn::Quit => break DisconnectReason::Quit,
+                    }
+                }
+            },
+            Err(reason) => reason,
+        };
+        let reason = if session.expired() { DisconnectReason::SessionExpired } else { reason };
+        session.end();
+        set_state(&state, &main_chan, ConnectionState::Closed);
+        let _ = main_chan.send(MainControlMsg::ClientDisconnected(reason));
+    });
+    handle
+}
+
+// A connection of the UI, with the state of the chat over it.
+struct Conversation {
+    address: String,
+    component: ComponentHandle<ComponentControlMsg>,
+    peer_name: String,
+    // The fingerprint of the peer's identity key, once received.
+    peer_identity: Option<String>,
+    // The peer gave its nickname, rather than being known by our placeholder.
+    peer_named: bool,
+    // On the server, where the client being served connected from.
+    peer_address: Option<IpAddr>,
+    history: History,
+    // On the client, tried once, before asking for the password.
+    resume_token: Option<String>,
+    // The peer's message is waiting for our reply.
+    our_turn: bool,
+    closed: bool,
+    // The names of the files being sent, by their number, for `/cancel`.
+    transfers: BTreeMap<TransferId, String>,
+    // Measured by pings, and how many of those to come were asked for with `/ping`, to be shown.
+    link: LinkQuality,
+    pings_asked: usize,
+    // The peer's messages shown but not seen yet, such as while the chat is in the tray,
+    // for the peer to be told once they are.
+    unseen: Vec<MessageId>,
+}
+
+impl Conversation {
+    fn new(address: &str, component: ComponentHandle<ComponentControlMsg>, peer_name: &str) -> Conversation {
+        Conversation {
+            address: address.to_string(),
+            component,
+            peer_name: peer_name.to_string(),
+            peer_identity: None,
+            peer_named: false,
+            peer_address: None,
+            history: History::default(),
+            resume_token: None,
+            our_turn: false,
+            closed: false,
+            transfers: BTreeMap::new(),
+            link: LinkQuality::default(),
+            pings_asked: 0,
+            unseen: vec![],
+        }
+    }
+}
+
+// The settings of the server which the config file can change while it runs.
+struct ServerSettings {
+    rate_limit: Arc<Mutex<Option<RateLimit>>>,
+    motd: Arc<Mutex<Option<String>>>,
+    // That of `--throttle`, for a rate limit set in the config file.
+    throttle_policy: ThrottlePolicy,
+}
+
+// The state of the UI, which commands act upon.
+struct Ui {
+    nick: String,
+    feedback: FeedbackSettings,
+    // The server has one, a client one per server it is connected to.
+    conversations: Vec<Conversation>,
+    // The conversation events are handled for, and messages are sent to.
+    focused: ConnectionId,
+    // On the server, the tokens issued to clients.
+    resume_tokens: Option<Arc<Mutex<TokenStore>>>,
+    // On the server, the messages waiting for the next client.
+    offline_queue: Option<Arc<Mutex<OfflineQueue>>>,
+    // On the server, the addresses clients are refused from.
+    bans: Option<Arc<Mutex<BanList>>>,
+    server_settings: Option<ServerSettings>,
+    // Our own events, for those raised by commands.
+    events: EventSender,
+    // Where messages are kept across sessions, unless disabled.
+    store: Option<MessageStore>,
+    ignored: Vec<String>,
+    // Do not disturb, and the like, see `/dnd`.
+    notifications: NotificationSettings,
+    // What is being written to each peer, see `/draft`.
+    drafts: Drafts,
+    // The messages to send later, see `/schedule`.
+    schedule: Schedule,
+    // Set with `/away`, until `/back`.
+    away: Option<Away>,
+    config_path: Option<PathBuf>,
+    // The fingerprint of our identity key.
+    identity: Option<String>,
+    // On the server in support-queue mode, the clients waiting and served so far,
+    // and the transcript of the conversation with the one being served.
+    support_queue: bool,
+    // On the server in broadcast mode, for `/msg`.
+    broadcast: bool,
+    visitors_waiting: usize,
+    visitors_served: usize,
+    transcript: Option<Transcript>,
+    stats: SessionStats,
+    output: Output,
+    // Where the subscribers to the messages are, with `--control`.
+    control: Option<ControlSocket>,
+    // For commands to ask the user first, such as before opening a link.
+    input_mode: InputMode,
+    // Opened on the first `/copy`, and kept for what was copied to stay there, on X11 at least.
+    clipboard: Option<Clipboard>,
+}
+
+impl Ui {
+    fn conversation(&self) -> &Conversation {
+        &self.conversations[self.focused]
+    }
+
+    fn conversation_mut(&mut self) -> &mut Conversation {
+        &mut self.conversations[self.focused]
+    }
+
+    fn quit(&self) {
+        for conversation in self.conversations.iter().filter(|conversation| !conversation.closed) {
+            let _ = conversation.component.send(ComponentControlMsg::Quit);
+        }
+    }
+
+    // Where a peer waits for our reply, the focused conversation first.
+    fn pending_turn(&self) -> Option<ConnectionId> {
+        let pending = |conversation: &Conversation| conversation.our_turn && !conversation.closed;
+        if pending(self.conversation()) {
+            return Some(self.focused);
+        }
+        self.conversations.iter().position(pending)
+    }
+
+    // Kept until delivered, or as typed so far in the window, for it not to be lost.
+    fn save_draft(&mut self, connection: ConnectionId, draft: &str) {
+        if let Err(error) = self.drafts.set(&self.conversations[connection].peer_name, draft) {
+            self.output.error(tr!("draft-not-saved", error = error));
+        }
+    }
+
+    fn send_message(&mut self, text: String) {
+        self.save_draft(self.focused, &text);
+        self.stats.record_sent(&text);
+        self.conversation_mut().our_turn = false;
+        let _ = self.conversation().component.send(ComponentControlMsg::OutgoingMessage(text));
+    }
+
+    // Send the scheduled messages which are due, each once its peer waits for our reply,
+    // letting the user and the subscribers to the control socket know.
+    fn send_scheduled(&mut self) {
+        let now = Local::now();
+        loop {
+            let conversations = &self.conversations;
+            let ready = |connection: ConnectionId| conversations[connection].our_turn && !conversations[connection].closed;
+            let scheduled = match self.schedule.take_due(now, ready) {
+                Some(scheduled) => scheduled,
+                None => return,
+            };
+            self.focused = scheduled.connection;
+            self.send_message(scheduled.text.clone());
+            say!(self.output, "scheduled-sent", id = scheduled.id, peer = self.conversation().peer_name.as_str());
+            if let Some(ref control) = self.control {
+                control.notify("scheduled_sent", json!({
+                    "conversation": scheduled.connection + 1,
+                    "id": scheduled.id,
+                    "text": scheduled.text,
+                }));
+            }
+        }
+    }
+
+    // Send a message from the control socket, in the conversation numbered from 1 if given,
+    // returning the number of the conversation it was sent in.
+    fn send_to(&mut self, conversation: Option<usize>, text: String) -> Result<usize, String> {
+        let connection = match conversation {
+            Some(number) if number == 0 || number > self.conversations.len() => {
+                return Err(tr!("no-conversation", number = number));
+            },
+            Some(number) => number - 1,
+            None => self.pending_turn().ok_or_else(|| tr!("waiting-for-reply", peer = self.conversation().peer_name.as_str()))?,
+        };
+        let conversation = &self.conversations[connection];
+        if conversation.closed {
+            return Err(tr!("connection-closed", address = conversation.address.as_str()));
+        }
+        if !conversation.our_turn {
+            return Err(tr!("waiting-for-reply", peer = conversation.peer_name.as_str()));
+        }
+        self.focused = connection;
+        self.send_message(text);
+        Ok(connection + 1)
+    }
+
+    fn peers(&self) -> serde_json::Value {
+        let peers: Vec<serde_json::Value> = self.conversations.iter().enumerate().map(|(connection, conversation)| json!({
+            "conversation": connection + 1,
+            "name": conversation.peer_name,
+            "address": conversation.address,
+            "state": conversation.component.state().to_string(),
+            "our_turn": conversation.our_turn,
+            "closed": conversation.closed,
+        })).collect();
+        serde_json::Value::Array(peers)
+    }
+
+    // To the subscribers of the control socket.
+    fn publish(&self, entry: &HistoryEntry) {
+        if let Some(ref control) = self.control {
+            let sent_at: DateTime<Local> = entry.sent_at.into();
+            control.notify("message", json!({
+                "conversation": self.focused + 1,
+                "id": entry.id,
+                "own": entry.own,
+                "sender": entry.sender,
+                "sent_at": sent_at.to_rfc3339(),
+                "text": entry.text,
+            }));
+        }
+    }
+
+    // Flagged if not signed by a peer which has an identity key, and removed after its time to live if any.
+    fn show_incoming(&mut self, message: ChatMessage, verified: bool, ttl: Option<Duration>) {
+        let id = message.id;
+        let sender = message.sender_or(&self.conversation().peer_name).to_string();
+        let mut entry = HistoryEntry::new(id, false, &sender, message.sent_at, message.body);
+        entry.unverified = !verified && self.conversation().peer_identity.is_some();
+        entry.never_persist = ttl.is_some();
+        if let Some(ref text) = entry.text {
+            if mentions::mentions(text, &self.nick) {
+                entry.mentions = Some(self.nick.clone());
+            }
+            entry.keywords = mentions::watched(text, &self.notifications.keywords).into_iter().map(str::to_string).collect();
+        }
+        self.output.entry(&entry);
+        self.store_message(&mut entry);
+        self.record_transcript(&entry);
+        self.publish(&entry);
+        self.conversation_mut().history.push(entry);
+        self.conversation_mut().unseen.push(id);
+        if let Some(ttl) = ttl {
+            self.expire_after(id, false, ttl);
+        }
+    }
+
+    // The user has seen the peer's messages shown so far, in each conversation,
+    // telling the peers unless `read_receipts_sent` is off.
+    fn messages_seen(&mut self) {
+        let send = self.feedback.read_receipts_sent;
+        for conversation in self.conversations.iter_mut() {
+            for id in conversation.unseen.drain(..) {
+                if send {
+                    let _ = conversation.component.send(ComponentControlMsg::MessageRead(id));
+                }
+            }
+        }
+    }
+
+    // Remove the ephemeral message from the focused conversation once its time to live is over.
+    fn expire_after(&self, id: MessageId, own: bool, ttl: Duration) {
+        let events = EventSender::new(self.focused, self.events.chan.clone());
+        let _ = thread::Builder::new().spawn(move || {
+            thread::sleep(ttl);
+            let _ = events.send(MainControlMsg::MessageExpired { id, own });
+        });
+    }
+
+    fn record_transcript(&mut self, entry: &HistoryEntry) {
+        if entry.never_persist {
+            return;
+        }
+        if let Some(ref mut transcript) = self.transcript {
+            if let Err(error) = transcript.record(entry) {
+                say!(self.output, "transcript-write-failed", error = error);
+            }
+        }
+    }
+
+    fn store_message(&self, entry: &mut HistoryEntry) {
+        let (store, text) = match (self.store.as_ref(), entry.text.as_ref()) {
+            (Some(store), Some(text)) if !entry.never_persist => (store, text),
+            _ => return,
+        };
+        let peer_name = &self.conversation().peer_name;
+        let (sender, peer, status) = if entry.own {
+            (&self.nick, peer_name, MessageStatus::Delivered)
+        } else {
+            (peer_name, &self.nick, MessageStatus::Received)
+        };
+        match store.record(entry.sent_at, sender, peer, status, text) {
+            Ok(row) => entry.stored_as = Some(row),
+            Err(error) => say!(self.output, "store-failed", error = error),
+        }
+    }
+
+    // Returns whether the message was found, and not already deleted.
+    fn delete_message(&mut self, id: MessageId, own: bool) -> bool {
+        let stored_as = match self.conversation_mut().history.delete(id, own) {
+            Some(entry) => entry.stored_as,
+            None => return false,
+        };
+        if let (Some(store), Some(row)) = (self.store.as_ref(), stored_as) {
+            if let Err(error) = store.mark_deleted(row) {
+                say!(self.output, "store-delete-failed", error = error);
+            }
+        }
+        true
+    }
+
+    // Returns whether our message was found, and not already read.
+    fn message_read(&mut self, id: MessageId) -> bool {
+        let stored_as = match self.conversation_mut().history.read(id) {
+            Some(entry) => entry.stored_as,
+            None => return false,
+        };
+        if let (Some(store), Some(row)) = (self.store.as_ref(), stored_as) {
+            if let Err(error) = store.mark_read(row) {
+                say!(self.output, "store-failed", error = error);
+            }
+        }
+        true
+    }
+
+    // Servers are kept with their address, to connect to them again with `--contact`.
+    fn remember_contact(&self, is_client: bool) {
+        let (store, conversation) = match self.store.as_ref() {
+            Some(store) if self.conversation().peer_named => (store, self.conversation()),
+            _ => return,
+        };
+        let address = if is_client { Some(conversation.address.as_str()) } else { None };
+        if let Err(error) = store.saw_contact(&conversation.peer_name, address,
+                                              conversation.peer_identity.as_deref(), SystemTime::now()) {
+            say!(self.output, "contact-save-failed", error = error);
+        }
+    }
+
+    // For the status bar, with the quality of the link once measured.
+    fn status_text(&self, state: ConnectionState) -> String {
+        let conversation = self.conversation();
+        let text = format!("{}: {}", conversation.peer_name, state);
+        if conversation.link.loss().is_none() {
+            return text;
+        }
+        format!("{}, {}", text, conversation.link)
+    }
+
+    fn peer_ignored(&self) -> bool {
+        self.ignored.iter().any(|nick| nick.eq_ignore_ascii_case(&self.conversation().peer_name))
+    }
+
+    // Keep the ignore list in the config file, the commands changing it only once it is.
+    fn save_ignored(&self, ignored: &[String]) -> Result<(), String> {
+        let path = self.config_path.as_ref().ok_or_else(|| tr!("no-config-file"))?;
+        config::save_ignored(path, ignored)
+    }
+
+    #[cfg(feature = "gui")]
+    fn save_zoom(&self, zoom: f32) -> Result<(), String> {
+        let path = self.config_path.as_ref().ok_or_else(|| tr!("no-config-file-zoom"))?;
+        config::save_zoom(path, zoom)
+    }
+
+    // Keep the notification settings in the config file, the commands changing them only once they are.
+    fn save_notifications(&self, settings: &NotificationSettings) -> Result<(), String> {
+        let path = self.config_path.as_ref().ok_or_else(|| tr!("no-config-file-notifications"))?;
+        config::save_notifications(path, settings)
+    }
+
+    // Apply the settings of the config file which changed, returning their names.
+    // Those no longer in the file keep their value, such as that of a flag.
+    fn reload_config(&mut self) -> Result<Vec<&'static str>, String> {
+        let path = self.config_path.as_ref().ok_or_else(|| tr!("no-config-file"))?;
+        let config = Config::load(path)?;
+        let mut changed = vec![];
+        if config.ignored != self.ignored {
+            self.ignored = config.ignored;
+            changed.push("ignored");
+        }
+        if config.notifications != self.notifications {
+            self.notifications = config.notifications;
+            changed.push("notifications");
+        }
+        let settings = match self.server_settings {
+            Some(ref settings) => settings,
+            None => return Ok(changed),
+        };
+        let mut motd = settings.motd.lock().unwrap();
+        if config.motd.is_some() && config.motd != *motd {
+            *motd = config.motd;
+            changed.push("motd");
+        }
+        let rate_limit = config.rate_limit.map(|messages_per_second| RateLimit {
+            messages_per_second,
+            policy: settings.throttle_policy,
+        });
+        let mut current = settings.rate_limit.lock().unwrap();
+        if rate_limit.is_some() && rate_limit != *current {
+            *current = rate_limit;
+            changed.push("rate_limit");
+        }
+        Ok(changed)
+    }
+}
+
+// Where the server saved as a contact under that nickname was last seen.
+fn contact_address(nick: &str) -> Result<String, String> {
+    MessageStore::open_default()?.contact(nick)?
+        .ok_or_else(|| tr!("no-such-contact", nick = nick))?
+        .address
+        .ok_or_else(|| tr!("contact-without-address", nick = nick))
+}
+
+// Print the stored messages matching the query.
+fn search(store: &MessageStore, query: &str) -> Result<String, String> {
+    let found = store.search(query, SEARCH_RESULTS)?;
+    if found.is_empty() {
+        return Ok(tr!("no-search-results", query = query));
+    }
+    let lines: Vec<String> = found.iter().map(ToString::to_string).collect();
+    Ok(lines.join("\n"))
+}
+
+// `export [--format json|html|txt] [--since YYYY-MM-DD] [--output <path>]`
+fn export_history<I: Iterator<Item=String>>(mut arguments: I) -> Result<(), String> {
+    let mut format = ExportFormat::Text;
+    let mut since = UNIX_EPOCH;
+    let mut output = None;
+    while let Some(argument) = arguments.next() {
+        let value = arguments.next().ok_or_else(|| tr!("option-needs-value", option = argument.as_str()))?;
+        match argument.as_ref() {
+            "--format" => format = ExportFormat::parse(&value)?,
+            "--since" => since = export::parse_since(&value)?,
+            "--output" => output = Some(value),
+            _ => return Err(tr!("unknown-option", option = argument.as_str())),
+        }
+    }
+    let output = output.unwrap_or_else(|| format!("simple_chat-export.{}", format.extension()));
+    let messages = MessageStore::open_default()?.messages_since(since)?;
+    fs::write(&output, export::export(&messages, format))
+        .map_err(|error| tr!("export-write-failed", path = output.as_str(), error = error.to_string()))?;
+    println!("{}", tr!("exported", count = messages.len(), path = output.as_str()));
+    Ok(())
+}
+
+// Forward the clients at the first address to the server at the second, under the network conditions given.
+fn run_proxy<I: Iterator<Item=String>>(mut arguments: I) -> Result<(), String> {
+    let usage = || tr!("usage", usage = "simple_chat proxy <listen address> <server address> [--latency <ms>] [--jitter <ms>] [--truncate <chance>] [--drop <chance>] [--seed <number>]");
+    let address = arguments.next().ok_or_else(usage)?;
+    let server = arguments.next().ok_or_else(usage)?;
+    let conditions = NetworkConditions::parse(arguments)?;
+    let listener = TcpListener::bind(&address)
+        .map_err(|error| tr!("listen-failed", address = address.as_str(), error = error.to_string()))?;
+    println!("{}", tr!("proxying", address = address.as_str(), server = server.as_str(), conditions = format!("{:?}", conditions)));
+    proxy::start_proxy(listener, move || TcpStream::connect(&server), conditions);
+    loop {
+        thread::park();
+    }
+}
+
+// Introduce the peers meeting in the same room, and relay those which can't reach each other.
+fn run_rendezvous<I: Iterator<Item=String>>(mut arguments: I) -> Result<(), String> {
+    let address = arguments.next().ok_or_else(|| tr!("usage", usage = "simple_chat rendezvous <listen address>"))?;
+    let address = rendezvous::start(&address, invite::default_path())
+        .map_err(|error| tr!("listen-failed", address = address.as_str(), error = error.to_string()))?;
+    println!("{}", tr!("rendezvous-started", address = address.to_string()));
+    loop {
+        thread::park();
+    }
+}
+
+// Issue an invitation to a room of the rendezvous server run by the same user, which is then only for
+// the peers giving one.
+fn run_invite<I: Iterator<Item=String>>(mut arguments: I) -> Result<(), String> {
+    let usage = || tr!("usage", usage = "simple_chat invite --room <name> [--expires <duration>]");
+    let mut room = None;
+    let mut lifetime = invite::DEFAULT_LIFETIME;
+    while let Some(argument) = arguments.next() {
+        let value = arguments.next().ok_or_else(|| tr!("option-needs-value", option = argument.as_str()))?;
+        match argument.as_ref() {
+            "--room" => room = Some(value),
+            "--expires" => lifetime = session::parse_duration(&value)?,
+            _ => return Err(tr!("unknown-option", option = argument.as_str())),
+        }
+    }
+    let room = room.ok_or_else(usage)?;
+    let token = Invitations::load(invite::default_path().as_deref()).issue(&room, lifetime)?;
+    let expires: DateTime<Local> = (SystemTime::now() + lifetime).into();
+    println!("{}", tr!("invitation-issued", room = room.as_str(), expires = expires.format("%Y-%m-%d %H:%M").to_string(), token = token.as_str()));
+    Ok(())
+}
+
+// Show a session recorded with `--record`, with its timing, or with `--connect`,
+// send the frames one of its connections sent to a live peer, showing what it answers.
+fn run_replay<I: Iterator<Item=String>>(mut arguments: I) -> Result<(), String> {
+    let path = arguments.next().ok_or_else(|| tr!("usage", usage = "simple_chat replay <log> [--connect <address>] [--wire <format>] [--connection <number>] [--speed <factor>]"))?;
+    let mut peer = None;
+    let mut wire_format = WireFormat::default();
+    let mut connection = None;
+    let mut speed = 1.0;
+    while let Some(argument) = arguments.next() {
+        let value = arguments.next().ok_or_else(|| tr!("option-needs-value", option = argument.as_str()))?;
+        match argument.as_ref() {
+            "--connect" => peer = Some(value),
+            "--wire" => wire_format = WireFormat::parse(&value)?,
+            "--connection" => connection = Some(value.parse().map_err(|_| tr!("invalid-connection", connection = value.as_str()))?),
+            "--speed" => {
+                speed = value.parse().ok().filter(|speed: &f64| *speed > 0.0)
+                    .ok_or_else(|| tr!("speed-needed", speed = value.as_str()))?;
+            },
+            _ => return Err(tr!("unknown-option", option = argument.as_str())),
+        }
+    }
+    let frames = record::load(&path)?;
+    // Those of a single connection are sent to the peer, by default the first one.
+    let connection = match peer {
+        Some(_) => connection.or_else(|| frames.first().map(|frame| frame.connection)),
+        None => connection,
+    };
+    let frames: Vec<RecordedFrame> = frames.into_iter()
+        .filter(|frame| connection.is_none_or(|connection| frame.connection == connection))
+        .collect();
+    let (chan, port) = mpsc::channel();
+    let mut stream = match peer {
+        Some(ref peer) => {
+            let stream = TcpStream::connect(peer)
+                .and_then(|stream| FrameStream::connect(stream, wire_format))
+                .map_err(|error| tr!("replay-no-peer", address = peer.as_str(), error = error.to_string()))?;
+            let mut reader = stream.try_clone().map_err(|error| error.to_string())?;
+            let _ = thread::Builder::new().spawn(move || {
+                while let Ok((_, frame)) = reader.read_frame() {
+                    if chan.send(frame).is_err() {
+                        break;
+                    }
+                }
+            });
+            Some(stream)
+        },
+        None => None,
+    };
+    // Shows what the peer sent until then.
+    let wait_until = |deadline: Instant| {
+        while let Ok(frame) = port.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            println!("<- {:?}", frame);
+        }
+    };
+    let mut previous = None;
+    for frame in frames.iter() {
+        let pause = previous.map_or(Duration::from_secs(0), |previous| Duration::from_millis(frame.at.saturating_sub(previous)).div_f64(speed));
+        previous = Some(frame.at);
+        wait_until(Instant::now() + pause);
+        match stream {
+            Some(ref mut stream) if frame.direction == FrameDirection::Sent => {
+                stream.write_frame(SystemTime::now(), &frame.frame).map_err(|error| error.to_string())?;
+                println!("-> {:?}", frame.frame);
+            },
+            Some(_) => {},
+            None => println!("{}", frame.describe()),
+        }
+    }
+    // For the answers to the last frames.
+    if stream.is_some() {
+        wait_until(Instant::now() + Duration::from_secs(2));
+    }
+    Ok(())
+}
+
+// Measure how fast messages go between a server and a client, over the loopback interface.
+fn run_bench<I: Iterator<Item=String>>(mut arguments: I) -> Result<(), String> {
+    let mut options = BenchOptions::default();
+    while let Some(argument) = arguments.next() {
+        let value = arguments.next().ok_or_else(|| format!("{} takes a value", argument))?;
+        let number = || value.parse().ok().filter(|&number: &usize| number > 0)
+            .ok_or_else(|| format!("{} takes a positive number, not '{}'", argument, value));
+        match argument.as_ref() {
+            "--messages" => options.messages = number()?,
+            "--size" => options.size = number()?,
+            "--window" => options.window = number()?,
+            "--wire" => options.wire_format = WireFormat::parse(&value)?,
+            _ => {
+                let usage = "simple_chat bench [--messages <number>] [--size <bytes>] [--window <number>] [--wire <format>]";
+                return Err(tr!("usage", usage = usage));
+            },
+        }
+    }
+    println!("{}", tr!("bench-running", messages = options.messages, size = options.size));
+    let report = bench::run(options).map_err(|error| error.to_string())?;
+    println!("{}", report);
+    Ok(())
+}
+
+fn search_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let store = ui.store.as_ref().ok_or_else(|| tr!("history-not-kept"))?;
+    if arguments.is_empty() {
+        return Err(tr!("usage", usage = "/search <query>"));
+    }
+    search(store, &arguments.join(" ")).map(CommandOutcome::Reply)
+}
+
+// `/contacts` lists them, and `/contacts note <nick> <text>` keeps a note about one.
+fn contacts_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let store = ui.store.as_ref().ok_or_else(|| tr!("history-not-kept"))?;
+    match arguments {
+        [] => {
+            let contacts = store.contacts()?;
+            if contacts.is_empty() {
+                return Ok(CommandOutcome::Reply(tr!("no-contacts")));
+            }
+            let lines: Vec<String> = contacts.iter().map(ToString::to_string).collect();
+            Ok(CommandOutcome::Reply(lines.join("\n")))
+        },
+        ["note", nick, notes @ ..] => {
+            if !store.set_contact_notes(nick, &notes.join(" "))? {
+                return Err(tr!("no-such-contact", nick = *nick));
+            }
+            Ok(CommandOutcome::Reply(tr!("contact-noted", nick = *nick)))
+        },
+        _ => Err(tr!("usage", usage = "/contacts [note <nick> <text>]")),
+    }
+}
+
+fn quit_command(_ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    Ok(CommandOutcome::Quit)
+}
+
+fn nick_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    match arguments {
+        [nick] => {
+            ui.nick = nick.to_string();
+            for conversation in ui.conversations.iter() {
+                let _ = conversation.component.send(ComponentControlMsg::Nick(ui.nick.clone()));
+            }
+            Ok(CommandOutcome::Reply(tr!("nick-changed", nick = ui.nick.as_str())))
+        },
+        _ => Err(tr!("usage", usage = "/nick <name>")),
+    }
+}
+
+fn who_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    Ok(CommandOutcome::Reply(tr!("who", nick = ui.nick.as_str(), peer = ui.conversation().peer_name.as_str())))
+}
+
+fn queue_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    if !ui.support_queue {
+        return Err(tr!("not-support-queue"));
+    }
+    Ok(CommandOutcome::Reply(tr!("visitors-waiting", count = ui.visitors_waiting)))
+}
+
+fn close_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    if !ui.support_queue {
+        return Err(tr!("not-support-queue"));
+    }
+    ui.conversation_mut().our_turn = false;
+    let _ = ui.conversation().component.send(ComponentControlMsg::CloseVisitor);
+    Ok(CommandOutcome::EndTurn(tr!("closing-visitor", peer = ui.conversation().peer_name.as_str())))
+}
+
+// Only valid between conversations, when it is handled by the idle prompt.
+fn next_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    if !ui.support_queue {
+        return Err(tr!("not-support-queue"));
+    }
+    Err(tr!("close-first", peer = ui.conversation().peer_name.as_str()))
+}
+
+// The address of the peer with the nickname, if it is a client being served.
+fn client_address(ui: &Ui, nick: &str) -> Result<IpAddr, String> {
+    let conversation = ui.conversation();
+    if !nick.eq_ignore_ascii_case(&conversation.peer_name) {
+        return Err(tr!("no-client-named", nick = nick));
+    }
+    conversation.peer_address.ok_or_else(|| tr!("address-unknown", nick = nick))
+}
+
+// Close the connections from the address, ending our turn if the peer is one of them.
+fn kick(ui: &mut Ui, address: IpAddr, notice: String) -> CommandOutcome {
+    let _ = ui.conversation().component.send(ComponentControlMsg::Kick {
+        address,
+        notice: notice.clone(),
+    });
+    if ui.conversation().peer_address != Some(address) {
+        return CommandOutcome::Reply(notice);
+    }
+    let conversation = ui.conversation_mut();
+    conversation.our_turn = false;
+    conversation.peer_address = None;
+    CommandOutcome::EndTurn(notice)
+}
+
+fn kick_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    if ui.bans.is_none() {
+        return Err(tr!("only-server-kicks"));
+    }
+    let nick = match arguments {
+        [nick] => nick,
+        _ => return Err(tr!("usage", usage = "/kick <nick>")),
+    };
+    let address = client_address(ui, nick)?;
+    Ok(kick(ui, address, tr!("kicked", nick = *nick)))
+}
+
+fn ban_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let bans = ui.bans.clone().ok_or_else(|| tr!("only-server-bans"))?;
+    let target = match arguments {
+        [target] => target,
+        _ => return Err(tr!("usage", usage = "/ban <nick|ip>")),
+    };
+    let address = match target.parse() {
+        Ok(address) => address,
+        Err(_) => client_address(ui, target)?,
+    };
+    if !bans.lock().unwrap().ban(address)? {
+        return Err(tr!("already-banned", address = address.to_string()));
+    }
+    Ok(kick(ui, address, tr!("banned", target = *target)))
+}
+
+// `/msg <nick> <text>`, in broadcast mode, to the client with the nickname only.
+fn msg_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    if !ui.broadcast {
+        return Err(tr!("only-broadcast-whispers"));
+    }
+    let (nick, words) = match arguments {
+        [nick, words @ ..] if !words.is_empty() => (nick, words),
+        _ => return Err(tr!("usage", usage = "/msg <nick> <text>")),
+    };
+    let text = words.join(" ");
+    let _ = ui.conversation().component.send(ComponentControlMsg::Whisper {
+        nick: nick.to_string(),
+        text: text.clone(),
+    });
+    Ok(CommandOutcome::Reply(tr!("whisper-to", nick = *nick, text = text)))
+}
+
+fn sendfile_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let path = match arguments {
+        [path] => path,
+        _ => return Err(tr!("usage", usage = "/sendfile <path>")),
+    };
+    let metadata = fs::metadata(path).map_err(|error| format!("{}: {}", path, error))?;
+    if !metadata.is_file() {
+        return Err(tr!("not-a-file", path = *path));
+    }
+    let _ = ui.conversation().component.send(ComponentControlMsg::OutgoingFile(PathBuf::from(path)));
+    Ok(CommandOutcome::Reply(tr!("preparing-file", path = *path, size = metadata.len())))
+}
+
+fn image_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let path = match arguments {
+        [path] => path,
+        _ => return Err(tr!("usage", usage = "/image <path>")),
+    };
+    let data = fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+    let (data, mime) = images::prepare(data, MAX_BINARY_SIZE).map_err(|error| format!("{}: {}", path, error))?;
+    let size = data.len();
+    let _ = ui.conversation().component.send(ComponentControlMsg::OutgoingBinary {
+        mime: Some(mime.to_string()),
+        data,
+    });
+    Ok(CommandOutcome::Reply(tr!("sending-image", path = *path, mime = mime, size = size)))
+}
+
+fn stats_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    Ok(CommandOutcome::Reply(ui.stats.to_string()))
+}
+
+fn status_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    Ok(CommandOutcome::Reply(tr!("connection-status", state = ui.conversation().component.state().to_string())))
+}
+
+fn ping_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let conversation = ui.conversation_mut();
+    if conversation.closed {
+        return Err(tr!("connection-closed", address = conversation.address.as_str()));
+    }
+    conversation.pings_asked += 1;
+    let _ = conversation.component.send(ComponentControlMsg::Ping);
+    let peer = conversation.peer_name.as_str();
+    Ok(CommandOutcome::Reply(if conversation.our_turn { tr!("pinging", peer = peer) } else { tr!("ping-on-our-turn", peer = peer) }))
+}
+
+fn ignore_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let nick = match arguments {
+        [nick] => nick.to_string(),
+        _ => return Err(tr!("usage", usage = "/ignore <nick>")),
+    };
+    if nick == ui.nick {
+        return Err(tr!("cant-ignore-yourself"));
+    }
+    if ui.ignored.iter().any(|ignored| ignored.eq_ignore_ascii_case(&nick)) {
+        return Err(tr!("already-ignored", nick = nick));
+    }
+    let mut ignored = ui.ignored.clone();
+    ignored.push(nick.clone());
+    ui.save_ignored(&ignored)?;
+    ui.ignored = ignored;
+    Ok(CommandOutcome::Reply(tr!("ignoring", nick = nick)))
+}
+
+fn unignore_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let nick = match arguments {
+        [nick] => nick,
+        _ => return Err(tr!("usage", usage = "/unignore <nick>")),
+    };
+    let mut ignored = ui.ignored.clone();
+    ignored.retain(|ignored| !ignored.eq_ignore_ascii_case(nick));
+    if ignored.len() == ui.ignored.len() {
+        return Err(tr!("not-ignored", nick = *nick));
+    }
+    ui.save_ignored(&ignored)?;
+    ui.ignored = ignored;
+    Ok(CommandOutcome::Reply(tr!("unignored", nick = *nick)))
+}
+
+fn ignores_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    if ui.ignored.is_empty() {
+        return Ok(CommandOutcome::Reply(tr!("nobody-ignored")));
+    }
+    Ok(CommandOutcome::Reply(tr!("ignored-list", nicks = ui.ignored.join(", "))))
+}
+
+// `/dnd` alone shows the settings, and each change is kept in the config file.
+fn dnd_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let mut settings = ui.notifications.clone();
+    match arguments {
+        [] => return Ok(CommandOutcome::Reply(tr!("notification-settings", settings = settings.to_string()))),
+        [dnd] => settings.dnd = notify::parse_toggle(dnd)?,
+        ["mute", nick] if settings.is_muted(nick) => return Err(tr!("already-muted", nick = *nick)),
+        ["mute", nick] => settings.muted.push(nick.to_string()),
+        ["unmute", nick] if !settings.is_muted(nick) => return Err(tr!("not-muted", nick = *nick)),
+        ["unmute", nick] => settings.muted.retain(|muted| !muted.eq_ignore_ascii_case(nick)),
+        ["quiet", "off"] => settings.quiet_hours = None,
+        ["quiet", hours] => settings.quiet_hours = Some(QuietHours::parse(hours)?),
+        ["mentions", mentions_only] => settings.mentions_only = notify::parse_toggle(mentions_only)?,
+        ["desktop", desktop] => settings.desktop = notify::parse_toggle(desktop)?,
+        _ => return Err(tr!("usage", usage = "/dnd [on|off|mute <nick>|unmute <nick>|quiet <HH:MM-HH:MM|off>|mentions <on|off>|desktop <on|off>]")),
+    }
+    ui.save_notifications(&settings)?;
+    ui.notifications = settings;
+    Ok(CommandOutcome::Reply(tr!("notification-settings", settings = ui.notifications.to_string())))
+}
+
+// `/draft` alone lists the drafts kept, of all peers.
+fn draft_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let peer = ui.conversation().peer_name.clone();
+    match arguments {
+        [] if ui.drafts.is_empty() => Ok(CommandOutcome::Reply(tr!("no-drafts"))),
+        [] => {
+            let lines: Vec<String> = ui.drafts.iter().map(|(peer, draft)| format!("{}: {}", peer, draft)).collect();
+            Ok(CommandOutcome::Reply(lines.join("\n")))
+        },
+        ["send"] => {
+            let draft = ui.drafts.get(&peer).ok_or_else(|| tr!("no-draft", peer = peer.as_str()))?.to_string();
+            let conversation = ui.conversation();
+            if conversation.closed || !conversation.our_turn {
+                return Err(tr!("waiting-for-reply", peer = peer.as_str()));
+            }
+            ui.send_message(draft);
+            Ok(CommandOutcome::EndTurn(tr!("draft-sent", peer = peer.as_str())))
+        },
+        ["clear"] => {
+            ui.drafts.get(&peer).ok_or_else(|| tr!("no-draft", peer = peer.as_str()))?;
+            ui.save_draft(ui.focused, "");
+            Ok(CommandOutcome::Reply(tr!("draft-cleared", peer = peer.as_str())))
+        },
+        _ => Err(tr!("usage", usage = "/draft [send|clear]")),
+    }
+}
+
+// The time is local, quoted or not, such as `/schedule "2024-06-01 09:00" Happy birthday!`.
+fn schedule_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let (at, words) = match arguments {
+        [date, time, words @ ..] if !words.is_empty() => {
+            (schedule::parse_time(&format!("{} {}", date, time).replace('"', ""))?, words)
+        },
+        _ => return Err(tr!("usage", usage = "/schedule \"YYYY-MM-DD HH:MM\" <text>")),
+    };
+    if at <= Local::now() {
+        return Err(tr!("scheduled-in-past", at = at.format("%Y-%m-%d %H:%M").to_string()));
+    }
+    let scheduled = ui.schedule.add(ui.focused, at, words.join(" "));
+    let id = scheduled.id;
+    // The events of the focused conversation, for it to be the one the message is sent in.
+    let events = EventSender {
+        connection: ui.focused,
+        ..ui.events.clone()
+    };
+    let delay = (at - Local::now()).to_std().unwrap_or_default();
+    let _ = thread::Builder::new().spawn(move || {
+        thread::sleep(delay);
+        let _ = events.send(MainControlMsg::ScheduledDue);
+    });
+    Ok(CommandOutcome::Reply(tr!("scheduled", id = id, peer = ui.conversation().peer_name.as_str(),
+                                 at = at.format("%Y-%m-%d %H:%M").to_string())))
+}
+
+fn scheduled_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    match arguments {
+        [] => {
+            let lines: Vec<String> = ui.schedule.iter()
+                .map(|scheduled| format!("{} ({})", scheduled, ui.conversations[scheduled.connection].peer_name))
+                .collect();
+            if lines.is_empty() {
+                return Ok(CommandOutcome::Reply(tr!("nothing-scheduled")));
+            }
+            Ok(CommandOutcome::Reply(lines.join("\n")))
+        },
+        ["cancel", id] => {
+            let id: u64 = id.trim_start_matches('#').parse().map_err(|_| tr!("invalid-id", id = *id))?;
+            ui.schedule.cancel(id).ok_or_else(|| tr!("not-scheduled", id = id))?;
+            Ok(CommandOutcome::Reply(tr!("schedule-cancelled", id = id)))
+        },
+        _ => Err(tr!("usage", usage = "/scheduled [cancel <id>]")),
+    }
+}
+
+fn away_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    if arguments.is_empty() {
+        return Err(tr!("usage", usage = "/away <reason>"));
+    }
+    let reason = arguments.join(" ");
+    // Giving another reason keeps the messages missed so far, and doesn't tell the peers again.
+    match ui.away {
+        Some(ref mut away) => away.reason = reason.clone(),
+        None => ui.away = Some(Away::new(reason.clone())),
+    }
+    Ok(CommandOutcome::Reply(tr!("now-away", reason = reason)))
+}
+
+fn back_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let away = ui.away.take().ok_or_else(|| tr!("not-away"))?;
+    Ok(CommandOutcome::Reply(away.summary()))
+}
+
+// `/watch` alone lists the keywords watched, kept in the config file with the other notification settings.
+fn watch_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let keywords = &ui.notifications.keywords;
+    let keyword = match arguments {
+        [] if keywords.is_empty() => return Ok(CommandOutcome::Reply(tr!("no-keywords"))),
+        [] => return Ok(CommandOutcome::Reply(tr!("keywords-list", keywords = keywords.join(", ")))),
+        [keyword] => keyword,
+        _ => return Err(tr!("usage", usage = "/watch [<keyword>]")),
+    };
+    if keywords.iter().any(|watched| watched.eq_ignore_ascii_case(keyword)) {
+        return Err(tr!("already-watched", keyword = *keyword));
+    }
+    let mut settings = ui.notifications.clone();
+    settings.keywords.push(keyword.to_string());
+    ui.save_notifications(&settings)?;
+    ui.notifications = settings;
+    Ok(CommandOutcome::Reply(tr!("watching", keyword = *keyword)))
+}
+
+fn unwatch_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let keyword = match arguments {
+        [keyword] => keyword,
+        _ => return Err(tr!("usage", usage = "/unwatch <keyword>")),
+    };
+    let mut settings = ui.notifications.clone();
+    settings.keywords.retain(|watched| !watched.eq_ignore_ascii_case(keyword));
+    if settings.keywords.len() == ui.notifications.keywords.len() {
+        return Err(tr!("not-watched", keyword = *keyword));
+    }
+    ui.save_notifications(&settings)?;
+    ui.notifications = settings;
+    Ok(CommandOutcome::Reply(tr!("unwatched", keyword = *keyword)))
+}
+
+fn identity_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let yours = ui.identity.clone().unwrap_or_else(|| tr!("identity-none"));
+    let conversation = ui.conversation();
+    let theirs = conversation.peer_identity.clone().unwrap_or_else(|| tr!("identity-not-received"));
+    Ok(CommandOutcome::Reply(tr!("identity-keys", yours = yours, peer = conversation.peer_name.as_str(), theirs = theirs)))
+}
+
+fn settings_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    match arguments {
+        [] => {},
+        [name, value] => ui.feedback.set(name, value)?,
+        _ => return Err(tr!("usage", usage = "/settings [<name> <on|off>]")),
+    }
+    Ok(CommandOutcome::Reply(tr!("settings", settings = ui.feedback.to_string())))
+}
+
+fn revoke_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let tokens = ui.resume_tokens.as_ref().ok_or_else(|| tr!("only-server-tokens"))?;
+    let revoked = match arguments {
+        [] => tokens.lock().unwrap().revoke(None),
+        [user] => tokens.lock().unwrap().revoke(Some(user)),
+        _ => return Err(tr!("usage", usage = "/revoke [<user>]")),
+    };
+    Ok(CommandOutcome::Reply(tr!("tokens-revoked", count = revoked)))
+}
+
+fn queued_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let queue = ui.offline_queue.as_ref().ok_or_else(|| tr!("only-server-queues"))?;
+    let lines: Vec<String> = queue.lock().unwrap().queued()
+        .map(|(id, text)| format!("#{} {}", id, text))
+        .collect();
+    if lines.is_empty() {
+        return Ok(CommandOutcome::Reply(tr!("no-messages-queued")));
+    }
+    Ok(CommandOutcome::Reply(lines.join("\n")))
+}
+
+// A file being sent, or else a message queued for the next client.
+fn cancel_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let id = match arguments {
+        [id] => id.trim_start_matches('#'),
+        _ => return Err(tr!("usage", usage = "/cancel <id>")),
+    };
+    let transfer = id.parse::<TransferId>().ok().and_then(|id| Some((id, ui.conversation().transfers.get(&id)?.clone())));
+    if let Some((transfer, name)) = transfer {
+        let _ = ui.conversation().component.send(ComponentControlMsg::CancelTransfer(transfer));
+        return Ok(CommandOutcome::Reply(tr!("cancelling-file", name = name)));
+    }
+    let queue = ui.offline_queue.as_ref().ok_or_else(|| tr!("no-transfer", id = id))?;
+    let id = id.parse().map_err(|_| tr!("invalid-id", id = id))?;
+    if !cancel_queued(queue, &ui.events, id) {
+        return Err(tr!("no-queued-message", id = id));
+    }
+    Ok(CommandOutcome::Reply(tr!("queued-cancelled", id = id)))
+}
+
+fn delete_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let id = match arguments {
+        [id] => id.trim_start_matches('#').parse().map_err(|_| tr!("invalid-id", id = *id))?,
+        _ => return Err(tr!("usage", usage = "/delete <id>")),
+    };
+    if !ui.delete_message(id, true) {
+        return Err(tr!("no-own-message", id = id));
+    }
+    let _ = ui.conversation().component.send(ComponentControlMsg::Delete(id));
+    Ok(CommandOutcome::Reply(tr!("message-deleted-by-you", id = id)))
+}
+
+fn ephemeral_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let (ttl, words) = match arguments {
+        [ttl, words @ ..] if !words.is_empty() => {
+            let ttl = ttl.parse::<u64>().ok().filter(|&ttl| ttl > 0).ok_or_else(|| tr!("invalid-ttl", ttl = *ttl))?;
+            (Duration::from_secs(ttl), words)
+        },
+        _ => return Err(tr!("usage", usage = "/ephemeral <seconds> <text>")),
+    };
+    let conversation = ui.conversation();
+    if conversation.closed || !conversation.our_turn {
+        return Err(tr!("waiting-for-reply", peer = conversation.peer_name.as_str()));
+    }
+    let text = words.join(" ");
+    ui.stats.record_sent(&text);
+    ui.conversation_mut().our_turn = false;
+    let _ = ui.conversation().component.send(ComponentControlMsg::OutgoingEphemeral { text, ttl });
+    Ok(CommandOutcome::EndTurn(tr!("ephemeral-sent", seconds = ttl.as_secs())))
+}
+
+fn react_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let (id, emoji) = match arguments {
+        [id, emoji] => {
+            let id: MessageId = id.trim_start_matches('#').parse().map_err(|_| tr!("invalid-id", id = *id))?;
+            (id, emoji.to_string())
+        },
+        _ => return Err(tr!("usage", usage = "/react <id> <emoji>")),
+    };
+    let nick = ui.nick.clone();
+    let entry = match ui.conversation_mut().history.react(id, false, &nick, &emoji) {
+        Some(entry) => entry.to_string(),
+        None => return Err(tr!("no-message-to-react", id = id, peer = ui.conversation().peer_name.as_str())),
+    };
+    let _ = ui.conversation().component.send(ComponentControlMsg::React {
+        id,
+        emoji,
+    });
+    Ok(CommandOutcome::Reply(entry))
+}
+
+// The text of a message from the peer, to paste elsewhere.
+fn copy_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let id: MessageId = match arguments {
+        [id] => id.trim_start_matches('#').parse().map_err(|_| tr!("invalid-id", id = *id))?,
+        _ => return Err(tr!("usage", usage = "/copy <id>")),
+    };
+    let conversation = ui.conversation();
+    let text = conversation.history.text(id, false)
+        .ok_or_else(|| tr!("no-message-to-copy", id = id, peer = conversation.peer_name.as_str()))?
+        .to_string();
+    if ui.clipboard.is_none() {
+        ui.clipboard = Some(Clipboard::new().map_err(|error| tr!("clipboard-failed", error = error.to_string()))?);
+    }
+    ui.clipboard.as_mut().unwrap().set_text(text).map_err(|error| tr!("clipboard-failed", error = error.to_string()))?;
+    Ok(CommandOutcome::Reply(tr!("message-copied", id = id)))
+}
+
+fn history_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let count = match arguments {
+        [] => 20,
+        [count] => count.parse().map_err(|_| tr!("invalid-count", count = *count))?,
+        _ => return Err(tr!("usage", usage = "/history [<count>]")),
+    };
+    let lines: Vec<String> = ui.conversation().history.last(count).iter().map(HistoryEntry::to_string).collect();
+    Ok(CommandOutcome::Reply(lines.join("\n")))
+}
+
+// The links in the peer's messages, the latest first, as numbered for `/open`.
+fn links_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let links = ui.conversation().history.links();
+    if links.is_empty() {
+        return Ok(CommandOutcome::Reply(tr!("no-links")));
+    }
+    let lines: Vec<String> = links.iter().enumerate().map(|(index, link)| format!("{} {}", index + 1, link)).collect();
+    Ok(CommandOutcome::Reply(lines.join("\n")))
+}
+
+fn open_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let number = match arguments {
+        [] => 1,
+        [number] => number.parse::<usize>().ok().filter(|&number| number > 0).ok_or_else(|| tr!("invalid-number", number = *number))?,
+        _ => return Err(tr!("usage", usage = "/open [<number>]")),
+    };
+    let link = match ui.conversation().history.links().get(number - 1) {
+        Some(link) => link.to_string(),
+        None => return Err(tr!("no-such-link", number = number)),
+    };
+    // The peer chose the link, so the user gets to see where it leads first.
+    if !ui.input_mode.confirm(&tr!("title-open-link"), &tr!("confirm-open-link", url = links::url(&link))) {
+        return Ok(CommandOutcome::Reply(tr!("link-not-opened")));
+    }
+    links::open(&link).map_err(|error| tr!("link-open-failed", error = error.to_string()))?;
+    Ok(CommandOutcome::Reply(tr!("link-opened", url = links::url(&link))))
+}
+
+fn conversations_command(ui: &mut Ui, _arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let lines: Vec<String> = ui.conversations.iter().enumerate().map(|(connection, conversation)| {
+        let status = if conversation.closed {
+            format!(" {}", tr!("conversation-closed"))
+        } else if conversation.our_turn {
+            format!(" {}", tr!("conversation-your-turn"))
+        } else {
+            String::new()
+        };
+        let focus = if connection == ui.focused { "*" } else { " " };
+        let peer = if conversation.peer_name == conversation.address {
+            conversation.address.clone()
+        } else {
+            tr!("peer-on-address", peer = conversation.peer_name.as_str(), address = conversation.address.as_str())
+        };
+        format!("{}{} {}{}", focus, connection + 1, peer, status)
+    }).collect();
+    Ok(CommandOutcome::Reply(lines.join("\n")))
+}
+
+fn switch_command(ui: &mut Ui, arguments: &[&str]) -> Result<CommandOutcome, String> {
+    let connection = match arguments {
+        [number] => number.parse::<usize>().ok()
+            .filter(|number| (1..=ui.conversations.len()).contains(number))
+            .ok_or_else(|| tr!("no-conversation-see", number = *number))? - 1,
+        _ => return Err(tr!("usage", usage = "/switch <number>")),
+    };
+    let conversation = &ui.conversations[connection];
+    if conversation.closed {
+        return Err(tr!("connection-closed", address = conversation.address.as_str()));
+    }
+    if !conversation.our_turn {
+        return Err(tr!("waiting-for-reply", peer = conversation.peer_name.as_str()));
+    }
+    ui.focused = connection;
+    Ok(CommandOutcome::Reply(tr!("now-chatting", peer = ui.conversation().peer_name.as_str())))
+}
+
+fn ui_commands() -> CommandRegistry<Ui> {
+    let mut commands = CommandRegistry::new();
+    commands.register("quit", "", tr!("help-quit"), quit_command);
+    commands.register("nick", "<name>", tr!("help-nick"), nick_command);
+    commands.register("who", "", tr!("help-who"), who_command);
+    commands.register("msg", "<nick> <text>", tr!("help-msg"), msg_command);
+    commands.register("identity", "", tr!("help-identity"), identity_command);
+    commands.register("conversations", "", tr!("help-conversations"), conversations_command);
+    commands.register("switch", "<number>", tr!("help-switch"), switch_command);
+    commands.register("status", "", tr!("help-status"), status_command);
+    commands.register("stats", "", tr!("help-stats"), stats_command);
+    commands.register("ping", "", tr!("help-ping"), ping_command);
+    commands.register("ignore", "<nick>", tr!("help-ignore"), ignore_command);
+    commands.register("unignore", "<nick>", tr!("help-unignore"), unignore_command);
+    commands.register("ignores", "", tr!("help-ignores"), ignores_command);
+    commands.register("schedule", "\"<YYYY-MM-DD HH:MM>\" <text>", tr!("help-schedule"), schedule_command);
+    commands.register("scheduled", "[cancel <id>]", tr!("help-scheduled"), scheduled_command);
+    commands.register("away", "<reason>", tr!("help-away"), away_command);
+    commands.register("back", "", tr!("help-back"), back_command);
+    commands.register("watch", "[<keyword>]", tr!("help-watch"), watch_command);
+    commands.register("unwatch", "<keyword>", tr!("help-unwatch"), unwatch_command);
+    commands.register("draft", "[send|clear]", tr!("help-draft"), draft_command);
+    commands.register("dnd", "[on|off|mute <nick>|unmute <nick>|quiet <HH:MM-HH:MM|off>|mentions <on|off>|desktop <on|off>]", tr!("help-dnd"), dnd_command);
+    commands.register("settings", "[<name> <on|off>]", tr!("help-settings"), settings_command);
+    commands.register("sendfile", "<path>", tr!("help-sendfile"), sendfile_command);
+    commands.register("image", "<path>", tr!("help-image"), image_command);
+    commands.register("delete", "<id>", tr!("help-delete"), delete_command);
+    commands.register("react", "<id> <emoji>", tr!("help-react"), react_command);
+    commands.register("copy", "<id>", tr!("help-copy"), copy_command);
+    commands.register("ephemeral", "<seconds> <text>", tr!("help-ephemeral"), ephemeral_command);
+    commands.register("search", "<query>", tr!("help-search"), search_command);
+    commands.register("contacts", "[note <nick> <text>]", tr!("help-contacts"), contacts_command);
+    commands.register("history", "[<count>]", tr!("help-history"), history_command);
+    commands.register("links", "", tr!("help-links"), links_command);
+    commands.register("open", "[<number>]", tr!("help-open"), open_command);
+    commands.register("queued", "", tr!("help-queued"), queued_command);
+    commands.register("cancel", "<id>", tr!("help-cancel"), cancel_command);
+    commands.register("queue", "", tr!("help-queue"), queue_command);
+    commands.register("close", "", tr!("help-close"), close_command);
+    commands.register("next", "", tr!("help-next"), next_command);
+    commands.register("kick", "<nick>", tr!("help-kick"), kick_command);
+    commands.register("ban", "<nick|ip>", tr!("help-ban"), ban_command);
+    commands.register("revoke", "[<user>]", tr!("help-revoke"), revoke_command);
+    commands
+}
+
+// What the log says of the event, if it is about a connection, an error, or a message.
+fn log_line(event: &MainControlMsg, connection: ConnectionId, peer: &str, bodies: bool) -> Option<String> {
+    let body = |text: &str| if bodies { format!(": {}", text) } else { String::new() };
+    let line = match *event {
+        MainControlMsg::ConnectionStateChanged(state) => format!("{:?}", state),
+        MainControlMsg::ClientAddress(address) => format!("client connected from {}", address),
+        MainControlMsg::BannedClientRefused(address) => format!("refused banned client from {}", address),
+        MainControlMsg::ServerFullRefused(address) => match address {
+            Some(address) => format!("refused a client from {}, the server being full", address),
+            None => "refused a client, the server being full".to_string(),
+        },
+        MainControlMsg::WorkersSaturated { workers, waiting } => {
+            format!("all {} workers busy, {} clients waiting to be read from", workers, waiting)
+        },
+        MainControlMsg::AuthenticationFailed => "a client failed to authenticate".to_string(),
+        MainControlMsg::AuthenticatorError(ref error) => format!("authenticator error: {}", error),
+        MainControlMsg::ClientThrottled => format!("{} throttled", peer),
+        MainControlMsg::Throttled { id, retry_after, dropped } => {
+            format!("throttled by {} for {}ms, #{} {}", peer, retry_after.as_millis(), id, if dropped { "dropped" } else { "delayed" })
+        },
+        MainControlMsg::PeerRenamed(ref nick) => format!("{} is now {}", peer, nick),
+        MainControlMsg::Presence(Presence::Joined(ref nick)) => format!("{} joined", nick),
+        MainControlMsg::Presence(Presence::Left(ref nick)) => format!("{} left", nick),
+        MainControlMsg::Presence(Presence::Renamed { ref from, ref to }) => format!("{} is now {}", from, to),
+        MainControlMsg::IncomingMessage { ref message, .. } |
+        MainControlMsg::IncomingMessagePart { ref message, .. } if message.kind == MessageKind::Text => {
+            format!("received #{} from {}, {} bytes{}", message.id, peer, message.body.len(), body(&message.body))
+        },
+        MainControlMsg::IncomingBinary { id, ref mime, ref data, .. } => {
+            format!("received #{} from {}, {} bytes of {}", id, peer, data.len(), mime.as_deref().unwrap_or("data"))
+        },
+        MainControlMsg::MessageDelivered { id, ref text, roundtrip } => {
+            format!("delivered #{} to {}, {} bytes in {}ms{}", id, peer, text.len(), roundtrip.as_millis(), body(text))
+        },
+        MainControlMsg::BinaryDelivered { id, roundtrip } => format!("delivered #{} to {} in {}ms", id, peer, roundtrip.as_millis()),
+        MainControlMsg::MessageRead(id) => format!("#{} read by {}", id, peer),
+        MainControlMsg::FileSending { transfer, ref name, size, offset } => {
+            format!("sending file {} ({}) to {}, {} bytes from byte {}", transfer, name, peer, size, offset)
+        },
+        MainControlMsg::FileSent { transfer, ref name } => format!("sent file {} ({}) to {}", transfer, name, peer),
+        MainControlMsg::FileReceived { transfer, ref name, .. } => format!("received file {} ({}) from {}", transfer, name, peer),
+        MainControlMsg::TransferCancelled { transfer, ref name, by_peer } => {
+            format!("file {} ({}) cancelled by {}", transfer, name, if by_peer { peer } else { "us" })
+        },
+        MainControlMsg::TransferFailed { ref name, ref error } => format!("transfer of {} failed: {}", name, error),
+        MainControlMsg::SendFailed(id) => format!("failed to deliver #{} to {}", id, peer),
+        MainControlMsg::MessageQueued(id) => format!("queued #{} for the next client", id),
+        MainControlMsg::MessageInjected(ref text) => format!("injected a message, {} bytes{}", text.len(), body(text)),
+        MainControlMsg::AnnouncementSent { ref text, listeners } => {
+            format!("announced to {} clients, {} bytes{}", listeners, text.len(), body(text))
+        },
+        MainControlMsg::ListenersChanged(listeners) => format!("{} clients listening", listeners),
+        MainControlMsg::ConnectAttemptFailed { ref error, attempt, retries } => {
+            format!("failed to reach the server, attempt {} of {}: {}", attempt, retries + 1, error)
+        },
+        MainControlMsg::SocketConfigured(ref applied) => format!("socket options: {}", applied),
+        MainControlMsg::PingAnswered(Some(roundtrip)) => format!("ping to {} answered in {}ms", peer, roundtrip.as_millis()),
+        MainControlMsg::PingAnswered(None) => format!("ping to {} lost", peer),
+        MainControlMsg::ProtocolViolation(ref violation) => format!("skipped a frame from {}: {}", peer, violation),
+        MainControlMsg::MessageFiltered { id, action, ref words } => format!("filter {} #{}{}", action.name(), id, body(&words.join(", "))),
+        MainControlMsg::Error(ref error) => format!("error: {}", error),
+        MainControlMsg::ClientDisconnected(ref reason) => format!("disconnected: {:?}", reason),
+        MainControlMsg::ServerShutDown(ref reason) => format!("shut down: {:?}", reason),
+        _ => return None,
+    };
+    Some(format!("#{} {}", connection + 1, line))
+}
+
+fn presence_text(presence: &Presence) -> String {
+    match *presence {
+        Presence::Joined(ref nick) => tr!("presence-joined", nick = nick.as_str()),
+        Presence::Left(ref nick) => tr!("presence-left", nick = nick.as_str()),
+        Presence::Renamed { ref from, ref to } => tr!("presence-renamed", from = from.as_str(), to = to.as_str()),
+    }
+}
+
+// Alert the user about a message from the peer, according to the rules, and then the `/dnd` settings,
+// mentions and the keywords watched being alerted like those.
+fn alert(output: &Output, rules: &NotificationRules, settings: &NotificationSettings, own_name: &str, peer_name: &str, text: &str) {
+    let keywords = mentions::watched(text, &settings.keywords);
+    let mentioned = mentions::mentions(text, own_name);
+    let notification = Notification {
+        room: None,
+        peer: peer_name,
+        mentioned: mentioned || !keywords.is_empty(),
+    };
+    let now = Local::now().time();
+    let alert = settings.apply(rules.evaluate(&notification, now), &notification, now);
+    let line = match alert {
+        Alert::Silent => return,
+        Alert::Normal if mentioned => tr!("mentioned-by", peer = peer_name),
+        Alert::Normal if !keywords.is_empty() => tr!("keyword-watched", peer = peer_name, keywords = keywords.join(", ")),
+        Alert::Normal => {
+            print!("\u{7}");
+            return;
+        },
+        Alert::Urgent => tr!("urgent-message", peer = peer_name),
+    };
+    output.line(format!("\u{7}{}", line));
+    if settings.desktop && notification.mentioned {
+        notify::desktop(&line, text);
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn load_script(path: &str) -> script::ScriptHook {
+    script::ScriptHook::load(path).unwrap_or_else(|error| panic!("{}", error))
+}
+
+#[cfg(not(feature = "scripting"))]
+fn load_script(_path: &str) {
+    panic!("--script requires the 'scripting' feature");
+}
+
+// Returns the text to display, if any, and a reply to send automatically.
+#[cfg(feature = "scripting")]
+fn apply_script(output: &Output,
+                script: &Option<script::ScriptHook>,
+                sender: &str,
+                text: String)
+                -> (Option<String>, Option<String>) {
+    let script = match *script {
+        Some(ref script) => script,
+        None => return (Some(text), None),
+    };
+    match script.on_message(sender, &text) {
+        Ok(outcome) => (outcome.text, outcome.reply),
+        Err(error) => {
+            output.error(error);
+            (Some(text), None)
+        },
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+fn apply_script(_output: &Output,
+                _script: &Option<()>,
+                _sender: &str,
+                text: String)
+                -> (Option<String>, Option<String>) {
+    (Some(text), None)
+}
+
+// The output of the chat, and the updates of the window it is shown in with `--gui`,
+// along with how it is shown, from the flags and the config file.
+#[cfg(feature = "gui")]
+fn window_output(gui: bool,
+                 theme: Theme,
+                 scrollback: Option<usize>,
+                 to_tray: bool,
+                 font_size: Option<f32>,
+                 zoom: Option<f32>)
+                 -> (Output, Option<(mpsc::Receiver<gui::Update>, gui::Options)>) {
+    if !gui {
+        return (Output::new(theme), None);
+    }
+    let (updates, port) = mpsc::channel();
+    let options = gui::Options {
+        scrollback: scrollback.unwrap_or(gui::DEFAULT_SCROLLBACK),
+        to_tray,
+        font_size,
+        zoom: zoom.unwrap_or(1.0),
+    };
+    (Output::window(theme, updates), Some((port, options)))
+}
+
+#[cfg(not(feature = "gui"))]
+fn window_output(gui: bool,
+                 theme: Theme,
+                 _scrollback: Option<usize>,
+                 _to_tray: bool,
+                 _font_size: Option<f32>,
+                 _zoom: Option<f32>)
+                 -> (Output, Option<((), ())>) {
+    assert!(!gui, "--gui requires the 'gui' feature");
+    (Output::new(theme), None)
+}
+
+// Run the chat on its own thread, the window on this one, until both are done.
+#[cfg(feature = "gui")]
+fn run_in_window<F: FnOnce() + Send + 'static>(title: &str,
+                                               updates: mpsc::Receiver<gui::Update>,
+                                               options: gui::Options,
+                                               events: EventSender,
+                                               chat: F) {
+    let chat = thread::spawn(chat);
+    let shown = gui::run(title, updates, &chat, options, |event| {
+        let _ = events.send(MainControlMsg::Window(event));
+    });
+    if let Err(error) = shown {
+        println!("{}", tr!("window-failed", error = error));
+        let _ = events.send(MainControlMsg::Window(gui::Event::Closed));
+    }
+    let _ = chat.join();
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_in_window<F: FnOnce()>(_title: &str, _updates: (), _options: (), _events: EventSender, _chat: F) {
+    unreachable!("there is no window without the 'gui' feature");
 }
 
 fn main() {
-    let mut arguments = env::args();
-    let _ = arguments.next();
+    let mut arguments = env::args().skip(1).collect::<Vec<String>>();
+    // Taken out first, for the subcommands to be in the language too.
+    let lang = match arguments.iter().position(|argument| argument == "--lang") {
+        Some(index) if index + 1 < arguments.len() => {
+            arguments.remove(index);
+            Some(arguments.remove(index))
+        },
+        Some(_) => panic!("--lang takes a language, such as 'fr'"),
+        None => locale::detect(),
+    };
+    if let Some(lang) = lang {
+        locale::set(&lang).unwrap_or_else(|error| panic!("{}", error));
+    }
+    let mut arguments = arguments.into_iter();
     let server_or_client = arguments.next().unwrap();
-    let (chan, port) = channel();
-    let (component, peer_name) = match server_or_client.as_ref() {
-        "server" => (start_server(chan), "client"),
-        "client" => (start_client(chan), "server"),
-        _ => panic!("unknown argument - usage is 'cargo run -- {server|client}")
+    if server_or_client == "export" {
+        if let Err(error) = export_history(arguments) {
+            println!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if server_or_client == "proxy" {
+        if let Err(error) = run_proxy(arguments) {
+            println!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if server_or_client == "rendezvous" {
+        if let Err(error) = run_rendezvous(arguments) {
+            println!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if server_or_client == "invite" {
+        if let Err(error) = run_invite(arguments) {
+            println!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if server_or_client == "bench" {
+        if let Err(error) = run_bench(arguments) {
+            println!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if server_or_client == "replay" {
+        if let Err(error) = run_replay(arguments) {
+            println!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if server_or_client == "search" {
+        let query = arguments.collect::<Vec<String>>().join(" ");
+        match MessageStore::open_default().and_then(|store| search(&store, &query)) {
+            Ok(found) => println!("{}", found),
+            Err(error) => {
+                println!("{}", error);
+                process::exit(1);
+            },
+        }
+        return;
+    }
+    let config_path = arguments.clone().skip_while(|argument| argument != "--config").nth(1).map(PathBuf::from);
+    let config = match config_path {
+        Some(ref path) => Config::load(path),
+        None => Config::load_default(),
+    }.unwrap_or_else(|error| panic!("{}", error));
+    // Where `/ignore` keeps its list.
+    let config_path = config_path.or_else(config::default_path);
+    let ignored = config.ignored;
+    let notifications = config.notifications;
+    let mut address = config.address.unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let mut port = config.port.unwrap_or(DEFAULT_PORT);
+    let mut nick = config.nickname.unwrap_or_else(|| server_or_client.clone());
+    let mut input_mode = config.ui;
+    // The lines kept in the window.
+    let mut scrollback = config.scrollback;
+    let mut font_size = config.font_size;
+    let zoom = config.zoom;
+    let theme = config.theme;
+    let socket = config.socket;
+    let mut allow_downgrade = false;
+    let mut keep_history = true;
+    let mut session_limit = None;
+    let mut support_queue = false;
+    let mut broadcast = false;
+    let mut channels = ChannelConfig::default();
+    let mut ack = AckPolicy::default();
+    // The servers a client connects to, if not only the one at `--address` and `--port`.
+    let mut servers = vec![];
+    let mut options = ServerOptions::default();
+    // Overridden by `--password` or `--auth`.
+    if let Some(ref spec) = config.auth {
+        options.authenticator = Some(Arc::from(auth::authenticator_from_spec(spec).unwrap_or_else(|error| panic!("{}", error))));
+    }
+    let mut client_options = ClientOptions {
+        connect_timeout: Some(CONNECT_TIMEOUT),
+        connect_retries: CONNECT_RETRIES,
+        retry_delay: RETRY_DELAY,
+        ..ClientOptions::default()
     };
-    loop {
-        let incoming = match port.try_recv() {
-            Err(_) => continue,
-            Ok(incoming) => incoming,
-        };
-        let received = match incoming {
-            MainControlMsg::IncomingMessage(received) => received,
-            MainControlMsg::RoundTrip(duration) => {
-                println!("Roundtrip took: {:?}", duration);
-                continue
+    let mut script_path = None;
+    let mut feedback = FeedbackSettings::default();
+    let mut notification_rules = NotificationRules::default();
+    let mut summary_log = None;
+    // The log of connections, errors and messages, without their text unless `--log-bodies`.
+    let mut log_file = config.log_file;
+    let mut log_rotation = config.log_rotate
+        .map(|rotation| Rotation::parse(&rotation).unwrap_or_else(|error| panic!("log_rotate: {}", error)))
+        .unwrap_or_default();
+    let mut log_bodies = false;
+    let mut stats_interval = None;
+    let mut ping_interval = None;
+    let mut throttle_policy = ThrottlePolicy::Delay;
+    let mut messages_per_second = None;
+    let mut gui = false;
+    let mut web_port = None;
+    let mut control_address = None;
+    // Where p2p peers meet, when not reaching each other at `--address` and `--port`.
+    let mut rendezvous = None;
+    let mut room = None;
+    let mut invitation = None;
+    // Showing the server's URI as a QR code.
+    let mut qr_code = false;
+    let mut tray_icon = false;
+    // Taking the user names and passwords of `--auth`, sent as they are, on a connection which isn't encrypted.
+    let mut cleartext_passwords = false;
+    // Tor's control port, to publish the server as an onion service.
+    let mut tor_control = None;
+    // The host clients reach the server at, for its URI, such as a name or a public address.
+    let mut advertised = None;
+    while let Some(argument) = arguments.next() {
+        match argument.as_ref() {
+            "--password" => {
+                let password = arguments.next().expect("--password takes a password");
+                options.authenticator = Some(Arc::new(StaticPassword::new(password)));
             },
-            MainControlMsg::ClientDisconnected => {
-                assert_eq!(server_or_client, "client");
-                print!("No server available, quitting");
-                break;
+            "--auth" => {
+                let spec = arguments.next().expect("--auth takes an authenticator, such as htpasswd:<path>");
+                options.authenticator = Some(Arc::from(auth::authenticator_from_spec(&spec).unwrap_or_else(|error| panic!("{}", error))));
             },
-            MainControlMsg::ServerShutDown => {
-                assert_eq!(server_or_client, "server");
-                print!("Server has gone away");
-                break;
+            "--rate-limit" => {
+                let rate: f64 = arguments.next()
+                    .and_then(|rate| rate.parse().ok())
+                    .expect("--rate-limit takes a number of messages per second");
+                assert!(rate > 0.0, "--rate-limit must be positive");
+                messages_per_second = Some(rate);
             },
-        };
-        println!("{:?} received: {:?}", server_or_client, received);
-        let title = format!("Simple chat {} - Choose 'Cancel' to quit", server_or_client);
-        let prompt = format!("Send message to {}", peer_name);
-        match tinyfiledialogs::input_box(&title, &prompt, &"") {
-            Some(input) => {
-                let _ = component.send(ComponentControlMsg::OutgoingMessage(input));
+            "--config" => {
+                // Already loaded.
+                let _ = arguments.next();
+            },
+            "--address" => address = arguments.next().expect("--address takes an IP address or host name"),
+            "--connect" => servers.push(arguments.next().expect("--connect takes a server address, such as host:8000")),
+            "--contact" => {
+                let nick = arguments.next().expect("--contact takes the nickname of a saved contact");
+                servers.push(contact_address(&nick).unwrap_or_else(|error| panic!("{}", error)));
+            },
+            "--rendezvous" => rendezvous = Some(arguments.next().expect("--rendezvous takes the address of a rendezvous server, such as host:8000")),
+            "--room" => room = Some(arguments.next().expect("--room takes the name both peers give")),
+            "--invite" => invitation = Some(arguments.next().expect("--invite takes the token of an invitation to the room")),
+            "--qr" => qr_code = true,
+            "--advertise" => advertised = Some(arguments.next().expect("--advertise takes the host name or address clients reach the server at")),
+            "--onion" => tor_control = Some(tor::DEFAULT_CONTROL.to_string()),
+            "--tor-control" => tor_control = Some(arguments.next().expect("--tor-control takes the address of Tor's control port, such as 127.0.0.1:9051")),
+            "--tor-socks" => client_options.tor_socks = Some(arguments.next().expect("--tor-socks takes the address of Tor's SOCKS port, such as 127.0.0.1:9050")),
+            "--port" => {
+                port = arguments.next()
+                    .and_then(|port| port.parse().ok())
+                    .expect("--port takes a port number");
+            },
+            "--allow-downgrade" => allow_downgrade = true,
+            "--no-history" => keep_history = false,
+            "--nick" => nick = arguments.next().expect("--nick takes a nickname"),
+            "--script" => script_path = arguments.next(),
+            "--stdin" => input_mode = Some(InputMode::Stdin),
+            "--headless" => input_mode = Some(InputMode::Headless),
+            "--control" => {
+                let address = arguments.next().expect("--control takes a local address, such as 127.0.0.1:9000");
+                // Whoever reaches it drives the chat as the user.
+                assert!(uri::is_loopback(&address), "--control only listens on a loopback address, such as 127.0.0.1:9000");
+                control_address = Some(address);
+            },
+            "--gui" => gui = true,
+            "--tray" => tray_icon = true,
+            "--allow-cleartext-passwords" => cleartext_passwords = true,
+            "--scrollback" => {
+                scrollback = Some(arguments.next()
+                    .and_then(|lines| lines.parse().ok())
+                    .filter(|&lines| lines > 0)
+                    .expect("--scrollback takes a positive number of lines"));
             },
-            None => {
-                println!("{:?} quitting", server_or_client);
-                let _ = component.send(ComponentControlMsg::Quit);
-                break;
+            "--font-size" => {
+                font_size = Some(arguments.next()
+                    .and_then(|points| points.parse().ok())
+                    .filter(|&points: &f32| points > 0.0)
+                    .expect("--font-size takes a positive number of points"));
             },
+            "--summary-log" => summary_log = arguments.next(),
+            "--log-file" => log_file = Some(arguments.next().expect("--log-file takes the path of the log")),
+            "--log-rotate" => {
+                log_rotation = Rotation::parse(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--log-rotate: {}", error));
+            },
+            "--log-bodies" => log_bodies = true,
+            "--stats-interval" => {
+                let interval = session::parse_duration(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--stats-interval: {}", error));
+                stats_interval = Some(interval);
+            },
+            "--ping-interval" => {
+                let interval = session::parse_duration(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--ping-interval: {}", error));
+                ping_interval = Some(interval);
+            },
+            "--filter" => {
+                let path = arguments.next().expect("--filter takes the path of a filter file");
+                options.filter = Some(Arc::new(WordFilter::load(&path).unwrap_or_else(|error| panic!("{}", error))));
+            },
+            "--notify-rules" => {
+                let path = arguments.next().expect("--notify-rules takes the path of a rules file");
+                notification_rules = NotificationRules::load(&path).unwrap_or_else(|error| panic!("{}", error));
+            },
+            "--feedback" => {
+                let setting = arguments.next().expect("--feedback takes a 'name=on|off' setting");
+                feedback.set_from_str(&setting).unwrap_or_else(|error| panic!("{}", error));
+            },
+            "--plugin" => {
+                let name = arguments.next().expect("--plugin takes the name of a plugin");
+                match plugin::plugin_by_name(&name) {
+                    Some(plugin) => options.register_plugin(plugin),
+                    None => panic!("unknown plugin: {}", name),
+                }
+            },
+            "--webhook" => {
+                let url = arguments.next().expect("--webhook takes a URL");
+                webhook::parse_url(&url).unwrap_or_else(|error| panic!("--webhook: {}", error));
+                options.webhook_url = Some(url);
+            },
+            "--webhook-listen" => options.inject_address = arguments.next(),
+            "--web-port" => {
+                web_port = Some(arguments.next()
+                    .and_then(|port| port.parse::<u16>().ok())
+                    .expect("--web-port takes a port number"));
+            },
+            "--wire" => {
+                client_options.wire_format = WireFormat::parse(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--wire: {}", error));
+            },
+            "--session-timeout" => {
+                let length = session::parse_duration(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--session-timeout: {}", error));
+                session_limit = Some(length);
+                options.session_limit = session_limit;
+            },
+            "--ack-timeout" => {
+                ack.timeout = session::parse_duration(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--ack-timeout: {}", error));
+            },
+            "--connect-timeout" => {
+                let timeout = session::parse_duration(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--connect-timeout: {}", error));
+                client_options.connect_timeout = Some(timeout);
+            },
+            "--connect-retries" => {
+                client_options.connect_retries = arguments.next()
+                    .and_then(|retries| retries.parse().ok())
+                    .expect("--connect-retries takes a number of times to try reaching the server again");
+            },
+            "--ack-retries" => {
+                ack.retries = arguments.next()
+                    .and_then(|retries| retries.parse().ok())
+                    .expect("--ack-retries takes a number of times to send a message again");
+            },
+            "--broadcast" => {
+                broadcast = true;
+                options.broadcast = true;
+            },
+            "--channel-capacity" => {
+                channels.capacity = arguments.next()
+                    .and_then(|capacity| capacity.parse().ok())
+                    .filter(|&capacity| capacity > 0)
+                    .expect("--channel-capacity takes a positive number of messages");
+            },
+            "--channel-overflow" => {
+                channels.policy = OverflowPolicy::parse(&arguments.next().unwrap_or_default())
+                    .unwrap_or_else(|error| panic!("--channel-overflow: {}", error));
+            },
+            "--support-queue" => {
+                support_queue = true;
+                options.support_queue = true;
+            },
+            "--workers" => {
+                options.workers = Some(arguments.next()
+                    .and_then(|workers| workers.parse().ok())
+                    .filter(|&workers| workers > 0)
+                    .expect("--workers takes a positive number of threads"));
+            },
+            "--max-clients" => {
+                options.max_clients = Some(arguments.next()
+                    .and_then(|max| max.parse().ok())
+                    .filter(|&max| max > 0)
+                    .expect("--max-clients takes a positive number of clients"));
+            },
+            "--offline-queue" => {
+                let size = arguments.next()
+                    .and_then(|size| size.parse().ok())
+                    .expect("--offline-queue takes a number of messages");
+                options.offline_queue = Arc::new(Mutex::new(OfflineQueue::new(size)));
+            },
+            "--record" => {
+                let recorder = Recorder::create(&arguments.next().expect("--record takes the path of the log"))
+                    .unwrap_or_else(|error| panic!("{}", error));
+                options.recorder = Some(recorder.clone());
+                client_options.recorder = Some(recorder);
+            },
+            "--throttle" => {
+                throttle_policy = match arguments.next().as_deref() {
+                    Some("drop") => ThrottlePolicy::Drop,
+                    Some("delay") => ThrottlePolicy::Delay,
+                    _ => panic!("--throttle takes either 'drop' or 'delay'"),
+                };
+            },
+            // All of where to connect and how at once, shared by the peer.
+            uri if uri::is_uri(uri) => {
+                let uri = ChatUri::parse(uri, DEFAULT_PORT).unwrap_or_else(|error| panic!("{}", error));
+                if server_or_client == "p2p" {
+                    rendezvous = Some(uri.address);
+                    room = uri.room;
+                    invitation = uri.invitation;
+                } else {
+                    assert!(uri.room.is_none() && uri.invitation.is_none(), "a room is for p2p, through a rendezvous server");
+                    servers.push(uri.address);
+                }
+                if let Some(uri_nick) = uri.nick {
+                    nick = uri_nick;
+                }
+                if let Some(wire_format) = uri.wire_format {
+                    client_options.wire_format = wire_format;
+                }
+            },
+            _ => panic!("unknown option: {}", argument),
+        }
+    }
+    // The flag has precedence over the config file, until that is changed.
+    let messages_per_second = messages_per_second.or(config.rate_limit);
+    options.rate_limit = Arc::new(Mutex::new(messages_per_second.map(|messages_per_second| RateLimit {
+        messages_per_second,
+        policy: throttle_policy,
+    })));
+    options.motd = Arc::new(Mutex::new(config.motd));
+    assert!(!(options.broadcast && options.support_queue), "--broadcast and --support-queue can't be combined");
+    assert!(options.max_clients.is_none() || options.broadcast || options.support_queue,
+            "--max-clients needs --broadcast or --support-queue, the server otherwise serving a single client");
+    assert!(options.workers.is_none() || options.broadcast, "--workers is for --broadcast, the server otherwise reading from a single client");
+    assert!(servers.is_empty() || server_or_client == "client", "--connect is only for clients");
+    assert!(web_port.is_none() || server_or_client == "server", "--web-port is only for the server");
+    assert!(rendezvous.is_none() || server_or_client == "p2p", "--rendezvous is only for p2p");
+    assert!(invitation.is_none() || rendezvous.is_some(), "--invite is for a room of a --rendezvous server");
+    assert!(tor_control.is_none() || server_or_client == "server", "--onion is only for the server");
+    assert!(!qr_code || server_or_client == "server", "--qr is only for the server");
+    assert!(advertised.is_none() || server_or_client == "server", "--advertise is only for the server");
+    options.web_address = web_port.map(|web_port| format!("{}:{}", address, web_port));
+    options.channel = channels;
+    client_options.channel = channels;
+    options.ack = ack;
+    client_options.ack = ack;
+    options.socket = socket.clone();
+    client_options.socket = socket;
+    let address = format!("{}:{}", address, port);
+    if server_or_client == "doctor" {
+        process::exit(if doctor::run(&address) { 0 } else { 1 });
+    }
+    let (output, window) = window_output(gui, theme, scrollback, tray_icon, font_size, zoom);
+    let input_mode = input_mode.unwrap_or_else(InputMode::detect);
+    assert!(input_mode != InputMode::Headless || control_address.is_some(), "--headless needs --control to be driven");
+    assert!(!tray_icon || gui || input_mode == InputMode::Dialog, "--tray is for the dialogs, or the window, which it brings up");
+    let mut auto_replies = vec![];
+    let (chan, port) = channel::bounded(channels);
+    // No transport encryption is available yet.
+    let security = SecurityLevel::Plaintext;
+    say!(output, "session-security", security = security.to_string());
+    // Only the shared password of `--password` is proven without being sent.
+    let sends_passwords = options.authenticator.as_ref().is_some_and(|authenticator| authenticator.shared_password().is_none());
+    if sends_passwords && security == SecurityLevel::Plaintext {
+        assert!(cleartext_passwords,
+                "with this --auth, clients send their password in cleartext, the connection not being encrypted: \
+                 add --allow-cleartext-passwords to take them anyway, or use --password");
+        output.error(tr!("cleartext-passwords-taken"));
+    }
+    let mut resume_tokens = None;
+    let mut offline_queue = None;
+    let mut bans = None;
+    let mut server_settings = None;
+    let mut conversations = vec![];
+    // Our messages are signed with it, for the peer to tell them from those made up by whoever relays them.
+    let key = identity::load_or_create_key();
+    match key {
+        Ok(ref key) => {
+            options.signing_key = Some(key.clone());
+            client_options.signing_key = Some(key.clone());
+        },
+        Err(ref error) => say!(output, "identity-key-failed", error = error.as_str()),
+    }
+    match server_or_client.as_ref() {
+        "server" => {
+            resume_tokens = Some(options.resume_tokens.clone());
+            offline_queue = Some(options.offline_queue.clone());
+            options.bans = Arc::new(Mutex::new(BanList::load()));
+            bans = Some(options.bans.clone());
+            server_settings = Some(ServerSettings {
+                rate_limit: options.rate_limit.clone(),
+                motd: options.motd.clone(),
+                throttle_policy,
+            });
+            let peer_name = if options.broadcast { "everyone" } else { "client" };
+            let component = start_server(EventSender::new(0, chan.clone()), address.clone(), options);
+            conversations.push(Conversation::new(&address, component, peer_name));
+        },
+        "client" => {
+            if servers.is_empty() {
+                servers.push(address.clone());
+            }
+            for server in servers.iter() {
+                if let Err(error) = SecurityHistory::load().check(server, security, allow_downgrade) {
+                    say!(output, "refusing-connection", server = server.as_str(), error = error);
+                    continue;
+                }
+                // Servers are told apart by their address, until they give a nickname.
+                let peer_name = if servers.len() > 1 { server } else { "server" };
+                let component = start_client(EventSender::new(conversations.len(), chan.clone()), server.clone(), client_options.clone());
+                let mut conversation = Conversation::new(server, component, peer_name);
+                conversation.resume_token = resume::load_client_token(server);
+                conversations.push(conversation);
+            }
+            if conversations.is_empty() {
+                process::exit(1);
+            }
+        },
+        "p2p" => {
+            let events = EventSender::new(0, chan.clone());
+            let (peer_address, component) = match rendezvous {
+                Some(rendezvous) => {
+                    let room = room.expect("--rendezvous takes a --room, for the peers to meet in");
+                    say!(output, "p2p-rendezvous", rendezvous = rendezvous.as_str(), room = room.as_str());
+                    let (reached, server) = (events.clone(), rendezvous.clone());
+                    let component = start_peer_with(events, rendezvous.clone(), move || {
+                        let link = rendezvous::reach(&server, &room, invitation.as_deref())?;
+                        let _ = reached.send(MainControlMsg::PeerReached { relayed: link.relayed() });
+                        Ok(link)
+                    }, client_options);
+                    (rendezvous, component)
+                },
+                None => {
+                    say!(output, "p2p-started", address = address.as_str());
+                    (address.clone(), start_peer(events, address.clone(), client_options))
+                },
+            };
+            conversations.push(Conversation::new(&peer_address, component, "peer"));
+        },
+        _ => panic!("unknown argument - usage is 'cargo run -- [server|client|p2p|doctor|search <query>|export]'")
+    };
+    // Kept until we are done, Tor removing the service once its control connection closes.
+    let onion_service = tor_control.map(|control| {
+        let service = tor::publish(&control, &address).unwrap_or_else(|error| panic!("--onion: {}", error));
+        say!(output, "onion-published", address = service.address.as_str());
+        service
+    });
+    if server_or_client == "server" {
+        let reachable = match onion_service {
+            Some(ref service) => service.address.clone(),
+            None => uri::reachable_address(&address, advertised.as_deref()),
+        };
+        if uri::is_loopback(&reachable) {
+            say!(output, "server-loopback-only", address = reachable.as_str());
+        }
+        let uri = ChatUri { address: reachable, ..ChatUri::default() };
+        say!(output, "server-uri", uri = uri.to_string());
+        if qr_code {
+            match uri.qr_code() {
+                Ok(code) => output.terminal(code),
+                Err(error) => output.error(tr!("qr-code-failed", error = error)),
+            }
+        }
+    }
+    let log = log_file.map(|path| LogFile::open(&path, log_rotation).unwrap_or_else(|error| panic!("{}", error)));
+    let registry = ui_commands();
+    let mut ui = Ui {
+        nick,
+        feedback,
+        conversations,
+        focused: 0,
+        resume_tokens,
+        offline_queue,
+        bans,
+        server_settings,
+        events: EventSender::new(0, chan),
+        store: None,
+        ignored,
+        notifications,
+        drafts: Drafts::load(),
+        schedule: Schedule::default(),
+        away: None,
+        config_path,
+        identity: None,
+        support_queue,
+        broadcast,
+        visitors_waiting: 0,
+        visitors_served: 0,
+        transcript: None,
+        stats: SessionStats::new(),
+        output: output.clone(),
+        control: None,
+        input_mode,
+        clipboard: None,
+    };
+    if !ui.drafts.is_empty() {
+        say!(output, "drafts-kept", count = ui.drafts.iter().count());
+    }
+    if let Some(ref address) = control_address {
+        let events = ui.events.clone();
+        let control = control::start_control_socket(address, move |request| {
+            let _ = events.send(MainControlMsg::Control(request));
+        });
+        // The chat goes on without it.
+        match control {
+            Ok(control) => ui.control = Some(control),
+            Err(source) => ui.events.report(Err(ChatError::Listen { address: address.clone(), source })),
+        }
+    }
+    // The changes to the config file, and to the ban list on the server, apply as they are made.
+    let mut watched: Vec<PathBuf> = ui.config_path.iter().cloned().collect();
+    let bans_index = watched.len();
+    watched.extend(ui.bans.as_ref().and_then(|bans| bans.lock().unwrap().path().map(Path::to_path_buf)));
+    let changes = ui.events.clone();
+    let watcher = watch::watch_files(&watched, move |index| {
+        let _ = changes.send(if index < bans_index { MainControlMsg::ConfigChanged } else { MainControlMsg::BanListChanged });
+    });
+    if let Err(ref error) = watcher {
+        say!(output, "watch-failed", error = error.as_str());
+    }
+    if keep_history {
+        match MessageStore::open_default() {
+            Ok(store) => ui.store = Some(store),
+            Err(error) => say!(output, "history-open-failed", error = error),
+        }
+    }
+    if let Ok(ref key) = key {
+        ui.identity = Some(identity::fingerprint(&key.verifying_key().to_bytes()));
+    }
+    for conversation in ui.conversations.iter() {
+        if ui.nick != server_or_client {
+            let _ = conversation.component.send(ComponentControlMsg::Nick(ui.nick.clone()));
+        }
+        if let Ok(ref key) = key {
+            let _ = conversation.component.send(ComponentControlMsg::Identity(key.verifying_key().to_bytes().to_vec()));
+        }
+        // The server asks for its limit in the handshake.
+        if let (Some(length), "client" | "p2p") = (session_limit, server_or_client.as_ref()) {
+            let _ = conversation.component.send(ComponentControlMsg::SessionLimit(length));
+        }
+    }
+    if let Some(interval) = stats_interval {
+        start_timer(interval, ui.events.clone(), || MainControlMsg::StatsDue);
+    }
+    if let Some(interval) = ping_interval {
+        start_timer(interval, ui.events.clone(), || MainControlMsg::PingDue);
+    }
+    let mut known_peers = KnownPeers::load();
+    let title = tr!("title-security", role = server_or_client.as_str(), security = security.to_string());
+    let events = ui.events.clone();
+    let mut tray = if tray_icon {
+        let activated = ui.events.clone();
+        let tray = Tray::start(&title, move || {
+            let _ = activated.send(MainControlMsg::TrayActivated);
+        });
+        Some(tray.unwrap_or_else(|error| panic!("--tray: {}", error)))
+    } else {
+        None
+    };
+    // In a window, it runs on its own thread, and the script can't be moved there once loaded.
+    let chat = move || {
+        let _watcher = watcher;
+        let script = script_path.map(|path| load_script(&path));
+        // The prompt of the window, which stays open for commands and typing ahead.
+        let mut window_prompt = None;
+        // With `--tray`, the chat was brought up from there, for the next prompt to be shown.
+        let mut raised = false;
+        'events: loop {
+            ui.send_scheduled();
+            // The window's input is always there, only telling who a message goes to, while otherwise,
+            // once the events received so far are handled, a reply is prompted for if one is due.
+            if output.has_window() {
+                let prompt = ui.pending_turn()
+                    .map(|connection| tr!("prompt-send", peer = ui.conversations[connection].peer_name.as_str()));
+                if prompt != window_prompt {
+                    output.prompt(prompt.clone());
+                    window_prompt = prompt;
+                    let draft = ui.pending_turn().and_then(|connection| ui.drafts.get(&ui.conversations[connection].peer_name));
+                    if let Some(draft) = draft {
+                        output.draft(draft.to_string());
+                    }
+                }
+            } else if port.is_empty() && input_mode != InputMode::Headless {
+                if let Some(connection) = ui.pending_turn().filter(|_| tray.is_none() || raised) {
+                    raised = false;
+                    if let Some(ref mut tray) = tray {
+                        tray.read();
+                        ui.messages_seen();
+                    }
+                    ui.focused = connection;
+                    let title = tr!("title-security-cancel", role = server_or_client.as_str(), security = security.to_string());
+                    let input = loop {
+                        if ui.feedback.typing_sent {
+                            let _ = ui.conversation().component.send(ComponentControlMsg::Typing);
+                        }
+                        let peer = ui.conversation().peer_name.as_str();
+                        let prompt = tr!("prompt-send", peer = peer);
+                        let input = match input_mode.message_box(&title, &prompt, ui.drafts.get(peer).unwrap_or_default()) {
+                            Some(input) => input,
+                            None => break None,
+                        };
+                        match commands::parse(&input) {
+                            Input::Message(text) => break Some(text.to_string()),
+                            Input::Command(name, arguments) => match registry.run(&mut ui, name, &arguments) {
+                                Ok(CommandOutcome::Reply(reply)) => output.line(reply),
+                                Ok(CommandOutcome::EndTurn(reply)) => {
+                                    output.line(reply);
+                                    continue 'events;
+                                },
+                                Ok(CommandOutcome::Quit) => break None,
+                                Err(error) => output.error(error),
+                            },
+                        }
+                    };
+                    match input {
+                        Some(input) => ui.send_message(input),
+                        None => {
+                            say!(output, "quitting", role = server_or_client.as_str());
+                            ui.quit();
+                            break 'events;
+                        },
+                    }
+                    continue;
+                }
+            }
+            // Blocks until the next event, the components always ending with one.
+            let (connection, incoming) = match port.recv() {
+                Ok(event) => event,
+                Err(_) => break,
+            };
+            // Events are handled in the conversation they came from, and those of the user in the focused one.
+            if !incoming.is_from_user() {
+                ui.focused = connection;
+            }
+            if let Some(ref log) = log {
+                if let Some(line) = log_line(&incoming, connection, &ui.conversations[connection].peer_name, log_bodies) {
+                    log.write(&line);
+                }
+            }
+            let dropped = port.take_dropped();
+            if dropped > 0 {
+                say!(output, "events-dropped", count = dropped);
+            }
+            if let (MainControlMsg::IncomingMessage { ref message, .. } | MainControlMsg::IncomingMessagePart { ref message, .. }, Some(ref mut tray)) = (&incoming, tray.as_mut()) {
+                if message.kind == MessageKind::Text {
+                    tray.message_received();
+                }
+            }
+            let received = match incoming {
+                MainControlMsg::IncomingMessage { message, .. } if message.kind == MessageKind::System => {
+                    ui.conversation_mut().our_turn = true;
+                    let sender = tr!("system-sender", peer = message.sender_or(&ui.conversation().peer_name));
+                    output.system_message(message.sent_at, &sender, &message.body);
+                    continue
+                },
+                MainControlMsg::IncomingMessage { message, verified, ttl } => {
+                    ui.stats.record_received(&message.body);
+                    ui.conversation_mut().our_turn = true;
+                    Some((message, verified, ttl))
+                },
+                MainControlMsg::IncomingMessagePart { mut message, verified } => {
+                    ui.stats.record_received(&message.body);
+                    if ui.peer_ignored() {
+                        continue
+                    }
+                    let id = message.id;
+                    let sender = message.sender_or(&ui.conversation().peer_name).to_string();
+                    let (text, reply) = apply_script(&output, &script, &sender, message.body);
+                    if let Some(text) = text {
+                        alert(&output, &notification_rules, &ui.notifications, &ui.nick, &ui.conversation().peer_name, &text);
+                        let sent_at = message.sent_at;
+                        auto_replies.extend(ui.away.as_mut().and_then(|away| away.message(&sender, sent_at, &text)));
+                        message.body = text;
+                        ui.show_incoming(message, verified, None);
+                    }
+                    auto_replies.extend(reply);
+                    let _ = ui.conversation().component.send(ComponentControlMsg::MessageProcessed(id));
+                    if tray.is_none() {
+                        ui.messages_seen();
+                    }
+                    continue
+                },
+                MainControlMsg::MessageDelivered { id, text, roundtrip } => {
+                    ui.stats.record_roundtrip(roundtrip);
+                    if ui.drafts.get(&ui.conversation().peer_name) == Some(text.as_str()) {
+                        ui.save_draft(ui.focused, "");
+                    }
+                    if ui.feedback.delivery_ticks {
+                        say!(output, "message-delivered", id = id);
+                    }
+                    // Messages not sent from the prompt, such as plugin replies, weren't shown.
+                    let mut entry = ui.conversation_mut().history.take_echo(id)
+                        .unwrap_or_else(|| HistoryEntry::new(id, true, &ui.nick, SystemTime::now(), text));
+                    entry.delivery = Delivery::Delivered;
+                    ui.store_message(&mut entry);
+                    ui.record_transcript(&entry);
+                    ui.publish(&entry);
+                    ui.conversation_mut().history.push(entry);
+                    continue
+                },
+                MainControlMsg::BinaryDelivered { id, roundtrip } => {
+                    ui.stats.record_roundtrip(roundtrip);
+                    if ui.feedback.delivery_ticks {
+                        say!(output, "binary-delivered", id = id);
+                    }
+                    continue
+                },
+                MainControlMsg::FileSending { transfer, name, offset, .. } => {
+                    say!(output, "file-sending", transfer = transfer, name = name.as_str(), offset = offset);
+                    ui.conversation_mut().transfers.insert(transfer, name);
+                    continue
+                },
+                MainControlMsg::TransferProgress { id, sent, total } => {
+                    if let Some(name) = ui.conversation().transfers.get(&id) {
+                        output.progress(transfer::progress_bar(name, sent, total));
+                    }
+                    continue
+                },
+                MainControlMsg::FileSent { transfer, name } => {
+                    ui.conversation_mut().transfers.remove(&transfer);
+                    say!(output, "file-sent", name = name);
+                    continue
+                },
+                MainControlMsg::TransferCancelled { transfer, name, by_peer } => {
+                    if by_peer {
+                        say!(output, "file-cancelled-by-peer", peer = ui.conversation().peer_name.as_str(), name = name);
+                    } else {
+                        ui.conversation_mut().transfers.remove(&transfer);
+                        say!(output, "file-cancelled", name = name);
+                    }
+                    continue
+                },
+                MainControlMsg::FileIncoming { name, size, offset, .. } => {
+                    say!(output, "file-incoming", peer = ui.conversation().peer_name.as_str(), name = name, size = size, offset = offset);
+                    continue
+                },
+                MainControlMsg::FileReceived { sent_at, name, path, .. } => {
+                    let text = tr!("file-received", name = name, path = path.display().to_string());
+                    output.message(sent_at, &ui.conversation().peer_name, &text);
+                    continue
+                },
+                MainControlMsg::TransferFailed { name, error } => {
+                    ui.conversation_mut().transfers.retain(|_, sending| *sending != name);
+                    output.error(tr!("transfer-failed", name = name, error = error));
+                    continue
+                },
+                MainControlMsg::IncomingBinary { .. } if ui.peer_ignored() => continue,
+                MainControlMsg::IncomingBinary { id, sent_at, mime: Some(ref mime), ref data } if mime.starts_with("image/") => {
+                    let peer_name = ui.conversation().peer_name.clone();
+                    match images::save_received(data, mime, &peer_name) {
+                        Ok(path) => output.message(sent_at, &peer_name, &tr!("image-received", id = id, path = path.display().to_string())),
+                        Err(error) => say!(output, "image-save-failed", peer = peer_name.as_str(), error = error),
+                    }
+                    // Only drawn in a terminal, the path being enough otherwise.
+                    if io::stdout().is_terminal() {
+                        match images::thumbnail(data, THUMBNAIL_WIDTH.min(broadcast::terminal_width() as u32)) {
+                            Ok(thumbnail) => println!("{}", thumbnail),
+                            Err(error) => say!(output, "image-show-failed", error = error),
+                        }
+                    }
+                    continue
+                },
+                MainControlMsg::IncomingBinary { id, sent_at, data, .. } => {
+                    let preview: Vec<String> = data.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+                    let ellipsis = if data.len() > 8 { " ..." } else { "" };
+                    let text = tr!("binary-received", id = id, size = data.len(), preview = format!("{}{}", preview.join(" "), ellipsis));
+                    output.message(sent_at, &ui.conversation().peer_name, &text);
+                    continue
+                },
+                MainControlMsg::MessageSending { id, text, ttl } => {
+                    let mut entry = HistoryEntry::new(id, true, &ui.nick, SystemTime::now(), text);
+                    entry.never_persist = ttl.is_some();
+                    output.entry(ui.conversation_mut().history.echo(entry));
+                    if let Some(ttl) = ttl {
+                        ui.expire_after(id, true, ttl);
+                    }
+                    continue
+                },
+                MainControlMsg::SendFailed(id) => {
+                    if let Some(mut entry) = ui.conversation_mut().history.take_echo(id) {
+                        entry.delivery = Delivery::Failed;
+                        output.entry(&entry);
+                        ui.conversation_mut().history.push(entry);
+                    }
+                    continue
+                },
+                MainControlMsg::ReactionReceived { .. } |
+                MainControlMsg::PeerTyping if ui.peer_ignored() => continue,
+                MainControlMsg::ReactionReceived(reaction) => {
+                    let peer_name = reaction.sender_or(&ui.conversation().peer_name).to_string();
+                    if let Some(entry) = ui.conversation_mut().history.react(reaction.id, true, &peer_name, &reaction.body) {
+                        say!(output, "reaction-received", peer = peer_name.as_str(), emoji = reaction.body.as_str(), entry = entry.to_string());
+                    }
+                    continue
+                },
+                MainControlMsg::MessagesMissing { first, last } => {
+                    let peer = ui.conversation().peer_name.as_str();
+                    if first == last {
+                        say!(output, "message-missing", id = first, peer = peer);
+                    } else {
+                        say!(output, "messages-missing", first = first, last = last, peer = peer);
+                    }
+                    continue
+                },
+                MainControlMsg::MessageDeleted(id) => {
+                    if ui.delete_message(id, false) {
+                        say!(output, "peer-deleted", peer = ui.conversation().peer_name.as_str(), id = id);
+                    }
+                    continue
+                },
+                MainControlMsg::MessageExpired { id, own } => {
+                    if ui.conversation_mut().history.remove(id, own).is_some() {
+                        say!(output, "message-expired", id = id);
+                    }
+                    continue
+                },
+                MainControlMsg::MessageProcessed(id) => {
+                    // Only told to programs embedding the chat, the user being shown the `read` receipt, if any.
+                    if let Some(ref control) = ui.control {
+                        control.notify("processed", json!({
+                            "conversation": ui.focused + 1,
+                            "id": id,
+                        }));
+                    }
+                    continue
+                },
+                MainControlMsg::MessageRead(id) => {
+                    if ui.message_read(id) && ui.feedback.read_receipts {
+                        say!(output, "peer-read", peer = ui.conversation().peer_name.as_str(), id = id);
+                    }
+                    if let Some(ref control) = ui.control {
+                        control.notify("read", json!({
+                            "conversation": ui.focused + 1,
+                            "id": id,
+                        }));
+                    }
+                    continue
+                },
+                MainControlMsg::PeerRenamed(nick) => {
+                    // Known by our placeholder until then.
+                    let presence = if ui.conversation().peer_named {
+                        Presence::Renamed { from: ui.conversation().peer_name.clone(), to: nick.clone() }
+                    } else {
+                        Presence::Joined(nick.clone())
+                    };
+                    output.presence(presence_text(&presence));
+                    ui.conversation_mut().peer_name = nick;
+                    ui.conversation_mut().peer_named = true;
+                    ui.remember_contact(server_or_client == "client");
+                    continue
+                },
+                MainControlMsg::Presence(presence) => {
+                    output.presence(presence_text(&presence));
+                    continue
+                },
+                MainControlMsg::PeerIdentity(key) => {
+                    let fingerprint = identity::fingerprint(&key);
+                    // Servers are known by address, and clients by nickname.
+                    let conversation = ui.conversation();
+                    let peer = if server_or_client == "client" { &conversation.address } else { &conversation.peer_name }.clone();
+                    match known_peers.check(&peer, &key) {
+                        Ok(KeyCheck::New) => say!(output, "identity-first-session", peer = peer.as_str(), fingerprint = fingerprint.as_str()),
+                        Ok(KeyCheck::Known) => {},
+                        Ok(KeyCheck::Changed(previous)) => {
+                            let location = known_peers.path().map(|path| path.display().to_string()).unwrap_or_default();
+                            let warning = tr!("identity-changed", peer = peer.as_str(), previous = previous,
+                                              fingerprint = fingerprint.as_str(), location = location);
+                            input_mode.warn(&tr!("title", role = server_or_client.as_str()), &warning);
+                        },
+                        Err(error) => say!(output, "identity-remember-failed", peer = peer.as_str(), error = error),
+                    }
+                    ui.conversation_mut().peer_identity = Some(fingerprint);
+                    ui.remember_contact(server_or_client == "client");
+                    continue
+                },
+                MainControlMsg::SessionTimeLeft(left) => {
+                    say!(output, "session-ends-in", duration = session::format_duration(left));
+                    continue
+                },
+                MainControlMsg::SessionExpired => {
+                    say!(output, "session-time-up");
+                    continue
+                },
+                MainControlMsg::PeerTyping => {
+                    if ui.feedback.typing_received {
+                        say!(output, "peer-typing", peer = ui.conversation().peer_name.as_str());
+                    }
+                    continue
+                },
+                MainControlMsg::PasswordRequired |
+                MainControlMsg::CredentialsRequired if ui.conversation().resume_token.is_some() => {
+                    let conversation = ui.conversation_mut();
+                    let token = conversation.resume_token.take().unwrap();
+                    let _ = conversation.component.send(ComponentControlMsg::ResumeToken(token));
+                    continue
+                },
+                MainControlMsg::PasswordRequired => {
+                    let title = tr!("title", role = server_or_client.as_str());
+                    match input_mode.password_box(&title, &tr!("prompt-password")) {
+                        Some(password) => {
+                            let _ = ui.conversation().component.send(ComponentControlMsg::Password(password));
+                        },
+                        None => {
+                            let _ = ui.conversation().component.send(ComponentControlMsg::Quit);
+                        },
+                    }
+                    continue
+                },
+                MainControlMsg::CredentialsRequired => {
+                    if security == SecurityLevel::Plaintext {
+                        output.error(tr!("cleartext-password-asked"));
+                    }
+                    let title = tr!("title", role = server_or_client.as_str());
+                    let credentials = input_mode.input_box(&title, &tr!("prompt-user"), &ui.nick)
+                        .and_then(|user| {
+                            input_mode.password_box(&title, &tr!("prompt-password-only")).map(|password| (user, password))
+                        });
+                    match credentials {
+                        Some((user, password)) => {
+                            let _ = ui.conversation().component.send(ComponentControlMsg::Credentials { user, password });
+                        },
+                        None => {
+                            let _ = ui.conversation().component.send(ComponentControlMsg::Quit);
+                        },
+                    }
+                    continue
+                },
+                MainControlMsg::AuthenticatorError(error) => {
+                    say!(output, "authenticator-failed", error = error);
+                    continue
+                },
+                MainControlMsg::Error(error) => {
+                    output.error(error.to_string());
+                    continue
+                },
+                MainControlMsg::ProtocolViolation(violation) => {
+                    output.error(tr!("frame-skipped", peer = ui.conversation().peer_name.as_str(), error = violation.to_string()));
+                    continue
+                },
+                MainControlMsg::ResumeTokenIssued(token) => {
+                    if let Err(error) = resume::save_client_token(&ui.conversation().address, &token) {
+                        say!(output, "token-save-failed", error = error);
+                    }
+                    continue
+                },
+                MainControlMsg::PluginReply { plugin, text } => {
+                    ui.stats.record_sent(&text);
+                    output.message(SystemTime::now(), &tr!("plugin-sender", plugin = plugin), &text);
+                    continue
+                },
+                MainControlMsg::MessageInjected(text) => {
+                    ui.stats.record_sent(&text);
+                    output.message(SystemTime::now(), "webhook", &text);
+                    continue
+                },
+                MainControlMsg::MessageQueued(id) => {
+                    ui.stats.record_delivery_failure();
+                    say!(output, "message-queued", id = id);
+                    continue
+                },
+                // Already reported by `/cancel`.
+                MainControlMsg::MessageCancelled(_) => continue,
+                MainControlMsg::ClientThrottled => {
+                    say!(output, "client-throttled");
+                    continue
+                },
+                MainControlMsg::TrayActivated => {
+                    raised = true;
+                    if let Some(ref mut tray) = tray {
+                        tray.read();
+                        ui.messages_seen();
+                    }
+                    output.raise();
+                    continue
+                },
+                MainControlMsg::Throttled { id, retry_after, dropped } => {
+                    // Rounded up, not to retry too soon.
+                    let seconds = (retry_after.as_millis() as u64).div_ceil(1000);
+                    if dropped {
+                        output.error(tr!("throttled-dropped", id = id, seconds = seconds));
+                    } else {
+                        output.error(tr!("throttled-delayed", id = id, seconds = seconds));
+                    }
+                    if let Some(ref control) = ui.control {
+                        control.notify("throttled", json!({
+                            "conversation": ui.focused + 1,
+                            "id": id,
+                            "retry_after_ms": retry_after.as_millis() as u64,
+                            "dropped": dropped,
+                        }));
+                    }
+                    continue
+                },
+                MainControlMsg::AuthenticationFailed => {
+                    say!(output, "client-auth-failed");
+                    continue
+                },
+                MainControlMsg::QueueChanged(waiting) => {
+                    ui.visitors_waiting = waiting;
+                    say!(output, "visitors-waiting", count = waiting);
+                    continue
+                },
+                MainControlMsg::VisitorServed => {
+                    ui.visitors_served += 1;
+                    ui.conversation_mut().peer_name = format!("visitor{}", ui.visitors_served);
+                    ui.transcript = match Transcript::create_default(&ui.conversation().peer_name) {
+                        Ok(transcript) => Some(transcript),
+                        Err(error) => {
+                            say!(output, "transcript-start-failed", error = error);
+                            None
+                        },
+                    };
+                    say!(output, "visitor-served", peer = ui.conversation().peer_name.as_str());
+                    continue
+                },
+                MainControlMsg::VisitorLeft => {
+                    match ui.transcript.take() {
+                        Some(transcript) => say!(output, "visitor-left-transcript", peer = ui.conversation().peer_name.as_str(),
+                                                 path = transcript.path().display().to_string()),
+                        None => say!(output, "visitor-left", peer = ui.conversation().peer_name.as_str()),
+                    }
+                    // Between conversations, only commands can be run, until the operator is ready.
+                    let title = tr!("title-cancel", role = server_or_client.as_str());
+                    loop {
+                        let prompt = tr!("prompt-visitors", count = ui.visitors_waiting);
+                        let input = match input_mode.input_box(&title, &prompt, "") {
+                            Some(input) => input,
+                            None => {
+                                say!(output, "quitting", role = server_or_client.as_str());
+                                ui.quit();
+                                break 'events;
+                            },
+                        };
+                        match commands::parse(&input) {
+                            Input::Command("next", _) => break,
+                            Input::Command(name, arguments) => match registry.run(&mut ui, name, &arguments) {
+                                Ok(CommandOutcome::Reply(reply)) => output.line(reply),
+                                Ok(CommandOutcome::EndTurn(_)) => say!(output, "no-visitor"),
+                                Ok(CommandOutcome::Quit) => {
+                                    say!(output, "quitting", role = server_or_client.as_str());
+                                    ui.quit();
+                                    break 'events;
+                                },
+                                Err(error) => output.error(error),
+                            },
+                            Input::Message(_) => say!(output, "no-visitor-next"),
+                        }
+                    }
+                    let _ = ui.conversation().component.send(ComponentControlMsg::NextVisitor);
+                    continue
+                },
+                MainControlMsg::BroadcastReady => {
+                    ui.conversation_mut().our_turn = true;
+                    None
+                },
+                MainControlMsg::AnnouncementSent { listeners, .. } => {
+                    say!(output, "announcement-sent", count = listeners);
+                    ui.conversation_mut().our_turn = true;
+                    None
+                },
+                MainControlMsg::ListenersChanged(listeners) => {
+                    say!(output, "listeners-changed", count = listeners);
+                    continue
+                },
+                MainControlMsg::ClientAddress(address) => {
+                    ui.conversation_mut().peer_address = Some(address);
+                    continue
+                },
+                MainControlMsg::BannedClientRefused(address) => {
+                    say!(output, "client-banned", address = address.to_string());
+                    continue
+                },
+                MainControlMsg::WorkersSaturated { workers, waiting } => {
+                    ui.stats.record_saturation(waiting);
+                    say!(output, "workers-saturated", workers = workers, waiting = waiting);
+                    continue
+                },
+                MainControlMsg::ServerFullRefused(address) => {
+                    let address = address.map_or_else(|| tr!("unknown-address"), |address| address.to_string());
+                    say!(output, "client-refused-full", address = address);
+                    continue
+                },
+                MainControlMsg::Announcement { sent_at, text } => {
+                    ui.stats.record_received(&text);
+                    output.line(broadcast::render_announcement(sent_at, &text, broadcast::terminal_width()));
+                    continue
+                },
+                MainControlMsg::Whisper { sent_at, text } => {
+                    ui.stats.record_received(&text);
+                    output.message(sent_at, &tr!("whisper-from", peer = ui.conversation().peer_name.as_str()), &text);
+                    continue
+                },
+                MainControlMsg::WhisperUndelivered(nick) => {
+                    say!(output, "whisper-undelivered", nick = nick);
+                    continue
+                },
+                MainControlMsg::PeerReached { relayed } => {
+                    if relayed {
+                        say!(output, "p2p-relayed");
+                    } else {
+                        say!(output, "p2p-punched");
+                    }
+                    continue
+                },
+                MainControlMsg::PeerConnected { first } => {
+                    if first {
+                        say!(output, "p2p-first");
+                        ui.conversation_mut().our_turn = true;
+                    } else {
+                        say!(output, "p2p-second");
+                    }
+                    continue
+                },
+                MainControlMsg::QueuePosition(position) => {
+                    say!(output, "queue-position", position = position);
+                    continue
+                },
+                MainControlMsg::StatsDue => {
+                    output.line(ui.stats.to_string());
+                    continue
+                },
+                // Sent as the loop starts over, if the peer waits for our reply, or once it does.
+                MainControlMsg::ScheduledDue => continue,
+                MainControlMsg::MessageFiltered { id, action, words } => {
+                    let words = words.join(", ");
+                    match action {
+                        FilterAction::Drop => say!(output, "filter-dropped", id = id, words = words.as_str()),
+                        FilterAction::Redact => say!(output, "filter-redacted", id = id, words = words.as_str()),
+                        FilterAction::Flag => say!(output, "filter-flagged", id = id, words = words.as_str()),
+                    }
+                    if let Some(ref control) = ui.control {
+                        control.notify("filtered", json!({
+                            "conversation": ui.focused + 1,
+                            "id": id,
+                            "action": action.name(),
+                            "words": words,
+                        }));
+                    }
+                    continue
+                },
+                MainControlMsg::PingDue => {
+                    // The peer only reads our frames on our turn.
+                    for conversation in ui.conversations.iter().filter(|conversation| conversation.our_turn && !conversation.closed) {
+                        let _ = conversation.component.send(ComponentControlMsg::Ping);
+                    }
+                    continue
+                },
+                MainControlMsg::PingAnswered(roundtrip) => {
+                    let conversation = ui.conversation_mut();
+                    conversation.link.record(roundtrip);
+                    if conversation.pings_asked > 0 {
+                        conversation.pings_asked -= 1;
+                        let peer = conversation.peer_name.as_str();
+                        match roundtrip {
+                            Some(roundtrip) => say!(output, "ping-answered", peer = peer, roundtrip = format!("{:.1?}", roundtrip),
+                                                    link = conversation.link.to_string()),
+                            None => say!(output, "ping-lost", peer = peer, link = conversation.link.to_string()),
+                        }
+                    }
+                    let state = ui.conversation().component.state();
+                    output.status(ui.status_text(state), state == ConnectionState::Active);
+                    continue
+                },
+                MainControlMsg::ConfigChanged => {
+                    match ui.reload_config() {
+                        Ok(changed) if !changed.is_empty() => say!(output, "config-reloaded", settings = changed.join(", ")),
+                        Ok(_) => {},
+                        Err(error) => say!(output, "config-reload-failed", error = error),
+                    }
+                    continue
+                },
+                MainControlMsg::BanListChanged => {
+                    if let Some(ref bans) = ui.bans {
+                        bans.lock().unwrap().reload();
+                        say!(output, "bans-reloaded");
+                    }
+                    continue
+                },
+                #[cfg(feature = "gui")]
+                MainControlMsg::Window(gui::Event::Typing) => {
+                    if let (Some(connection), true) = (ui.pending_turn(), ui.feedback.typing_sent) {
+                        let _ = ui.conversations[connection].component.send(ComponentControlMsg::Typing);
+                    }
+                    continue
+                },
+                #[cfg(feature = "gui")]
+                MainControlMsg::Window(gui::Event::Draft(draft)) => {
+                    // A command being typed is no message.
+                    if let Input::Message(_) = commands::parse(&draft) {
+                        let connection = ui.pending_turn().unwrap_or(ui.focused);
+                        ui.save_draft(connection, &draft);
+                    }
+                    continue
+                },
+                #[cfg(feature = "gui")]
+                MainControlMsg::Window(gui::Event::Line(input)) => {
+                    if let Some(ref mut tray) = tray {
+                        tray.read();
+                        ui.messages_seen();
+                    }
+                    // Run as from the prompt, in the conversation waiting for a reply if any.
+                    if let Some(connection) = ui.pending_turn() {
+                        ui.focused = connection;
+                    }
+                    match commands::parse(&input) {
+                        // Kept as a draft, to be sent once it is our turn.
+                        Input::Message(_) if ui.pending_turn().is_none() => {
+                            say!(output, "waiting-for-reply-draft", peer = ui.conversation().peer_name.as_str());
+                        },
+                        Input::Message(text) => ui.send_message(text.to_string()),
+                        Input::Command(name, arguments) => match registry.run(&mut ui, name, &arguments) {
+                            Ok(CommandOutcome::Reply(reply)) |
+                            Ok(CommandOutcome::EndTurn(reply)) => output.line(reply),
+                            Ok(CommandOutcome::Quit) => {
+                                say!(output, "quitting", role = server_or_client.as_str());
+                                ui.quit();
+                                break 'events;
+                            },
+                            Err(error) => output.error(error),
+                        },
+                    }
+                    continue
+                },
+                #[cfg(feature = "gui")]
+                MainControlMsg::Window(gui::Event::Zoomed(zoom)) => {
+                    if let Err(error) = ui.save_zoom(zoom) {
+                        output.error(error);
+                    }
+                    continue
+                },
+                #[cfg(feature = "gui")]
+                MainControlMsg::Window(gui::Event::Closed) => {
+                    say!(output, "quitting", role = server_or_client.as_str());
+                    ui.quit();
+                    break 'events;
+                },
+                MainControlMsg::Control(request) => {
+                    let control = ui.control.clone().expect("only the control socket sends requests");
+                    let result = match request.method {
+                        control::Method::Send { ref text, conversation } => {
+                            ui.send_to(conversation, text.clone()).map(|conversation| json!(conversation))
+                        },
+                        control::Method::Subscribe => {
+                            control.subscribe(request.client);
+                            Ok(json!(true))
+                        },
+                        control::Method::ListPeers => Ok(ui.peers()),
+                        control::Method::Shutdown => {
+                            control.reply(&request, Ok(json!(true)));
+                            say!(output, "quitting", role = server_or_client.as_str());
+                            ui.quit();
+                            break 'events;
+                        },
+                    };
+                    control.reply(&request, result);
+                    continue
+                },
+                MainControlMsg::ConnectionStateChanged(state) => {
+                    output.status(ui.status_text(state), state == ConnectionState::Active);
+                    continue
+                },
+                MainControlMsg::ConnectAttemptFailed { error, attempt, retries } => {
+                    say!(output, "connect-failed", address = ui.conversation().address.as_str(), error = error,
+                         attempt = attempt, attempts = retries + 1);
+                    continue
+                },
+                MainControlMsg::ConnectRetryIn(seconds) => {
+                    output.status(tr!("connect-retry-in", seconds = seconds), false);
+                    continue
+                },
+                MainControlMsg::SocketConfigured(_) => continue,
+                MainControlMsg::ClientDisconnected(reason) => {
+                    assert_ne!(server_or_client, "server");
+                    let several = ui.conversations.len() > 1;
+                    let conversation = ui.conversation_mut();
+                    conversation.closed = true;
+                    if reason != DisconnectReason::Quit && reason != DisconnectReason::SessionExpired {
+                        if several {
+                            say!(output, "disconnected-from", address = conversation.address.as_str(), reason = reason.to_string());
+                        } else {
+                            say!(output, "disconnected", reason = reason.to_string());
+                        }
+                    }
+                    if ui.conversations.iter().all(|conversation| conversation.closed) {
+                        break;
+                    }
+                    continue
+                },
+                MainControlMsg::ServerShutDown(reason) => {
+                    assert_eq!(server_or_client, "server");
+                    if reason != DisconnectReason::Quit {
+                        say!(output, "server-shut-down", reason = reason.to_string());
+                    }
+                    break;
+                },
+            };
+            // An ignored peer's message is dropped, but it is still our turn.
+            if let (Some((mut message, verified, ttl)), false) = (received, ui.peer_ignored()) {
+                let id = message.id;
+                let sender = message.sender_or(&ui.conversation().peer_name).to_string();
+                let (received, reply) = apply_script(&output, &script, &sender, message.body);
+                if let Some(received) = received {
+                    alert(&output, &notification_rules, &ui.notifications, &ui.nick, &ui.conversation().peer_name, &received);
+                    let sent_at = message.sent_at;
+                    auto_replies.extend(ui.away.as_mut().and_then(|away| away.message(&sender, sent_at, &received)));
+                    message.body = received;
+                    ui.show_incoming(message, verified, ttl);
+                }
+                auto_replies.extend(reply);
+                let _ = ui.conversation().component.send(ComponentControlMsg::MessageProcessed(id));
+                if tray.is_none() {
+                    ui.messages_seen();
+                }
+            }
+            if !auto_replies.is_empty() {
+                let reply = auto_replies.join("\n");
+                auto_replies.clear();
+                output.message(SystemTime::now(), "auto-reply", &reply);
+                ui.send_message(reply);
+                continue;
+            }
+        }
+        output.line(ui.stats.to_string());
+        if let Some(path) = summary_log {
+            let logged = OpenOptions::new().create(true).append(true).open(&path)
+                .and_then(|mut file| writeln!(file, "[{}] {} {}", Local::now().to_rfc3339(), server_or_client, ui.stats));
+            if let Err(error) = logged {
+                say!(output, "summary-log-failed", path = path.as_str(), error = error.to_string());
+            }
         }
+    };
+    match window {
+        Some((updates, options)) => run_in_window(&title, updates, options, events, chat),
+        None => chat(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread::sleep;
+    use pipe::{self, PipeEnd};
+    use std::cell::Cell;
+
+    fn incoming_text(msg: MainControlMsg) -> String {
+        match msg {
+            MainControlMsg::IncomingMessage { message, .. } => {
+                // The sender's timestamp should be recent.
+                assert!(message.sent_at.elapsed().unwrap() < Duration::new(5, 0));
+                assert_eq!(message.kind, MessageKind::Text);
+                message.body
+            },
+            _ => panic!("expected an incoming message"),
+        }
+    }
 
     #[test]
     fn test_server_and_client_messaging() {
-        let (server_chan, server_port) = channel();
-        let (client_chan, client_port) = channel();
-        let server = start_server(server_chan);
-        // Ensure the server has had time to start.
-        sleep(Duration::new(1, 0));
-        let client = start_client(client_chan.clone());
-        let mut server_msgs = server_port.iter();
-        let mut client_msgs = client_port.iter();
-        assert!(client_msgs.next().is_some());
+        let (server_chan, server_port) = channel::bounded(ChannelConfig::default());
+        let (client_chan, client_port) = channel::bounded(ChannelConfig::default());
+        let address = format!("{}:{}", DEFAULT_ADDRESS, DEFAULT_PORT);
+        // In memory, the client connecting to the listener as soon as it is there.
+        let (listener, connector) = pipe::pipe_listener();
+        let server = start_server_with(EventSender::new(0, server_chan), listener, Default::default());
+        let connect = connector.clone();
+        let client = start_client_with(EventSender::new(0, client_chan.clone()), address.clone(), move || connect.connect(), Default::default());
+        // State changes are checked on the handles.
+        let mut server_msgs = server_port.iter().map(|(_, msg)| msg).filter(|msg| !matches!(msg, MainControlMsg::ConnectionStateChanged(_)));
+        let mut client_msgs = client_port.iter().map(|(_, msg)| msg).filter(|msg| !matches!(msg, MainControlMsg::ConnectionStateChanged(_)));
+        // The handshake is the message of the day, apart from the chat.
+        assert!(matches!(client_msgs.next(), Some(MainControlMsg::IncomingMessage { ref message, .. })
+                         if message.kind == MessageKind::System && message.body == DEFAULT_MOTD));
 
         // Send a message to the server, via the client component.
         let _ = client.send(ComponentControlMsg::OutgoingMessage("test one".to_string()));
-        let from_client_message = "test one\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}".to_string();
-        assert_eq!(server_msgs.next().unwrap(), MainControlMsg::IncomingMessage(from_client_message));
+        // Shown before being delivered.
+        assert_eq!(client_msgs.next().unwrap(), MainControlMsg::MessageSending { id: 1, text: "test one".to_string(), ttl: None });
+        let local = DEFAULT_ADDRESS.parse().unwrap();
+        assert_eq!(server_msgs.next().unwrap(), MainControlMsg::ClientAddress(local));
+        assert_eq!(incoming_text(server_msgs.next().unwrap()), "test one");
+        assert_eq!(client.state(), ConnectionState::Active);
+        assert_eq!(server.state(), ConnectionState::Active);
         // Check that we got the roundtrip message from the client component.
         let mut roundtrip = false;
-        if let Some(MainControlMsg::RoundTrip(_)) = client_msgs.next() {
+        if let Some(MainControlMsg::MessageDelivered { .. }) = client_msgs.next() {
             roundtrip = true;
         }
         assert!(roundtrip);
+        // Confirm the message was processed by the server UI.
+        let _ = server.send(ComponentControlMsg::MessageProcessed(1));
+        assert_eq!(client_msgs.next().unwrap(), MainControlMsg::MessageProcessed(1));
+        // Then seen by its user.
+        let _ = server.send(ComponentControlMsg::MessageRead(1));
+        assert_eq!(client_msgs.next().unwrap(), MainControlMsg::MessageRead(1));
 
         // Send a message to the client, via the server.
         let _ = server.send(ComponentControlMsg::OutgoingMessage("test two".to_string()));
-        let from_server_message = "test two\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}".to_string();
-        assert_eq!(client_msgs.next().unwrap(), MainControlMsg::IncomingMessage(from_server_message));
+        assert_eq!(incoming_text(client_msgs.next().unwrap()), "test two");
+        assert_eq!(server_msgs.next().unwrap(), MainControlMsg::MessageSending { id: 2, text: "test two".to_string(), ttl: None });
         // Check that we got the roundtrip message from the server component.
         let mut server_roundtrip = false;
-        if let Some(MainControlMsg::RoundTrip(_)) = server_msgs.next() {
+        if let Some(MainControlMsg::MessageDelivered { .. }) = server_msgs.next() {
             server_roundtrip = true;
         }
         assert!(server_roundtrip);
@@ -223,21 +5252,25 @@ mod tests {
         let _ = client.send(ComponentControlMsg::Quit);
         // Check that the client disconnects
         let disconnect = client_msgs.next().unwrap();
-        assert_eq!(MainControlMsg::ClientDisconnected, disconnect);
+        assert_eq!(MainControlMsg::ClientDisconnected(DisconnectReason::Quit), disconnect);
+        assert_eq!(client.state(), ConnectionState::Closed);
 
         // Start a new client.
-        let client_2 = start_client(client_chan);
+        // Using another wire format.
+        let client_2 = start_client_with(EventSender::new(1, client_chan), address, move || connector.connect(),
+                                         ClientOptions { wire_format: WireFormat::Json, ..Default::default() });
         // Check that we got the "let's chat" handshake from the server.
         assert!(client_msgs.next().is_some());
 
         // Send a message to the server, via the new client component.
-        let _ = client_2.send(ComponentControlMsg::OutgoingMessage("test three".to_string()));
-        let from_client_2_message = "test three\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}".to_string();
-        assert_eq!(server_msgs.next().unwrap(), MainControlMsg::IncomingMessage(from_client_2_message));
+        let _ = client_2.send(ComponentControlMsg::OutgoingMessage("test three, longer than a single frame".to_string()));
+        assert_eq!(server_msgs.next().unwrap(), MainControlMsg::ClientAddress(local));
+        assert_eq!(incoming_text(server_msgs.next().unwrap()), "test three, longer than a single frame");
+        assert!(matches!(client_msgs.next(), Some(MainControlMsg::MessageSending { id: 1, .. })));
 
         // Check that we got the roundtrip message from the client component.
         let mut roundtrip_2 = false;
-        if let Some(MainControlMsg::RoundTrip(_)) = client_msgs.next() {
+        if let Some(MainControlMsg::MessageDelivered { .. }) = client_msgs.next() {
             roundtrip_2 = true;
         }
         assert!(roundtrip_2);
@@ -246,10 +5279,458 @@ mod tests {
         let _ = server.send(ComponentControlMsg::Quit);
         // Check that the server shuts down.
         let disconnect = server_msgs.next().unwrap();
-        assert_eq!(MainControlMsg::ServerShutDown, disconnect);
+        assert_eq!(MainControlMsg::ServerShutDown(DisconnectReason::Quit), disconnect);
+        assert_eq!(server.state(), ConnectionState::Closed);
 
         // Check that the client disconnects when the server is gone.
         let disconnect = client_msgs.next().unwrap();
-        assert_eq!(MainControlMsg::ClientDisconnected, disconnect);
+        assert_eq!(MainControlMsg::ClientDisconnected(DisconnectReason::PeerQuit), disconnect);
+    }
+
+    // A peer reading the messages sent to it, acknowledging them as `acknowledge` tells it to,
+    // and returning their texts once the connection is closed.
+    fn start_peer<F>(mut acknowledge: F) -> (FrameStream<PipeEnd>, thread::JoinHandle<Vec<String>>)
+        where F: FnMut(&mut FrameStream<PipeEnd>, MessageId, &[String]) + Send + 'static {
+        let (ours, theirs) = pipe::pipe();
+        let peer = thread::spawn(move || {
+            let mut stream = FrameStream::accept(theirs).unwrap();
+            let mut received = vec![];
+            while let Ok((_, frame)) = stream.read_frame() {
+                if let Frame::MessagePart { id, text, .. } = frame {
+                    received.push(text);
+                    acknowledge(&mut stream, id, &received);
+                }
+            }
+            received
+        });
+        let stream = FrameStream::connect(ours, WireFormat::Bincode).unwrap();
+        (stream, peer)
+    }
+
+    fn messages(texts: &[&str]) -> VecDeque<(SystemTime, String)> {
+        texts.iter().map(|text| (SystemTime::now(), text.to_string())).collect()
+    }
+
+    // The messages of a component, but for its state changes, checked on the handles.
+    fn messages_of(port: &channel::Receiver<(usize, MainControlMsg)>) -> impl Iterator<Item=MainControlMsg> + '_ {
+        port.iter().map(|(_, msg)| msg).filter(|msg| !matches!(msg, MainControlMsg::ConnectionStateChanged(_)))
+    }
+
+    // The handle of a component, and where its messages arrive.
+    type Component = (ComponentHandle<ComponentControlMsg>, channel::Receiver<(usize, MainControlMsg)>);
+
+    // A server, and a client connecting to it through the proxy.
+    fn start_proxied(conditions: NetworkConditions) -> (Component, Component) {
+        let (server_chan, server_port) = channel::bounded(ChannelConfig::default());
+        let (client_chan, client_port) = channel::bounded(ChannelConfig::default());
+        let (listener, server_connector) = pipe::pipe_listener();
+        let (proxy_listener, connector) = pipe::pipe_listener();
+        proxy::start_proxy(proxy_listener, move || server_connector.connect(), conditions);
+        let server = start_server_with(EventSender::new(0, server_chan), listener, Default::default());
+        let client = start_client_with(EventSender::new(0, client_chan), "proxy".to_string(), move || connector.connect(), Default::default());
+        ((server, server_port), (client, client_port))
+    }
+
+    #[test]
+    fn test_network_conditions() {
+        // Slow, the messages still arrive, in order.
+        let ((server, server_port), (client, client_port)) = start_proxied(NetworkConditions {
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(50),
+            seed: Some(1),
+            ..Default::default()
+        });
+        let (mut server_msgs, mut client_msgs) = (messages_of(&server_port), messages_of(&client_port));
+        assert!(client_msgs.next().is_some());
+        let _ = client.send(ComponentControlMsg::OutgoingMessage("slowly".to_string()));
+        assert!(matches!(server_msgs.next(), Some(MainControlMsg::ClientAddress(_))));
+        assert_eq!(incoming_text(server_msgs.next().unwrap()), "slowly");
+        assert!(matches!(client_msgs.nth(1), Some(MainControlMsg::MessageDelivered { .. })));
+        let _ = server.send(ComponentControlMsg::Quit);
+
+        // Dropped before the handshake, the client is told rather than left waiting.
+        let ((server, _server_port), (client, client_port)) = start_proxied(NetworkConditions {
+            drop: 1.0,
+            ..Default::default()
+        });
+        let mut client_msgs = messages_of(&client_port);
+        assert!(matches!(client_msgs.next(), Some(MainControlMsg::ClientDisconnected(_))));
+        assert_eq!(client.state(), ConnectionState::Closed);
+        let _ = server.send(ComponentControlMsg::Quit);
+    }
+
+    #[test]
+    fn test_ack_timeout() {
+        // Only acknowledging messages the second time they are sent.
+        let (mut stream, peer) = start_peer(|stream, id, received| {
+            if received.iter().filter(|text| **text == received[received.len() - 1]).count() > 1 {
+                let _ = stream.write_ack(id);
+            }
+        });
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let main_chan = EventSender::new(0, chan);
+
+        let mut outgoing = Outgoing::new(AckPolicy { timeout: Duration::from_millis(100), retries: 1 }, None);
+        assert!(deliver(&mut stream, &main_chan, messages(&["once"]), false, &mut outgoing).is_ok());
+        assert!(matches!(port.recv().unwrap().1, MainControlMsg::MessageDelivered { id: 1, .. }));
+
+        outgoing.ack.retries = 0;
+        assert_eq!(deliver(&mut stream, &main_chan, messages(&["never"]), false, &mut outgoing).unwrap_err().len(), 1);
+        assert_eq!(port.recv().unwrap().1, MainControlMsg::SendFailed(2));
+
+        stream.get_ref().shutdown();
+        assert_eq!(peer.join().unwrap(), vec!["once", "once", "never"]);
+    }
+
+    #[test]
+    fn test_pipelined_delivery() {
+        // Acknowledging the messages only once all are received, the last one first.
+        let (mut stream, peer) = start_peer(|stream, _, received| {
+            if received.len() == 3 {
+                for id in (1..=3).rev() {
+                    let _ = stream.write_ack(id);
+                }
+            }
+        });
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let main_chan = EventSender::new(0, chan);
+
+        let mut outgoing = Outgoing::new(AckPolicy { timeout: Duration::from_secs(5), retries: 0 }, None);
+        assert!(deliver(&mut stream, &main_chan, messages(&["one", "two", "three"]), false, &mut outgoing).is_ok());
+        for expected in (1..=3).rev() {
+            assert!(matches!(port.recv().unwrap().1, MainControlMsg::MessageDelivered { id, .. } if id == expected));
+        }
+
+        stream.get_ref().shutdown();
+        assert_eq!(peer.join().unwrap(), vec!["one", "two", "three"]);
+    }
+
+    #[test]
+    fn test_long_message() {
+        // Too long for a frame in any format, JSON escaping the control character in six.
+        let text = "\u{1}é".repeat(200 * 1024 / 3);
+        for format in [WireFormat::Bincode, WireFormat::Json, WireFormat::MessagePack].iter() {
+            let (ours, theirs) = pipe::pipe();
+            let mut stream = FrameStream::connect(ours, *format).unwrap();
+            let mut peer = FrameStream::accept(theirs).unwrap();
+            let (chan, port) = channel::bounded(ChannelConfig::default());
+            let main_chan = EventSender::new(0, chan);
+            let key = SigningKey::from_bytes(&[1; 32]);
+            let mut outgoing = Outgoing::new(AckPolicy::default(), Some(key.clone()));
+            let id = outgoing.send(&mut stream, &main_chan, SystemTime::now(), Payload::Text(text.clone()), true);
+            assert!(id > 1);
+
+            let (peer_chan, peer_port) = channel::bounded(ChannelConfig::default());
+            let (_control_chan, control_port) = channel::bounded(ChannelConfig::default());
+            let mut incoming = IncomingMessages::new(REORDER_WINDOW);
+            incoming.peer_key = Some(key.verifying_key());
+            let received = wait_for_message(&mut peer, &EventSender::new(0, peer_chan), &control_port, &SharedState::new(),
+                                            &SessionLimit::new(), &mut None, &mut incoming);
+            assert_eq!(received.unwrap(), vec![text.clone()]);
+            let joined = messages_of(&peer_port).next().unwrap();
+            assert!(matches!(joined, MainControlMsg::IncomingMessage { ref message, verified: true, .. } if message.id == id && message.body == text));
+            assert!(await_acks(&mut stream, &main_chan, &mut outgoing).is_ok());
+            assert!(matches!(port.recv().unwrap().1, MainControlMsg::MessageDelivered { id: delivered, .. } if delivered == id));
+        }
+    }
+
+    #[test]
+    fn test_signed_messages() {
+        let (ours, theirs) = pipe::pipe();
+        let mut stream = FrameStream::connect(ours, WireFormat::Bincode).unwrap();
+        let mut peer = FrameStream::accept(theirs).unwrap();
+        let alice = SigningKey::from_bytes(&[1; 32]);
+        let mallory = SigningKey::from_bytes(&[2; 32]);
+        peer.write_frame(SystemTime::now(), &Frame::Identity(alice.verifying_key().to_bytes().to_vec())).unwrap();
+        // Made up by whoever relays the peer's messages.
+        let forged = chat_frame(1, Payload::Text("send me your password".to_string()), false, Some(&mallory));
+        peer.write_frame(SystemTime::now(), &forged).unwrap();
+        peer.write_frame(SystemTime::now(), &chat_frame(2, Payload::Text("hi".to_string()), true, Some(&alice))).unwrap();
+
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let (_control_chan, control_port) = channel::bounded(ChannelConfig::default());
+        let mut incoming = IncomingMessages::new(REORDER_WINDOW);
+        let received = wait_for_message(&mut stream, &EventSender::new(0, chan), &control_port, &SharedState::new(),
+                                        &SessionLimit::new(), &mut None, &mut incoming);
+        assert_eq!(received.unwrap(), vec!["send me your password", "hi"]);
+        let mut events = messages_of(&port);
+        assert!(matches!(events.next(), Some(MainControlMsg::PeerIdentity(_))));
+        assert!(matches!(events.next(), Some(MainControlMsg::IncomingMessagePart { ref message, verified: false }) if message.id == 1));
+        assert!(matches!(events.next(), Some(MainControlMsg::IncomingMessage { ref message, verified: true, .. }) if message.id == 2));
+    }
+
+    #[test]
+    fn test_max_clients() {
+        let (server_chan, server_port) = channel::bounded(ChannelConfig::default());
+        let (listener, connector) = pipe::pipe_listener();
+        let options = ServerOptions { support_queue: true, max_clients: Some(1), ..Default::default() };
+        let _server = start_server_with(EventSender::new(0, server_chan), listener, options);
+        let start_client = || {
+            let (chan, port) = channel::bounded(ChannelConfig::default());
+            let connect = connector.clone();
+            let client = start_client_with(EventSender::new(0, chan), "pipe".to_string(), move || connect.connect(), Default::default());
+            (client, port)
+        };
+        // Served before the second one connects.
+        let (_served, served_port) = start_client();
+        assert!(messages_of(&served_port).any(|msg| matches!(msg, MainControlMsg::IncomingMessage { ref message, .. } if message.kind == MessageKind::System)));
+
+        let (_refused, refused_port) = start_client();
+        let reason = messages_of(&refused_port).find_map(|msg| match msg {
+            MainControlMsg::ClientDisconnected(reason) => Some(reason),
+            _ => None,
+        });
+        assert_eq!(reason, Some(DisconnectReason::ServerFull(1)));
+        assert!(messages_of(&server_port).any(|msg| matches!(msg, MainControlMsg::ServerFullRefused(Some(_)))));
+    }
+
+    #[test]
+    fn test_idle_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let listeners = Arc::new(Listeners::default());
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        accept_listeners(listener, listeners.clone(), Default::default(), EventSender::new(0, chan));
+        // Connected, without choosing its wire format.
+        let _idle = TcpStream::connect(address).unwrap();
+        let _listener = FrameStream::connect(TcpStream::connect(address).unwrap(), WireFormat::Json).unwrap();
+        assert!(messages_of(&port).any(|msg| msg == MainControlMsg::ListenersChanged(1)));
+    }
+
+    #[test]
+    fn test_idle_visitor() {
+        let (server_chan, _server_port) = channel::bounded(ChannelConfig::default());
+        let (listener, connector) = pipe::pipe_listener();
+        let options = ServerOptions { support_queue: true, ..Default::default() };
+        let _server = start_server_with(EventSender::new(0, server_chan), listener, options);
+        // Connected, without choosing its wire format.
+        let _idle = connector.connect().unwrap();
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let connect = connector.clone();
+        let _client = start_client_with(EventSender::new(0, chan), "pipe".to_string(), move || connect.connect(), Default::default());
+        assert!(messages_of(&port).any(|msg| matches!(msg, MainControlMsg::IncomingMessage { ref message, .. } if message.kind == MessageKind::System)));
+    }
+
+    #[test]
+    fn test_malformed_frames() {
+        let (ours, theirs) = pipe::pipe();
+        let mut garbage = theirs.try_clone().unwrap();
+        let mut stream = FrameStream::connect(ours, WireFormat::Json).unwrap();
+        let mut peer = FrameStream::accept(theirs).unwrap();
+        peer.write_frame(SystemTime::now(), &chat_frame(1, Payload::Text("one".to_string()), false, None)).unwrap();
+        garbage.write_all(b"\x00\xffnot a frame\n").unwrap();
+        peer.write_frame(SystemTime::now(), &chat_frame(2, Payload::Text("two".to_string()), true, None)).unwrap();
+
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let main_chan = EventSender::new(0, chan);
+        let (_control_chan, control_port) = channel::bounded(ChannelConfig::default());
+        let mut incoming = IncomingMessages::new(REORDER_WINDOW);
+        let received = wait_for_message(&mut stream, &main_chan, &control_port, &SharedState::new(),
+                                        &SessionLimit::new(), &mut None, &mut incoming);
+        // Skipped, the conversation going on.
+        assert_eq!(received.unwrap(), vec!["one", "two"]);
+        assert!(messages_of(&port).take(3).any(|msg| matches!(msg, MainControlMsg::ProtocolViolation(ProtocolViolation::Undecodable(_)))));
+
+        // A line too long to be a frame can't be skipped, the connection being given up on.
+        garbage.write_all(&vec![b'{'; codec::MAX_FRAME_SIZE + 1]).unwrap();
+        let received = wait_for_message(&mut stream, &main_chan, &control_port, &SharedState::new(),
+                                        &SessionLimit::new(), &mut None, &mut incoming);
+        let violation = ProtocolViolation::LineTooLong(codec::MAX_FRAME_SIZE);
+        assert_eq!(received.unwrap_err(), DisconnectReason::ProtocolViolation(violation));
+    }
+
+    #[test]
+    fn test_peers() {
+        let (one, other) = pipe::pipe();
+        let peers: Vec<Component> = vec![one, other].into_iter().map(|end| {
+            let (chan, port) = channel::bounded(ChannelConfig::default());
+            let peer = start_peer_with(EventSender::new(0, chan), "pipe".to_string(), move || Ok(end), Default::default());
+            (peer, port)
+        }).collect();
+        let first: Vec<bool> = peers.iter().map(|(_, port)| match messages_of(port).next() {
+            Some(MainControlMsg::PeerConnected { first }) => first,
+            msg => panic!("expected the toss, got {:?}", msg),
+        }).collect();
+        // Whichever dialed, a single one speaks first.
+        assert_ne!(first[0], first[1]);
+        let (speaker, listener) = if first[0] { (&peers[0], &peers[1]) } else { (&peers[1], &peers[0]) };
+        let _ = speaker.0.send(ComponentControlMsg::OutgoingMessage("hello peer".to_string()));
+        assert_eq!(incoming_text(messages_of(&listener.1).next().unwrap()), "hello peer");
+        assert_eq!(listener.0.state(), ConnectionState::Active);
+
+        // An ephemeral reply comes with its time to live.
+        let ttl = Duration::from_secs(60);
+        let _ = listener.0.send(ComponentControlMsg::OutgoingEphemeral { text: "gone soon".to_string(), ttl });
+        let mut speaker_msgs = messages_of(&speaker.1).skip_while(|msg| !matches!(msg, MainControlMsg::IncomingMessage { .. }));
+        assert!(matches!(speaker_msgs.next(), Some(MainControlMsg::IncomingMessage { ttl: Some(received), .. }) if received == ttl));
+
+        let _ = speaker.0.send(ComponentControlMsg::Quit);
+        let mut listener_msgs = messages_of(&listener.1).skip_while(|msg| !matches!(msg, MainControlMsg::ClientDisconnected(_)));
+        assert_eq!(listener_msgs.next(), Some(MainControlMsg::ClientDisconnected(DisconnectReason::PeerQuit)));
+
+        // Peers using different wire formats are told so.
+        let (one, other) = pipe::pipe();
+        let peer = thread::spawn(move || FrameStream::pair(other, WireFormat::Json).map(|_| ()));
+        assert_eq!(FrameStream::pair(one, WireFormat::Bincode).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert!(peer.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_send_errors() {
+        let (ours, theirs) = pipe::pipe();
+        let mut stream = FrameStream::connect(ours, WireFormat::Bincode).unwrap();
+        let _peer = FrameStream::accept(theirs).unwrap();
+        stream.get_ref().shutdown();
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let (control_chan, control_port) = channel::bounded(ChannelConfig::default());
+        let _ = control_chan.send(ComponentControlMsg::Typing);
+        let _ = control_chan.send(ComponentControlMsg::Quit);
+        let mut outgoing = Outgoing::new(AckPolicy::default(), None);
+        let turn = wait_for_input(&mut stream, &EventSender::new(0, chan), &control_port, &SessionLimit::new(), &mut outgoing);
+        assert!(matches!(turn, Turn::Quit));
+        // Reported rather than ignored, the component going on until it finds the connection gone.
+        let error = ChatError::Send {
+            frame: "Typing",
+            source: io::ErrorKind::BrokenPipe.into(),
+        };
+        assert_eq!(messages_of(&port).collect::<Vec<_>>(), vec![MainControlMsg::Error(error)]);
+    }
+
+    #[test]
+    fn test_listen_failure() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = taken.local_addr().unwrap().to_string();
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let _server = start_server(EventSender::new(0, chan), address.clone(), Default::default());
+        let msgs: Vec<MainControlMsg> = messages_of(&port).collect();
+        // Reported, the server ending rather than the program.
+        assert!(matches!(msgs.first(), Some(&MainControlMsg::Error(ChatError::Listen { address: ref listened, ref source }))
+                         if *listened == address && source.kind() == io::ErrorKind::AddrInUse));
+        assert_eq!(msgs.last(), Some(&MainControlMsg::ServerShutDown(DisconnectReason::Quit)));
+    }
+
+    #[test]
+    fn test_connect_with_retries() {
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let main_chan = EventSender::new(0, chan);
+        let attempts = Cell::new(0);
+        let connect = || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 { Err(io::Error::from(io::ErrorKind::ConnectionRefused)) } else { Ok(attempts.get()) }
+        };
+        assert_eq!(connect_with_retries(&connect, 2, Duration::from_millis(10), &main_chan).unwrap(), 3);
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused).to_string();
+        assert_eq!(messages_of(&port).take(4).collect::<Vec<_>>(), vec![
+            MainControlMsg::ConnectAttemptFailed { error: refused.clone(), attempt: 1, retries: 2 },
+            MainControlMsg::ConnectRetryIn(1),
+            MainControlMsg::ConnectAttemptFailed { error: refused, attempt: 2, retries: 2 },
+            MainControlMsg::ConnectRetryIn(1),
+        ]);
+        // Giving up once out of retries.
+        attempts.set(0);
+        assert!(connect_with_retries(&connect, 1, Duration::ZERO, &main_chan).is_err());
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[test]
+    fn test_file_transfer() {
+        let path = env::temp_dir().join("simple_chat_test_sendfile.bin");
+        fs::write(&path, vec![7; transfer::CHUNK_SIZE + 5]).unwrap();
+        let (ours, theirs) = pipe::pipe();
+        let peer = thread::spawn(move || {
+            let mut stream = FrameStream::accept(theirs).unwrap();
+            let mut frames = vec![];
+            while let Ok((_, frame)) = stream.read_frame() {
+                frames.push(frame.name());
+                match frame {
+                    // Having received the first chunk before.
+                    Frame::FileStart { transfer, .. } => {
+                        let offset = transfer::CHUNK_SIZE as u64;
+                        stream.write_frame(SystemTime::now(), &Frame::FileAccept { transfer, offset }).unwrap();
+                    },
+                    Frame::Message { id, .. } => stream.write_ack(id).unwrap(),
+                    _ => {},
+                }
+            }
+            frames
+        });
+        let mut stream = FrameStream::connect(ours, WireFormat::Bincode).unwrap();
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let (control_chan, control_port) = channel::bounded(ChannelConfig::default());
+        let _ = control_chan.send(ComponentControlMsg::OutgoingFile(path.clone()));
+        let _ = control_chan.send(ComponentControlMsg::Typing);
+        let _ = control_chan.send(ComponentControlMsg::OutgoingFile(path.clone()));
+        let _ = control_chan.send(ComponentControlMsg::CancelTransfer(2));
+        let _ = control_chan.send(ComponentControlMsg::OutgoingMessage("here it is".to_string()));
+        let mut outgoing = Outgoing::new(AckPolicy::default(), None);
+        let turn = wait_for_input(&mut stream, &EventSender::new(0, chan), &control_port, &SessionLimit::new(), &mut outgoing);
+        assert!(matches!(turn, Turn::Sent(_)));
+        let events: Vec<_> = std::iter::from_fn(|| port.try_recv()).map(|(_, msg)| msg).collect();
+        assert!(events.iter().any(|msg| matches!(*msg, MainControlMsg::TransferCancelled { transfer: 2, by_peer: false, .. })));
+        assert!(events.iter().any(|msg| matches!(*msg, MainControlMsg::TransferProgress { id: 1, sent, total } if sent == total)));
+        assert!(events.iter().any(|msg| matches!(*msg, MainControlMsg::FileSent { transfer: 1, .. })));
+        stream.get_ref().shutdown();
+        // The chat going on between the chunks, our turn ending once they were all sent.
+        assert_eq!(peer.join().unwrap(), vec!["FileStart", "Typing", "FileStart", "FileCancel", "Chunk", "Message"]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_ping() {
+        let (ours, theirs) = pipe::pipe();
+        let _silent = theirs.try_clone().unwrap();
+        let peer = thread::spawn(move || {
+            let mut stream = FrameStream::accept(theirs).unwrap();
+            let (chan, _port) = channel::bounded(ChannelConfig::default());
+            let (_control_chan, control_port) = channel::bounded(ChannelConfig::default());
+            let mut incoming = IncomingMessages::new(REORDER_WINDOW);
+            wait_for_message(&mut stream, &EventSender::new(0, chan), &control_port, &SharedState::new(),
+                             &SessionLimit::new(), &mut None, &mut incoming)
+        });
+        let mut stream = FrameStream::connect(ours, WireFormat::Bincode).unwrap();
+        let (chan, port) = channel::bounded(ChannelConfig::default());
+        let main_chan = EventSender::new(0, chan);
+        let (control_chan, control_port) = channel::bounded(ChannelConfig::default());
+        let _ = control_chan.send(ComponentControlMsg::Ping);
+        let _ = control_chan.send(ComponentControlMsg::Ping);
+        let _ = control_chan.send(ComponentControlMsg::OutgoingMessage("still there?".to_string()));
+        let mut outgoing = Outgoing::new(AckPolicy { timeout: Duration::from_millis(200), retries: 0 }, None);
+        let turn = wait_for_input(&mut stream, &main_chan, &control_port, &SessionLimit::new(), &mut outgoing);
+        assert!(matches!(turn, Turn::Sent(_)));
+        // Answered by the peer waiting for our message, which is none the wiser.
+        assert_eq!(peer.join().unwrap().unwrap(), vec!["still there?"]);
+        let answered = std::iter::from_fn(|| port.try_recv())
+            .filter(|(_, msg)| matches!(*msg, MainControlMsg::PingAnswered(Some(_))))
+            .count();
+        assert_eq!(answered, 2);
+
+        // Lost, the peer being done reading yet still connected.
+        let _ = control_chan.send(ComponentControlMsg::Ping);
+        let _ = control_chan.send(ComponentControlMsg::Quit);
+        assert!(matches!(wait_for_input(&mut stream, &main_chan, &control_port, &SessionLimit::new(), &mut outgoing), Turn::Quit));
+        assert_eq!(port.try_recv().map(|(_, msg)| msg), Some(MainControlMsg::PingAnswered(None)));
+    }
+
+    #[test]
+    fn test_log_line() {
+        let message = MainControlMsg::IncomingMessage {
+            message: ChatMessage::new(3, None, SystemTime::now(), MessageKind::Text, "my secret".to_string()),
+            verified: false,
+            ttl: None,
+        };
+        assert_eq!(log_line(&message, 0, "alice", false).unwrap(), "#1 received #3 from alice, 9 bytes");
+        assert_eq!(log_line(&message, 0, "alice", true).unwrap(), "#1 received #3 from alice, 9 bytes: my secret");
+        assert!(log_line(&MainControlMsg::PeerTyping, 0, "alice", true).is_none());
+        assert_eq!(log_line(&MainControlMsg::PingAnswered(None), 0, "alice", false).unwrap(), "#1 ping to alice lost");
+        let presence = MainControlMsg::Presence(Presence::Renamed { from: "bob".to_string(), to: "robert".to_string() });
+        assert_eq!(log_line(&presence, 0, "alice", false).unwrap(), "#1 bob is now robert");
+    }
+
+    #[test]
+    fn test_auth_response() {
+        let nonce = [7; NONCE_SIZE];
+        let response = auth_response("secret", &nonce).finalize().into_bytes();
+        assert!(auth_response("secret", &nonce).verify_slice(&response).is_ok());
+        assert!(auth_response("wrong", &nonce).verify_slice(&response).is_err());
     }
 }
@@ -0,0 +1,199 @@
+// The subset of Markdown messages are styled with: `**bold**`, `*italic*` or `_italic_`,
+// `` `inline code` ``, and code blocks between lines of ```, anything else being shown as typed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    // Of a code block, its lines shown apart.
+    pub block: bool,
+}
+
+// Part of the text, without its markers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+fn push(spans: &mut Vec<Span>, text: &str, style: Style) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if last.style == style => last.text.push_str(text),
+        _ => spans.push(Span { text: text.to_string(), style }),
+    }
+}
+
+// Where the marker closing the one at the start of `text` is, if any, past a non-empty span
+// not starting or ending with a space, `_` only closing at the end of a word, so as not to style snake_case.
+fn closing(text: &str, marker: &str) -> Option<usize> {
+    let mut from = marker.len();
+    while let Some(found) = text[from..].find(marker) {
+        let end = from + found;
+        let inner = &text[marker.len()..end];
+        let after = text[end + marker.len()..].chars().next();
+        let word_goes_on = marker == "_" && after.is_some_and(char::is_alphanumeric);
+        // `*` closing where `**` would, only once the bold is closed.
+        let bold_marker = marker == "*" && text[end..].starts_with("**");
+        if !inner.is_empty() && !inner.starts_with(' ') && !inner.ends_with(' ') && !word_goes_on && !bold_marker {
+            return Some(end);
+        }
+        from = end + if bold_marker { 2 } else { marker.len() };
+    }
+    None
+}
+
+fn inline(text: &str, style: Style, spans: &mut Vec<Span>) {
+    let mut plain_from = 0;
+    let mut at = 0;
+    while at < text.len() {
+        let rest = &text[at..];
+        let previous = text[..at].chars().next_back();
+        // Backticks more than one at a time, such as an unclosed fence, are left as they are.
+        if rest.starts_with("``") {
+            at += rest.len() - rest.trim_start_matches('`').len();
+            continue;
+        }
+        let marker = if rest.starts_with('`') {
+            "`"
+        } else if rest.starts_with("**") {
+            "**"
+        } else if rest.starts_with('*') {
+            "*"
+        } else if rest.starts_with('_') && !previous.is_some_and(char::is_alphanumeric) {
+            "_"
+        } else {
+            ""
+        };
+        if let Some(end) = Some(marker).filter(|marker| !marker.is_empty()).and_then(|marker| closing(rest, marker)) {
+            push(spans, &text[plain_from..at], style);
+            let inner = &rest[marker.len()..end];
+            match marker {
+                // Nothing is styled within code.
+                "`" => push(spans, inner, Style { code: true, ..style }),
+                "**" => inline(inner, Style { bold: true, ..style }, spans),
+                _ => inline(inner, Style { italic: true, ..style }, spans),
+            }
+            at += end + marker.len();
+            plain_from = at;
+            continue;
+        }
+        at += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    push(spans, &text[plain_from..], style);
+}
+
+// The text, split where its style changes, its code blocks taken as they are.
+pub fn parse(text: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut lines = text.split('\n').peekable();
+    let mut paragraph: Vec<&str> = vec![];
+    while let Some(line) = lines.next() {
+        if !line.trim_start().starts_with("```") {
+            paragraph.push(line);
+            continue;
+        }
+        // A fence without a closing one is left as it is.
+        let block: Vec<&str> = lines.clone().take_while(|line| !line.trim_start().starts_with("```")).collect();
+        if lines.clone().nth(block.len()).is_none() {
+            paragraph.push(line);
+            continue;
+        }
+        if !paragraph.is_empty() {
+            inline(&format!("{}\n", paragraph.join("\n")), Style::default(), &mut spans);
+            paragraph.clear();
+        }
+        push(&mut spans, &block.join("\n"), Style { code: true, block: true, ..Style::default() });
+        for _ in 0..=block.len() {
+            lines.next();
+        }
+        if lines.peek().is_some() {
+            push(&mut spans, "\n", Style::default());
+        }
+    }
+    inline(&paragraph.join("\n"), Style::default(), &mut spans);
+    spans
+}
+
+// For a terminal, in bold, italics, and inverse video for code, its blocks on lines of their own.
+pub fn to_ansi(text: &str) -> String {
+    parse(text).into_iter().map(|span| {
+        let mut styled = span.text;
+        if span.style.block {
+            let lines: Vec<String> = styled.lines().map(|line| format!("    \u{1b}[7m{}\u{1b}[27m", line)).collect();
+            return format!("\n{}", lines.join("\n"));
+        }
+        if span.style.code {
+            styled = format!("\u{1b}[7m{}\u{1b}[27m", styled);
+        }
+        if span.style.italic {
+            styled = format!("\u{1b}[3m{}\u{1b}[23m", styled);
+        }
+        if span.style.bold {
+            styled = format!("\u{1b}[1m{}\u{1b}[22m", styled);
+        }
+        styled
+    }).collect()
+}
+
+// Where styles can't be shown: `*bold*`, `_italic_`, `` `code` `` and code blocks indented.
+pub fn to_plain(text: &str) -> String {
+    parse(text).into_iter().map(|span| {
+        let mut plain = span.text;
+        if span.style.block {
+            let lines: Vec<String> = plain.lines().map(|line| format!("    {}", line)).collect();
+            return format!("\n{}", lines.join("\n"));
+        }
+        if span.style.code {
+            plain = format!("`{}`", plain);
+        }
+        if span.style.italic {
+            plain = format!("_{}_", plain);
+        }
+        if span.style.bold {
+            plain = format!("*{}*", plain);
+        }
+        plain
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled(text: &str, bold: bool, italic: bool, code: bool) -> Span {
+        Span { text: text.to_string(), style: Style { bold, italic, code, block: false } }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("a **bold** and *italic* `x * y`"), vec![
+            styled("a ", false, false, false),
+            styled("bold", true, false, false),
+            styled(" and ", false, false, false),
+            styled("italic", false, true, false),
+            styled(" ", false, false, false),
+            styled("x * y", false, false, true),
+        ]);
+        assert_eq!(parse("**bold _and italic_**")[1], styled("and italic", true, true, false));
+        // Left as typed.
+        assert_eq!(parse("snake_case_name, 2 * 3 * 4, **"), vec![styled("snake_case_name, 2 * 3 * 4, **", false, false, false)]);
+        assert_eq!(parse("_not closed"), vec![styled("_not closed", false, false, false)]);
+
+        let spans = parse("look:\n```\nfn main() {\n    *x = 1;\n}\n```\ndone");
+        assert_eq!(spans[0], styled("look:\n", false, false, false));
+        assert_eq!(spans[1].text, "fn main() {\n    *x = 1;\n}");
+        assert!(spans[1].style.block);
+        assert_eq!(spans[2], styled("\ndone", false, false, false));
+        assert_eq!(parse("```\nnever closed"), vec![styled("```\nnever closed", false, false, false)]);
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(to_ansi("**hi** `x`"), "\u{1b}[1mhi\u{1b}[22m \u{1b}[7mx\u{1b}[27m");
+        assert_eq!(to_plain("**hi** *there* `x`"), "*hi* _there_ `x`");
+        assert_eq!(to_plain("see:\n```\nlet x = 1;\n```"), "see:\n\n    let x = 1;");
+    }
+}
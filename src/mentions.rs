@@ -0,0 +1,84 @@
+// A nickname goes on with letters, digits, `-` and `_`, for `@bob` not to be found in `@bobby`.
+fn in_nickname(character: char) -> bool {
+    character.is_alphanumeric() || character == '-' || character == '_'
+}
+
+// Where the word is in the text, ignoring case, as a whole word, such as a keyword or `@nick`.
+pub fn find_word(text: &str, word: &str) -> Vec<(usize, usize)> {
+    let word = word.to_lowercase();
+    if word.is_empty() || word.len() > text.len() {
+        return vec![];
+    }
+    let mut found = vec![];
+    for (start, _) in text.char_indices() {
+        let end = start + word.len();
+        let candidate = match text.get(start..end) {
+            Some(candidate) => candidate,
+            None => continue,
+        };
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        if candidate.to_lowercase() == word && !before.is_some_and(in_nickname) && !after.is_some_and(in_nickname) {
+            found.push((start, end));
+        }
+    }
+    found
+}
+
+// Where `@nick` is in the text.
+pub fn find_mentions(text: &str, nick: &str) -> Vec<(usize, usize)> {
+    if nick.is_empty() {
+        return vec![];
+    }
+    find_word(text, &format!("@{}", nick))
+}
+
+pub fn mentions(text: &str, nick: &str) -> bool {
+    !find_mentions(text, nick).is_empty()
+}
+
+// The keywords the text has, of those watched, see `/watch`.
+pub fn watched<'a>(text: &str, keywords: &'a [String]) -> Vec<&'a str> {
+    keywords.iter().filter(|keyword| !find_word(text, keyword).is_empty()).map(String::as_str).collect()
+}
+
+// The text with each of the words passed through `highlight`.
+pub fn highlight<F: Fn(&str) -> String>(text: &str, words: &[String], highlight: F) -> String {
+    let mut found: Vec<(usize, usize)> = words.iter().flat_map(|word| find_word(text, word)).collect();
+    found.sort();
+    let mut highlighted = String::new();
+    let mut shown = 0;
+    for (start, end) in found {
+        // Where words overlap, the first one found.
+        if start < shown {
+            continue;
+        }
+        highlighted.push_str(&text[shown..start]);
+        highlighted.push_str(&highlight(&text[start..end]));
+        shown = end;
+    }
+    highlighted.push_str(&text[shown..]);
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("@alice, lunch?", "alice"));
+        assert!(mentions("lunch, @Alice?", "alice"));
+        assert!(!mentions("lunch, alice?", "alice"));
+        assert!(!mentions("@alice_b, lunch?", "alice"));
+        assert!(!mentions("mail me at bob@alice.org", "alice"));
+        assert!(!mentions("@", ""));
+        let words = |words: &[&str]| words.iter().map(|word| word.to_string()).collect::<Vec<_>>();
+        assert_eq!(highlight("@bob and @Bob, not @bobby", &words(&["@bob"]), |mention| format!("[{}]", mention)),
+                   "[@bob] and [@Bob], not @bobby");
+        assert_eq!(highlight("née @zoé", &words(&["@zoé"]), str::to_uppercase), "née @ZOÉ");
+        assert_eq!(highlight("Deploy, @bob: redeploy at noon", &words(&["deploy", "@bob"]), |word| format!("[{}]", word)),
+                   "[Deploy], [@bob]: redeploy at noon");
+        assert_eq!(watched("the deploy failed", &words(&["deploy", "rollback", "fail"])), vec!["deploy"]);
+    }
+}
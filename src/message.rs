@@ -0,0 +1,55 @@
+use codec::MessageId;
+use std::time::SystemTime;
+
+
+// What a message from the peer is, beyond its body.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MessageKind {
+    Text,
+    // From the server rather than its operator, such as its message of the day.
+    System,
+    // To our message of its id, the body being the emoji.
+    Reaction,
+}
+
+// A message from the peer, as passed from the component to the UI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    // That of the message reacted to, for a reaction.
+    pub id: MessageId,
+    // The nickname the peer gave, if it did.
+    pub sender: Option<String>,
+    pub sent_at: SystemTime,
+    pub kind: MessageKind,
+    pub body: String,
+}
+
+impl ChatMessage {
+    pub fn new(id: MessageId, sender: Option<&str>, sent_at: SystemTime, kind: MessageKind, body: String) -> ChatMessage {
+        ChatMessage {
+            id,
+            sender: sender.map(str::to_string),
+            sent_at,
+            kind,
+            body,
+        }
+    }
+
+    // Who sent it, `peer_name` being what the UI calls the peer otherwise.
+    pub fn sender_or<'a>(&'a self, peer_name: &'a str) -> &'a str {
+        self.sender.as_deref().unwrap_or(peer_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sender() {
+        let message = ChatMessage::new(1, None, SystemTime::now(), MessageKind::Text, "hi".to_string());
+        assert_eq!(message.sender_or("client"), "client");
+        let message = ChatMessage::new(2, Some("alice"), SystemTime::now(), MessageKind::Reaction, "👍".to_string());
+        assert_eq!(message.sender_or("client"), "alice");
+    }
+}
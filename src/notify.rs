@@ -0,0 +1,316 @@
+use chrono::NaiveTime;
+use input;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::process::{Command, Stdio};
+use std::thread;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alert {
+    Silent,
+    Normal,
+    Urgent,
+}
+
+// What a notification is about, as seen by the rules.
+pub struct Notification<'a> {
+    pub room: Option<&'a str>,
+    pub peer: &'a str,
+    // Whether the message mentions us, or has a keyword we watch.
+    pub mentioned: bool,
+}
+
+// A rule, one per line in the rules file:
+// `<mute|alert|urgent> [room <name>] [from <peer>] [except mentions] [after HH:MM] [before HH:MM]`,
+// for example `mute room random except mentions`, or `urgent from alice after 22:00`.
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    alert: Alert,
+    room: Option<String>,
+    peer: Option<String>,
+    except_mentions: bool,
+    after: Option<NaiveTime>,
+    before: Option<NaiveTime>,
+}
+
+fn parse_time(time: Option<&str>) -> Result<NaiveTime, String> {
+    let time = time.ok_or_else(|| "expected a time".to_string())?;
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| format!("invalid time '{}'", time))
+}
+
+impl Rule {
+    pub fn parse(line: &str) -> Result<Rule, String> {
+        let mut words = line.split_whitespace();
+        let alert = match words.next() {
+            Some("mute") => Alert::Silent,
+            Some("alert") => Alert::Normal,
+            Some("urgent") => Alert::Urgent,
+            _ => return Err(format!("a rule starts with mute, alert or urgent: '{}'", line)),
+        };
+        let mut rule = Rule {
+            alert,
+            room: None,
+            peer: None,
+            except_mentions: false,
+            after: None,
+            before: None,
+        };
+        while let Some(word) = words.next() {
+            match word {
+                "room" => rule.room = words.next().map(str::to_string),
+                "from" => rule.peer = words.next().map(str::to_string),
+                "except" if words.next() == Some("mentions") => rule.except_mentions = true,
+                "after" => rule.after = Some(parse_time(words.next())?),
+                "before" => rule.before = Some(parse_time(words.next())?),
+                _ => return Err(format!("unexpected '{}' in rule '{}'", word, line)),
+            }
+        }
+        Ok(rule)
+    }
+
+    fn in_time_window(&self, time: NaiveTime) -> bool {
+        match (self.after, self.before) {
+            (Some(after), Some(before)) if after > before => {
+                // The window spans midnight.
+                time >= after || time < before
+            },
+            (after, before) => {
+                after.is_none_or(|after| time >= after) && before.is_none_or(|before| time < before)
+            },
+        }
+    }
+
+    fn matches(&self, notification: &Notification, time: NaiveTime) -> bool {
+        if self.except_mentions && notification.mentioned {
+            return false;
+        }
+        if let Some(ref room) = self.room {
+            if notification.room != Some(room.as_str()) {
+                return false;
+            }
+        }
+        if let Some(ref peer) = self.peer {
+            if notification.peer != peer {
+                return false;
+            }
+        }
+        self.in_time_window(time)
+    }
+}
+
+// Rules evaluated in order, the last matching one deciding on the alert,
+// and a normal alert if none match. Mentions are never silent.
+#[derive(Debug, Default)]
+pub struct NotificationRules {
+    rules: Vec<Rule>,
+}
+
+impl NotificationRules {
+    pub fn parse(source: &str) -> Result<NotificationRules, String> {
+        let rules = source.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Rule::parse)
+            .collect::<Result<Vec<Rule>, String>>()?;
+        Ok(NotificationRules {
+            rules,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<NotificationRules, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("failed to read notification rules {}: {}", path, error))?;
+        NotificationRules::parse(&source)
+    }
+
+    pub fn evaluate(&self, notification: &Notification, time: NaiveTime) -> Alert {
+        let alert = self.rules.iter()
+            .rev()
+            .find(|rule| rule.matches(notification, time))
+            .map_or(Alert::Normal, |rule| rule.alert);
+        if notification.mentioned && alert == Alert::Silent {
+            return Alert::Normal;
+        }
+        alert
+    }
+}
+
+// `on` or `off`, as `/dnd` takes them.
+pub fn parse_toggle(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("expected 'on' or 'off', got '{}'", value)),
+    }
+}
+
+// When not to be alerted, every day, such as `22:00-07:00`, spanning midnight if it ends before it starts.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietHours {
+    from: NaiveTime,
+    to: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn parse(hours: &str) -> Result<QuietHours, String> {
+        let (from, to) = hours.split_once('-').ok_or_else(|| format!("invalid quiet hours '{}', expected HH:MM-HH:MM", hours))?;
+        Ok(QuietHours {
+            from: parse_time(Some(from.trim()))?,
+            to: parse_time(Some(to.trim()))?,
+        })
+    }
+
+    fn contain(&self, time: NaiveTime) -> bool {
+        if self.from > self.to {
+            time >= self.from || time < self.to
+        } else {
+            time >= self.from && time < self.to
+        }
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = String;
+
+    fn try_from(hours: String) -> Result<QuietHours, String> {
+        QuietHours::parse(&hours)
+    }
+}
+
+impl From<QuietHours> for String {
+    fn from(hours: QuietHours) -> String {
+        hours.to_string()
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.from.format("%H:%M"), self.to.format("%H:%M"))
+    }
+}
+
+// The `[notifications]` section of the config, changed with `/dnd`, and applied over the rules:
+// with `dnd` on nothing alerts, while the muted peers, quiet hours and mention-only mode
+// silence all but the messages mentioning us.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationSettings {
+    pub dnd: bool,
+    pub muted: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+    pub mentions_only: bool,
+    // The words which alert, like mentions, see `/watch`.
+    pub keywords: Vec<String>,
+    // Whether mentions and keywords are also shown as desktop notifications, with notify-send.
+    pub desktop: bool,
+}
+
+impl NotificationSettings {
+    pub fn is_muted(&self, peer: &str) -> bool {
+        self.muted.iter().any(|muted| muted.eq_ignore_ascii_case(peer))
+    }
+
+    pub fn apply(&self, alert: Alert, notification: &Notification, time: NaiveTime) -> Alert {
+        if self.dnd {
+            return Alert::Silent;
+        }
+        if notification.mentioned {
+            return alert;
+        }
+        let quiet = self.quiet_hours.is_some_and(|hours| hours.contain(time));
+        if self.is_muted(notification.peer) || quiet || self.mentions_only {
+            return Alert::Silent;
+        }
+        alert
+    }
+}
+
+impl fmt::Display for NotificationSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let toggle = |value| if value { "on" } else { "off" };
+        let muted = if self.muted.is_empty() { "-".to_string() } else { self.muted.join(",") };
+        let quiet_hours = self.quiet_hours.map_or_else(|| "-".to_string(), |hours| hours.to_string());
+        write!(f,
+               "dnd={} muted={} quiet_hours={} mentions_only={} desktop={}",
+               toggle(self.dnd),
+               muted,
+               quiet_hours,
+               toggle(self.mentions_only),
+               toggle(self.desktop))
+    }
+}
+
+// Shown by the desktop, where notify-send is installed, as on most Linux desktops.
+pub fn desktop(summary: &str, body: &str) {
+    if !input::in_path("notify-send") {
+        return;
+    }
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name", "simple_chat", summary, body]).stdout(Stdio::null()).stderr(Stdio::null());
+    // Waited for on a thread of its own, not to hold the chat up.
+    let _ = thread::Builder::new().spawn(move || command.status());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_rules() {
+        let rules = NotificationRules::parse("
+            # Quiet nights, except for alice.
+            mute room random except mentions
+            mute after 22:00 before 07:00
+            urgent from alice after 22:00 before 07:00
+        ").unwrap();
+        let day = NaiveTime::from_hms_opt(14, 0, 0).unwrap();
+        let night = NaiveTime::from_hms_opt(23, 30, 0).unwrap();
+        let from = |room, peer, mentioned| Notification {
+            room,
+            peer,
+            mentioned,
+        };
+        assert_eq!(rules.evaluate(&from(None, "bob", false), day), Alert::Normal);
+        assert_eq!(rules.evaluate(&from(Some("random"), "bob", false), day), Alert::Silent);
+        assert_eq!(rules.evaluate(&from(Some("random"), "bob", true), day), Alert::Normal);
+        assert_eq!(rules.evaluate(&from(None, "bob", false), night), Alert::Silent);
+        assert_eq!(rules.evaluate(&from(None, "bob", true), night), Alert::Normal);
+        assert_eq!(rules.evaluate(&from(None, "alice", false), night), Alert::Urgent);
+        assert!(NotificationRules::parse("ring from bob").is_err());
+    }
+
+    #[test]
+    fn test_notification_settings() {
+        let day = NaiveTime::from_hms_opt(14, 0, 0).unwrap();
+        let night = NaiveTime::from_hms_opt(23, 30, 0).unwrap();
+        let from = |peer, mentioned| Notification {
+            room: None,
+            peer,
+            mentioned,
+        };
+        let mut settings = NotificationSettings {
+            muted: vec!["Bob".to_string()],
+            quiet_hours: Some(QuietHours::parse("22:00-07:00").unwrap()),
+            ..NotificationSettings::default()
+        };
+        assert_eq!(settings.apply(Alert::Urgent, &from("alice", false), day), Alert::Urgent);
+        assert_eq!(settings.apply(Alert::Normal, &from("bob", false), day), Alert::Silent);
+        assert_eq!(settings.apply(Alert::Normal, &from("bob", true), day), Alert::Normal);
+        assert_eq!(settings.apply(Alert::Urgent, &from("alice", false), night), Alert::Silent);
+        settings.mentions_only = true;
+        assert_eq!(settings.apply(Alert::Normal, &from("alice", false), day), Alert::Silent);
+        assert_eq!(settings.apply(Alert::Normal, &from("alice", true), night), Alert::Normal);
+        settings.dnd = true;
+        assert_eq!(settings.apply(Alert::Urgent, &from("alice", true), day), Alert::Silent);
+        assert_eq!(settings.to_string(), "dnd=on muted=Bob quiet_hours=22:00-07:00 mentions_only=on desktop=off");
+
+        assert_eq!(QuietHours::parse("09:00 - 17:30").unwrap().to_string(), "09:00-17:30");
+        assert!(QuietHours::parse("22:00").is_err());
+        assert!(QuietHours::parse("22:00-25:00").is_err());
+    }
+}
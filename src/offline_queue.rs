@@ -0,0 +1,106 @@
+use codec::MessageId;
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+
+// Messages waiting for a peer to (re)connect, with their original timestamps.
+// Once full, the oldest message is dropped to make room for a new one.
+// Each is numbered when first queued, to cancel it until it is delivered.
+#[derive(Default)]
+pub struct OfflineQueue {
+    messages: VecDeque<(MessageId, SystemTime, String)>,
+    capacity: usize,
+    last_id: MessageId,
+    // The ids of the messages last taken, to requeue those not delivered.
+    taken: Vec<MessageId>,
+}
+
+impl OfflineQueue {
+    pub fn new(capacity: usize) -> OfflineQueue {
+        OfflineQueue {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+            ..OfflineQueue::default()
+        }
+    }
+
+    // Returns the id of the message, if it was queued.
+    pub fn push(&mut self, sent_at: SystemTime, text: String) -> Option<MessageId> {
+        if self.capacity == 0 {
+            return None;
+        }
+        self.last_id += 1;
+        self.insert(self.last_id, sent_at, text);
+        Some(self.last_id)
+    }
+
+    fn insert(&mut self, id: MessageId, sent_at: SystemTime, text: String) {
+        if self.messages.len() == self.capacity {
+            let _ = self.messages.pop_front();
+        }
+        self.messages.push_back((id, sent_at, text));
+    }
+
+    pub fn take_all(&mut self) -> VecDeque<(SystemTime, String)> {
+        self.taken = self.messages.iter().map(|&(id, _, _)| id).collect();
+        self.messages.drain(..).map(|(_, sent_at, text)| (sent_at, text)).collect()
+    }
+
+    // Put back the last of the messages taken, which could not be delivered, under their ids.
+    pub fn requeue(&mut self, undelivered: VecDeque<(SystemTime, String)>) {
+        let ids = self.taken.split_off(self.taken.len().saturating_sub(undelivered.len()));
+        for (id, (sent_at, text)) in ids.into_iter().zip(undelivered) {
+            self.insert(id, sent_at, text);
+        }
+        self.taken.clear();
+    }
+
+    // Returns whether the message was still queued.
+    pub fn cancel(&mut self, id: MessageId) -> bool {
+        let count = self.messages.len();
+        self.messages.retain(|&(queued, _, _)| queued != id);
+        self.messages.len() != count
+    }
+
+    pub fn queued(&self) -> impl Iterator<Item = (MessageId, &str)> {
+        self.messages.iter().map(|(id, _, text)| (*id, text.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_queue() {
+        let mut queue = OfflineQueue::new(2);
+        let now = SystemTime::now();
+        assert_eq!(queue.push(now, "one".to_string()), Some(1));
+        assert_eq!(queue.push(now, "two".to_string()), Some(2));
+        assert_eq!(queue.push(now, "three".to_string()), Some(3));
+        let texts: Vec<String> = queue.take_all().into_iter().map(|(_, text)| text).collect();
+        assert_eq!(texts, vec!["two", "three"]);
+        assert!(queue.take_all().is_empty());
+
+        // A queue without capacity doesn't store anything.
+        assert!(OfflineQueue::new(0).push(now, "lost".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut queue = OfflineQueue::new(5);
+        let now = SystemTime::now();
+        for text in ["one", "two", "three"].iter() {
+            queue.push(now, text.to_string());
+        }
+        assert!(queue.cancel(2));
+        assert!(!queue.cancel(2));
+        let mut taken = queue.take_all();
+        // Only "one" was delivered.
+        taken.pop_front();
+        queue.requeue(taken);
+        assert_eq!(queue.queued().collect::<Vec<_>>(), vec![(3, "three")]);
+        assert!(queue.cancel(3));
+        assert_eq!(queue.queued().count(), 0);
+    }
+}
@@ -0,0 +1,183 @@
+#[cfg(feature = "gui")]
+use gui::Update;
+use chrono::{DateTime, Local};
+use history::HistoryEntry;
+use links;
+use markdown;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "gui")]
+use std::sync::mpsc::Sender;
+use std::time::SystemTime;
+use theme::Theme;
+
+
+// Where the chat is shown: printed, and also in the chat window when there is one.
+#[derive(Clone, Default)]
+pub struct Output {
+    theme: Theme,
+    // A progress bar is drawn on the last line of the terminal, to be replaced by the next line shown.
+    progress_shown: Arc<AtomicBool>,
+    #[cfg(feature = "gui")]
+    window: Option<Sender<Update>>,
+}
+
+// Colors are only for a terminal, and not wanted by those setting NO_COLOR.
+fn colored() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+impl Output {
+    pub fn new(theme: Theme) -> Output {
+        Output {
+            theme,
+            progress_shown: Arc::default(),
+            #[cfg(feature = "gui")]
+            window: None,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn window(theme: Theme, updates: Sender<Update>) -> Output {
+        Output {
+            theme,
+            progress_shown: Arc::default(),
+            window: Some(updates),
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn has_window(&self) -> bool {
+        self.window.is_some()
+    }
+
+    #[cfg(not(feature = "gui"))]
+    pub fn has_window(&self) -> bool {
+        false
+    }
+
+    // What the chat says, in the theme's color for it.
+    pub fn line(&self, line: String) {
+        self.show(line, |line, theme| theme.paint(theme.system, &links::underline(line)));
+    }
+
+    pub fn error(&self, error: String) {
+        self.show(error, |error, theme| theme.paint(theme.error, &links::underline(error)));
+    }
+
+    pub fn entry(&self, entry: &HistoryEntry) {
+        self.show_markdown(entry.to_string(), |_, theme| links::underline(&entry.paint(theme)));
+    }
+
+    // A message from someone other than the peer, such as a plugin, or from the peer but not kept.
+    pub fn message(&self, sent_at: SystemTime, sender: &str, text: &str) {
+        let sent_at: DateTime<Local> = sent_at.into();
+        let sent_at = format!("[{}]", sent_at.format("%H:%M:%S"));
+        self.show_markdown(format!("{} {}: {}", sent_at, sender, text), |_, theme| {
+            let text = if theme.markdown { markdown::to_ansi(text) } else { text.to_string() };
+            format!("{} {}: {}", theme.paint(theme.timestamp, &sent_at), theme.paint(theme.nickname(sender), sender), links::underline(&text))
+        });
+    }
+
+    // A message from the server itself, such as its greeting.
+    pub fn system_message(&self, sent_at: SystemTime, sender: &str, text: &str) {
+        let sent_at: DateTime<Local> = sent_at.into();
+        let line = format!("[{}] {}: {}", sent_at.format("%H:%M:%S"), sender, text);
+        self.show(line, |line, theme| theme.paint(theme.system, &links::underline(line)));
+    }
+
+    // Someone joining, leaving or being renamed, apart from the messages.
+    pub fn presence(&self, text: String) {
+        self.show(format!("* {}", text), |line, theme| theme.paint(theme.presence, line));
+    }
+
+    // Printed as it is, and not shown in the window, such as a QR code its font wouldn't draw.
+    pub fn terminal(&self, text: String) {
+        if self.progress_shown.swap(false, Ordering::Relaxed) {
+            print!("\r\u{1b}[2K");
+        }
+        println!("{}", text);
+    }
+
+    // Such as how far a file is, redrawn in place in a terminal, or printed as a line of its own otherwise.
+    pub fn progress(&self, bar: String) {
+        if !io::stdout().is_terminal() {
+            println!("{}", bar);
+            return;
+        }
+        print!("\r\u{1b}[2K{}", bar);
+        let _ = io::stdout().flush();
+        self.progress_shown.store(true, Ordering::Relaxed);
+    }
+
+    // Printed painted, with its links underlined, in a terminal, and shown as it is in the window,
+    // which makes links of them itself.
+    fn show<F: FnOnce(&str, &Theme) -> String>(&self, line: String, paint: F) {
+        self.show_as(line, paint, str::to_string);
+    }
+
+    // With Markdown in it, printed with the plain text equivalents of its styles where it can't be painted,
+    // and left to the window to style.
+    fn show_markdown<F: FnOnce(&str, &Theme) -> String>(&self, line: String, paint: F) {
+        self.show_as(line, paint, markdown::to_plain);
+    }
+
+    fn show_as<F, P>(&self, line: String, paint: F, plain: P)
+        where F: FnOnce(&str, &Theme) -> String,
+              P: FnOnce(&str) -> String {
+        if self.progress_shown.swap(false, Ordering::Relaxed) {
+            print!("\r\u{1b}[2K");
+        }
+        if colored() {
+            println!("{}", paint(&line, &self.theme));
+        } else {
+            println!("{}", plain(&line));
+        }
+        #[cfg(feature = "gui")]
+        self.update(Update::Line(line));
+    }
+
+    // What the input of the window is for, if it is for a message.
+    #[allow(unused_variables)]
+    pub fn prompt(&self, prompt: Option<String>) {
+        #[cfg(feature = "gui")]
+        self.update(Update::Prompt(prompt));
+    }
+
+    // Put back in the input of the window, when the prompt is for its peer.
+    #[allow(unused_variables)]
+    pub fn draft(&self, draft: String) {
+        #[cfg(feature = "gui")]
+        self.update(Update::Draft(draft));
+    }
+
+    #[allow(unused_variables)]
+    pub fn status(&self, text: String, connected: bool) {
+        #[cfg(feature = "gui")]
+        self.update(Update::Status { text, connected });
+    }
+
+    // Bring the window back from the tray.
+    pub fn raise(&self) {
+        #[cfg(feature = "gui")]
+        self.update(Update::Raise);
+    }
+
+    #[cfg(feature = "gui")]
+    fn update(&self, update: Update) {
+        // The window is gone once closed.
+        if let Some(ref window) = self.window {
+            let _ = window.send(update);
+        }
+    }
+}
+
+// Print the message of the given id in the UI's language, with its arguments as for `tr!`.
+macro_rules! say {
+    ($output:expr, $($arg:tt)*) => {{
+        let line = tr!($($arg)*);
+        $output.line(line)
+    }};
+}
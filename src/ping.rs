@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+
+// How many of the last pings the estimates are made over.
+const WINDOW: usize = 20;
+
+// The round-trip time and loss of the link, over the last pings,
+// each answered in time or lost.
+#[derive(Default)]
+pub struct LinkQuality {
+    pings: VecDeque<Option<Duration>>,
+}
+
+impl LinkQuality {
+    pub fn record(&mut self, roundtrip: Option<Duration>) {
+        if self.pings.len() == WINDOW {
+            self.pings.pop_front();
+        }
+        self.pings.push_back(roundtrip);
+    }
+
+    // The mean of the pings answered, if any was.
+    pub fn roundtrip(&self) -> Option<Duration> {
+        let answered: Vec<Duration> = self.pings.iter().flatten().cloned().collect();
+        if answered.is_empty() {
+            return None;
+        }
+        Some(answered.iter().sum::<Duration>() / answered.len() as u32)
+    }
+
+    // Of the pings sent, in percent, if any was.
+    pub fn loss(&self) -> Option<u32> {
+        if self.pings.is_empty() {
+            return None;
+        }
+        let lost = self.pings.iter().filter(|ping| ping.is_none()).count();
+        Some((lost * 100 / self.pings.len()) as u32)
+    }
+}
+
+// Such as `rtt 12ms, loss 5%`, for the status bar.
+impl fmt::Display for LinkQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let roundtrip = self.roundtrip().map_or_else(|| "-".to_string(), |roundtrip| format!("{:.1?}", roundtrip));
+        write!(f, "{}", tr!("link-quality", roundtrip = roundtrip, loss = self.loss().unwrap_or(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_quality() {
+        let mut link = LinkQuality::default();
+        assert_eq!(link.roundtrip(), None);
+        assert_eq!(link.loss(), None);
+        link.record(None);
+        assert_eq!(link.roundtrip(), None);
+        assert_eq!(link.loss(), Some(100));
+        assert_eq!(link.to_string(), "rtt -, loss 100%");
+        link.record(Some(Duration::from_millis(10)));
+        link.record(Some(Duration::from_millis(20)));
+        link.record(Some(Duration::from_millis(30)));
+        assert_eq!(link.roundtrip(), Some(Duration::from_millis(20)));
+        assert_eq!(link.loss(), Some(25));
+        assert_eq!(link.to_string(), "rtt 20.0ms, loss 25%");
+
+        // Only the last pings count, the lost one being forgotten.
+        for _ in 0..WINDOW - 3 {
+            link.record(Some(Duration::from_millis(20)));
+        }
+        assert_eq!(link.loss(), Some(0));
+        assert_eq!(link.roundtrip(), Some(Duration::from_millis(20)));
+    }
+}
@@ -0,0 +1,207 @@
+// An in-memory connection, and listener, for the tests to run the protocol without sockets.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use transport::{Listener, Transport};
+
+
+#[derive(Default)]
+struct Buffer {
+    bytes: VecDeque<u8>,
+    closed: bool,
+}
+
+// The bytes going one way.
+#[derive(Default)]
+struct Direction {
+    buffer: Mutex<Buffer>,
+    readable: Condvar,
+}
+
+impl Direction {
+    fn close(&self) {
+        self.buffer.lock().unwrap().closed = true;
+        self.readable.notify_all();
+    }
+}
+
+struct End {
+    incoming: Arc<Direction>,
+    outgoing: Arc<Direction>,
+    read_timeout: Mutex<Option<Duration>>,
+}
+
+// Once the last handle is dropped, as a socket being closed.
+impl Drop for End {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+// One end of an in-memory connection.
+pub struct PipeEnd {
+    end: Arc<End>,
+}
+
+// Both ends of an in-memory connection.
+pub fn pipe() -> (PipeEnd, PipeEnd) {
+    let (one_way, other_way) = (Arc::new(Direction::default()), Arc::new(Direction::default()));
+    let end = |incoming: &Arc<Direction>, outgoing: &Arc<Direction>| PipeEnd {
+        end: Arc::new(End {
+            incoming: incoming.clone(),
+            outgoing: outgoing.clone(),
+            read_timeout: Mutex::new(None),
+        }),
+    };
+    (end(&one_way, &other_way), end(&other_way, &one_way))
+}
+
+impl Read for PipeEnd {
+    // Ends with 0 bytes read once either end shut the connection down, or went away.
+    fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+        let deadline = self.end.read_timeout.lock().unwrap().map(|timeout| Instant::now() + timeout);
+        let incoming = &self.end.incoming;
+        let mut buffer = incoming.buffer.lock().unwrap();
+        while buffer.bytes.is_empty() && !buffer.closed && !bytes.is_empty() {
+            buffer = match deadline {
+                None => incoming.readable.wait(buffer).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::ErrorKind::WouldBlock.into());
+                    }
+                    incoming.readable.wait_timeout(buffer, deadline - now).unwrap().0
+                },
+            };
+        }
+        let count = bytes.len().min(buffer.bytes.len());
+        for (byte, read) in bytes.iter_mut().zip(buffer.bytes.drain(..count)) {
+            *byte = read;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for PipeEnd {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let outgoing = &self.end.outgoing;
+        let mut buffer = outgoing.buffer.lock().unwrap();
+        if buffer.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        buffer.bytes.extend(bytes);
+        outgoing.readable.notify_all();
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for PipeEnd {
+    fn try_clone(&self) -> io::Result<PipeEnd> {
+        Ok(PipeEnd {
+            end: self.end.clone(),
+        })
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.end.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    // The peer is in this process.
+    fn peer_ip(&self) -> Option<IpAddr> {
+        Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+
+    fn shutdown(&self) {
+        self.end.incoming.close();
+        self.end.outgoing.close();
+    }
+}
+
+// Connects to its `PipeListener`.
+#[derive(Clone)]
+pub struct PipeConnector {
+    connections: Sender<PipeEnd>,
+}
+
+impl PipeConnector {
+    pub fn connect(&self) -> io::Result<PipeEnd> {
+        let (ours, theirs) = pipe();
+        self.connections.send(theirs).map_err(|_| io::Error::from(io::ErrorKind::ConnectionRefused))?;
+        Ok(ours)
+    }
+}
+
+pub struct PipeListener {
+    connections: Arc<Mutex<Receiver<PipeEnd>>>,
+    // Keeps waiting for connections, as a socket would, once the connectors are gone.
+    connector: PipeConnector,
+}
+
+// A listener in memory, and where to connect to it.
+pub fn pipe_listener() -> (PipeListener, PipeConnector) {
+    let (chan, port) = mpsc::channel();
+    let connector = PipeConnector {
+        connections: chan,
+    };
+    let listener = PipeListener {
+        connections: Arc::new(Mutex::new(port)),
+        connector: connector.clone(),
+    };
+    (listener, connector)
+}
+
+impl Listener for PipeListener {
+    type Stream = PipeEnd;
+
+    fn accept(&self) -> io::Result<PipeEnd> {
+        self.connections.lock().unwrap().recv().map_err(|_| io::ErrorKind::ConnectionAborted.into())
+    }
+
+    fn try_clone(&self) -> io::Result<PipeListener> {
+        Ok(PipeListener {
+            connections: self.connections.clone(),
+            connector: self.connector.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_pipe() {
+        let (listener, connector) = pipe_listener();
+        let client = thread::spawn(move || {
+            let mut stream = connector.connect().unwrap();
+            stream.write_all(b"hello").unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        });
+        let mut stream = listener.accept().unwrap();
+        let mut greeting = [0; 5];
+        stream.read_exact(&mut greeting).unwrap();
+        assert_eq!(&greeting, b"hello");
+
+        stream.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        assert_eq!(stream.read(&mut greeting).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        let mut writer = stream.try_clone().unwrap();
+        writer.write_all(b"hi").unwrap();
+        // The client reads until the connection is closed, once both handles are gone.
+        drop(stream);
+        drop(writer);
+        assert_eq!(client.join().unwrap(), "hi");
+    }
+}
@@ -0,0 +1,83 @@
+use chrono::Local;
+
+
+// In-process extensions of the server, such as bots.
+// The replies returned by the hooks are sent to the client,
+// before the server operator's own reply.
+pub trait ChatPlugin: Send {
+    fn name(&self) -> &str;
+
+    fn on_connect(&mut self) -> Vec<String> {
+        vec![]
+    }
+
+    fn on_message(&mut self, _text: &str) -> Vec<String> {
+        vec![]
+    }
+
+    fn on_disconnect(&mut self) {}
+}
+
+// Repeats every message back to the client.
+pub struct EchoPlugin;
+
+impl ChatPlugin for EchoPlugin {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn on_message(&mut self, text: &str) -> Vec<String> {
+        vec![text.to_string()]
+    }
+}
+
+// Logs the activity of clients to the console.
+pub struct LoggingPlugin;
+
+impl LoggingPlugin {
+    fn log(&self, event: &str) {
+        println!("[{}] log: {}", Local::now().format("%H:%M:%S"), event);
+    }
+}
+
+impl ChatPlugin for LoggingPlugin {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn on_connect(&mut self) -> Vec<String> {
+        self.log("client connected");
+        vec![]
+    }
+
+    fn on_message(&mut self, text: &str) -> Vec<String> {
+        self.log(&format!("client sent {} bytes", text.len()));
+        vec![]
+    }
+
+    fn on_disconnect(&mut self) {
+        self.log("client disconnected");
+    }
+}
+
+pub fn plugin_by_name(name: &str) -> Option<Box<dyn ChatPlugin>> {
+    match name {
+        "echo" => Some(Box::new(EchoPlugin)),
+        "log" => Some(Box::new(LoggingPlugin)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_by_name() {
+        let mut echo = plugin_by_name("echo").unwrap();
+        assert_eq!(echo.name(), "echo");
+        assert_eq!(echo.on_connect(), Vec::<String>::new());
+        assert_eq!(echo.on_message("hello"), vec!["hello"]);
+        assert!(plugin_by_name("unknown").is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Jobs {
+    waiting: VecDeque<Job>,
+    // The workers started so far, up to the size of the pool.
+    workers: usize,
+    // Those of them waiting for a job.
+    idle: usize,
+}
+
+// Runs jobs, such as reading from the clients of a server in broadcast mode,
+// on at most `size` threads, started as needed, the jobs beyond waiting for one to be free, in order.
+#[derive(Clone)]
+pub struct WorkerPool {
+    jobs: Arc<(Mutex<Jobs>, Condvar)>,
+    size: usize,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> WorkerPool {
+        WorkerPool {
+            jobs: Arc::new((Mutex::new(Jobs {
+                waiting: VecDeque::new(),
+                workers: 0,
+                idle: 0,
+            }), Condvar::new())),
+            size,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Returns how many jobs are waiting for a worker, this one included, 0 if a worker took it.
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) -> usize {
+        let (ref jobs, ref available) = *self.jobs;
+        let mut jobs = jobs.lock().unwrap();
+        jobs.waiting.push_back(Box::new(job));
+        if jobs.waiting.len() <= jobs.idle {
+            available.notify_one();
+            return 0;
+        }
+        if jobs.workers < self.size && self.start_worker() {
+            jobs.workers += 1;
+            return 0;
+        }
+        jobs.waiting.len() - jobs.idle
+    }
+
+    fn start_worker(&self) -> bool {
+        let shared = self.jobs.clone();
+        thread::Builder::new().spawn(move || {
+            let (ref jobs, ref available) = *shared;
+            loop {
+                let job = {
+                    let mut jobs = jobs.lock().unwrap();
+                    loop {
+                        if let Some(job) = jobs.waiting.pop_front() {
+                            break job;
+                        }
+                        jobs.idle += 1;
+                        jobs = available.wait(jobs).unwrap();
+                        jobs.idle -= 1;
+                    }
+                };
+                // A job panicking doesn't take its worker down with it.
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            }
+        }).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_worker_pool() {
+        let pool = WorkerPool::new(2);
+        let (started_chan, started_port) = mpsc::channel();
+        let (done_chan, done_port) = mpsc::channel();
+        let mut releases = vec![];
+        for job in 0..3 {
+            let (release, wait) = mpsc::channel::<()>();
+            releases.push(release);
+            let (started_chan, done_chan) = (started_chan.clone(), done_chan.clone());
+            let waiting = pool.execute(move || {
+                let _ = started_chan.send(job);
+                let _ = wait.recv();
+                let _ = done_chan.send(job);
+            });
+            // The third job waits for one of the two workers.
+            assert_eq!(waiting, if job < 2 { 0 } else { 1 });
+            if job < 2 {
+                started_port.recv().unwrap();
+            }
+        }
+        assert!(started_port.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(releases.remove(0));
+        assert_eq!(done_port.recv().unwrap(), 0);
+        assert_eq!(started_port.recv().unwrap(), 2);
+        drop(releases);
+        let mut done: Vec<_> = done_port.iter().take(2).collect();
+        done.sort();
+        assert_eq!(done, vec![1, 2]);
+
+        // A job panicking doesn't take its worker down with it.
+        for _ in 0..2 {
+            pool.execute(|| panic!("a job panicking"));
+        }
+        pool.execute(move || done_chan.send(3).unwrap());
+        assert_eq!(done_port.recv().unwrap(), 3);
+    }
+}
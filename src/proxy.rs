@@ -0,0 +1,200 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp;
+use std::io;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+use transport::{Listener, Transport};
+
+
+const CHUNK_SIZE: usize = 4096;
+
+// The network to simulate between the clients and the server.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkConditions {
+    // Added to each chunk of data, the order of the data being kept.
+    pub latency: Duration,
+    // The most the latency varies by, either way.
+    pub jitter: Duration,
+    // The chance, from 0 to 1, of a chunk being cut short, the rest of it being lost.
+    pub truncate: f64,
+    // The chance of the connection being dropped before a chunk, possibly mid-frame.
+    pub drop: f64,
+    // For the same conditions to happen again, such as in a test.
+    pub seed: Option<u64>,
+}
+
+impl NetworkConditions {
+    // From the options of `simple_chat proxy`, such as `--latency 200 --drop 0.01`.
+    pub fn parse<I: Iterator<Item=String>>(mut arguments: I) -> Result<NetworkConditions, String> {
+        let mut conditions = NetworkConditions::default();
+        while let Some(argument) = arguments.next() {
+            let value = arguments.next().ok_or_else(|| tr!("option-needs-value", option = argument.as_str()))?;
+            let number = || value.parse::<f64>().ok().filter(|number| *number >= 0.0)
+                .ok_or_else(|| tr!("positive-number-needed", option = argument.as_str(), value = value.as_str()));
+            let chance = || number().and_then(|chance| if chance <= 1.0 {
+                Ok(chance)
+            } else {
+                Err(tr!("chance-needed", option = argument.as_str()))
+            });
+            match argument.as_ref() {
+                "--latency" => conditions.latency = Duration::from_millis(number()? as u64),
+                "--jitter" => conditions.jitter = Duration::from_millis(number()? as u64),
+                "--truncate" => conditions.truncate = chance()?,
+                "--drop" => conditions.drop = chance()?,
+                "--seed" => conditions.seed = Some(value.parse().map_err(|_| tr!("invalid-seed", seed = value.as_str()))?),
+                _ => return Err(tr!("unknown-option", option = argument.as_str())),
+            }
+        }
+        Ok(conditions)
+    }
+
+    fn delay(&self, rng: &mut StdRng) -> Duration {
+        if self.jitter.is_zero() {
+            return self.latency;
+        }
+        let jitter = rng.gen_range(0..=self.jitter.as_micros() as u64 * 2);
+        (self.latency + Duration::from_micros(jitter)).saturating_sub(self.jitter)
+    }
+}
+
+// Forward the data from `from` to `to` under the conditions, until either end closes.
+fn forward<T: Transport>(mut from: T, to: T, conditions: NetworkConditions, mut rng: StdRng) {
+    let (chan, port) = channel::<(Instant, Vec<u8>)>();
+    let mut writer = match to.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let _ = thread::Builder::new().spawn(move || {
+        for (deliver_at, chunk) in port {
+            thread::sleep(deliver_at.saturating_duration_since(Instant::now()));
+            if writer.write_all(&chunk).is_err() {
+                break;
+            }
+        }
+        // Once the data still on its way reached the peer, as it would have over a slow network.
+        writer.shutdown();
+    });
+    let mut last_delivery = Instant::now();
+    let mut chunk = [0; CHUNK_SIZE];
+    loop {
+        let count = match from.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(count) => count,
+        };
+        if rng.gen_bool(conditions.drop) {
+            from.shutdown();
+            to.shutdown();
+            return;
+        }
+        let count = if rng.gen_bool(conditions.truncate) { rng.gen_range(0..count) } else { count };
+        // Not overtaking the chunks before, as TCP keeps them in order.
+        last_delivery = cmp::max(last_delivery, Instant::now() + conditions.delay(&mut rng));
+        if chan.send((last_delivery, chunk[..count].to_vec())).is_err() {
+            break;
+        }
+    }
+}
+
+// Accept clients on the listener, connecting each to the server with `connect`,
+// and forwarding their data both ways under the conditions, to see how the chat copes.
+pub fn start_proxy<L, F>(listener: L, connect: F, conditions: NetworkConditions)
+    where L: Listener, F: Fn() -> io::Result<L::Stream> + Send + 'static {
+    let mut rng = match conditions.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let _ = thread::Builder::new().spawn(move || {
+        while let Ok(client) = listener.accept() {
+            let server = match connect() {
+                Ok(server) => server,
+                Err(_) => {
+                    client.shutdown();
+                    continue;
+                },
+            };
+            splice(client, server, &conditions, &mut rng);
+        }
+    });
+}
+
+// Forward the data of each connection to the other under the conditions, until either end closes.
+pub fn splice<T: Transport>(client: T, server: T, conditions: &NetworkConditions, rng: &mut StdRng) {
+    let (client_reader, server_reader) = match (client.try_clone(), server.try_clone()) {
+        (Ok(client_reader), Ok(server_reader)) => (client_reader, server_reader),
+        _ => return,
+    };
+    let (upstream, downstream) = (conditions.clone(), conditions.clone());
+    let (upstream_rng, downstream_rng) = (StdRng::seed_from_u64(rng.gen()), StdRng::seed_from_u64(rng.gen()));
+    let _ = thread::Builder::new().spawn(move || forward(client_reader, server, upstream, upstream_rng));
+    let _ = thread::Builder::new().spawn(move || forward(server_reader, client, downstream, downstream_rng));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pipe::{pipe_listener, PipeEnd};
+    use std::io::{Read, Write};
+
+    fn through_proxy(conditions: NetworkConditions) -> (PipeEnd, PipeEnd) {
+        let (server_listener, server_connector) = pipe_listener();
+        let (listener, connector) = pipe_listener();
+        start_proxy(listener, move || server_connector.connect(), conditions);
+        let client = connector.connect().unwrap();
+        (client, server_listener.accept().unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        let arguments = ["--latency", "200", "--jitter", "50", "--drop", "0.01", "--seed", "7"];
+        let conditions = NetworkConditions::parse(arguments.iter().map(|argument| argument.to_string())).unwrap();
+        assert_eq!(conditions, NetworkConditions {
+            latency: Duration::from_millis(200),
+            jitter: Duration::from_millis(50),
+            truncate: 0.0,
+            drop: 0.01,
+            seed: Some(7),
+        });
+        assert!(NetworkConditions::parse(["--drop", "2"].iter().map(|argument| argument.to_string())).is_err());
+        assert!(NetworkConditions::parse(["--latency"].iter().map(|argument| argument.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_latency() {
+        let (mut client, mut server) = through_proxy(NetworkConditions {
+            latency: Duration::from_millis(100),
+            jitter: Duration::from_millis(20),
+            seed: Some(1),
+            ..Default::default()
+        });
+        let started = Instant::now();
+        client.write_all(b"hello").unwrap();
+        client.write_all(b" world").unwrap();
+        let mut received = [0; 11];
+        server.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"hello world");
+        assert!(started.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[test]
+    fn test_drop_and_truncate() {
+        let (mut client, mut server) = through_proxy(NetworkConditions {
+            drop: 1.0,
+            ..Default::default()
+        });
+        let _ = client.write_all(b"hello");
+        let mut received = vec![];
+        assert_eq!(server.read_to_end(&mut received).unwrap(), 0);
+        assert_eq!(client.read(&mut [0; 1]).unwrap(), 0);
+
+        let (mut client, mut server) = through_proxy(NetworkConditions {
+            truncate: 1.0,
+            ..Default::default()
+        });
+        client.write_all(b"hello").unwrap();
+        client.shutdown();
+        server.read_to_end(&mut received).unwrap();
+        assert!(received.len() < 5);
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThrottlePolicy {
+    // Acknowledge excess messages, but don't pass them on.
+    Drop,
+    // Wait until the bucket has refilled before handling the message.
+    Delay,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub messages_per_second: f64,
+    pub policy: ThrottlePolicy,
+}
+
+// A token bucket, holding up to one second worth of messages.
+pub struct RateLimiter {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> RateLimiter {
+        RateLimiter {
+            limit,
+            tokens: RateLimiter::capacity(limit),
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    pub fn policy(&self) -> ThrottlePolicy {
+        self.limit.policy
+    }
+
+    fn capacity(limit: RateLimit) -> f64 {
+        limit.messages_per_second.max(1.0)
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill);
+        let refilled = self.tokens + elapsed.as_secs_f64() * self.limit.messages_per_second;
+        self.tokens = refilled.min(RateLimiter::capacity(self.limit));
+        self.last_refill = now;
+    }
+
+    // Take a token if one is available.
+    pub fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+        false
+    }
+
+    // How long until a token is available, none if one already is.
+    pub fn retry_after(&mut self) -> Duration {
+        self.refill();
+        let missing = (1.0 - self.tokens).max(0.0);
+        Duration::from_secs_f64(missing / self.limit.messages_per_second)
+    }
+
+    // Block until a token is available, and take it.
+    pub fn acquire(&mut self) {
+        while !self.try_acquire() {
+            ::std::thread::sleep(self.retry_after());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let mut limiter = RateLimiter::new(RateLimit {
+            messages_per_second: 2.0,
+            policy: ThrottlePolicy::Drop,
+        });
+        // A full bucket allows a burst of one second worth of messages.
+        assert!(limiter.try_acquire());
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+        let retry_after = limiter.retry_after();
+        assert!(retry_after > Duration::from_millis(400) && retry_after <= Duration::from_millis(500));
+        // Waiting refills the bucket.
+        let start = Instant::now();
+        limiter.acquire();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Local};
+use codec::Frame;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameDirection {
+    Sent,
+    Received,
+}
+
+// A frame of a recorded session, each a JSON line of the log.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordedFrame {
+    // When it was sent or received, in milliseconds since the unix epoch.
+    pub at: u64,
+    // The connections are numbered from 1 as they are recorded.
+    pub connection: u32,
+    pub direction: FrameDirection,
+    pub frame: Frame,
+}
+
+impl RecordedFrame {
+    pub fn at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.at)
+    }
+
+    // Such as `[12:00:01.250] #1 -> Message { id: 1, text: "hi" }`.
+    pub fn describe(&self) -> String {
+        let at: DateTime<Local> = self.at().into();
+        let arrow = match self.direction {
+            FrameDirection::Sent => "->",
+            FrameDirection::Received => "<-",
+        };
+        format!("[{}] #{} {} {:?}", at.format("%H:%M:%S%.3f"), self.connection, arrow, self.frame)
+    }
+}
+
+// Records the frames of all the connections of the chat to a log, shared by them.
+#[derive(Clone)]
+pub struct Recorder {
+    log: Arc<Mutex<BufWriter<File>>>,
+    connections: Arc<AtomicU32>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, String> {
+        let log = File::create(path).map_err(|error| format!("failed to create {}: {}", path, error))?;
+        Ok(Recorder {
+            log: Arc::new(Mutex::new(BufWriter::new(log))),
+            connections: Arc::new(AtomicU32::new(0)),
+        })
+    }
+
+    // The number of a new connection.
+    pub fn connection(&self) -> u32 {
+        self.connections.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    // The log is only for debugging, the chat going on if it can't be written to.
+    pub fn record(&self, connection: u32, direction: FrameDirection, frame: &Frame) {
+        let recorded = RecordedFrame {
+            at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            connection,
+            direction,
+            frame: frame.clone(),
+        };
+        let mut log = self.log.lock().unwrap();
+        if let Ok(line) = serde_json::to_string(&recorded) {
+            let _ = writeln!(log, "{}", line);
+            let _ = log.flush();
+        }
+    }
+}
+
+pub fn load(path: &str) -> Result<Vec<RecordedFrame>, String> {
+    let log = fs::read_to_string(path).map_err(|error| format!("failed to read {}: {}", path, error))?;
+    log.lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| serde_json::from_str(line)
+            .map_err(|error| format!("{}, line {}: {}", path, index + 1, error)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::{FrameStream, WireFormat};
+    use std::env;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_record_and_load() {
+        let path = env::temp_dir().join("simple_chat_test_session.log");
+        let path = path.to_str().unwrap();
+        let recorder = Recorder::create(path).unwrap();
+        let mut client = FrameStream::connect(Cursor::new(vec![]), WireFormat::Json).unwrap();
+        client.record(&recorder);
+        client.write_frame(SystemTime::now(), &Frame::Message { id: 1, text: "hi".to_string(), signature: None, ttl: None }).unwrap();
+        let mut server = FrameStream::accept(Cursor::new(client.get_ref().get_ref().clone())).unwrap();
+        server.record(&recorder);
+        server.read_frame().unwrap();
+
+        let frames = load(path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].connection, frames[0].direction), (1, FrameDirection::Sent));
+        assert_eq!((frames[1].connection, frames[1].direction), (2, FrameDirection::Received));
+        assert_eq!(frames[1].frame, Frame::Message { id: 1, text: "hi".to_string(), signature: None, ttl: None });
+        assert!(frames[0].at().elapsed().unwrap() < Duration::from_secs(5));
+        assert!(frames[0].describe().ends_with("#1 -> Message { id: 1, text: \"hi\", signature: None, ttl: None }"));
+
+        fs::write(path, "{\"at\": 0}\n").unwrap();
+        assert!(load(path).unwrap_err().contains("line 1"));
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,347 @@
+use datagram::{self, DatagramStream};
+use invite::{self, Invitations};
+use proxy::{self, NetworkConditions};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use transport::Transport;
+
+
+// How often a peer registers again, while waiting for the other.
+const REGISTER_INTERVAL: Duration = Duration::from_secs(1);
+// A peer which stopped registering for this long is forgotten.
+const ROOM_TIMEOUT: Duration = Duration::from_secs(30);
+const HELLO_INTERVAL: Duration = Duration::from_millis(100);
+// Long enough for both NATs to open their hole, without keeping the peers waiting for the relay.
+const PUNCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_ROOM_LENGTH: usize = 256;
+// The room, and the token of an invitation to it after a tab, if it is protected.
+const MAX_REQUEST_LENGTH: usize = MAX_ROOM_LENGTH + 1 + 2 * invite::TOKEN_SIZE;
+
+// A peer registering in a room, followed by its name, and the server
+// telling each of the two in a room about the other, followed by its address,
+// or refusing a peer without a valid invitation to a protected room.
+const REGISTER: u8 = b'R';
+const PEER: u8 = b'P';
+const REFUSED: u8 = b'X';
+
+// The peer's connection, punched through the NATs, or relayed by the rendezvous server when that failed.
+pub enum PeerLink {
+    Punched(DatagramStream),
+    Relayed(TcpStream),
+}
+
+impl PeerLink {
+    pub fn relayed(&self) -> bool {
+        match *self {
+            PeerLink::Punched(_) => false,
+            PeerLink::Relayed(_) => true,
+        }
+    }
+}
+
+impl Read for PeerLink {
+    fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            PeerLink::Punched(ref mut stream) => stream.read(bytes),
+            PeerLink::Relayed(ref mut stream) => stream.read(bytes),
+        }
+    }
+}
+
+impl Write for PeerLink {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        match *self {
+            PeerLink::Punched(ref mut stream) => stream.write(bytes),
+            PeerLink::Relayed(ref mut stream) => stream.write(bytes),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            PeerLink::Punched(ref mut stream) => stream.flush(),
+            PeerLink::Relayed(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+impl Transport for PeerLink {
+    fn try_clone(&self) -> io::Result<PeerLink> {
+        Ok(match *self {
+            PeerLink::Punched(ref stream) => PeerLink::Punched(Transport::try_clone(stream)?),
+            PeerLink::Relayed(ref stream) => PeerLink::Relayed(Transport::try_clone(stream)?),
+        })
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match *self {
+            PeerLink::Punched(ref stream) => Transport::set_read_timeout(stream, timeout),
+            PeerLink::Relayed(ref stream) => Transport::set_read_timeout(stream, timeout),
+        }
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        match *self {
+            PeerLink::Punched(ref stream) => stream.peer_ip(),
+            PeerLink::Relayed(ref stream) => Transport::peer_ip(stream),
+        }
+    }
+
+    fn shutdown(&self) {
+        match *self {
+            PeerLink::Punched(ref stream) => Transport::shutdown(stream),
+            PeerLink::Relayed(ref stream) => Transport::shutdown(stream),
+        }
+    }
+}
+
+// Start the rendezvous server on the address, over UDP for the peers to learn each other's
+// public address, and over TCP on the same port to relay those which can't reach each other,
+// the rooms invited to with `simple_chat invite` being for those with an invitation.
+// Returns the address, for a port of 0 to be known.
+pub fn start(address: &str, invitations: Option<PathBuf>) -> io::Result<SocketAddr> {
+    let socket = UdpSocket::bind(address)?;
+    let address = socket.local_addr()?;
+    let listener = TcpListener::bind(address)?;
+    let relay_invitations = invitations.clone();
+    thread::Builder::new().spawn(move || introduce(socket, invitations))?;
+    thread::Builder::new().spawn(move || relay(listener, relay_invitations))?;
+    Ok(address)
+}
+
+fn request(room: &str, invitation: Option<&str>) -> String {
+    match invitation {
+        Some(token) => format!("{}\t{}", room, token),
+        None => room.to_string(),
+    }
+}
+
+// The room asked for, if the peer may join it. The invitations are read again each time,
+// for those issued since to be valid at once.
+fn admitted(request: &str, invitations: Option<&Path>) -> Option<String> {
+    let (room, token) = match request.split_once('\t') {
+        Some((room, token)) => (room, Some(token)),
+        None => (request, None),
+    };
+    if room.is_empty() || !Invitations::load(invitations).admits(room, token, SystemTime::now()) {
+        return None;
+    }
+    Some(room.to_string())
+}
+
+// Tell the two peers registered in a room each other's address, as the server saw it.
+fn introduce(socket: UdpSocket, invitations: Option<PathBuf>) {
+    let mut rooms: HashMap<String, Vec<(SocketAddr, Instant)>> = HashMap::new();
+    let mut datagram = [0; MAX_REQUEST_LENGTH + 1];
+    while let Ok((size, from)) = socket.recv_from(&mut datagram) {
+        let request = match (datagram.first(), str::from_utf8(&datagram[1..size])) {
+            (Some(&REGISTER), Ok(request)) => request,
+            _ => continue,
+        };
+        let room = match admitted(request, invitations.as_deref()) {
+            Some(room) => room,
+            None => {
+                let _ = socket.send_to(&[REFUSED], from);
+                continue;
+            },
+        };
+        rooms.retain(|_, peers| {
+            peers.retain(|&(_, registered)| registered.elapsed() < ROOM_TIMEOUT);
+            !peers.is_empty()
+        });
+        let peers = rooms.entry(room).or_default();
+        peers.retain(|&(peer, _)| peer != from);
+        peers.push((from, Instant::now()));
+        // A third one takes the place of the one which registered first.
+        if peers.len() > 2 {
+            peers.remove(0);
+        }
+        if let [(one, _), (other, _)] = peers[..] {
+            let _ = socket.send_to(&[&[PEER], other.to_string().as_bytes()].concat(), one);
+            let _ = socket.send_to(&[&[PEER], one.to_string().as_bytes()].concat(), other);
+        }
+    }
+}
+
+// Pair the connections naming the same room, forwarding the data of each to the other.
+fn relay(listener: TcpListener, invitations: Option<PathBuf>) {
+    let waiting: Arc<Mutex<HashMap<String, TcpStream>>> = Default::default();
+    while let Ok((stream, _)) = listener.accept() {
+        let waiting = waiting.clone();
+        let invitations = invitations.clone();
+        let _ = thread::Builder::new().spawn(move || {
+            let room = match read_request(&stream).map(|request| admitted(&request, invitations.as_deref())) {
+                Ok(Some(room)) => room,
+                _ => return Transport::shutdown(&stream),
+            };
+            let other = waiting.lock().unwrap().remove(&room);
+            match other {
+                Some(other) => proxy::splice(other, stream, &NetworkConditions::default(), &mut StdRng::from_entropy()),
+                None => {
+                    waiting.lock().unwrap().insert(room, stream);
+                },
+            }
+        });
+    }
+}
+
+// The room a connection to the relay names first, on a line.
+fn read_request(mut stream: &TcpStream) -> io::Result<String> {
+    stream.set_read_timeout(Some(ROOM_TIMEOUT))?;
+    let mut request = vec![];
+    let mut byte = [0];
+    while request.len() <= MAX_REQUEST_LENGTH {
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            stream.set_read_timeout(None)?;
+            return String::from_utf8(request).map_err(|_| io::ErrorKind::InvalidData.into());
+        }
+        request.push(byte[0]);
+    }
+    Err(io::ErrorKind::InvalidData.into())
+}
+
+// Reach the peer which names the same room on the rendezvous server: both learn each other's
+// public address from it, and send hellos there at the same time for each NAT to let the other's in.
+// If none come through in time, the server relays the chat instead.
+// A protected room takes the token of an invitation to it.
+pub fn reach(rendezvous: &str, room: &str, invitation: Option<&str>) -> io::Result<PeerLink> {
+    if room.is_empty() || room.len() > MAX_ROOM_LENGTH || room.contains(['\n', '\t']) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the room takes a name of up to 256 bytes, on a line"));
+    }
+    if invitation.is_some_and(|token| token.len() != 2 * invite::TOKEN_SIZE) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not the token of an invitation"));
+    }
+    let server = rendezvous.to_socket_addrs()?.next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no address for {}", rendezvous)))?;
+    let unspecified: SocketAddr = if server.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0; 16], 0).into() };
+    let socket = UdpSocket::bind(unspecified)?;
+    let request = request(room, invitation);
+    let peer = register(&socket, server, &request)?;
+    if punch(&socket, peer)? {
+        return DatagramStream::new(socket, peer).map(PeerLink::Punched);
+    }
+    let mut stream = TcpStream::connect(server)?;
+    stream.write_all(format!("{}\n", request).as_bytes())?;
+    Ok(PeerLink::Relayed(stream))
+}
+
+// Returns the peer's address once it registered too.
+fn register(socket: &UdpSocket, server: SocketAddr, request: &str) -> io::Result<SocketAddr> {
+    socket.set_read_timeout(Some(REGISTER_INTERVAL))?;
+    let mut datagram = [0; 64];
+    loop {
+        socket.send_to(&[&[REGISTER], request.as_bytes()].concat(), server)?;
+        let deadline = Instant::now() + REGISTER_INTERVAL;
+        while Instant::now() < deadline {
+            match socket.recv_from(&mut datagram) {
+                Ok((size, from)) if from == server && size > 0 && datagram[0] == REFUSED => {
+                    return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the room takes a valid invitation, given with --invite"));
+                },
+                Ok((size, from)) if from == server && size > 1 && datagram[0] == PEER => {
+                    if let Some(peer) = str::from_utf8(&datagram[1..size]).ok().and_then(|peer| peer.parse().ok()) {
+                        return Ok(peer);
+                    }
+                },
+                Ok(_) => {},
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut => {},
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+// Returns whether the hellos came through both ways.
+fn punch(socket: &UdpSocket, peer: SocketAddr) -> io::Result<bool> {
+    socket.set_read_timeout(Some(HELLO_INTERVAL))?;
+    let deadline = Instant::now() + PUNCH_TIMEOUT;
+    let mut heard = false;
+    let mut datagram = [0; 64];
+    while Instant::now() < deadline {
+        let hello = if heard { datagram::HEARD } else { datagram::NOT_HEARD };
+        socket.send_to(&[datagram::HELLO, hello], peer)?;
+        match socket.recv_from(&mut datagram) {
+            Ok((size, from)) if from == peer && size > 1 && datagram[0] == datagram::HELLO => {
+                heard = true;
+                // Anything but a first hello means it heard ours.
+                if datagram[1] != datagram::NOT_HEARD {
+                    // For it to know we heard it too, a few times in case some are lost.
+                    for _ in 0..3 {
+                        socket.send_to(&[datagram::HELLO, datagram::HEARD], peer)?;
+                    }
+                    return Ok(true);
+                }
+            },
+            Ok(_) => {},
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut => {},
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn reach_both<F>(reach: F) -> (PeerLink, PeerLink)
+        where F: Fn() -> io::Result<PeerLink> + Send + Sync + 'static {
+        let reach = Arc::new(reach);
+        let other = reach.clone();
+        let other = thread::spawn(move || other().unwrap());
+        (reach().unwrap(), other.join().unwrap())
+    }
+
+    fn chat(mut one: PeerLink, mut other: PeerLink) {
+        one.write_all(b"hello").unwrap();
+        let mut received = [0; 5];
+        other.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"hello");
+        other.write_all(b"world").unwrap();
+        one.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"world");
+    }
+
+    #[test]
+    fn test_punched() {
+        let server = start("127.0.0.1:0", None).unwrap().to_string();
+        let (one, other) = reach_both(move || reach(&server, "room", None));
+        assert!(!one.relayed() && !other.relayed());
+        chat(one, other);
+    }
+
+    #[test]
+    fn test_relayed() {
+        let server = start("127.0.0.1:0", None).unwrap();
+        let relay = move || {
+            let mut stream = TcpStream::connect(server)?;
+            stream.write_all(b"room\n")?;
+            Ok(PeerLink::Relayed(stream))
+        };
+        let (one, other) = reach_both(relay);
+        chat(one, other);
+        assert!(reach(&server.to_string(), "", None).is_err());
+    }
+
+    #[test]
+    fn test_invited() {
+        let path = env::temp_dir().join("simple_chat_test_rendezvous_invitations");
+        let _ = fs::remove_file(&path);
+        let token = Invitations::load(Some(&path)).issue("private", Duration::from_secs(60)).unwrap();
+        let server = start("127.0.0.1:0", Some(path.clone())).unwrap().to_string();
+        let refused = reach(&server, "private", None).err().unwrap();
+        assert_eq!(refused.kind(), io::ErrorKind::PermissionDenied);
+        let (one, other) = reach_both(move || reach(&server, "private", Some(&token)));
+        chat(one, other);
+        let _ = fs::remove_file(&path);
+    }
+}
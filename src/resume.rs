@@ -0,0 +1,114 @@
+use config;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+
+const TOKEN_SIZE: usize = 16;
+const TOKEN_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+// Tokens issued by the server to authenticated clients,
+// each redeemable once, before it expires, in place of the credentials.
+pub struct TokenStore {
+    lifetime: Duration,
+    // By token, the user it was issued to, and when it expires.
+    tokens: HashMap<String, (String, Instant)>,
+}
+
+impl Default for TokenStore {
+    fn default() -> TokenStore {
+        TokenStore::new(TOKEN_LIFETIME)
+    }
+}
+
+impl TokenStore {
+    pub fn new(lifetime: Duration) -> TokenStore {
+        TokenStore {
+            lifetime,
+            tokens: HashMap::new(),
+        }
+    }
+
+    pub fn issue(&mut self, user: &str) -> String {
+        let now = Instant::now();
+        self.tokens.retain(|_, &mut (_, expires)| expires > now);
+        let mut bytes = [0; TOKEN_SIZE];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.tokens.insert(token.clone(), (user.to_string(), now + self.lifetime));
+        token
+    }
+
+    // The user the token was issued to, if it is still valid, the token then being used up.
+    pub fn redeem(&mut self, token: &str) -> Option<String> {
+        match self.tokens.remove(token) {
+            Some((user, expires)) if expires > Instant::now() => Some(user),
+            _ => None,
+        }
+    }
+
+    // Revoke the tokens of a user, or of all users, returning how many were revoked.
+    pub fn revoke(&mut self, user: Option<&str>) -> usize {
+        let before = self.tokens.len();
+        self.tokens.retain(|_, &mut (ref issued_to, _)| user.is_some_and(|user| user != issued_to));
+        before - self.tokens.len()
+    }
+}
+
+// Where the client keeps its last token, as `<server address> <token>`.
+fn client_token_path() -> Option<PathBuf> {
+    Some(config::config_dir()?.join("resume_token"))
+}
+
+pub fn load_client_token(address: &str) -> Option<String> {
+    let saved = fs::read_to_string(client_token_path()?).ok()?;
+    match saved.trim().split_once(' ') {
+        Some((saved_address, token)) if saved_address == address => Some(token.to_string()),
+        _ => None,
+    }
+}
+
+pub fn save_client_token(address: &str, token: &str) -> Result<(), String> {
+    let path = client_token_path().ok_or("no config directory to save the resume token in")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Only readable by the user, as it stands for their credentials.
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    writeln!(file, "{} {}", address, token).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_store() {
+        let mut tokens = TokenStore::default();
+        let token = tokens.issue("alice");
+        assert_eq!(token.len(), 2 * TOKEN_SIZE);
+        assert_eq!(tokens.redeem(&token), Some("alice".to_string()));
+        // Rotated on use.
+        assert_eq!(tokens.redeem(&token), None);
+
+        let alice = tokens.issue("alice");
+        let bob = tokens.issue("bob");
+        assert_eq!(tokens.revoke(Some("alice")), 1);
+        assert_eq!(tokens.redeem(&alice), None);
+        assert_eq!(tokens.revoke(None), 1);
+        assert_eq!(tokens.redeem(&bob), None);
+
+        let mut expiring = TokenStore::new(Duration::from_secs(0));
+        let token = expiring.issue("alice");
+        assert_eq!(expiring.redeem(&token), None);
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::fmt;
+
+
+// A message to send at a given time, see `/schedule`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduledMessage {
+    pub id: u64,
+    // The conversation it goes to.
+    pub connection: usize,
+    pub at: DateTime<Local>,
+    pub text: String,
+}
+
+impl fmt::Display for ScheduledMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {} {}", self.id, self.at.format("%Y-%m-%d %H:%M"), self.text)
+    }
+}
+
+// The messages scheduled and not sent yet, in the order they are due.
+#[derive(Default)]
+pub struct Schedule {
+    messages: Vec<ScheduledMessage>,
+    last_id: u64,
+}
+
+// A local time such as `2024-06-01 09:00`.
+pub fn parse_time(time: &str) -> Result<DateTime<Local>, String> {
+    let invalid = || format!("invalid time '{}', expected YYYY-MM-DD HH:MM", time);
+    let time = NaiveDateTime::parse_from_str(time.trim(), "%Y-%m-%d %H:%M").map_err(|_| invalid())?;
+    // The earliest, when the clocks go back.
+    Local.from_local_datetime(&time).earliest().ok_or_else(invalid)
+}
+
+impl Schedule {
+    pub fn add(&mut self, connection: usize, at: DateTime<Local>, text: String) -> &ScheduledMessage {
+        self.last_id += 1;
+        let message = ScheduledMessage {
+            id: self.last_id,
+            connection,
+            at,
+            text,
+        };
+        let index = self.messages.iter().position(|scheduled| scheduled.at > at).unwrap_or(self.messages.len());
+        self.messages.insert(index, message);
+        &self.messages[index]
+    }
+
+    pub fn cancel(&mut self, id: u64) -> Option<ScheduledMessage> {
+        let index = self.messages.iter().position(|scheduled| scheduled.id == id)?;
+        Some(self.messages.remove(index))
+    }
+
+    // The first message due by `now` in a conversation `ready` to send it.
+    pub fn take_due<F: Fn(usize) -> bool>(&mut self, now: DateTime<Local>, ready: F) -> Option<ScheduledMessage> {
+        let index = self.messages.iter()
+            .take_while(|scheduled| scheduled.at <= now)
+            .position(|scheduled| ready(scheduled.connection))?;
+        Some(self.messages.remove(index))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ScheduledMessage> {
+        self.messages.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_schedule() {
+        let now = Local::now();
+        let mut schedule = Schedule::default();
+        let later = schedule.add(0, now + Duration::hours(1), "later".to_string()).id;
+        let soon = schedule.add(1, now - Duration::minutes(1), "soon".to_string()).id;
+        schedule.add(0, now - Duration::minutes(2), "sooner".to_string());
+        let texts: Vec<&str> = schedule.iter().map(|scheduled| scheduled.text.as_str()).collect();
+        assert_eq!(texts, vec!["sooner", "soon", "later"]);
+
+        // Only in a conversation ready for it.
+        assert_eq!(schedule.take_due(now, |connection| connection == 1).map(|scheduled| scheduled.id), Some(soon));
+        assert_eq!(schedule.take_due(now, |connection| connection == 1), None);
+        assert_eq!(schedule.take_due(now, |_| true).unwrap().text, "sooner");
+        assert_eq!(schedule.take_due(now, |_| true), None);
+        assert_eq!(schedule.cancel(later).unwrap().text, "later");
+        assert_eq!(schedule.cancel(later), None);
+
+        assert_eq!(parse_time("2024-06-01 09:00").unwrap().format("%d/%m %H:%M").to_string(), "01/06 09:00");
+        assert!(parse_time("2024-06-01").is_err());
+        assert!(parse_time("tomorrow 09:00").is_err());
+    }
+}
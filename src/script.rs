@@ -0,0 +1,93 @@
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use std::path::PathBuf;
+
+
+// What to do with an incoming message, according to the script.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptOutcome {
+    // The text to display, `None` if the message was dropped.
+    pub text: Option<String>,
+    // A reply to send automatically, instead of prompting the user.
+    pub reply: Option<String>,
+}
+
+// A rhai script defining an `on_message(sender, text)` function, returning either:
+// - `()` to drop the message,
+// - a string to display instead of the message,
+// - a map with optional `text` and `reply` keys, to also reply automatically.
+pub struct ScriptHook {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptHook {
+    pub fn load(path: &str) -> Result<ScriptHook, String> {
+        let engine = Engine::new();
+        let ast = engine.compile_file(PathBuf::from(path))
+            .map_err(|error| format!("failed to load script {}: {}", path, error))?;
+        Ok(ScriptHook {
+            engine,
+            ast,
+        })
+    }
+
+    #[cfg(test)]
+    fn from_source(source: &str) -> ScriptHook {
+        let engine = Engine::new();
+        let ast = engine.compile(source).unwrap();
+        ScriptHook {
+            engine,
+            ast,
+        }
+    }
+
+    pub fn on_message(&self, sender: &str, text: &str) -> Result<ScriptOutcome, String> {
+        let result: Dynamic = self.engine
+            .call_fn(&mut Scope::new(), &self.ast, "on_message", (sender.to_string(), text.to_string()))
+            .map_err(|error| format!("script error: {}", error))?;
+        if result.is_unit() {
+            return Ok(ScriptOutcome::default());
+        }
+        if result.is_string() {
+            return Ok(ScriptOutcome {
+                text: result.into_string().ok(),
+                reply: None,
+            });
+        }
+        let map = result.try_cast::<Map>()
+            .ok_or_else(|| "on_message must return (), a string or a map".to_string())?;
+        let field = |key: &str| {
+            map.get(key).cloned().and_then(|value| value.into_string().ok())
+        };
+        Ok(ScriptOutcome {
+            text: field("text"),
+            reply: field("reply"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_outcomes() {
+        let script = ScriptHook::from_source(r#"
+            fn on_message(sender, text) {
+                if text == "spam" {
+                    return ();
+                }
+                if text == "ping" {
+                    return #{ text: text, reply: "pong" };
+                }
+                sender + " says " + text
+            }
+        "#);
+        assert_eq!(script.on_message("client", "spam").unwrap(), ScriptOutcome::default());
+        assert_eq!(script.on_message("client", "ping").unwrap(), ScriptOutcome {
+            text: Some("ping".to_string()),
+            reply: Some("pong".to_string()),
+        });
+        assert_eq!(script.on_message("client", "hi").unwrap().text, Some("client says hi".to_string()));
+    }
+}
@@ -0,0 +1,118 @@
+use config;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+
+// How well a session is protected, from weakest to strongest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecurityLevel {
+    Plaintext,
+    Tls,
+    EndToEnd,
+}
+
+impl SecurityLevel {
+    fn parse(name: &str) -> Option<SecurityLevel> {
+        match name {
+            "plaintext" => Some(SecurityLevel::Plaintext),
+            "tls" => Some(SecurityLevel::Tls),
+            "e2e" => Some(SecurityLevel::EndToEnd),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            SecurityLevel::Plaintext => "plaintext",
+            SecurityLevel::Tls => "tls",
+            SecurityLevel::EndToEnd => "e2e",
+        }
+    }
+}
+
+impl fmt::Display for SecurityLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SecurityLevel::Plaintext => write!(f, "{}", tr!("security-plaintext")),
+            SecurityLevel::Tls => write!(f, "{}", tr!("security-tls")),
+            SecurityLevel::EndToEnd => write!(f, "{}", tr!("security-e2e")),
+        }
+    }
+}
+
+// The strongest level used so far with each peer, one `<peer> <level>` per line.
+#[derive(Debug, Default)]
+pub struct SecurityHistory {
+    path: Option<PathBuf>,
+    levels: HashMap<String, SecurityLevel>,
+}
+
+impl SecurityHistory {
+    pub fn parse(source: &str) -> SecurityHistory {
+        let levels = source.lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter_map(|(peer, level)| Some((peer.to_string(), SecurityLevel::parse(level.trim())?)))
+            .collect();
+        SecurityHistory {
+            path: None,
+            levels,
+        }
+    }
+
+    // The history kept in the config directory, empty if there is none yet.
+    pub fn load() -> SecurityHistory {
+        let path = config::config_dir().map(|dir| dir.join("security_levels"));
+        let source = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        SecurityHistory {
+            path,
+            ..SecurityHistory::parse(&source)
+        }
+    }
+
+    // Refuse a level weaker than used before with the peer, unless allowed,
+    // otherwise remember it if it is the strongest yet.
+    pub fn check(&mut self, peer: &str, level: SecurityLevel, allow_downgrade: bool) -> Result<(), String> {
+        match self.levels.get(peer) {
+            Some(&previous) if previous > level && !allow_downgrade => {
+                return Err(format!("the session with {} is {}, but was {} before, \
+                                    use --allow-downgrade to continue anyway", peer, level, previous));
+            },
+            Some(&previous) if previous >= level => return Ok(()),
+            _ => {},
+        }
+        self.levels.insert(peer.to_string(), level);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        let source: String = self.levels.iter()
+            .map(|(peer, level)| format!("{} {}\n", peer, level.name()))
+            .collect();
+        fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downgrade_protection() {
+        let mut history = SecurityHistory::parse("127.0.0.1:8000 tls\nexample.com:8000 unknown\n");
+        assert!(history.check("127.0.0.1:8000", SecurityLevel::Plaintext, false).is_err());
+        assert!(history.check("127.0.0.1:8000", SecurityLevel::Plaintext, true).is_ok());
+        assert!(history.check("127.0.0.1:8000", SecurityLevel::EndToEnd, false).is_ok());
+        assert!(history.check("127.0.0.1:8000", SecurityLevel::Tls, false).is_err());
+        assert!(history.check("example.com:8000", SecurityLevel::Plaintext, false).is_ok());
+        assert!(SecurityLevel::Plaintext < SecurityLevel::Tls);
+    }
+}
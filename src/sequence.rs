@@ -0,0 +1,119 @@
+use codec::MessageId;
+use std::collections::BTreeMap;
+
+
+// A message received in order, or the ids of those given up on.
+#[derive(Debug, PartialEq)]
+pub enum Ordered<T> {
+    Message(MessageId, T),
+    Missing {
+        first: MessageId,
+        last: MessageId,
+    },
+}
+
+// Puts the messages received on a connection back in the order they were sent,
+// by their id, the sender numbering them from 1.
+// Those received after a gap wait for the missing ones, at most `window` of them,
+// after which the missing ones are given up on.
+pub struct ReorderBuffer<T> {
+    next: MessageId,
+    pending: BTreeMap<MessageId, T>,
+    window: usize,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(window: usize) -> ReorderBuffer<T> {
+        ReorderBuffer {
+            next: 1,
+            pending: BTreeMap::new(),
+            window,
+        }
+    }
+
+    // Whether the message wasn't received already, rather than sent again.
+    pub fn is_new(&self, id: MessageId) -> bool {
+        id >= self.next && !self.pending.contains_key(&id)
+    }
+
+    pub fn push(&mut self, id: MessageId, message: T) {
+        if self.is_new(id) {
+            self.pending.insert(id, message);
+        }
+    }
+
+    // The messages which are next in order.
+    pub fn ready(&mut self) -> Vec<Ordered<T>> {
+        let mut ordered = self.take_in_order();
+        while self.pending.len() > self.window {
+            ordered.extend(self.skip_gap());
+            ordered.extend(self.take_in_order());
+        }
+        ordered
+    }
+
+    // All the messages waiting, giving up on those missing before them,
+    // once the sender won't send more for now.
+    pub fn flush(&mut self) -> Vec<Ordered<T>> {
+        let mut ordered = self.take_in_order();
+        while !self.pending.is_empty() {
+            ordered.extend(self.skip_gap());
+            ordered.extend(self.take_in_order());
+        }
+        ordered
+    }
+
+    fn take_in_order(&mut self) -> Vec<Ordered<T>> {
+        let mut ordered = vec![];
+        while let Some(message) = self.pending.remove(&self.next) {
+            ordered.push(Ordered::Message(self.next, message));
+            self.next += 1;
+        }
+        ordered
+    }
+
+    fn skip_gap(&mut self) -> Option<Ordered<T>> {
+        let first_pending = *self.pending.keys().next()?;
+        let gap = Ordered::Missing {
+            first: self.next,
+            last: first_pending - 1,
+        };
+        self.next = first_pending;
+        Some(gap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reorder() {
+        let mut buffer = ReorderBuffer::new(2);
+        buffer.push(2, "two");
+        assert!(buffer.ready().is_empty());
+        buffer.push(1, "one");
+        assert_eq!(buffer.ready(), vec![Ordered::Message(1, "one"), Ordered::Message(2, "two")]);
+        // Sent again.
+        assert!(!buffer.is_new(2));
+        buffer.push(2, "two");
+        assert!(buffer.ready().is_empty());
+
+        // Three is missing, and given up on once more than two wait behind it.
+        buffer.push(4, "four");
+        buffer.push(5, "five");
+        assert!(buffer.ready().is_empty());
+        buffer.push(6, "six");
+        assert_eq!(buffer.ready(), vec![
+            Ordered::Missing { first: 3, last: 3 },
+            Ordered::Message(4, "four"),
+            Ordered::Message(5, "five"),
+            Ordered::Message(6, "six"),
+        ]);
+        assert!(!buffer.is_new(3));
+
+        buffer.push(9, "nine");
+        assert_eq!(buffer.flush(), vec![Ordered::Missing { first: 7, last: 8 }, Ordered::Message(9, "nine")]);
+        assert!(buffer.flush().is_empty());
+    }
+}
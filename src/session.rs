@@ -0,0 +1,141 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+
+// When this much time is left, the user is warned the session is ending.
+const WARNINGS: [Duration; 3] = [Duration::from_secs(5 * 60), Duration::from_secs(60), Duration::from_secs(10)];
+
+// Such as `90s`, `30m` or `2h`.
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{}', use a number followed by s, m or h", duration);
+    if duration.len() < 2 || !duration.is_char_boundary(duration.len() - 1) {
+        return Err(invalid());
+    }
+    let (count, unit) = duration.split_at(duration.len() - 1);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => count,
+        "m" => count * 60,
+        "h" => count * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    if seconds == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}m{:02}s", seconds / 60, seconds % 60)
+}
+
+struct Limit {
+    started: Instant,
+    // The shortest length asked for by either side, if any.
+    length: Option<Duration>,
+    warnings_given: usize,
+    ended: bool,
+}
+
+// How long a connection may last, shared between the component and its timer.
+#[derive(Clone)]
+pub struct SessionLimit {
+    limit: Arc<Mutex<Limit>>,
+}
+
+impl SessionLimit {
+    // A session starting now, without a limit until one is asked for.
+    pub fn new() -> SessionLimit {
+        SessionLimit {
+            limit: Arc::new(Mutex::new(Limit {
+                started: Instant::now(),
+                length: None,
+                warnings_given: 0,
+                ended: false,
+            })),
+        }
+    }
+
+    // Agree to a length asked for by either side, keeping the shortest.
+    pub fn agree(&self, length: Duration) {
+        let mut limit = self.limit.lock().unwrap();
+        limit.length = Some(limit.length.map_or(length, |current| current.min(length)));
+    }
+
+    pub fn time_left(&self) -> Option<Duration> {
+        let limit = self.limit.lock().unwrap();
+        limit.length.map(|length| length.saturating_sub(limit.started.elapsed()))
+    }
+
+    // The time left, if a warning is due since the last one.
+    pub fn warning_due(&self) -> Option<Duration> {
+        let left = self.time_left()?;
+        let mut limit = self.limit.lock().unwrap();
+        let mut due = false;
+        while limit.warnings_given < WARNINGS.len() && left <= WARNINGS[limit.warnings_given] {
+            limit.warnings_given += 1;
+            due = true;
+        }
+        if due && !left.is_zero() {
+            Some(left)
+        } else {
+            None
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.time_left().is_some_and(|left| left.is_zero())
+    }
+
+    // The connection ended, for whatever reason.
+    pub fn end(&self) {
+        self.limit.lock().unwrap().ended = true;
+    }
+
+    pub fn ended(&self) -> bool {
+        self.limit.lock().unwrap().ended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("1é").is_err());
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m05s");
+    }
+
+    #[test]
+    fn test_session_limit() {
+        let session = SessionLimit::new();
+        assert_eq!(session.time_left(), None);
+        assert_eq!(session.warning_due(), None);
+        session.agree(Duration::from_secs(30 * 60));
+        session.agree(Duration::from_secs(2 * 60));
+        session.agree(Duration::from_secs(60 * 60));
+        // The shortest length is kept, and the first warnings are given at once.
+        assert!(session.time_left().unwrap() <= Duration::from_secs(2 * 60));
+        assert!(session.warning_due().is_some());
+        assert_eq!(session.warning_due(), None);
+        assert!(!session.expired());
+
+        let session = SessionLimit::new();
+        session.agree(Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(session.expired());
+        // Nothing is left to warn about.
+        assert_eq!(session.warning_due(), None);
+        assert!(!session.ended());
+        session.end();
+        assert!(session.ended());
+    }
+}
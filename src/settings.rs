@@ -0,0 +1,91 @@
+use std::fmt;
+
+
+// Toggles for the feedback shown in the UI, all enabled by default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedbackSettings {
+    // Tell the peer when we are typing a message.
+    pub typing_sent: bool,
+    // Show when the peer is typing a message.
+    pub typing_received: bool,
+    // Show when the peer has read our message.
+    pub read_receipts: bool,
+    // Tell the peer when we have read its message, off for privacy.
+    pub read_receipts_sent: bool,
+    // Show when our message was delivered.
+    pub delivery_ticks: bool,
+}
+
+impl Default for FeedbackSettings {
+    fn default() -> FeedbackSettings {
+        FeedbackSettings {
+            typing_sent: true,
+            typing_received: true,
+            read_receipts: true,
+            read_receipts_sent: true,
+            delivery_ticks: true,
+        }
+    }
+}
+
+impl FeedbackSettings {
+    // Set a toggle from its name, and `on` or `off`.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = match value {
+            "on" => true,
+            "off" => false,
+            _ => return Err(format!("expected 'on' or 'off', got '{}'", value)),
+        };
+        match name {
+            "typing_sent" => self.typing_sent = value,
+            "typing_received" => self.typing_received = value,
+            "read_receipts" => self.read_receipts = value,
+            "read_receipts_sent" => self.read_receipts_sent = value,
+            "delivery_ticks" => self.delivery_ticks = value,
+            _ => return Err(format!("unknown setting '{}'", name)),
+        }
+        Ok(())
+    }
+
+    // Parse `name=on|off`, as given on the command line.
+    pub fn set_from_str(&mut self, setting: &str) -> Result<(), String> {
+        let mut parts = setting.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => self.set(name, value),
+            _ => Err(format!("expected 'name=on|off', got '{}'", setting)),
+        }
+    }
+}
+
+impl fmt::Display for FeedbackSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let toggle = |value| if value { "on" } else { "off" };
+        write!(f,
+               "typing_sent={} typing_received={} read_receipts={} read_receipts_sent={} delivery_ticks={}",
+               toggle(self.typing_sent),
+               toggle(self.typing_received),
+               toggle(self.read_receipts),
+               toggle(self.read_receipts_sent),
+               toggle(self.delivery_ticks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_feedback() {
+        let mut settings = FeedbackSettings::default();
+        settings.set_from_str("read_receipts=off").unwrap();
+        settings.set("typing_sent", "off").unwrap();
+        settings.set("read_receipts_sent", "off").unwrap();
+        assert!(!settings.read_receipts);
+        assert!(!settings.read_receipts_sent);
+        assert!(!settings.typing_sent);
+        assert!(settings.set("typing_sent", "maybe").is_err());
+        assert!(settings.set_from_str("sounds=on").is_err());
+        assert_eq!(settings.to_string(),
+                   "typing_sent=off typing_received=on read_receipts=off read_receipts_sent=off delivery_ticks=on");
+    }
+}
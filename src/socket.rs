@@ -0,0 +1,95 @@
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+use session;
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::net::TcpStream;
+use std::time::Duration;
+
+
+// How the TCP connections to the peer are tuned, from the `[socket]` section of the config.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SocketOptions {
+    // Send small frames, such as typing notifications, right away rather than batching them.
+    pub nodelay: bool,
+    // How long the connection stays idle before probing whether the peer is still there, such as "60s".
+    #[serde(deserialize_with = "duration")]
+    pub keepalive: Option<Duration>,
+    // In bytes, if not the system's defaults.
+    pub send_buffer: Option<usize>,
+    pub recv_buffer: Option<usize>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> SocketOptions {
+        SocketOptions {
+            nodelay: true,
+            keepalive: None,
+            send_buffer: None,
+            recv_buffer: None,
+        }
+    }
+}
+
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(duration) => session::parse_duration(&duration).map(Some).map_err(D::Error::custom),
+        None => Ok(None),
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "on" } else { "off" }
+}
+
+impl SocketOptions {
+    // Returns the values the system settled on, which may differ from those asked for,
+    // Linux doubling the buffer sizes for instance.
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<String> {
+        stream.set_nodelay(self.nodelay)?;
+        let socket = SockRef::from(stream);
+        match self.keepalive {
+            Some(time) => socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?,
+            None => socket.set_keepalive(false)?,
+        }
+        if let Some(size) = self.send_buffer {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size)?;
+        }
+        let keepalive = match (socket.keepalive()?, self.keepalive) {
+            (true, Some(time)) => format!("after {}s", time.as_secs()),
+            (on, _) => on_off(on).to_string(),
+        };
+        Ok(format!("nodelay {}, keepalive {}, send buffer {} bytes, receive buffer {} bytes",
+                   on_off(stream.nodelay()?), keepalive, socket.send_buffer_size()?, socket.recv_buffer_size()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use toml;
+
+    #[test]
+    fn test_socket_options() {
+        let options: SocketOptions = toml::from_str("keepalive = \"1m\"\nsend_buffer = 65536").unwrap();
+        assert_eq!(options, SocketOptions {
+            keepalive: Some(Duration::from_secs(60)),
+            send_buffer: Some(65536),
+            ..SocketOptions::default()
+        });
+        assert!(toml::from_str::<SocketOptions>("keepalive = \"soon\"").is_err());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let applied = options.apply(&stream).unwrap();
+        assert!(applied.starts_with("nodelay on, keepalive after 60s, send buffer "), "{}", applied);
+        assert!(stream.nodelay().unwrap());
+        let applied = SocketOptions { nodelay: false, ..SocketOptions::default() }.apply(&stream).unwrap();
+        assert!(applied.starts_with("nodelay off, keepalive off"), "{}", applied);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+
+// Durations rounded to three significant digits of microseconds,
+// with how many times each was recorded.
+#[derive(Default)]
+struct Histogram {
+    buckets: BTreeMap<u64, u32>,
+    count: u32,
+}
+
+impl Histogram {
+    fn record(&mut self, duration: Duration) {
+        let mut micros = duration.as_micros() as u64;
+        let mut scale = 1;
+        while micros >= 1000 {
+            micros /= 10;
+            scale *= 10;
+        }
+        *self.buckets.entry(micros * scale).or_insert(0) += 1;
+        self.count += 1;
+    }
+
+    // The duration at or below which `percent` of those recorded are.
+    fn percentile(&self, percent: u32) -> Option<Duration> {
+        let rank = (self.count as u64 * percent as u64).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (&micros, &count) in self.buckets.iter() {
+            seen += count as u64;
+            if seen >= rank {
+                return Some(Duration::from_micros(micros));
+            }
+        }
+        None
+    }
+}
+
+
+// Counters for the session, updated by the UI as events come in.
+pub struct SessionStats {
+    started: Instant,
+    messages_sent: usize,
+    messages_received: usize,
+    bytes_sent: usize,
+    bytes_received: usize,
+    roundtrips: u32,
+    total_roundtrip: Duration,
+    roundtrip_histogram: Histogram,
+    delivery_failures: usize,
+    // In broadcast mode, how many times clients had to wait for a worker, and the most waiting at once.
+    saturations: usize,
+    most_waiting: usize,
+}
+
+impl SessionStats {
+    pub fn new() -> SessionStats {
+        SessionStats {
+            started: Instant::now(),
+            messages_sent: 0,
+            messages_received: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            roundtrips: 0,
+            total_roundtrip: Duration::default(),
+            roundtrip_histogram: Histogram::default(),
+            delivery_failures: 0,
+            saturations: 0,
+            most_waiting: 0,
+        }
+    }
+
+    pub fn record_sent(&mut self, text: &str) {
+        self.messages_sent += 1;
+        self.bytes_sent += text.len();
+    }
+
+    pub fn record_received(&mut self, text: &str) {
+        self.messages_received += 1;
+        self.bytes_received += text.len();
+    }
+
+    pub fn record_roundtrip(&mut self, duration: Duration) {
+        self.roundtrips += 1;
+        self.total_roundtrip += duration;
+        self.roundtrip_histogram.record(duration);
+    }
+
+    pub fn record_delivery_failure(&mut self) {
+        self.delivery_failures += 1;
+    }
+
+    pub fn record_saturation(&mut self, waiting: usize) {
+        self.saturations += 1;
+        self.most_waiting = self.most_waiting.max(waiting);
+    }
+
+    pub fn average_roundtrip(&self) -> Option<Duration> {
+        if self.roundtrips == 0 {
+            return None;
+        }
+        Some(self.total_roundtrip / self.roundtrips)
+    }
+
+    // From 0 for the shortest roundtrip, to 100 for the longest.
+    pub fn roundtrip_percentile(&self, percent: u32) -> Option<Duration> {
+        self.roundtrip_histogram.percentile(percent)
+    }
+}
+
+// The session summary, printed on exit, and by `/stats`.
+impl fmt::Display for SessionStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let duration = self.started.elapsed().as_secs();
+        writeln!(f, "{}", tr!("stats-summary"))?;
+        writeln!(f, "  {}", tr!("stats-duration", duration = format!("{}m{:02}s", duration / 60, duration % 60)))?;
+        writeln!(f, "  {}", tr!("stats-messages", sent = self.messages_sent, received = self.messages_received))?;
+        writeln!(f, "  {}", tr!("stats-bytes", sent = self.bytes_sent, received = self.bytes_received))?;
+        let average = self.average_roundtrip().map(|average| format!("{:?}", average)).unwrap_or_else(|| "n/a".to_string());
+        writeln!(f, "  {}", tr!("stats-average-roundtrip", average = average))?;
+        if let (Some(min), Some(median), Some(p95), Some(p99)) = (self.roundtrip_percentile(0),
+                                                                   self.roundtrip_percentile(50),
+                                                                   self.roundtrip_percentile(95),
+                                                                   self.roundtrip_percentile(99)) {
+            writeln!(f, "  {}", tr!("stats-roundtrip", min = format!("{:?}", min), median = format!("{:?}", median),
+                                     p95 = format!("{:?}", p95), p99 = format!("{:?}", p99)))?;
+        }
+        write!(f, "  {}", tr!("stats-delivery-failures", count = self.delivery_failures))?;
+        if self.saturations > 0 {
+            write!(f, "\n  {}", tr!("stats-saturations", count = self.saturations, waiting = self.most_waiting))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_summary() {
+        let mut stats = SessionStats::new();
+        assert_eq!(stats.average_roundtrip(), None);
+        stats.record_sent("hello");
+        stats.record_received("hi");
+        stats.record_roundtrip(Duration::from_millis(10));
+        stats.record_roundtrip(Duration::from_millis(20));
+        stats.record_delivery_failure();
+        assert_eq!(stats.average_roundtrip(), Some(Duration::from_millis(15)));
+        let summary = stats.to_string();
+        assert!(summary.contains("messages: 1 sent, 1 received"));
+        assert!(summary.contains("bytes: 5 sent, 2 received"));
+        assert!(summary.contains("delivery failures: 1"));
+        assert!(summary.contains("roundtrip: min 10ms, median 10ms, p95 20ms, p99 20ms"));
+        assert!(!summary.contains("workers"));
+        stats.record_saturation(3);
+        stats.record_saturation(1);
+        assert!(stats.to_string().ends_with("all workers busy: 2 times, up to 3 clients waiting"));
+    }
+
+    #[test]
+    fn test_roundtrip_percentiles() {
+        let mut stats = SessionStats::new();
+        assert_eq!(stats.roundtrip_percentile(50), None);
+        for millis in 1..=100 {
+            stats.record_roundtrip(Duration::from_millis(millis));
+        }
+        assert_eq!(stats.roundtrip_percentile(0), Some(Duration::from_millis(1)));
+        assert_eq!(stats.roundtrip_percentile(50), Some(Duration::from_millis(50)));
+        assert_eq!(stats.roundtrip_percentile(95), Some(Duration::from_millis(95)));
+        assert_eq!(stats.roundtrip_percentile(99), Some(Duration::from_millis(99)));
+        assert_eq!(stats.roundtrip_percentile(100), Some(Duration::from_millis(100)));
+        // Rounded to three significant digits.
+        stats.record_roundtrip(Duration::from_micros(123_456));
+        assert_eq!(stats.roundtrip_percentile(100), Some(Duration::from_micros(123_000)));
+    }
+}
@@ -0,0 +1,369 @@
+use chrono::{DateTime, Local};
+use config;
+use rusqlite::{self, Connection, Row, params};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+// Bumped when the schema changes, see `migrate`.
+const SCHEMA_VERSION: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageStatus {
+    // Sent by us, and acknowledged by the peer.
+    Delivered,
+    // Sent by us, and seen by the peer, if it sends read receipts.
+    Read,
+    Received,
+    // Retracted by its sender, its text is then forgotten.
+    Deleted,
+}
+
+impl MessageStatus {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MessageStatus::Delivered => "delivered",
+            MessageStatus::Read => "read",
+            MessageStatus::Received => "received",
+            MessageStatus::Deleted => "deleted",
+        }
+    }
+
+    fn parse(name: &str) -> MessageStatus {
+        match name {
+            "delivered" => MessageStatus::Delivered,
+            "read" => MessageStatus::Read,
+            "deleted" => MessageStatus::Deleted,
+            _ => MessageStatus::Received,
+        }
+    }
+}
+
+// A message read back from the store.
+pub struct StoredMessage {
+    pub sent_at: SystemTime,
+    pub sender: String,
+    // Who the conversation was with.
+    pub peer: String,
+    pub status: MessageStatus,
+    pub text: String,
+}
+
+impl StoredMessage {
+    fn from_row(row: &Row) -> rusqlite::Result<StoredMessage> {
+        let millis: i64 = row.get(0)?;
+        let status: String = row.get(3)?;
+        Ok(StoredMessage {
+            sent_at: UNIX_EPOCH + Duration::from_millis(millis as u64),
+            sender: row.get(1)?,
+            peer: row.get(2)?,
+            status: MessageStatus::parse(&status),
+            text: row.get(4)?,
+        })
+    }
+}
+
+impl fmt::Display for StoredMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sent_at: DateTime<Local> = self.sent_at.into();
+        write!(f, "[{}] {} (with {}): ", sent_at.format("%Y-%m-%d %H:%M:%S"), self.sender, self.peer)?;
+        match self.status {
+            MessageStatus::Deleted => write!(f, "(message deleted)"),
+            _ => write!(f, "{}", self.text),
+        }
+    }
+}
+
+// A peer chatted with before, kept with the messages.
+#[derive(Debug, PartialEq)]
+pub struct Contact {
+    pub nickname: String,
+    // Where to connect to, for a server.
+    pub address: Option<String>,
+    // The fingerprint of its identity key, once it sent one.
+    pub identity: Option<String>,
+    pub last_seen: SystemTime,
+    pub notes: String,
+}
+
+impl Contact {
+    fn from_row(row: &Row) -> rusqlite::Result<Contact> {
+        let millis: i64 = row.get(3)?;
+        Ok(Contact {
+            nickname: row.get(0)?,
+            address: row.get(1)?,
+            identity: row.get(2)?,
+            last_seen: UNIX_EPOCH + Duration::from_millis(millis as u64),
+            notes: row.get(4)?,
+        })
+    }
+}
+
+impl fmt::Display for Contact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let last_seen: DateTime<Local> = self.last_seen.into();
+        write!(f, "{}", self.nickname)?;
+        if let Some(ref address) = self.address {
+            write!(f, " at {}", address)?;
+        }
+        write!(f, ", last seen {}", last_seen.format("%Y-%m-%d %H:%M"))?;
+        if let Some(ref identity) = self.identity {
+            write!(f, ", key {}", identity)?;
+        }
+        if !self.notes.is_empty() {
+            write!(f, ": {}", self.notes)?;
+        }
+        Ok(())
+    }
+}
+
+// The messages of all sessions, kept in SQLite with a full-text index, and the peers they were with.
+pub struct MessageStore {
+    connection: Connection,
+}
+
+fn to_string(error: rusqlite::Error) -> String {
+    error.to_string()
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+impl MessageStore {
+    pub fn open(path: &Path) -> Result<MessageStore, String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        let connection = Connection::open(path).map_err(to_string)?;
+        MessageStore::with_connection(connection)
+    }
+
+    // The store in the data directory.
+    pub fn open_default() -> Result<MessageStore, String> {
+        let dir = config::data_dir().ok_or("no data directory to keep the history in")?;
+        MessageStore::open(&dir.join("history.db"))
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<MessageStore, String> {
+        MessageStore::with_connection(Connection::open_in_memory().map_err(to_string)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<MessageStore, String> {
+        MessageStore::migrate(&connection).map_err(to_string)?;
+        Ok(MessageStore {
+            connection,
+        })
+    }
+
+    fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+        let version: i32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+        connection.execute_batch("BEGIN;")?;
+        if version < 1 {
+            connection.execute_batch(
+                "CREATE VIRTUAL TABLE messages_v1
+                 USING fts5(sent_at UNINDEXED, sender UNINDEXED, peer UNINDEXED, status UNINDEXED, text);"
+            )?;
+            // Version 0 had no delivery status.
+            let has_messages: bool = connection.query_row(
+                "SELECT count(*) > 0 FROM sqlite_master WHERE name = 'messages'", [], |row| row.get(0))?;
+            if has_messages {
+                connection.execute_batch(
+                    "INSERT INTO messages_v1 (sent_at, sender, peer, status, text)
+                     SELECT sent_at, sender, peer, '', text FROM messages;
+                     DROP TABLE messages;"
+                )?;
+            }
+            connection.execute_batch("ALTER TABLE messages_v1 RENAME TO messages;")?;
+        }
+        if version < 2 {
+            connection.execute_batch(
+                "CREATE TABLE contacts (
+                     nickname TEXT PRIMARY KEY COLLATE NOCASE,
+                     address TEXT,
+                     identity TEXT,
+                     last_seen INTEGER NOT NULL,
+                     notes TEXT NOT NULL DEFAULT ''
+                 );"
+            )?;
+        }
+        connection.execute_batch(&format!(
+            "PRAGMA user_version = {};
+             COMMIT;", SCHEMA_VERSION))
+    }
+
+    // Returns the row of the message, to later change its status.
+    pub fn record(&self,
+                  sent_at: SystemTime,
+                  sender: &str,
+                  peer: &str,
+                  status: MessageStatus,
+                  text: &str)
+                  -> Result<i64, String> {
+        self.connection.execute(
+            "INSERT INTO messages (sent_at, sender, peer, status, text) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![to_millis(sent_at), sender, peer, status.name(), text],
+        ).map_err(to_string)?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    pub fn mark_deleted(&self, row: i64) -> Result<(), String> {
+        self.connection.execute(
+            "UPDATE messages SET status = ?1, text = '' WHERE rowid = ?2",
+            params![MessageStatus::Deleted.name(), row],
+        ).map_err(to_string)?;
+        Ok(())
+    }
+
+    // A message deleted since stays so.
+    pub fn mark_read(&self, row: i64) -> Result<(), String> {
+        self.connection.execute(
+            "UPDATE messages SET status = ?1 WHERE rowid = ?2 AND status = ?3",
+            params![MessageStatus::Read.name(), row, MessageStatus::Delivered.name()],
+        ).map_err(to_string)?;
+        Ok(())
+    }
+
+    // The messages matching a full-text query, such as `hello` or `"good morning" OR hi`,
+    // oldest first.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<StoredMessage>, String> {
+        let mut statement = self.connection.prepare(
+            "SELECT sent_at, sender, peer, status, text FROM
+             (SELECT sent_at, sender, peer, status, text FROM messages
+              WHERE messages MATCH ?1 ORDER BY rank LIMIT ?2)
+             ORDER BY sent_at"
+        ).map_err(to_string)?;
+        let rows = statement.query_map(params![query, limit as i64], StoredMessage::from_row)
+            .map_err(|error| format!("invalid search '{}': {}", query, error))?;
+        rows.collect::<Result<Vec<StoredMessage>, rusqlite::Error>>()
+            .map_err(|error| format!("invalid search '{}': {}", query, error))
+    }
+
+    // The messages sent since then, oldest first.
+    pub fn messages_since(&self, since: SystemTime) -> Result<Vec<StoredMessage>, String> {
+        let mut statement = self.connection.prepare(
+            "SELECT sent_at, sender, peer, status, text FROM messages
+             WHERE CAST(sent_at AS INTEGER) >= ?1 ORDER BY sent_at"
+        ).map_err(to_string)?;
+        let rows = statement.query_map(params![to_millis(since)], StoredMessage::from_row).map_err(to_string)?;
+        rows.collect::<Result<Vec<StoredMessage>, rusqlite::Error>>().map_err(to_string)
+    }
+
+    // Add the peer to the contacts, or update when it was last seen,
+    // its address and identity being kept if not known this time.
+    pub fn saw_contact(&self,
+                       nickname: &str,
+                       address: Option<&str>,
+                       identity: Option<&str>,
+                       at: SystemTime)
+                       -> Result<(), String> {
+        self.connection.execute(
+            "INSERT INTO contacts (nickname, address, identity, last_seen) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (nickname) DO UPDATE SET
+                 address = coalesce(excluded.address, address),
+                 identity = coalesce(excluded.identity, identity),
+                 last_seen = excluded.last_seen",
+            params![nickname, address, identity, to_millis(at)],
+        ).map_err(to_string)?;
+        Ok(())
+    }
+
+    // Returns whether there is such a contact.
+    pub fn set_contact_notes(&self, nickname: &str, notes: &str) -> Result<bool, String> {
+        let changed = self.connection.execute(
+            "UPDATE contacts SET notes = ?1 WHERE nickname = ?2",
+            params![notes, nickname],
+        ).map_err(to_string)?;
+        Ok(changed > 0)
+    }
+
+    pub fn contact(&self, nickname: &str) -> Result<Option<Contact>, String> {
+        let mut statement = self.connection.prepare(
+            "SELECT nickname, address, identity, last_seen, notes FROM contacts WHERE nickname = ?1"
+        ).map_err(to_string)?;
+        let mut rows = statement.query_map(params![nickname], Contact::from_row).map_err(to_string)?;
+        rows.next().transpose().map_err(to_string)
+    }
+
+    // The most recently seen first.
+    pub fn contacts(&self) -> Result<Vec<Contact>, String> {
+        let mut statement = self.connection.prepare(
+            "SELECT nickname, address, identity, last_seen, notes FROM contacts ORDER BY last_seen DESC"
+        ).map_err(to_string)?;
+        let rows = statement.query_map([], Contact::from_row).map_err(to_string)?;
+        rows.collect::<Result<Vec<Contact>, rusqlite::Error>>().map_err(to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let store = MessageStore::open_in_memory().unwrap();
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        store.record(earlier, "alice", "bob", MessageStatus::Delivered, "good morning bob").unwrap();
+        store.record(SystemTime::now(), "bob", "alice", MessageStatus::Received, "morning, coffee?").unwrap();
+        let row = store.record(SystemTime::now(), "alice", "bob", MessageStatus::Delivered, "sure").unwrap();
+        let read = store.record(SystemTime::now() + Duration::from_secs(1), "alice", "bob", MessageStatus::Delivered, "see you there").unwrap();
+        store.mark_read(read).unwrap();
+        let found = store.search("morning", 10).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].text, "good morning bob");
+        assert_eq!(found[1].sender, "bob");
+        assert!(found[1].to_string().ends_with("bob (with alice): morning, coffee?"));
+        assert_eq!(store.search("coffee", 10).unwrap().len(), 1);
+        assert!(store.search("\"unbalanced", 10).is_err());
+
+        store.mark_deleted(row).unwrap();
+        store.mark_read(row).unwrap();
+        assert!(store.search("sure", 10).unwrap().is_empty());
+        let recent = store.messages_since(earlier + Duration::from_secs(1)).unwrap();
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[1].status, MessageStatus::Deleted);
+        assert_eq!(recent[2].status, MessageStatus::Read);
+    }
+
+    #[test]
+    fn test_migrate_from_version_0() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(
+            "CREATE VIRTUAL TABLE messages USING fts5(sent_at UNINDEXED, sender UNINDEXED, peer UNINDEXED, text);
+             INSERT INTO messages VALUES (0, 'alice', 'bob', 'hello');"
+        ).unwrap();
+        let store = MessageStore::with_connection(connection).unwrap();
+        let messages = store.messages_since(UNIX_EPOCH).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "hello");
+        assert_eq!(messages[0].status, MessageStatus::Received);
+        assert!(store.contacts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_contacts() {
+        let store = MessageStore::open_in_memory().unwrap();
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        store.saw_contact("alice", Some("10.0.0.1:8000"), None, earlier).unwrap();
+        store.saw_contact("bob", None, Some("SHA256:bob"), earlier + Duration::from_secs(1)).unwrap();
+        // What isn't known this time is kept, and nicknames are told apart regardless of case.
+        store.saw_contact("Alice", None, Some("SHA256:alice"), SystemTime::now()).unwrap();
+        assert!(store.set_contact_notes("bob", "met at the conference").unwrap());
+        assert!(!store.set_contact_notes("carol", "who?").unwrap());
+
+        let contacts = store.contacts().unwrap();
+        assert_eq!(contacts.len(), 2);
+        assert_eq!(contacts[0].nickname, "alice");
+        assert_eq!(contacts[0].address.as_deref(), Some("10.0.0.1:8000"));
+        assert_eq!(contacts[0].identity.as_deref(), Some("SHA256:alice"));
+        assert!(contacts[1].to_string().ends_with(", key SHA256:bob: met at the conference"));
+        assert_eq!(store.contact("ALICE").unwrap().unwrap().nickname, "alice");
+        assert_eq!(store.contact("carol").unwrap(), None);
+    }
+}
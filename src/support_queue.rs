@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+
+
+// Visitors waiting for the operator, in order of arrival.
+pub struct VisitorQueue<V> {
+    waiting: Mutex<VecDeque<V>>,
+    arrived: Condvar,
+    // Whether the operator is with the visitor popped last.
+    serving: AtomicBool,
+}
+
+impl<V> Default for VisitorQueue<V> {
+    fn default() -> VisitorQueue<V> {
+        VisitorQueue {
+            waiting: Mutex::new(VecDeque::new()),
+            arrived: Condvar::new(),
+            serving: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<V> VisitorQueue<V> {
+    // Returns the position of the visitor, from 1.
+    pub fn push(&self, visitor: V) -> usize {
+        let mut waiting = self.waiting.lock().unwrap();
+        waiting.push_back(visitor);
+        self.arrived.notify_one();
+        waiting.len()
+    }
+
+    // The next visitor, waiting for one to arrive if needed,
+    // the operator being with it until `served` is called.
+    pub fn pop(&self) -> V {
+        let mut waiting = self.waiting.lock().unwrap();
+        self.serving.store(false, Ordering::SeqCst);
+        loop {
+            if let Some(visitor) = waiting.pop_front() {
+                self.serving.store(true, Ordering::SeqCst);
+                return visitor;
+            }
+            waiting = self.arrived.wait(waiting).unwrap();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiting.lock().unwrap().len()
+    }
+
+    // The visitor popped last left.
+    pub fn served(&self) {
+        self.serving.store(false, Ordering::SeqCst);
+    }
+
+    // Those waiting, and the one the operator is with.
+    pub fn clients(&self) -> usize {
+        self.len() + usize::from(self.serving.load(Ordering::SeqCst))
+    }
+
+    // Visit those waiting, with their position, dropping those for which `visit` fails.
+    pub fn retain_waiting<F: FnMut(usize, &mut V) -> bool>(&self, mut visit: F) {
+        let mut waiting = self.waiting.lock().unwrap();
+        let mut position = 0;
+        waiting.retain_mut(|visitor| {
+            let keep = visit(position + 1, visitor);
+            if keep {
+                position += 1;
+            }
+            keep
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_visitor_queue() {
+        let queue = Arc::new(VisitorQueue::default());
+        assert_eq!(queue.push("alice"), 1);
+        assert_eq!(queue.push("bob"), 2);
+        assert_eq!(queue.push("carol"), 3);
+        assert_eq!(queue.clients(), 3);
+        assert_eq!(queue.pop(), "alice");
+        assert_eq!(queue.clients(), 3);
+        // Bob left while waiting, and Carol moves up.
+        let mut positions = vec![];
+        queue.retain_waiting(|position, visitor| {
+            if *visitor == "bob" {
+                return false;
+            }
+            positions.push((position, *visitor));
+            true
+        });
+        assert_eq!(positions, vec![(1, "carol")]);
+        assert_eq!(queue.len(), 1);
+        queue.served();
+        assert_eq!(queue.clients(), 1);
+        assert_eq!(queue.pop(), "carol");
+
+        let arriving = queue.clone();
+        let visitor = thread::spawn(move || arriving.push("dave"));
+        assert_eq!(queue.pop(), "dave");
+        visitor.join().unwrap();
+    }
+}
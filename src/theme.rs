@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+
+// A color of the terminal's palette, by its ANSI code, or any color, on terminals supporting those.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Color {
+    // Whatever the terminal shows text in.
+    Default,
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+const NAMES: &[&str] = &["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+impl Color {
+    // Such as "yellow", "bright-blue", "#ff8800" or "default".
+    pub fn parse(name: &str) -> Result<Color, String> {
+        let invalid = || format!("invalid color '{}', expected one of {}, bright-<color>, #rrggbb or default", name, NAMES.join(", "));
+        if name == "default" {
+            return Ok(Color::Default);
+        }
+        if let Some(hex) = name.strip_prefix('#') {
+            let channel = |at: usize| hex.get(at..at + 2).and_then(|channel| u8::from_str_radix(channel, 16).ok());
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(red), Some(green), Some(blue)) => Ok(Color::Rgb(red, green, blue)),
+                _ => Err(invalid()),
+            };
+        }
+        let (name, base) = match name.strip_prefix("bright-") {
+            Some(name) => (name, 90),
+            None => (name, 30),
+        };
+        let index = NAMES.iter().position(|&known| known == name).ok_or_else(invalid)?;
+        Ok(Color::Ansi(base + index as u8))
+    }
+
+    fn escape(&self) -> Option<String> {
+        match *self {
+            Color::Default => None,
+            Color::Ansi(code) => Some(format!("\u{1b}[{}m", code)),
+            Color::Rgb(red, green, blue) => Some(format!("\u{1b}[38;2;{};{};{}m", red, green, blue)),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Color, String> {
+        Color::parse(&name)
+    }
+}
+
+// The colors of the chat in a terminal, from the `[theme]` section of the config.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub timestamp: Color,
+    // What the chat itself says, such as replies to commands and the server's greeting.
+    pub system: Color,
+    // Someone joining, leaving or being renamed.
+    pub presence: Color,
+    // Our nickname, where a message mentions it.
+    pub mention: Color,
+    // Our own nickname, in front of our messages.
+    pub own: Color,
+    pub error: Color,
+    // Each peer's nickname is always given the same one of these, from the nickname itself.
+    pub nicknames: Vec<Color>,
+    // Whether bold, italic and code in messages are styled, rather than shown as typed.
+    pub markdown: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        let colors = |names: &[&str]| names.iter().map(|name| Color::parse(name).unwrap()).collect::<Vec<_>>();
+        Theme {
+            timestamp: Color::Ansi(90),
+            system: Color::Ansi(36),
+            presence: Color::Ansi(90),
+            mention: Color::Ansi(91),
+            own: Color::Ansi(32),
+            error: Color::Ansi(31),
+            // Not those of the rest of the chat, for peers not to be mistaken for it.
+            nicknames: colors(&["yellow", "blue", "magenta", "bright-yellow", "bright-blue", "bright-magenta", "bright-cyan"]),
+            markdown: true,
+        }
+    }
+}
+
+impl Theme {
+    // Without colors, such as when not printing to a terminal.
+    pub fn plain() -> Theme {
+        Theme {
+            timestamp: Color::Default,
+            system: Color::Default,
+            presence: Color::Default,
+            mention: Color::Default,
+            own: Color::Default,
+            error: Color::Default,
+            nicknames: vec![],
+            markdown: false,
+        }
+    }
+
+    pub fn paint(&self, color: Color, text: &str) -> String {
+        match color.escape() {
+            Some(escape) => format!("{}{}\u{1b}[39m", escape, text),
+            None => text.to_string(),
+        }
+    }
+
+    // The same for a nickname from one session to the next, FNV-1a being stable, unlike Rust's hasher.
+    pub fn nickname(&self, nickname: &str) -> Color {
+        if self.nicknames.is_empty() {
+            return Color::Default;
+        }
+        let hash = nickname.bytes().fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+        self.nicknames[hash as usize % self.nicknames.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(Color::parse("bright-blue"), Ok(Color::Ansi(94)));
+        assert_eq!(Color::parse("#ff8800"), Ok(Color::Rgb(255, 136, 0)));
+        assert!(Color::parse("#ff88").is_err());
+        assert!(Color::parse("orange").is_err());
+        let theme = Theme::default();
+        assert_eq!(theme.paint(Color::Ansi(31), "oops"), "\u{1b}[31moops\u{1b}[39m");
+        assert_eq!(theme.paint(Color::Default, "plain"), "plain");
+        assert_eq!(theme.nickname("alice"), theme.nickname("alice"));
+        assert!(theme.nicknames.contains(&theme.nickname("bob")));
+        assert_eq!(Theme::plain().nickname("alice"), Color::Default);
+    }
+}
@@ -0,0 +1,200 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+
+// Where a local Tor listens by default.
+pub const DEFAULT_SOCKS: &str = "127.0.0.1:9050";
+pub const DEFAULT_CONTROL: &str = "127.0.0.1:9051";
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CONNECT: u8 = 1;
+const DOMAIN_NAME: u8 = 3;
+
+fn split_address(address: &str) -> io::Result<(&str, u16)> {
+    address.rsplit_once(':')
+        .and_then(|(host, port)| port.parse().ok().map(|port| (host, port)))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is not a host:port address", address)))
+}
+
+// Whether the address is that of an onion service, only reachable through Tor.
+pub fn is_onion(address: &str) -> bool {
+    split_address(address).map(|(host, _)| host.ends_with(".onion")).unwrap_or(false)
+}
+
+// Connect to the address through Tor's SOCKS port, the name being resolved by Tor, not here.
+pub fn connect(socks: &str, address: &str) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(socks)
+        .map_err(|error| io::Error::new(error.kind(), format!("Tor isn't running at {} ({})", socks, error)))?;
+    socks_connect(&mut stream, address)?;
+    Ok(stream)
+}
+
+// Ask the SOCKS5 proxy at the other end of the stream to connect it to the address.
+fn socks_connect<S: Read + Write>(stream: &mut S, address: &str) -> io::Result<()> {
+    let (host, port) = split_address(address)?;
+    if host.len() > u8::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the host name {} is too long", host)));
+    }
+    stream.write_all(&[SOCKS_VERSION, 1, NO_AUTHENTICATION])?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply)?;
+    if reply != [SOCKS_VERSION, NO_AUTHENTICATION] {
+        return Err(io::Error::other("the SOCKS proxy asks for authentication"));
+    }
+    let mut request = vec![SOCKS_VERSION, CONNECT, 0, DOMAIN_NAME, host.len() as u8];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request)?;
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply)?;
+    match reply[1] {
+        0 => {},
+        4 => return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is unreachable through Tor", address))),
+        5 => return Err(io::Error::new(io::ErrorKind::ConnectionRefused, format!("{} refused the connection", address))),
+        code => return Err(io::Error::other(format!("Tor failed to connect to {} (SOCKS error {})", address, code))),
+    }
+    // The address the proxy connected from, of no use to us.
+    let length = match reply[3] {
+        1 => 4,
+        4 => 16,
+        DOMAIN_NAME => {
+            let mut length = [0];
+            stream.read_exact(&mut length)?;
+            length[0] as usize
+        },
+        _ => return Err(io::ErrorKind::InvalidData.into()),
+    };
+    stream.read_exact(&mut vec![0; length + 2])
+}
+
+// An onion service published through Tor's control port,
+// removed by Tor when dropped, along with the control connection.
+pub struct OnionService {
+    _control: TcpStream,
+    // Such as `abc...xyz.onion:8000`, for clients to connect to.
+    pub address: String,
+}
+
+// Publish the server listening at the local address as an onion service, on the same port.
+pub fn publish(control: &str, local_address: &str) -> io::Result<OnionService> {
+    let (_, port) = split_address(local_address)?;
+    let stream = TcpStream::connect(control)
+        .map_err(|error| io::Error::new(error.kind(), format!("Tor's control port isn't open at {} ({})", control, error)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream.try_clone()?;
+    let service_id = add_onion(&mut reader, &mut writer, port, local_address)?;
+    Ok(OnionService {
+        _control: stream,
+        address: format!("{}.onion:{}", service_id, port),
+    })
+}
+
+// The lines of Tor's reply to a command, failing unless it succeeded.
+fn read_reply<R: BufRead>(reader: &mut R) -> io::Result<Vec<String>> {
+    let mut lines = vec![];
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = line.trim_end().to_string();
+        if !line.starts_with("250") {
+            return Err(io::Error::other(format!("Tor refused: {}", line)));
+        }
+        // The last line has a space after the code, the others a dash.
+        let last = line.as_bytes().get(3) != Some(&b'-');
+        lines.push(line[4.min(line.len())..].to_string());
+        if last {
+            return Ok(lines);
+        }
+    }
+}
+
+fn command<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, command: &str) -> io::Result<Vec<String>> {
+    writer.write_all(format!("{}\r\n", command).as_bytes())?;
+    read_reply(reader)
+}
+
+// Authenticate as Tor allows, without a password or with its cookie file, and add the service.
+// Returns its id, the onion address without the `.onion`.
+fn add_onion<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, port: u16, target: &str) -> io::Result<String> {
+    let info = command(reader, writer, "PROTOCOLINFO 1")?;
+    let auth = info.iter().find(|line| line.starts_with("AUTH "))
+        .ok_or_else(|| io::Error::other("Tor didn't say how to authenticate"))?;
+    let methods: Vec<&str> = auth.split_whitespace()
+        .find_map(|field| field.strip_prefix("METHODS="))
+        .map(|methods| methods.split(',').collect())
+        .unwrap_or_default();
+    let authenticate = if methods.contains(&"NULL") {
+        "AUTHENTICATE".to_string()
+    } else if methods.contains(&"COOKIE") {
+        let path = auth.split("COOKIEFILE=\"").nth(1).and_then(|rest| rest.split('"').next())
+            .ok_or_else(|| io::Error::other("Tor didn't say where its cookie file is"))?;
+        let cookie = fs::read(path)
+            .map_err(|error| io::Error::new(error.kind(), format!("can't read Tor's cookie file {} ({})", path, error)))?;
+        let cookie: String = cookie.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("AUTHENTICATE {}", cookie)
+    } else {
+        return Err(io::Error::other(format!("Tor asks for authentication by {}, enable CookieAuthentication", methods.join(", "))));
+    };
+    command(reader, writer, &authenticate)?;
+    let added = command(reader, writer, &format!("ADD_ONION NEW:ED25519-V3 Flags=DiscardPK Port={},{}", port, target))?;
+    added.iter().find_map(|line| line.strip_prefix("ServiceID=").map(str::to_string))
+        .ok_or_else(|| io::Error::other("Tor didn't give the address of the service"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pipe::pipe;
+    use std::io::Cursor;
+    use std::thread;
+
+    #[test]
+    fn test_socks_connect() {
+        let (mut client, mut tor) = pipe();
+        let tor = thread::spawn(move || {
+            let mut greeting = [0; 3];
+            tor.read_exact(&mut greeting).unwrap();
+            tor.write_all(&[SOCKS_VERSION, NO_AUTHENTICATION]).unwrap();
+            let mut request = [0; 5];
+            tor.read_exact(&mut request).unwrap();
+            let mut host = vec![0; request[4] as usize + 2];
+            tor.read_exact(&mut host).unwrap();
+            tor.write_all(&[SOCKS_VERSION, 0, 0, 1, 127, 0, 0, 1, 0, 80]).unwrap();
+            tor.write_all(b"hello").unwrap();
+            host
+        });
+        socks_connect(&mut client, "example.onion:8000").unwrap();
+        let mut received = [0; 5];
+        client.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"hello");
+        let host = tor.join().unwrap();
+        assert_eq!(&host[..host.len() - 2], b"example.onion");
+        assert_eq!(&host[host.len() - 2..], &8000u16.to_be_bytes());
+
+        assert!(is_onion("example.onion:8000"));
+        assert!(!is_onion("example.com:8000"));
+        assert!(!is_onion("example.onion"));
+    }
+
+    #[test]
+    fn test_add_onion() {
+        let replies = "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=NULL\r\n250-VERSION Tor=\"0.4.8\"\r\n250 OK\r\n\
+                       250 OK\r\n\
+                       250-ServiceID=abcdef\r\n250 OK\r\n";
+        let mut sent = vec![];
+        let id = add_onion(&mut Cursor::new(replies), &mut sent, 8000, "127.0.0.1:8000").unwrap();
+        assert_eq!(id, "abcdef");
+        let sent = String::from_utf8(sent).unwrap();
+        assert!(sent.contains("AUTHENTICATE\r\n"));
+        assert!(sent.contains("Port=8000,127.0.0.1:8000"));
+
+        let refused = "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=HASHEDPASSWORD\r\n250 OK\r\n";
+        assert!(add_onion(&mut Cursor::new(refused), &mut vec![], 8000, "127.0.0.1:8000").is_err());
+        let failed = "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=NULL\r\n250 OK\r\n515 Authentication failed\r\n";
+        assert!(add_onion(&mut Cursor::new(failed), &mut vec![], 8000, "127.0.0.1:8000").is_err());
+    }
+}
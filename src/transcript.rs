@@ -0,0 +1,62 @@
+use chrono::Local;
+use config;
+use history::HistoryEntry;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+
+// The messages of a conversation with one visitor, written as they come.
+pub struct Transcript {
+    file: File,
+    path: PathBuf,
+}
+
+impl Transcript {
+    pub fn create(path: &Path) -> Result<Transcript, String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        let file = File::create(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Ok(Transcript {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    // In the `transcripts` data directory, named after the time and the visitor.
+    pub fn create_default(visitor: &str) -> Result<Transcript, String> {
+        let dir = config::data_dir().ok_or("no data directory to keep transcripts in")?.join("transcripts");
+        Transcript::create(&dir.join(format!("{}-{}.txt", Local::now().format("%Y-%m-%d-%H%M%S"), visitor)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, entry: &HistoryEntry) -> Result<(), String> {
+        writeln!(self.file, "{}", entry).map_err(|error| format!("{}: {}", self.path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_transcript() {
+        let path = env::temp_dir().join("simple_chat_test_transcripts").join("visitor1.txt");
+        let mut transcript = Transcript::create(&path).unwrap();
+        let entry = HistoryEntry::new(1, false, "visitor1", SystemTime::now(), "my order is late".to_string());
+        transcript.record(&entry).unwrap();
+        let entry = HistoryEntry::new(1, true, "support", SystemTime::now(), "let me check".to_string());
+        transcript.record(&entry).unwrap();
+        let lines: Vec<String> = fs::read_to_string(transcript.path()).unwrap().lines().map(String::from).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("#1 visitor1: my order is late"));
+        assert!(lines[1].ends_with("#1 support: let me check"));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,288 @@
+use codec::{TransferId, MAX_BINARY_SIZE};
+use config;
+use identity;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+
+// The most bytes of a file sent in a frame, the chat going on between two of them.
+pub const CHUNK_SIZE: usize = MAX_BINARY_SIZE;
+// How often the UI is told how far a file is, at most.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+// Of the progress bar, in characters, between its brackets.
+const BAR_WIDTH: usize = 30;
+
+// The SHA-256 of the file, read a chunk at a time, from its start.
+fn file_hash(file: &mut File) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+// A file being sent, read a chunk at a time, never all of it at once.
+pub struct OutgoingTransfer {
+    pub id: TransferId,
+    pub name: String,
+    pub size: u64,
+    // For the peer to recognize the file, resuming an interrupted transfer of it,
+    // and to check it once received.
+    pub hash: Vec<u8>,
+    file: File,
+    // The number of the next chunk, from 0.
+    next_seq: u64,
+    pub sent: u64,
+    // When the UI was last told how far the file is.
+    progress_shown: Option<Instant>,
+}
+
+impl OutgoingTransfer {
+    pub fn open(id: TransferId, path: &Path) -> io::Result<OutgoingTransfer> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let hash = file_hash(&mut file)?;
+        file.seek(SeekFrom::Start(0))?;
+        let name = path.file_name().map_or_else(|| "file".to_string(), |name| name.to_string_lossy().into_owned());
+        Ok(OutgoingTransfer {
+            id,
+            name,
+            size,
+            hash,
+            file,
+            next_seq: 0,
+            sent: 0,
+            progress_shown: None,
+        })
+    }
+
+    // Skip what the peer already has, from an interrupted transfer, a whole number of chunks.
+    pub fn resume_at(&mut self, offset: u64) -> io::Result<()> {
+        if !offset.is_multiple_of(CHUNK_SIZE as u64) || offset > self.size {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("can't resume at byte {}", offset)));
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.next_seq = offset / CHUNK_SIZE as u64;
+        self.sent = offset;
+        Ok(())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.sent >= self.size
+    }
+
+    // Whether to tell the UI how far the file is, every so often, and once it was all sent.
+    pub fn progress_due(&mut self) -> bool {
+        let due = self.is_done() || self.progress_shown.is_none_or(|shown| shown.elapsed() >= PROGRESS_INTERVAL);
+        if due {
+            self.progress_shown = Some(Instant::now());
+        }
+        due
+    }
+
+    // The next chunk and its number, the last one possibly shorter.
+    pub fn next_chunk(&mut self) -> io::Result<(u64, Vec<u8>)> {
+        let left = (self.size - self.sent).min(CHUNK_SIZE as u64) as usize;
+        let mut data = vec![0; left];
+        self.file.read_exact(&mut data)?;
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.sent += left as u64;
+        Ok((seq, data))
+    }
+}
+
+// Such as `[#######-----] 60% photos.zip`.
+pub fn progress_bar(name: &str, sent: u64, total: u64) -> String {
+    let ratio = if total == 0 { 1.0 } else { sent as f64 / total as f64 };
+    let done = (ratio * BAR_WIDTH as f64) as usize;
+    format!("[{}{}] {:>3}% {}", "#".repeat(done), "-".repeat(BAR_WIDTH - done), (ratio * 100.0) as u32, name)
+}
+
+// What is kept of a file being received, next to its chunks,
+// to pick it up where it was if the transfer is interrupted.
+#[derive(Deserialize, Serialize)]
+struct PartialFile {
+    name: String,
+    size: u64,
+}
+
+// A file being received, written as its chunks arrive, under `.partial`, named after its hash,
+// until complete and checked, for an interrupted one to be resumed, and not to be taken for the file.
+pub struct IncomingTransfer {
+    pub name: String,
+    pub size: u64,
+    hash: Vec<u8>,
+    received: u64,
+    next_seq: u64,
+    file: File,
+    partial: PathBuf,
+    dir: PathBuf,
+}
+
+// `$XDG_DATA_HOME/simple_chat/files`.
+pub fn files_dir() -> Result<PathBuf, String> {
+    Ok(config::data_dir().ok_or("no data directory to keep files in")?.join("files"))
+}
+
+// The name the peer gave, without any directory, not to write outside of ours,
+// numbered if a file already has it.
+fn free_path(dir: &Path, name: &str) -> PathBuf {
+    let name = Path::new(name).file_name().map_or_else(|| "file".to_string(), |name| name.to_string_lossy().into_owned());
+    let mut path = dir.join(&name);
+    let mut number = 1;
+    while path.exists() {
+        path = dir.join(format!("{} ({})", name, number));
+        number += 1;
+    }
+    path
+}
+
+fn state_path(partial: &Path) -> PathBuf {
+    partial.with_extension("json")
+}
+
+impl IncomingTransfer {
+    // Resuming the transfer of the same file, if it was interrupted, from its last whole chunk.
+    pub fn create(dir: &Path, name: &str, size: u64, hash: &[u8]) -> Result<IncomingTransfer, String> {
+        if hash.len() != 32 {
+            return Err(format!("invalid hash of {}", name));
+        }
+        let partials = dir.join(".partial");
+        fs::create_dir_all(&partials).map_err(|error| error.to_string())?;
+        let partial = partials.join(format!("{}.part", identity::to_hex(hash)));
+        let error = |error: io::Error| format!("{}: {}", partial.display(), error);
+        let state = fs::read(state_path(&partial)).ok()
+            .and_then(|state| serde_json::from_slice::<PartialFile>(&state).ok());
+        let file = OpenOptions::new().create(true).write(true).truncate(false).open(&partial).map_err(error)?;
+        let received = match state {
+            Some(ref state) if state.size == size => {
+                let length = file.metadata().map_err(error)?.len().min(size);
+                length - length % CHUNK_SIZE as u64
+            },
+            _ => 0,
+        };
+        // Of the chunk written last, what may have not been written whole.
+        file.set_len(received).map_err(error)?;
+        let state = PartialFile {
+            name: name.to_string(),
+            size,
+        };
+        fs::write(state_path(&partial), serde_json::to_vec(&state).map_err(|error| error.to_string())?).map_err(error)?;
+        let mut transfer = IncomingTransfer {
+            name: name.to_string(),
+            size,
+            hash: hash.to_vec(),
+            received,
+            next_seq: received / CHUNK_SIZE as u64,
+            file,
+            partial,
+            dir: dir.to_path_buf(),
+        };
+        transfer.file.seek(SeekFrom::Start(received)).map_err(|error| error.to_string())?;
+        Ok(transfer)
+    }
+
+    // Where the sender is to start from, 0 unless resuming.
+    pub fn offset(&self) -> u64 {
+        self.received
+    }
+
+    // Returns where the file was saved, once its last chunk is written and the file checked.
+    pub fn write(&mut self, seq: u64, data: &[u8]) -> Result<Option<PathBuf>, String> {
+        if seq != self.next_seq {
+            return Err(format!("chunk {} of {} came in place of chunk {}", seq, self.name, self.next_seq));
+        }
+        if self.received + data.len() as u64 > self.size {
+            return Err(format!("{} is bigger than the {} bytes announced", self.name, self.size));
+        }
+        self.file.write_all(data).map_err(|error| format!("{}: {}", self.partial.display(), error))?;
+        self.next_seq += 1;
+        self.received += data.len() as u64;
+        if self.received < self.size {
+            return Ok(None);
+        }
+        let mut file = File::open(&self.partial).map_err(|error| error.to_string())?;
+        if file_hash(&mut file).map_err(|error| error.to_string())? != self.hash {
+            return Err(format!("{} doesn't match its checksum", self.name));
+        }
+        let path = free_path(&self.dir, &self.name);
+        fs::rename(&self.partial, &path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        let _ = fs::remove_file(state_path(&self.partial));
+        Ok(Some(path))
+    }
+
+    // Give up on the file, removing what was received of it.
+    pub fn abandon(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.partial);
+        let _ = fs::remove_file(state_path(&self.partial));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_transfer() {
+        let dir = env::temp_dir().join("simple_chat_test_transfer");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("big.bin");
+        let content: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|byte| byte as u8).collect();
+        fs::write(&source, &content).unwrap();
+
+        let received = dir.join("received");
+        let mut outgoing = OutgoingTransfer::open(1, &source).unwrap();
+        let mut incoming = IncomingTransfer::create(&received, "../big.bin", outgoing.size, &outgoing.hash).unwrap();
+        assert_eq!(incoming.offset(), 0);
+        let (seq, data) = outgoing.next_chunk().unwrap();
+        assert_eq!(incoming.write(seq, &data).unwrap(), None);
+        // Interrupted, after a chunk and a half.
+        incoming.file.write_all(&data[..CHUNK_SIZE / 2]).unwrap();
+        drop(incoming);
+
+        let mut outgoing = OutgoingTransfer::open(2, &source).unwrap();
+        let mut incoming = IncomingTransfer::create(&received, "big.bin", outgoing.size, &outgoing.hash).unwrap();
+        assert_eq!(incoming.offset(), CHUNK_SIZE as u64);
+        outgoing.resume_at(incoming.offset()).unwrap();
+        let mut saved = None;
+        while !outgoing.is_done() {
+            let (seq, data) = outgoing.next_chunk().unwrap();
+            assert!(data.len() <= CHUNK_SIZE);
+            saved = incoming.write(seq, &data).unwrap();
+        }
+        let saved = saved.unwrap();
+        assert_eq!(saved, received.join("big.bin"));
+        assert_eq!(fs::read(&saved).unwrap(), content);
+        assert_eq!(fs::read_dir(received.join(".partial")).unwrap().count(), 0);
+
+        // Never overwriting a file, nor taking chunks out of order, or a file not matching its hash.
+        let mut incoming = IncomingTransfer::create(&received, "big.bin", 10, &Sha256::digest([0; 10])).unwrap();
+        assert!(incoming.write(1, &[0; 5]).is_err());
+        assert!(incoming.write(0, &[0; 11]).is_err());
+        assert_eq!(incoming.write(0, &[0; 10]).unwrap(), Some(received.join("big.bin (1)")));
+        let mut incoming = IncomingTransfer::create(&received, "big.bin", 10, &Sha256::digest([0; 10])).unwrap();
+        assert!(incoming.write(0, &[1; 10]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar("a.zip", 0, 10), format!("[{}]   0% a.zip", "-".repeat(BAR_WIDTH)));
+        assert_eq!(progress_bar("a.zip", 5, 10), format!("[{}{}]  50% a.zip", "#".repeat(15), "-".repeat(15)));
+        assert_eq!(progress_bar("empty", 0, 0), format!("[{}] 100% empty", "#".repeat(BAR_WIDTH)));
+    }
+}
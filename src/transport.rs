@@ -0,0 +1,63 @@
+use std::io::{self, Read, Write};
+use socket::SocketOptions;
+use std::net::{IpAddr, Shutdown, TcpListener, TcpStream};
+use std::time::Duration;
+
+
+// A connection to the peer, over TCP, or in memory for the tests.
+pub trait Transport: Read + Write + Send + Sized + 'static {
+    // Another handle to the same connection, to read from one thread while writing from another.
+    fn try_clone(&self) -> io::Result<Self>;
+    // Reads then fail with `WouldBlock` or `TimedOut` if nothing came in time.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    fn peer_ip(&self) -> Option<IpAddr>;
+    // Ends the reads and writes of all the handles, on both ends.
+    fn shutdown(&self);
+    // Tune the connection, if it is a socket, returning the values applied.
+    fn configure(&self, _options: &SocketOptions) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+// Where the server accepts its clients.
+pub trait Listener: Send + Sized + 'static {
+    type Stream: Transport;
+
+    fn accept(&self) -> io::Result<Self::Stream>;
+    // Another handle, to accept from another thread.
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl Transport for TcpStream {
+    fn try_clone(&self) -> io::Result<TcpStream> {
+        TcpStream::try_clone(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        self.peer_addr().ok().map(|peer| peer.ip())
+    }
+
+    fn shutdown(&self) {
+        let _ = TcpStream::shutdown(self, Shutdown::Both);
+    }
+
+    fn configure(&self, options: &SocketOptions) -> io::Result<Option<String>> {
+        options.apply(self).map(Some)
+    }
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept(&self) -> io::Result<TcpStream> {
+        TcpListener::accept(self).map(|(stream, _)| stream)
+    }
+
+    fn try_clone(&self) -> io::Result<TcpListener> {
+        TcpListener::try_clone(self)
+    }
+}
@@ -0,0 +1,123 @@
+#[cfg(feature = "tray")]
+use ksni::{self, Icon, Status, ToolTip};
+#[cfg(feature = "tray")]
+use ksni::blocking::{Handle, TrayMethods};
+
+
+// Drawn over the icon while there are unread messages, as a red dot in its corner.
+#[cfg(feature = "tray")]
+const BADGE_SIZE: i32 = 16;
+
+#[cfg(feature = "tray")]
+struct ChatTray {
+    title: String,
+    unread: usize,
+    on_activate: Box<dyn FnMut() + Send>,
+}
+
+#[cfg(feature = "tray")]
+fn badge() -> Icon {
+    let center = (BADGE_SIZE - 1) as f32 / 2.0;
+    let radius = BADGE_SIZE as f32 / 4.0;
+    let data = (0..BADGE_SIZE * BADGE_SIZE).flat_map(|pixel| {
+        let (x, y) = ((pixel % BADGE_SIZE) as f32, (pixel / BADGE_SIZE) as f32);
+        let inside = (x - center - radius).hypot(y - center + radius) <= radius;
+        // ARGB.
+        if inside { [0xff, 0xe0, 0x1b, 0x24] } else { [0, 0, 0, 0] }
+    }).collect();
+    Icon {
+        width: BADGE_SIZE,
+        height: BADGE_SIZE,
+        data,
+    }
+}
+
+#[cfg(feature = "tray")]
+impl ksni::Tray for ChatTray {
+    fn id(&self) -> String {
+        "simple_chat".to_string()
+    }
+
+    fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    fn icon_name(&self) -> String {
+        "internet-group-chat".to_string()
+    }
+
+    fn attention_icon_name(&self) -> String {
+        "mail-unread".to_string()
+    }
+
+    fn overlay_icon_pixmap(&self) -> Vec<Icon> {
+        if self.unread == 0 { vec![] } else { vec![badge()] }
+    }
+
+    fn status(&self) -> Status {
+        if self.unread == 0 { Status::Active } else { Status::NeedsAttention }
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self.title.clone(),
+            description: tr!("tray-unread", count = self.unread),
+            ..ToolTip::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        (self.on_activate)();
+    }
+}
+
+// The icon of the chat in the system tray, with `--tray`, showing how many messages
+// are unread, and bringing the chat up when clicked.
+pub struct Tray {
+    // The messages received since the chat was last brought up, or replied to.
+    unread: usize,
+    #[cfg(feature = "tray")]
+    handle: Handle<ChatTray>,
+}
+
+impl Tray {
+    #[cfg(feature = "tray")]
+    pub fn start<F: FnMut() + Send + 'static>(title: &str, on_activate: F) -> Result<Tray, String> {
+        let tray = ChatTray {
+            title: title.to_string(),
+            unread: 0,
+            on_activate: Box::new(on_activate),
+        };
+        let handle = tray.spawn().map_err(|error| error.to_string())?;
+        Ok(Tray {
+            unread: 0,
+            handle,
+        })
+    }
+
+    #[cfg(not(feature = "tray"))]
+    pub fn start<F: FnMut() + Send + 'static>(_title: &str, _on_activate: F) -> Result<Tray, String> {
+        Err("requires the 'tray' feature".to_string())
+    }
+
+    pub fn message_received(&mut self) {
+        self.unread += 1;
+        self.show_unread();
+    }
+
+    pub fn read(&mut self) {
+        if self.unread > 0 {
+            self.unread = 0;
+            self.show_unread();
+        }
+    }
+
+    #[cfg(feature = "tray")]
+    fn show_unread(&self) {
+        let unread = self.unread;
+        self.handle.update(|tray| tray.unread = unread);
+    }
+
+    #[cfg(not(feature = "tray"))]
+    fn show_unread(&self) {}
+}
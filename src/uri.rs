@@ -0,0 +1,224 @@
+use codec::WireFormat;
+use qrcode::QrCode;
+use qrcode::render::unicode::Dense1x2;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::str;
+
+
+const SCHEME: &str = "chat://";
+
+// Where to chat and how, in one string to copy and paste, such as
+// `chat://alice@example.com:8000/general?wire=json&invite=<token>`: the nickname to take,
+// the server, or the rendezvous server of a p2p chat with the room to meet in and the
+// invitation to it, and the wire format.
+#[derive(Debug, Default, PartialEq)]
+pub struct ChatUri {
+    pub nick: Option<String>,
+    // The host and port.
+    pub address: String,
+    pub room: Option<String>,
+    pub invitation: Option<String>,
+    pub wire_format: Option<WireFormat>,
+}
+
+pub fn is_uri(argument: &str) -> bool {
+    argument.starts_with(SCHEME)
+}
+
+// The address of this machine on the network packets to others go out on, if any.
+fn local_ip(v6: bool) -> Option<IpAddr> {
+    // Connecting a UDP socket sends nothing, only choosing the interface, towards an address reserved for documentation.
+    let (any, remote) = if v6 { ("[::]:0", "[2001:db8::1]:80") } else { ("0.0.0.0:0", "192.0.2.1:80") };
+    let socket = UdpSocket::bind(any).ok()?;
+    socket.connect(remote).ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_unspecified() && !ip.is_loopback())
+}
+
+// The address other machines reach a server listening at the address with: the one advertised,
+// such as a host name, given its port if it has none, or for a server listening on all interfaces,
+// the address of this machine on the network, the address listened at being kept otherwise.
+pub fn reachable_address(address: &str, advertised: Option<&str>) -> String {
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host.trim_start_matches('[').trim_end_matches(']'), port),
+        None => return address.to_string(),
+    };
+    if let Some(advertised) = advertised {
+        let has_port = advertised.parse::<SocketAddr>().is_ok() || advertised.matches(':').count() == 1;
+        return match advertised.parse::<IpAddr>() {
+            Ok(ip) => format!("{}", SocketAddr::new(ip, port.parse().unwrap_or_default())),
+            Err(_) if has_port => advertised.to_string(),
+            Err(_) => format!("{}:{}", advertised, port),
+        };
+    }
+    match (host.parse::<IpAddr>(), port.parse()) {
+        (Ok(ip), Ok(port)) if ip.is_unspecified() => match local_ip(ip.is_ipv6()) {
+            Some(local) => SocketAddr::new(local, port).to_string(),
+            None => address.to_string(),
+        },
+        _ => address.to_string(),
+    }
+}
+
+// Whether only this machine reaches the address.
+pub fn is_loopback(address: &str) -> bool {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+// Percent-encoded bytes, such as `%20` for a space, decoded.
+fn decode(text: &str) -> Result<String, String> {
+    let invalid = || format!("invalid percent-encoding in '{}'", text);
+    let mut bytes = vec![];
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte != b'%' {
+            bytes.push(byte);
+            rest = after;
+            continue;
+        }
+        let hex = after.get(..2).and_then(|hex| str::from_utf8(hex).ok()).ok_or_else(invalid)?;
+        bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        rest = &after[2..];
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+fn encode(text: &str) -> String {
+    text.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+impl ChatUri {
+    // The port is the default one if not given.
+    pub fn parse(uri: &str, default_port: u16) -> Result<ChatUri, String> {
+        let rest = uri.strip_prefix(SCHEME).ok_or_else(|| format!("'{}' doesn't start with {}", uri, SCHEME))?;
+        let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (authority, room) = match rest.split_once('/') {
+            Some((authority, room)) => (authority, Some(room)),
+            None => (rest, None),
+        };
+        let (nick, host) = match authority.rsplit_once('@') {
+            Some((nick, host)) => (Some(decode(nick)?), host),
+            None => (None, authority),
+        };
+        if host.is_empty() || host.starts_with(':') {
+            return Err(format!("no host in '{}'", uri));
+        }
+        // An IPv6 address is in brackets, its colons not being the port's.
+        let has_port = host.rfind(':').is_some_and(|colon| !host[colon..].contains(']'));
+        let address = if has_port { host.to_string() } else { format!("{}:{}", host, default_port) };
+        let mut parsed = ChatUri {
+            nick: nick.filter(|nick| !nick.is_empty()),
+            address,
+            room: room.filter(|room| !room.is_empty()).map(decode).transpose()?,
+            ..ChatUri::default()
+        };
+        for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let value = decode(value)?;
+            match key {
+                "wire" => parsed.wire_format = Some(WireFormat::parse(&value)?),
+                "invite" => parsed.invitation = Some(value),
+                // Not to connect without, rather than in plaintext as the chat does for now.
+                "tls" if value == "0" => {},
+                "tls" => return Err("TLS isn't available yet, the chat being in plaintext".to_string()),
+                _ => return Err(format!("unknown parameter '{}' in '{}'", key, uri)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // For a phone on the same network to scan, in the terminal: light on dark, as most are,
+    // two rows of the code to a line.
+    pub fn qr_code(&self) -> Result<String, String> {
+        let code = QrCode::new(self.to_string()).map_err(|error| error.to_string())?;
+        Ok(render(&code))
+    }
+}
+
+fn render(code: &QrCode) -> String {
+    code.render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build()
+}
+
+impl fmt::Display for ChatUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", SCHEME)?;
+        if let Some(ref nick) = self.nick {
+            write!(f, "{}@", encode(nick))?;
+        }
+        write!(f, "{}", self.address)?;
+        if let Some(ref room) = self.room {
+            write!(f, "/{}", encode(room))?;
+        }
+        let mut parameters = vec![];
+        if let Some(wire_format) = self.wire_format {
+            parameters.push(format!("wire={}", wire_format.name()));
+        }
+        if let Some(ref invitation) = self.invitation {
+            parameters.push(format!("invite={}", encode(invitation)));
+        }
+        if !parameters.is_empty() {
+            write!(f, "?{}", parameters.join("&"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri() {
+        let uri = ChatUri::parse("chat://alice@example.com:9000/general%20chat?wire=json&invite=0f1e&tls=0", 8000).unwrap();
+        assert_eq!(uri, ChatUri {
+            nick: Some("alice".to_string()),
+            address: "example.com:9000".to_string(),
+            room: Some("general chat".to_string()),
+            invitation: Some("0f1e".to_string()),
+            wire_format: Some(WireFormat::Json),
+        });
+        assert_eq!(uri.to_string(), "chat://alice@example.com:9000/general%20chat?wire=json&invite=0f1e");
+        assert_eq!(ChatUri::parse(&uri.to_string(), 8000).unwrap(), uri);
+
+        assert_eq!(ChatUri::parse("chat://example.com", 8000).unwrap().address, "example.com:8000");
+        assert_eq!(ChatUri::parse("chat://[::1]", 8000).unwrap().address, "[::1]:8000");
+        assert_eq!(ChatUri::parse("chat://[::1]:9000/", 8000).unwrap().address, "[::1]:9000");
+        assert!(ChatUri::parse("chat://example.com?tls=1", 8000).is_err());
+        assert!(ChatUri::parse("chat://example.com?colour=red", 8000).is_err());
+        assert!(ChatUri::parse("chat://alice@", 8000).is_err());
+        assert!(ChatUri::parse("chat://example.com/%zz", 8000).is_err());
+        assert!(ChatUri::parse("http://example.com", 8000).is_err());
+
+        let code = uri.qr_code().unwrap();
+        let widths: Vec<usize> = code.lines().map(|line| line.chars().count()).collect();
+        assert!(widths.len() > 10 && widths.iter().all(|&width| width == widths[0]));
+        // That of the URI itself.
+        let expected = QrCode::new("chat://alice@example.com:9000/general%20chat?wire=json&invite=0f1e").unwrap();
+        assert_eq!(code, render(&expected));
+        assert_ne!(ChatUri::parse("chat://example.com", 8000).unwrap().qr_code().unwrap(), code);
+    }
+
+    #[test]
+    fn test_reachable_address() {
+        assert_eq!(reachable_address("192.168.1.5:8000", None), "192.168.1.5:8000");
+        assert_eq!(reachable_address("127.0.0.1:8000", None), "127.0.0.1:8000");
+        assert_eq!(reachable_address("0.0.0.0:8000", Some("chat.example.com")), "chat.example.com:8000");
+        assert_eq!(reachable_address("0.0.0.0:8000", Some("chat.example.com:443")), "chat.example.com:443");
+        assert_eq!(reachable_address("0.0.0.0:8000", Some("2001:db8::5")), "[2001:db8::5]:8000");
+        // This machine's address, if it is on a network.
+        let reachable = reachable_address("0.0.0.0:8000", None);
+        assert!(reachable == "0.0.0.0:8000" || (reachable.ends_with(":8000") && !is_loopback(&reachable)));
+        assert!(is_loopback("127.0.0.1:8000"));
+        assert!(is_loopback("localhost:8000"));
+        assert!(is_loopback("[::1]:8000"));
+        assert!(!is_loopback("example.com:8000"));
+    }
+}
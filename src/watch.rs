@@ -0,0 +1,65 @@
+use fs_notify::{self as notify, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::env;
+use std::path::{Path, PathBuf};
+
+
+// The path, absolute as in the events of the watcher, if its directory exists.
+fn absolute(path: &Path) -> Option<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => env::current_dir().ok()?,
+    };
+    Some(dir.canonicalize().ok()?.join(path.file_name()?))
+}
+
+// Call `on_change` with the index of the files in `paths` as they change, as long as the watcher is kept.
+// Their directories are watched, editors often replacing a file rather than writing to it,
+// and a file which doesn't exist yet is watched for being created.
+pub fn watch_files<F>(paths: &[PathBuf], on_change: F) -> Result<RecommendedWatcher, String>
+    where F: Fn(usize) + Send + 'static {
+    let paths: Vec<Option<PathBuf>> = paths.iter().map(|path| absolute(path)).collect();
+    let watched = paths.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let event = match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_)) => event,
+            _ => return,
+        };
+        for (index, path) in watched.iter().enumerate() {
+            if matches!(*path, Some(ref path) if event.paths.contains(path)) {
+                on_change(index);
+            }
+        }
+    }).map_err(|error| error.to_string())?;
+    for path in paths.iter().flatten() {
+        if let Some(dir) = path.parent() {
+            watcher.watch(dir, RecursiveMode::NonRecursive)
+                .map_err(|error| format!("failed to watch {}: {}", dir.display(), error))?;
+        }
+    }
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn test_watch_files() {
+        let dir = env::temp_dir().join("simple_chat_test_watch");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let _ = fs::remove_file(&path);
+        let (chan, port) = channel();
+        let missing = env::temp_dir().join("simple_chat_test_missing").join("bans");
+        let _watcher = watch_files(&[missing, path.clone()], move |index| {
+            let _ = chan.send(index);
+        }).unwrap();
+        fs::write(dir.join("other.toml"), "").unwrap();
+        fs::write(&path, "port = 9000\n").unwrap();
+        assert_eq!(port.recv_timeout(Duration::from_secs(5)), Ok(1));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,341 @@
+use sha1::{Digest, Sha1};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::thread;
+
+
+// The chat page, which joins the chat through the WebSocket at `/chat`.
+const PAGE: &str = include_str!("web.html");
+// Beyond this, a message from the browser closes its connection.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+// Appended to the key of the browser, to show the upgrade was understood.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// The `Sec-WebSocket-Accept` answering a `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+    base64(&sha1.finalize())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    websocket_key: Option<String>,
+    host: Option<String>,
+    // The site of the page asking for the upgrade, which browsers always tell.
+    origin: Option<String>,
+}
+
+impl Request {
+    // Whether the upgrade comes from the chat page itself, rather than from a page of another site
+    // the browser is on, which could otherwise join the chat as the user.
+    fn same_origin(&self) -> bool {
+        let origin = self.origin.as_ref()
+            .and_then(|origin| origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://")));
+        match (origin, self.host.as_ref()) {
+            (Some(origin), Some(host)) => origin.eq_ignore_ascii_case(host),
+            _ => false,
+        }
+    }
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Request> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut words = request_line.split_whitespace();
+    let method = words.next().unwrap_or("").to_string();
+    let path = words.next().unwrap_or("").to_string();
+    let mut websocket_key = None;
+    let mut host = None;
+    let mut origin = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let value = parts.next().map(|value| value.trim().to_string());
+        if name.eq_ignore_ascii_case("sec-websocket-key") {
+            websocket_key = value;
+        } else if name.eq_ignore_ascii_case("host") {
+            host = value;
+        } else if name.eq_ignore_ascii_case("origin") {
+            origin = value;
+        }
+    }
+    Ok(Request {
+        method,
+        path,
+        websocket_key,
+        host,
+        origin,
+    })
+}
+
+fn write_frame<W: Write>(stream: &mut W, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    // A single frame, unmasked as sent by a server.
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        },
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        },
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+pub fn write_text<W: Write>(stream: &mut W, text: &str) -> io::Result<()> {
+    write_frame(stream, OPCODE_TEXT, text.as_bytes())
+}
+
+// The next text message from the browser, or None once it closed the connection,
+// answering pings along the way.
+pub fn read_text<S: Read + Write>(stream: &mut S) -> io::Result<Option<String>> {
+    let mut message = vec![];
+    loop {
+        let mut header = [0; 2];
+        stream.read_exact(&mut header)?;
+        let last = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0f;
+        let length = match header[1] & 0x7f {
+            126 => {
+                let mut length = [0; 2];
+                stream.read_exact(&mut length)?;
+                u16::from_be_bytes(length) as usize
+            },
+            127 => {
+                let mut length = [0; 8];
+                stream.read_exact(&mut length)?;
+                u64::from_be_bytes(length) as usize
+            },
+            length => length as usize,
+        };
+        // The length is the browser's, which could be made to overflow.
+        if length > MAX_MESSAGE_SIZE - message.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+        }
+        // Browsers always mask their frames.
+        let mut mask = [0; 4];
+        if header[1] & 0x80 != 0 {
+            stream.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0; length];
+        stream.read_exact(&mut payload)?;
+        for (index, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[index % 4];
+        }
+        match opcode {
+            OPCODE_TEXT | OPCODE_CONTINUATION => message.extend(payload),
+            OPCODE_PING => {
+                write_frame(stream, OPCODE_PONG, &payload)?;
+                continue;
+            },
+            OPCODE_PONG => continue,
+            OPCODE_CLOSE => {
+                let _ = write_frame(stream, OPCODE_CLOSE, &[]);
+                return Ok(None);
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "only text messages are supported")),
+        }
+        if last {
+            return String::from_utf8(message)
+                .map(Some)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error));
+        }
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) {
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, content_type, body.len(), body);
+    let _ = stream.write_all(response.as_bytes());
+}
+
+// Relay the frames between the browser and the server as they come,
+// the browser having joined the chat as a client, in JSON so that each frame is a line.
+fn relay(browser: TcpStream, mut chat: TcpStream) -> io::Result<()> {
+    chat.write_all(b"json\n")?;
+    let mut to_browser = browser.try_clone()?;
+    let from_chat = BufReader::new(chat.try_clone()?);
+    let _ = thread::Builder::new().spawn(move || {
+        for line in from_chat.lines() {
+            let sent = line.and_then(|line| write_text(&mut to_browser, &line));
+            if sent.is_err() {
+                break;
+            }
+        }
+        let _ = write_frame(&mut to_browser, OPCODE_CLOSE, &[]);
+        let _ = to_browser.shutdown(Shutdown::Both);
+    });
+    let mut from_browser = browser;
+    loop {
+        match read_text(&mut from_browser) {
+            // A frame is a line, which the browser can't send more of at once.
+            Ok(Some(frame)) if !frame.contains('\n') => chat.write_all(format!("{}\n", frame).as_bytes())?,
+            _ => break,
+        }
+    }
+    chat.shutdown(Shutdown::Both)
+}
+
+fn serve(mut stream: TcpStream, chat_address: &str) {
+    let request = match stream.try_clone().and_then(|stream| read_request(&mut BufReader::new(stream))) {
+        Ok(request) => request,
+        Err(_) => return respond(&mut stream, "400 Bad Request", "text/plain", ""),
+    };
+    let path = request.path.split('?').next().unwrap_or("");
+    match (request.method.as_ref(), path, request.websocket_key.clone()) {
+        ("GET", "/", _) => respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+        ("GET", "/chat", Some(_)) if !request.same_origin() => respond(&mut stream, "403 Forbidden", "text/plain", ""),
+        ("GET", "/chat", Some(key)) => {
+            let chat = match TcpStream::connect(chat_address) {
+                Ok(chat) => chat,
+                Err(_) => return respond(&mut stream, "502 Bad Gateway", "text/plain", ""),
+            };
+            let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                                    Sec-WebSocket-Accept: {}\r\n\r\n", accept_key(&key));
+            if stream.write_all(response.as_bytes()).is_ok() {
+                let _ = relay(stream, chat);
+            }
+        },
+        _ => respond(&mut stream, "404 Not Found", "text/plain", ""),
+    }
+}
+
+// Serve the chat page, each browser joining the chat at `chat_address` as a client.
+pub fn start_web_endpoint(address: &str, chat_address: String) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let _ = thread::Builder::new().spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let chat_address = chat_address.clone();
+            let _ = thread::Builder::new().spawn(move || serve(stream, &chat_address));
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Frames from the browser, and those written to it.
+    struct Browser {
+        sent: Cursor<Vec<u8>>,
+        received: Vec<u8>,
+    }
+
+    impl Read for Browser {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.sent.read(buffer)
+        }
+    }
+
+    impl Write for Browser {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            self.received.write(buffer)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // As sent by a browser.
+    fn masked_frame(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![first, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(index, byte)| byte ^ mask[index % 4]));
+        frame
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // The example of RFC 6455.
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+    }
+
+    #[test]
+    fn test_read_text() {
+        let mut frames = masked_frame(OPCODE_TEXT, b"hel");
+        frames.extend(masked_frame(OPCODE_PING, b"ping"));
+        frames.extend(masked_frame(0x80 | OPCODE_CONTINUATION, b"lo"));
+        frames.extend(masked_frame(0x80 | OPCODE_CLOSE, b""));
+        let mut browser = Browser {
+            sent: Cursor::new(frames),
+            received: vec![],
+        };
+        assert_eq!(read_text(&mut browser).unwrap(), Some("hello".to_string()));
+        assert_eq!(read_text(&mut browser).unwrap(), None);
+        assert_eq!(browser.received, vec![0x80 | OPCODE_PONG, 4, b'p', b'i', b'n', b'g', 0x80 | OPCODE_CLOSE, 0]);
+
+        let mut sent = vec![];
+        write_text(&mut sent, &"a".repeat(200)).unwrap();
+        assert_eq!(&sent[..4], &[0x80 | OPCODE_TEXT, 126, 0, 200]);
+
+        // A length which would overflow once added to what was received.
+        let mut frames = masked_frame(OPCODE_TEXT, b"hel");
+        frames.extend_from_slice(&[0x80 | OPCODE_CONTINUATION, 0x80 | 127]);
+        frames.extend_from_slice(&u64::MAX.to_be_bytes());
+        let mut browser = Browser {
+            sent: Cursor::new(frames),
+            received: vec![],
+        };
+        assert_eq!(read_text(&mut browser).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let request = |origin: &str| {
+            let source = format!("GET /chat HTTP/1.1\r\nHost: 192.168.1.10:8080\r\n{}Sec-WebSocket-Key: a\r\n\r\n", origin);
+            read_request(&mut Cursor::new(source)).unwrap()
+        };
+        assert!(request("Origin: http://192.168.1.10:8080\r\n").same_origin());
+        assert!(!request("Origin: https://example.com\r\n").same_origin());
+        assert!(!request("Origin: http://192.168.1.10:8080.example.com\r\n").same_origin());
+        assert!(!request("").same_origin());
+    }
+}
@@ -0,0 +1,260 @@
+use serde_json::{self, Value};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+// Beyond this, an injected request is rejected.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    // Sent by the client.
+    Incoming,
+    // Sent by the server, to the client.
+    Outgoing,
+}
+
+// A message relayed by the server, posted as JSON to the webhook.
+#[derive(Clone)]
+pub struct WebhookEvent {
+    pub direction: Direction,
+    pub sent_at: SystemTime,
+    pub text: String,
+}
+
+impl WebhookEvent {
+    fn to_value(&self) -> Value {
+        let direction = match self.direction {
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing",
+        };
+        let timestamp = self.sent_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        json!({
+            "direction": direction,
+            "timestamp": timestamp.as_secs(),
+            "text": self.text,
+        })
+    }
+
+    fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+}
+
+// The last messages relayed by the server, to be read back with `GET /messages`.
+pub struct RecentMessages {
+    events: VecDeque<WebhookEvent>,
+    capacity: usize,
+}
+
+impl RecentMessages {
+    pub fn new(capacity: usize) -> RecentMessages {
+        RecentMessages {
+            events: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, event: WebhookEvent) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    // As a JSON array, oldest first.
+    fn since_json(&self, since: SystemTime) -> String {
+        let events: Vec<Value> = self.events.iter()
+            .filter(|event| event.sent_at >= since)
+            .map(WebhookEvent::to_value)
+            .collect();
+        Value::Array(events).to_string()
+    }
+}
+
+// Split a `http://host[:port]/path` URL into an address and a path.
+pub fn parse_url(url: &str) -> Result<(String, String), String> {
+    let rest = match url.strip_prefix("http://") {
+        Some(rest) => rest,
+        None => return Err(format!("only http:// webhook URLs are supported, got '{}'", url)),
+    };
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(format!("missing host in webhook URL '{}'", url));
+    }
+    let address = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Ok((address, path.to_string()))
+}
+
+fn post(address: &str, path: &str, body: &str) -> Result<(), String> {
+    let mut stream = TcpStream::connect(address).map_err(|error| error.to_string())?;
+    let host = address.split(':').next().unwrap_or(address);
+    let request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+                           Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                          path, host, body.len(), body);
+    stream.write_all(request.as_bytes()).map_err(|error| error.to_string())?;
+    let mut status = String::new();
+    let _ = BufReader::new(stream).read_line(&mut status);
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("unexpected response '{}'", status.trim())),
+    }
+}
+
+// Post events to the URL, one at a time, from a dedicated thread.
+pub fn start_webhook(url: &str) -> Result<Sender<WebhookEvent>, String> {
+    let (address, path) = parse_url(url)?;
+    let (chan, port) = channel::<WebhookEvent>();
+    let _ = thread::Builder::new().spawn(move || {
+        for event in port.iter() {
+            if let Err(error) = post(&address, &path, &event.to_json()) {
+                println!("Webhook failed: {}", error);
+            }
+        }
+    });
+    Ok(chan)
+}
+
+fn respond(stream: &mut TcpStream, status: &str) {
+    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn respond_json(stream: &mut TcpStream, body: &str) {
+    let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                            Connection: close\r\n\r\n{}", body.len(), body);
+    let _ = stream.write_all(response.as_bytes());
+}
+
+enum ApiRequest {
+    // `POST /messages` with a `{"text": "..."}` body.
+    Inject(String),
+    // `GET /messages[?since=<unix timestamp>]`.
+    Recent(SystemTime),
+}
+
+// The time from the `since` parameter of the query, if any, in seconds since the unix epoch.
+fn parse_since(query: &str) -> Result<SystemTime, &'static str> {
+    let since = query.split('&')
+        .filter_map(|parameter| parameter.strip_prefix("since="))
+        .next();
+    match since {
+        Some(seconds) => {
+            let seconds = seconds.parse().map_err(|_| "400 Bad Request")?;
+            Ok(UNIX_EPOCH + Duration::from_secs(seconds))
+        },
+        None => Ok(UNIX_EPOCH),
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<ApiRequest, &'static str> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    let _ = reader.read_line(&mut request_line);
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => return Err("400 Bad Request"),
+            Ok(_) => {},
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = parts.next().and_then(|value| value.trim().parse().ok()).unwrap_or(0);
+        }
+    }
+    let mut words = request_line.split_whitespace();
+    let method = words.next().unwrap_or("");
+    let mut target = words.next().unwrap_or("").splitn(2, '?');
+    let (path, query) = (target.next().unwrap_or(""), target.next().unwrap_or(""));
+    match (method, path) {
+        ("GET", "/messages") => return parse_since(query).map(ApiRequest::Recent),
+        ("POST", "/messages") => {},
+        _ => return Err("404 Not Found"),
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err("413 Payload Too Large");
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|_| "400 Bad Request")?;
+    let body: Value = serde_json::from_slice(&body).map_err(|_| "400 Bad Request")?;
+    match body.get("text").and_then(Value::as_str) {
+        Some(text) => Ok(ApiRequest::Inject(text.to_string())),
+        None => Err("400 Bad Request"),
+    }
+}
+
+// Accept messages to inject into the chat, on `POST /messages`,
+// and serve the recent ones, on `GET /messages`.
+pub fn start_api_endpoint<F>(address: &str, recent: Arc<Mutex<RecentMessages>>, inject: F) -> io::Result<()>
+    where F: Fn(String) + Send + 'static {
+    let listener = TcpListener::bind(address)?;
+    let _ = thread::Builder::new().spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            match read_request(&stream) {
+                Ok(ApiRequest::Inject(text)) => {
+                    inject(text);
+                    respond(&mut stream, "202 Accepted");
+                },
+                Ok(ApiRequest::Recent(since)) => {
+                    let body = recent.lock().unwrap().since_json(since);
+                    respond_json(&mut stream, &body);
+                },
+                Err(status) => respond(&mut stream, status),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(parse_url("http://localhost:9000/hook").unwrap(),
+                   ("localhost:9000".to_string(), "/hook".to_string()));
+        assert_eq!(parse_url("http://example.com").unwrap(),
+                   ("example.com:80".to_string(), "/".to_string()));
+        assert!(parse_url("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_recent_messages() {
+        let mut recent = RecentMessages::new(2);
+        for (seconds, text) in [(10, "one"), (20, "two"), (30, "three")] {
+            recent.push(WebhookEvent {
+                direction: Direction::Incoming,
+                sent_at: UNIX_EPOCH + Duration::from_secs(seconds),
+                text: text.to_string(),
+            });
+        }
+        let since = parse_since("since=30").unwrap();
+        assert_eq!(recent.since_json(since), r#"[{"direction":"incoming","text":"three","timestamp":30}]"#);
+        let all: Vec<Value> = serde_json::from_str(&recent.since_json(parse_since("").unwrap())).unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(parse_since("since=yesterday").is_err());
+    }
+}